@@ -0,0 +1,98 @@
+use clap::ValueEnum;
+
+/// Shortest beep by default, in frames: the COSMAC VIP buzzer does not respond to a
+/// sound timer below 2
+pub const DEFAULT_MIN_BEEP_FRAMES: u8 = 2;
+
+/// How the sound timer drives the buzzer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AudioConfig {
+    /// Sound timer values set below this still beep for this many frames, 0 to play them as is
+    pub min_beep_frames: u8,
+    /// Beep this many frames when a sprite collides, for players relying on sound cues
+    /// (0 to stay silent)
+    pub collision_cue_frames: u8,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            min_beep_frames: DEFAULT_MIN_BEEP_FRAMES,
+            collision_cue_frames: 0,
+        }
+    }
+}
+
+/// What the buzzer does while turbo runs several frames per real frame
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum TurboAudio {
+    /// Beeps shortened along with the timers, each still sounding for a real frame at least
+    Scale,
+    /// Silence until turbo is released
+    Mute,
+}
+
+/// Buzzer state presented once per real frame, from the frames run during it
+pub struct Buzzer {
+    on: bool,
+    batch: Option<bool>, // whether any frame run since the last presentation sounded
+}
+
+impl Buzzer {
+    pub fn new() -> Self {
+        Buzzer {
+            on: false,
+            batch: None,
+        }
+    }
+
+    /// Record the sound of a frame just run
+    pub fn push_frame(&mut self, sound_on: bool) {
+        self.batch = Some(self.batch.unwrap_or(false) || sound_on);
+    }
+
+    /// Whether the buzzer sounds this real frame. Out of turbo it follows the last frame run,
+    /// in turbo beeps shorter than a real frame are kept instead of flickering or vanishing.
+    pub fn present(&mut self, sound_on: bool, turbo: Option<TurboAudio>) -> bool {
+        if let Some(batch) = self.batch.take() {
+            self.on = match turbo {
+                None => sound_on,
+                Some(TurboAudio::Scale) => batch,
+                Some(TurboAudio::Mute) => false,
+            };
+        }
+        self.on
+    }
+}
+
+impl Default for Buzzer {
+    fn default() -> Self {
+        Buzzer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buzzer() {
+        let mut buzzer = Buzzer::new();
+        buzzer.push_frame(true);
+        buzzer.push_frame(false);
+        assert!(!buzzer.present(false, None));
+
+        // a beep within the frames of a real frame
+        for sound_on in [false, true, false, false] {
+            buzzer.push_frame(sound_on);
+        }
+        assert!(buzzer.present(false, Some(TurboAudio::Scale)));
+        // no frame run, e.g. paused
+        assert!(buzzer.present(false, Some(TurboAudio::Scale)));
+
+        buzzer.push_frame(true);
+        assert!(!buzzer.present(true, Some(TurboAudio::Mute)));
+        buzzer.push_frame(true);
+        assert!(buzzer.present(true, None));
+    }
+}
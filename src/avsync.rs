@@ -0,0 +1,141 @@
+use crate::chip8::Chip8;
+use crate::framebuffer::FrameBuffer;
+use crate::quirks::Profile;
+
+/// Largest distance in frames between a beep onset and the draw it goes with
+pub const MAX_OFFSET: i64 = 1;
+
+/// Program beeping and toggling a sprite together every 30 frames
+#[rustfmt::skip]
+pub const SYNC_ROM: [u8; 23] = [
+    0x61, 0x04, // 200: V1 = 4, beep length
+    0x63, 0x00, // 202: V3 = 0, sprite position
+    0xA2, 0x16, // 204: I = sprite
+    0x60, 0x1E, // 206: V0 = 30
+    0xF0, 0x15, // 208: delay timer = V0
+    0xF2, 0x07, // 20A: V2 = delay timer
+    0x32, 0x00, // 20C: skip next if V2 == 0
+    0x12, 0x0A, // 20E: jump to 20A
+    0xF1, 0x18, // 210: sound timer = V1
+    0xD3, 0x31, // 212: draw the sprite at (V3, V3), toggling it
+    0x12, 0x06, // 214: jump to 206
+    0xFF,       // 216: sprite
+];
+
+/// Frames where the buzzer starts and where the display changes, as the frontend
+/// presents them at the end of each frame
+pub struct SyncMonitor {
+    frame: u64,
+    sound: bool,
+    display: FrameBuffer,
+    onsets: Vec<u64>,
+    changes: Vec<u64>,
+}
+
+impl SyncMonitor {
+    pub fn new() -> Self {
+        SyncMonitor {
+            frame: 0,
+            sound: false,
+            display: FrameBuffer::new(),
+            onsets: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Record the buzzer and the display at the end of a frame
+    pub fn push_frame(&mut self, sound_on: bool, display: FrameBuffer) {
+        self.frame += 1;
+        if sound_on && !self.sound {
+            self.onsets.push(self.frame);
+        }
+        if display != self.display {
+            self.changes.push(self.frame);
+        }
+        self.sound = sound_on;
+        self.display = display;
+    }
+
+    /// Frame of each beep onset with the offset of the closest display change, positive when
+    /// the display changes after the beep, None if it never changes
+    pub fn offsets(&self) -> Vec<(u64, Option<i64>)> {
+        self.onsets
+            .iter()
+            .map(|&onset| {
+                let offset = self
+                    .changes
+                    .iter()
+                    .map(|&change| change as i64 - onset as i64)
+                    .min_by_key(|offset| offset.abs());
+                (onset, offset)
+            })
+            .collect()
+    }
+
+    /// Whether there was a beep and every beep came with a display change within
+    /// `MAX_OFFSET` frames
+    pub fn in_sync(&self) -> bool {
+        !self.onsets.is_empty()
+            && self
+                .offsets()
+                .iter()
+                .all(|(_, offset)| offset.is_some_and(|offset| offset.abs() <= MAX_OFFSET))
+    }
+}
+
+impl Default for SyncMonitor {
+    fn default() -> Self {
+        SyncMonitor::new()
+    }
+}
+
+/// Run a program headlessly for `frames` frames, recording when it beeps and draws
+pub fn run(program: &[u8], profile: Profile, cycles_per_frame: u32, frames: u32) -> SyncMonitor {
+    let mut chip8 = Chip8::new();
+    chip8.interpreter.set_quirks(profile.quirks());
+    chip8.interpreter.set_timer_mode(profile.timer_mode());
+    chip8.interpreter.set_stack_depth(profile.stack_depth());
+    chip8.memory.set_address_space(profile.address_space());
+    chip8.load(program);
+
+    let mut monitor = SyncMonitor::new();
+    for _ in 0..frames {
+        chip8.run_frame(cycles_per_frame);
+        chip8.frame();
+        monitor.push_frame(
+            chip8.interpreter.sound_on(),
+            FrameBuffer::from_memory(&chip8.memory),
+        );
+    }
+
+    monitor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        let mut drawn = FrameBuffer::new();
+        drawn.set_pixel(0, 0, true);
+
+        let mut monitor = SyncMonitor::new();
+        monitor.push_frame(true, FrameBuffer::new());
+        monitor.push_frame(false, FrameBuffer::new());
+        monitor.push_frame(false, FrameBuffer::new());
+        monitor.push_frame(false, drawn);
+
+        assert_eq!(vec![(1, Some(3))], monitor.offsets());
+        assert!(!monitor.in_sync());
+    }
+
+    #[test]
+    fn test_no_beep() {
+        let mut monitor = SyncMonitor::new();
+        monitor.push_frame(false, FrameBuffer::new());
+
+        assert!(monitor.offsets().is_empty());
+        assert!(!monitor.in_sync());
+    }
+}
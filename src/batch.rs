@@ -0,0 +1,238 @@
+use crate::chip8::Chip8;
+use crate::container;
+use crate::disassembler::Extension;
+use crate::error::{Error, HaltReason};
+use crate::framebuffer::FrameBuffer;
+use crate::info;
+use crate::quirks::Profile;
+use crate::timing::DEFAULT_CYCLES_PER_FRAME;
+
+use rayon::prelude::*;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File extensions of the programs run from a directory
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "c8c"];
+
+/// Process exit codes of a headless run (the `run` command), for CI pipelines to branch on
+pub const EXIT_HALTED: i32 = 0;
+pub const EXIT_FAULT: i32 = 1; // memory fault or jump outside the guarded regions
+pub const EXIT_UNKNOWN_OPCODE: i32 = 2;
+pub const EXIT_STACK_FAULT: i32 = 3;
+pub const EXIT_TIMEOUT: i32 = 4;
+
+/// How a program ended its headless run
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// Still running when the instruction budget ran out, the expected outcome for games
+    Running,
+    /// Reached an exit instruction (0000) or a jump to itself
+    Exited,
+    /// Reached an opcode the interpreter does not implement
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// Stopped on another fault (stack, memory)
+    Crashed(Error),
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Running => "running",
+            Status::Exited => "exited",
+            Status::UnknownOpcode { .. } => "unknown opcode",
+            Status::Crashed(_) => "crashed",
+        }
+    }
+
+    /// Whether the interpreter ran the program without a fault
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Status::Running | Status::Exited)
+    }
+
+    /// Exit code of a run ending with this status, still running being a timeout
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Status::Exited => EXIT_HALTED,
+            Status::Running => EXIT_TIMEOUT,
+            Status::UnknownOpcode { .. } => EXIT_UNKNOWN_OPCODE,
+            Status::Crashed(Error::StackOverflow { .. } | Error::StackUnderflow { .. }) => {
+                EXIT_STACK_FAULT
+            }
+            Status::Crashed(_) => EXIT_FAULT,
+        }
+    }
+}
+
+/// Outcome of one program of a batch
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RomResult {
+    pub path: PathBuf,
+    pub platform: Extension, // detected from the opcodes used
+    pub profile: Profile,
+    pub status: Status,
+    pub instructions: u64,
+    pub frame: FrameBuffer, // display at the end
+}
+
+/// Run a program headlessly for up to `cycles` instructions, in 60 Hz frames
+pub fn run(program: &[u8], profile: Profile, cycles: u64) -> (Status, u64, FrameBuffer) {
+    let (status, instructions, chip8) = run_machine(program, profile, cycles);
+    (
+        status,
+        instructions,
+        FrameBuffer::from_memory(&chip8.memory),
+    )
+}
+
+/// Like `run`, returns the machine as the program left it
+pub fn run_machine(program: &[u8], profile: Profile, cycles: u64) -> (Status, u64, Chip8) {
+    let mut chip8 = Chip8::new();
+    chip8.interpreter.set_quirks(profile.quirks());
+    chip8.interpreter.set_timer_mode(profile.timer_mode());
+    chip8.interpreter.set_stack_depth(profile.stack_depth());
+    chip8.memory.set_address_space(profile.address_space());
+    chip8.load(program);
+
+    // the last frame is cut short to stop at the budget exactly
+    let mut remaining = cycles;
+    while remaining > 0 && !chip8.interpreter.stop() {
+        let frame_cycles = remaining.min(DEFAULT_CYCLES_PER_FRAME as u64);
+        chip8.run_frame(frame_cycles as u32);
+        chip8.frame();
+        remaining -= frame_cycles;
+    }
+
+    let status = match chip8.interpreter.halt_reason() {
+        Some(HaltReason::Error(Error::UnknownOpcode { pc, opcode })) => Status::UnknownOpcode {
+            pc: *pc,
+            opcode: *opcode,
+        },
+        Some(HaltReason::Error(error)) => Status::Crashed(error.clone()),
+        Some(_) => Status::Exited,
+        None => Status::Running,
+    };
+    let instructions = chip8.metrics().instructions;
+
+    (status, instructions, chip8)
+}
+
+/// Programs of a directory, sorted by path
+pub fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        if path.is_file() && ROM_EXTENSIONS.contains(&extension.as_deref().unwrap_or_default()) {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+
+    Ok(roms)
+}
+
+/// Run every program of a directory in parallel, with `profile` or the one of its `.c8c`
+/// container, or else the one suggested by the extensions each program uses
+pub fn run_dir(dir: &Path, profile: Option<Profile>, cycles: u64) -> io::Result<Vec<RomResult>> {
+    let programs = find_roms(dir)?
+        .into_iter()
+        .map(|path| container::read(&path).map(|(program, container)| (path, program, container)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(programs
+        .into_par_iter()
+        .map(|(path, program, container)| {
+            let platform = info::detect_platform(&program);
+            let profile = profile
+                .or(container.and_then(|container| container.profile))
+                .unwrap_or_else(|| info::suggested_profile(&program));
+            let (status, instructions, frame) = run(&program, profile, cycles);
+            RomResult {
+                path,
+                platform,
+                profile,
+                status,
+                instructions,
+                frame,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let exits = [
+            0x60, 0x01, // V0 = 1
+            0x00, 0x00, // exit
+        ];
+        let loops = [
+            0x60, 0x01, // 200: V0 = 1
+            0x12, 0x00, // 202: jump to 200
+        ];
+        let ends = [
+            0x12, 0x00, // jump to itself
+        ];
+        let unknown = [
+            0x00, 0xE1, // not an instruction
+        ];
+        let underflows = [
+            0x00, 0xEE, // return without call
+        ];
+
+        let (status, instructions, _) = run(&exits, Profile::Modern, 1000);
+        assert_eq!(Status::Exited, status);
+        assert_eq!(2, instructions);
+        let (status, instructions, _) = run(&loops, Profile::Modern, 1000);
+        assert_eq!(Status::Running, status);
+        assert_eq!(1000, instructions);
+        assert_eq!(10_000, run(&loops, Profile::Modern, 10_000).1);
+        assert_eq!(Status::Exited, run(&ends, Profile::Modern, 1000).0);
+        assert_eq!(
+            Status::UnknownOpcode {
+                pc: 0x200,
+                opcode: 0x00E1
+            },
+            run(&unknown, Profile::Modern, 1000).0
+        );
+        assert_eq!(
+            Status::Crashed(Error::StackUnderflow { pc: 0x200 }),
+            run(&underflows, Profile::Modern, 1000).0
+        );
+
+        // the failing instruction ends the history
+        let (_, _, chip8) = run_machine(&underflows, Profile::Modern, 1000);
+        assert_eq!(
+            Some((0x200, 0x00EE)),
+            chip8.interpreter.pc_history().iter().last()
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(EXIT_HALTED, Status::Exited.exit_code());
+        assert_eq!(EXIT_TIMEOUT, Status::Running.exit_code());
+        let unknown = Status::UnknownOpcode {
+            pc: 0x200,
+            opcode: 0x00E1,
+        };
+        assert_eq!(EXIT_UNKNOWN_OPCODE, unknown.exit_code());
+        let overflow = Status::Crashed(Error::StackOverflow {
+            pc: 0x200,
+            depth: 16,
+        });
+        assert_eq!(EXIT_STACK_FAULT, overflow.exit_code());
+        let guard = Status::Crashed(Error::PcOutOfRegion {
+            pc: 0x800,
+            from: 0x200,
+        });
+        assert_eq!(EXIT_FAULT, guard.exit_code());
+    }
+}
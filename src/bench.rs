@@ -0,0 +1,85 @@
+use crate::chip8::Chip8;
+use crate::executor::ExecutorKind;
+use crate::quirks::Profile;
+
+use std::time::{Duration, Instant};
+
+/// Outcome of a headless run
+#[derive(Clone, PartialEq, Debug)]
+pub struct BenchResult {
+    pub executor: ExecutorKind,
+    pub frames: u32,
+    pub instructions: u64,
+    pub elapsed: Duration,
+    pub registers: [u8; 16], // V0 to VF at the end, to check executors agree
+}
+
+impl BenchResult {
+    /// Millions of instructions per second
+    pub fn mips(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON) / 1e6
+    }
+}
+
+/// Run `frames` frames of `cycles` instructions as fast as possible, without a window.
+/// Stops early if the program stops.
+pub fn run(
+    program: &[u8],
+    profile: Profile,
+    executor: ExecutorKind,
+    frames: u32,
+    cycles: u32,
+) -> BenchResult {
+    let mut chip8 = Chip8::new();
+    chip8.interpreter.set_quirks(profile.quirks());
+    chip8.interpreter.set_timer_mode(profile.timer_mode());
+    chip8.interpreter.set_stack_depth(profile.stack_depth());
+    chip8.memory.set_address_space(profile.address_space());
+    chip8.set_executor(executor.create());
+    chip8.load(program);
+
+    let start = Instant::now();
+    let mut frames_run = 0;
+    while frames_run < frames && !chip8.interpreter.stop() {
+        chip8.run_frame(cycles);
+        frames_run += 1;
+    }
+
+    BenchResult {
+        executor,
+        frames: frames_run,
+        instructions: chip8.metrics().instructions,
+        elapsed: start.elapsed(),
+        registers: chip8.interpreter.vx,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_executors_agree() {
+        let program = [
+            0x60, 0x00, // 200: V0 = 0
+            0x70, 0x01, // 202: V0 += 1
+            0x81, 0x00, // 204: V1 = V0
+            0x71, 0x03, // 206: V1 += 3
+            0xF0, 0x15, // 208: delay timer = V0
+            0x12, 0x02, // 20A: jump to 0x202
+        ];
+
+        let results: Vec<BenchResult> = ExecutorKind::value_variants()
+            .iter()
+            .map(|executor| run(&program, Profile::Modern, *executor, 10, 333))
+            .collect();
+
+        for result in &results {
+            assert_eq!(10, result.frames);
+            assert_eq!(3330, result.instructions);
+            assert_eq!(results[0].registers, result.registers);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Mutation testing of the opcode handlers: applies small changes to the instruction
+//! implementations one at a time (flipped VF, inverted comparisons, swapped operators...)
+//! and checks that the test suite fails on each of them. Mutations surviving the tests
+//! show semantics no test covers.
+//!
+//! Usage: cargo run --bin mutate-check -- [--list] [--filter TEXT] [--timeout SECONDS]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Source file holding the opcode handlers
+const SOURCE: &str = "src/interpreter.rs";
+
+/// The function whose body gets mutated
+const HANDLERS: &str = "pub(crate) fn execute(";
+
+/// Replacements tried on every line of the handlers
+const OPERATORS: [(&str, &str); 16] = [
+    ("set_vf(1)", "set_vf(0)"),
+    ("set_vf(0)", "set_vf(1)"),
+    (" == ", " != "),
+    (" != ", " == "),
+    (" < ", " <= "),
+    (" > ", " >= "),
+    (" >= ", " > "),
+    ("wrapping_add", "wrapping_sub"),
+    ("wrapping_sub", "wrapping_add"),
+    (" & ", " | "),
+    (" | ", " & "),
+    (" ^ ", " | "),
+    (" + ", " - "),
+    (" - ", " + "),
+    (">> 1", ">> 2"),
+    ("<< 1", "<< 2"),
+];
+
+/// A single change of the source
+struct Mutation {
+    line: usize, // 0-based
+    column: usize,
+    from: &'static str,
+    to: &'static str,
+}
+
+impl Mutation {
+    fn apply(&self, source: &str) -> String {
+        let mut lines: Vec<String> = source.lines().map(String::from).collect();
+        let line = &mut lines[self.line];
+        line.replace_range(self.column..self.column + self.from.len(), self.to);
+        lines.join("\n") + "\n"
+    }
+
+    fn describe(&self, source: &str) -> String {
+        let line = source.lines().nth(self.line).unwrap_or_default().trim();
+        format!(
+            "{}:{}: `{}` -> `{}` in `{}`",
+            SOURCE,
+            self.line + 1,
+            self.from.trim(),
+            self.to.trim(),
+            line
+        )
+    }
+}
+
+/// Every mutation of the handlers, one operator occurrence at a time
+fn mutations(source: &str) -> Vec<Mutation> {
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(start) = lines.iter().position(|line| line.contains(HANDLERS)) else {
+        return Vec::new();
+    };
+    // the function ends at the first closing brace at its indentation
+    let indent = lines[start].len() - lines[start].trim_start().len();
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.len() == indent + 1 && line.ends_with('}'))
+        .map_or(lines.len(), |offset| start + offset);
+
+    let mut mutations = Vec::new();
+    for (line, text) in lines.iter().enumerate().take(end).skip(start + 1) {
+        let code = text.split("//").next().unwrap_or_default();
+        // the patterns of match arms (`Operation::Or | Operation::And =>`, also when split on
+        // lines starting with `|`) are not operators
+        let skipped = match code.find("=>") {
+            Some(arrow) => arrow + 2,
+            None if code.trim_start().starts_with('|') => code.len(),
+            None => 0,
+        };
+        for (from, to) in OPERATORS {
+            for (column, _) in code[skipped..].match_indices(from) {
+                mutations.push(Mutation {
+                    line,
+                    column: skipped + column,
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+
+    mutations
+}
+
+enum Outcome {
+    Killed,
+    Survived,
+    TimedOut,
+    Unviable, // does not build
+}
+
+/// Build the unit tests of the crate copy, returns the test binary. Only the library's: they
+/// cover the opcode handlers, the binaries' and integration tests would only lengthen the
+/// build of each mutation.
+fn build_tests(dir: &Path, target_dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("cargo")
+        .args([
+            "test",
+            "--lib",
+            "--no-run",
+            "--quiet",
+            "--message-format=json",
+            "--target-dir",
+        ])
+        .arg(target_dir)
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+        .expect("Could not run cargo");
+    if !output.status.success() {
+        return None;
+    }
+
+    // the artifact of the test binary, e.g. `..."executable":"/tmp/.../chip8_interpreter-1a2b",...`
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once("\"executable\":\"")?.1.split_once('"'))
+        .map(|(path, _)| PathBuf::from(path))
+        .next()
+}
+
+/// Run the tests of the crate copy, killing them after `timeout`
+fn run_tests(dir: &Path, target_dir: &Path, timeout: Duration) -> Outcome {
+    let Some(binary) = build_tests(dir, target_dir) else {
+        return Outcome::Unviable;
+    };
+
+    // the test binary itself rather than cargo, which would leave it running once killed
+    let mut tests = Command::new(binary)
+        .arg("--quiet")
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Could not run the tests");
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = tests.try_wait().expect("Could not wait for the tests") {
+            return match status.success() {
+                true => Outcome::Survived,
+                false => Outcome::Killed,
+            };
+        }
+        if start.elapsed() > timeout {
+            let _ = tests.kill();
+            let _ = tests.wait();
+            return Outcome::TimedOut;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut list = false;
+    let mut filter = None;
+    let mut timeout = Duration::from_secs(60);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--filter" => filter = args.next(),
+            "--timeout" => {
+                let seconds = args.next().and_then(|s| s.parse().ok());
+                timeout =
+                    Duration::from_secs(seconds.expect("--timeout needs a number of seconds"));
+            }
+            _ => {
+                eprintln!("usage: mutate-check [--list] [--filter TEXT] [--timeout SECONDS]");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let source = std::fs::read_to_string(root.join(SOURCE)).expect("Could not read the source");
+    let mutations: Vec<Mutation> = mutations(&source)
+        .into_iter()
+        .filter(|mutation| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| mutation.describe(&source).contains(filter.as_str()))
+        })
+        .collect();
+
+    if list {
+        for mutation in &mutations {
+            println!("{}", mutation.describe(&source));
+        }
+        return;
+    }
+
+    // mutations are applied to a copy, the working tree stays untouched
+    let work = std::env::temp_dir().join("chip8-mutate-check");
+    let copy = work.join("crate");
+    let _ = std::fs::remove_dir_all(&copy);
+    // the tests read their expected frames from tests/
+    for dir in ["src", "tests"] {
+        copy_dir(&root.join(dir), &copy.join(dir)).expect("Could not copy the sources");
+    }
+    for file in ["Cargo.toml", "Cargo.lock"] {
+        if root.join(file).exists() {
+            std::fs::copy(root.join(file), copy.join(file)).expect("Could not copy the manifest");
+        }
+    }
+    let target_dir = work.join("target");
+
+    // mutations only count as killed if the tests pass without them
+    match run_tests(&copy, &target_dir, timeout) {
+        Outcome::Survived => (),
+        _ => {
+            eprintln!("The tests of the unmutated copy in {} fail", copy.display());
+            std::process::exit(2);
+        }
+    }
+
+    let mut survivors = Vec::new();
+    let (mut killed, mut unviable) = (0, 0);
+    for (i, mutation) in mutations.iter().enumerate() {
+        std::fs::write(copy.join(SOURCE), mutation.apply(&source)).expect("Could not mutate");
+
+        let outcome = run_tests(&copy, &target_dir, timeout);
+        let label = match outcome {
+            Outcome::Killed => "killed",
+            Outcome::TimedOut => "killed (timeout)",
+            Outcome::Survived => "SURVIVED",
+            Outcome::Unviable => "unviable",
+        };
+        println!(
+            "[{}/{}] {} {}",
+            i + 1,
+            mutations.len(),
+            label,
+            mutation.describe(&source)
+        );
+
+        match outcome {
+            Outcome::Killed | Outcome::TimedOut => killed += 1,
+            Outcome::Survived => survivors.push(mutation.describe(&source)),
+            Outcome::Unviable => unviable += 1,
+        }
+    }
+
+    println!(
+        "\n{} mutations: {} killed, {} survived, {} unviable",
+        mutations.len(),
+        killed,
+        survivors.len(),
+        unviable
+    );
+    for survivor in &survivors {
+        println!("survived: {}", survivor);
+    }
+    if !survivors.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mutations() {
+        let source = "\
+impl Interpreter {
+    pub(crate) fn execute(&mut self) {
+        if a == b { // a == b
+            self.set_vf(1);
+        }
+        match x {
+            Operation::Or | Operation::And => y = a | b,
+            Operation::Xor
+            | Operation::Sub => (),
+        }
+    }
+
+    fn other(&self) -> bool {
+        a == b
+    }
+}
+";
+        let mutations = mutations(source);
+
+        assert_eq!(3, mutations.len());
+        assert!(mutations[0].apply(source).contains("if a != b { // a == b"));
+        assert!(mutations[1].apply(source).contains("self.set_vf(0);"));
+        assert!(mutations[2]
+            .apply(source)
+            .contains("Operation::Or | Operation::And => y = a & b,"));
+        assert_eq!(
+            "src/interpreter.rs:4: `set_vf(1)` -> `set_vf(0)` in `self.set_vf(1);`",
+            mutations[1].describe(source)
+        );
+    }
+}
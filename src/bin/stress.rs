@@ -0,0 +1,308 @@
+//! Stress testing of the interpreter: runs many random programs in parallel, checking after
+//! every instruction that the machine stays in a valid state (PC within RAM, stack pointer
+//! within the stack depth, I within 12 bits with --strict). Jump targets mostly land in the
+//! program, so the programs loop through their own code instead of running off into zeros.
+//! Failing programs are minimized before being reported, along with panics.
+//!
+//! Usage: cargo run --release --bin stress -- [--programs N] [--length N] [--steps N]
+//!        [--seed N] [--threads N] [--profile PROFILE] [--strict]
+
+use chip8_interpreter::chip8::Chip8;
+use chip8_interpreter::disassembler::{self, Flow};
+use chip8_interpreter::interpreter::KeyEvent;
+use chip8_interpreter::memory::{MAX_SIZE, PROG_LOC};
+use chip8_interpreter::quirks::Profile;
+use chip8_interpreter::timing::DEFAULT_CYCLES_PER_FRAME;
+
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+
+use std::panic::{self, AssertUnwindSafe};
+
+/// Replaces instructions while minimizing: LD V0, V0 changes nothing
+const NOP: u16 = 0x8000;
+
+/// Failing programs printed at most, the others are only counted
+const MAX_REPORTED: usize = 10;
+
+struct Settings {
+    programs: u64,
+    length: usize, // words per program
+    steps: u32,    // instructions executed per program at most
+    seed: u64,
+    profile: Profile,
+    strict: bool,
+}
+
+/// Invariant broken by a program
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Failure {
+    invariant: &'static str,
+    detail: String,
+    step: u32,
+}
+
+/// Random decodable opcodes, with jump and call targets in the program 7 times out of 8
+fn generate(rng: &mut StdRng, length: usize) -> Vec<u16> {
+    let end = PROG_LOC + 2 * length as u16;
+
+    (0..length)
+        .map(|_| loop {
+            let opcode: u16 = rng.gen();
+            let Some(instruction) = disassembler::decode(opcode) else {
+                continue;
+            };
+            break match instruction.flow() {
+                Flow::Jump(_) | Flow::Call(_) | Flow::Indirect(_) if rng.gen_ratio(7, 8) => {
+                    opcode & 0xF000 | rng.gen_range(PROG_LOC..end) & !1
+                }
+                _ => opcode,
+            };
+        })
+        .collect()
+}
+
+fn check(chip8: &Chip8, strict: bool) -> Result<(), (&'static str, String)> {
+    let interpreter = &chip8.interpreter;
+
+    let size = chip8.memory.size();
+
+    if interpreter.pc() as usize >= size {
+        return Err(("PC outside of RAM", format!("PC {:04X}", interpreter.pc())));
+    }
+    if interpreter.sc() as usize > interpreter.stack_depth() {
+        let detail = format!("SP {} of {}", interpreter.sc(), interpreter.stack_depth());
+        return Err(("SP beyond the stack depth", detail));
+    }
+    // strict writes beyond the end of memory stop the program with an error, FX55 still
+    // moving I past it
+    if strict && !interpreter.stop() && interpreter.index() as usize >= size {
+        return Err(("I beyond 12 bits", format!("I {:04X}", interpreter.index())));
+    }
+
+    Ok(())
+}
+
+/// Run a program with keys pressed at random, until it halts or `steps` instructions
+fn run(program: &[u16], seed: u64, settings: &Settings) -> Option<Failure> {
+    let bytes: Vec<u8> = program.iter().flat_map(|word| word.to_be_bytes()).collect();
+    let mut keys = StdRng::seed_from_u64(seed);
+
+    let mut step = 0;
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut chip8 = Chip8::new();
+        chip8.interpreter.set_quirks(settings.profile.quirks());
+        chip8
+            .interpreter
+            .set_timer_mode(settings.profile.timer_mode());
+        chip8
+            .interpreter
+            .set_stack_depth(settings.profile.stack_depth());
+        chip8
+            .memory
+            .set_address_space(settings.profile.address_space());
+        chip8.interpreter.set_strict_memory(settings.strict);
+        chip8.interpreter.set_seed(seed);
+        chip8.load(&bytes);
+
+        while step < settings.steps && !chip8.interpreter.stop() {
+            if step % DEFAULT_CYCLES_PER_FRAME == 0 {
+                chip8.push_key_event(KeyEvent {
+                    key: keys.gen_range(0..16),
+                    down: keys.gen(),
+                });
+                chip8.begin_frame();
+            }
+            chip8.step();
+            step += 1;
+            check(&chip8, settings.strict)?;
+        }
+        Ok(())
+    }));
+
+    match outcome {
+        Ok(Ok(())) => None,
+        Ok(Err((invariant, detail))) => Some(Failure {
+            invariant,
+            detail,
+            step,
+        }),
+        Err(payload) => Some(Failure {
+            invariant: "panic",
+            detail: payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default(),
+            step,
+        }),
+    }
+}
+
+/// Smallest program found breaking the same invariant: the end cut, then instructions
+/// replaced by NOP one at a time
+fn minimize(mut program: Vec<u16>, seed: u64, settings: &Settings) -> (Vec<u16>, Failure) {
+    let mut failure = run(&program, seed, settings).expect("the program passes");
+    let mut fails = |candidate: &[u16]| match run(candidate, seed, settings) {
+        Some(other) if other.invariant == failure.invariant => {
+            failure = other;
+            true
+        }
+        _ => false,
+    };
+
+    while program.len() > 1 && fails(&program[..program.len() - 1]) {
+        program.pop();
+    }
+    for i in 0..program.len() {
+        if program[i] != NOP {
+            let mut candidate = program.clone();
+            candidate[i] = NOP;
+            if fails(&candidate) {
+                program = candidate;
+            }
+        }
+    }
+
+    (program, failure)
+}
+
+fn report(index: u64, program: &[u16], failure: &Failure) {
+    println!(
+        "program {}: {} ({}) at step {}",
+        index, failure.invariant, failure.detail, failure.step
+    );
+    for (i, &opcode) in program.iter().enumerate() {
+        if opcode != NOP {
+            let mnemonic = disassembler::decode(opcode)
+                .map_or("unknown".to_string(), |instruction| instruction.mnemonic);
+            println!(
+                "  {:03X}: {:04X}  {}",
+                PROG_LOC as usize + 2 * i,
+                opcode,
+                mnemonic
+            );
+        }
+    }
+    let hex: Vec<String> = program.iter().map(|word| format!("{:04X}", word)).collect();
+    println!("  hex: {}", hex.join(" "));
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: stress [--programs N] [--length N] [--steps N] [--seed N] [--threads N] \
+         [--profile PROFILE] [--strict]"
+    );
+    std::process::exit(2);
+}
+
+fn main() {
+    let mut settings = Settings {
+        programs: 10_000,
+        length: 64,
+        steps: 10_000,
+        seed: rand::random(),
+        profile: Profile::Modern,
+        strict: false,
+    };
+    let mut threads = 0; // rayon default, one per core
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--programs" => settings.programs = value().parse().unwrap_or_else(|_| usage()),
+            "--length" => settings.length = value().parse().unwrap_or_else(|_| usage()),
+            "--steps" => settings.steps = value().parse().unwrap_or_else(|_| usage()),
+            "--seed" => settings.seed = value().parse().unwrap_or_else(|_| usage()),
+            "--threads" => threads = value().parse().unwrap_or_else(|_| usage()),
+            "--profile" => {
+                settings.profile = Profile::from_str(&value(), true).unwrap_or_else(|_| usage())
+            }
+            "--strict" => settings.strict = true,
+            _ => usage(),
+        }
+    }
+    if settings.length == 0 || settings.length > (MAX_SIZE - PROG_LOC) as usize / 2 {
+        usage();
+    }
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+        .expect("Could not start the threads");
+    // panics are reported with the failing programs
+    panic::set_hook(Box::new(|_| {}));
+
+    println!(
+        "{} programs of {} instructions, seed {}",
+        settings.programs, settings.length, settings.seed
+    );
+    let mut failures: Vec<(u64, Vec<u16>, Failure)> = (0..settings.programs)
+        .into_par_iter()
+        .filter_map(|index| {
+            let seed = settings.seed.wrapping_add(index);
+            let program = generate(&mut StdRng::seed_from_u64(seed), settings.length);
+            run(&program, seed, &settings)?;
+            let (program, failure) = minimize(program, seed, &settings);
+            Some((index, program, failure))
+        })
+        .collect();
+    failures.sort_by_key(|(_, program, _)| program.iter().filter(|&&word| word != NOP).count());
+
+    for (index, program, failure) in failures.iter().take(MAX_REPORTED) {
+        report(*index, program, failure);
+    }
+    println!(
+        "{} of {} programs broke an invariant",
+        failures.len(),
+        settings.programs
+    );
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(strict: bool) -> Settings {
+        Settings {
+            programs: 1,
+            length: 8,
+            steps: 1000,
+            seed: 0,
+            profile: Profile::Modern,
+            strict,
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let program = generate(&mut StdRng::seed_from_u64(1), 256);
+
+        assert_eq!(256, program.len());
+        assert!(program
+            .iter()
+            .all(|&opcode| disassembler::decode(opcode).is_some()));
+    }
+
+    #[test]
+    fn test_minimize() {
+        let program = vec![
+            0x6A02, // 200: VA = 02
+            0xAFFF, // 202: I = FFF
+            0x6104, // 204: V1 = 04
+            0xF11E, // 206: I += V1
+            0x1200, // 208: jump to 200
+        ];
+        assert_eq!(None, run(&program, 0, &settings(false)));
+
+        let (minimized, failure) = minimize(program, 0, &settings(true));
+        assert_eq!("I beyond 12 bits", failure.invariant);
+        assert_eq!(vec![NOP, 0xAFFF, 0x6104, 0xF11E], minimized);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::memory::{Memory, MAX_SIZE};
+
+use clap::ValueEnum;
+
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+pub mod console;
+
+/// Address space seen by the interpreter: RAM, plus the ranges claimed by peripherals
+pub trait Bus {
+    fn read(&self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, data: u8);
+
+    /// Bits kept of the addresses of words, 12 by default
+    fn address_mask(&self) -> u16 {
+        MAX_SIZE - 1
+    }
+
+    /// The second byte of a word at the end of the address space is read at 0x000
+    fn read_u16(&self, addr: u16) -> u16 {
+        let mask = self.address_mask();
+        let lo = self.read(addr & mask) as u16;
+        let hi = self.read(addr.wrapping_add(1) & mask) as u16;
+
+        lo << 8 | hi
+    }
+
+    /// The second byte of a word at the end of the address space is written at 0x000
+    fn write_u16(&mut self, addr: u16, data: u16) {
+        let mask = self.address_mask();
+        self.write(addr & mask, (data >> 8) as u8);
+        self.write(addr.wrapping_add(1) & mask, data as u8);
+    }
+}
+
+/// Device answering the reads and writes of an address range claimed on the bus,
+/// instead of RAM. Addresses are given as offsets from the start of the range.
+pub trait Peripheral {
+    /// Shown in errors and listings
+    fn name(&self) -> &str;
+
+    /// Value read by the program, open bus (0) for write-only devices
+    fn read(&self, _offset: u16) -> u8 {
+        0
+    }
+
+    fn write(&mut self, offset: u16, data: u8);
+
+    /// Called when the machine is reset
+    fn reset(&mut self) {}
+}
+
+/// Non-standard peripherals a program can opt into, mapped at fixed addresses
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Extension {
+    /// Prints what is written to 0xEF0 (character), 0xEF1 (hexadecimal) and 0xEF2 (decimal)
+    Console,
+}
+
+impl Extension {
+    /// Map the peripheral in memory, printing to `out` for the console
+    pub fn attach<W: Write + 'static>(self, memory: &mut Memory, out: W) {
+        let result = match self {
+            Extension::Console => memory.map(console::RANGE, Box::new(console::Console::new(out))),
+        };
+        result.expect("Extensions have distinct address ranges");
+    }
+}
+
+/// A peripheral and the addresses it claims
+pub(crate) struct Mapping {
+    pub range: RangeInclusive<u16>,
+    pub device: Box<dyn Peripheral>,
+}
+
+impl Mapping {
+    pub fn contains(&self, addr: u16) -> bool {
+        self.range.contains(&addr)
+    }
+
+    pub fn offset(&self, addr: u16) -> u16 {
+        addr - self.range.start()
+    }
+}
@@ -0,0 +1,94 @@
+use crate::bus::Peripheral;
+
+use std::io::Write;
+use std::ops::RangeInclusive;
+
+/// Addresses claimed by the console
+pub const RANGE: RangeInclusive<u16> = 0x0EF0..=0x0EFF;
+
+/// Offsets of the console registers
+pub const CHAR: u16 = 0x0; // byte written as an ASCII character
+pub const HEX: u16 = 0x1; // byte written as two hexadecimal digits
+pub const DEC: u16 = 0x2; // byte written in decimal
+
+/// Write-only device printing what the program writes to it, for printf debugging.
+/// From CHIP-8 code: `AEF0` points I to it, then `F055` writes V0 as a character.
+pub struct Console<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Console<W> {
+    pub fn new(out: W) -> Self {
+        Console { out }
+    }
+}
+
+impl<W: Write> Peripheral for Console<W> {
+    fn name(&self) -> &str {
+        "console"
+    }
+
+    fn write(&mut self, offset: u16, data: u8) {
+        // printing must not stop the emulation (e.g. closed pipe)
+        let _ = match offset {
+            CHAR => self.out.write_all(&[data]),
+            HEX => write!(self.out, "{:02X}", data),
+            DEC => write!(self.out, "{}", data),
+            _ => return,
+        };
+        if data == b'\n' || offset != CHAR {
+            let _ = self.out.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::memory::Memory;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Output shared with the test once the console is mapped
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_console() {
+        let output = Output::default();
+        let mut mem = Memory::new();
+        mem.map(RANGE, Box::new(Console::new(output.clone())))
+            .unwrap();
+        mem.load_prog(&[
+            0xAE, 0xF0, // I = console character register
+            0x60, 0x48, // V0 = 'H'
+            0xF0, 0x55, // write V0
+            0x60, 0x69, // V0 = 'i'
+            0xF0, 0x55, // write V0
+            0x60, 0x2A, // V0 = 42
+            0xAE, 0xF2, // I = console decimal register
+            0xF0, 0x55, // write V0
+            0x00, 0x00,
+        ]);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_quirks(crate::quirks::Profile::Modern.quirks());
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(b"Hi42".to_vec(), *output.0.borrow());
+    }
+}
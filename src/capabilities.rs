@@ -0,0 +1,200 @@
+use crate::batch::ROM_EXTENSIONS;
+use crate::bus;
+use crate::executor::ExecutorKind;
+use crate::interpreter::Interpreter;
+use crate::opcodes::{Extension, OPCODES};
+use crate::quirks::{Profile, Quirks};
+use crate::rawvideo::PixelFormat;
+use crate::scaler::Filter;
+
+use clap::ValueEnum;
+
+use std::fmt::{self, Write};
+
+/// What this build supports, for launchers and scripts to adapt to the binary
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    pub version: String,
+    pub extensions: Vec<String>, // instruction sets executed in full
+    pub disassembly_only: Vec<String>, // the others, decoded but not all executed
+    pub profiles: Vec<String>,
+    pub quirks: Vec<String>,
+    pub executors: Vec<String>,      // the jit one only when built with it
+    pub peripherals: Vec<String>,    // mapped in memory by --extensions
+    pub audio_backends: Vec<String>, // none yet, the buzzer is only shown
+    pub renderers: Vec<String>,
+    pub filters: Vec<String>,
+    pub formats: Vec<(String, Vec<String>)>, // kind of file, extensions
+}
+
+impl Capabilities {
+    /// Capabilities of the running binary
+    pub fn current() -> Self {
+        let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let formats = [
+            ("programs", &ROM_EXTENSIONS[..]),
+            ("patches", &["ips", "bps"]),
+            ("palettes", &["png", "hex"]),
+            ("movies", &["c8m"]),
+            ("states", &["state"]),
+            ("screenshots", &["png"]),
+            ("reports", &["md", "html"]),
+        ];
+        // the window, --pipe-frames and --shmem
+        let mut renderers = vec!["window".to_string()];
+        renderers.extend(
+            value_names::<PixelFormat>()
+                .iter()
+                .map(|format| format!("rawvideo-{}", format)),
+        );
+        renderers.push("shmem".to_string());
+
+        // from what the interpreter runs, not from what the instruction table decodes
+        let (executed, decoded): (Vec<Extension>, Vec<Extension>) =
+            [Extension::Chip8, Extension::Schip, Extension::XoChip]
+                .into_iter()
+                .partition(|&extension| {
+                    OPCODES
+                        .iter()
+                        .filter(|row| row.extension == extension)
+                        .all(|row| Interpreter::executes(row.value))
+                });
+        let names = |extensions: Vec<Extension>| {
+            extensions
+                .iter()
+                .map(|extension| extension.name().to_string())
+                .collect()
+        };
+
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            extensions: names(executed),
+            disassembly_only: names(decoded),
+            profiles: Profile::ALL
+                .iter()
+                .map(|profile| profile.name().to_string())
+                .collect(),
+            quirks: strings(&Quirks::NAMES),
+            executors: value_names::<ExecutorKind>(),
+            peripherals: value_names::<bus::Extension>(),
+            audio_backends: Vec::new(),
+            renderers,
+            filters: value_names::<Filter>(),
+            formats: formats
+                .iter()
+                .map(|(kind, extensions)| (kind.to_string(), strings(extensions)))
+                .collect(),
+        }
+    }
+
+    /// The report as a JSON object, the formats as an object of lists of extensions
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        writeln!(out, "  \"version\": {},", json_string(&self.version)).unwrap();
+        for (key, values) in self.lists() {
+            writeln!(out, "  \"{}\": {},", key, json_array(values)).unwrap();
+        }
+        out.push_str("  \"formats\": {\n");
+        for (i, (kind, extensions)) in self.formats.iter().enumerate() {
+            let comma = if i + 1 < self.formats.len() { "," } else { "" };
+            writeln!(
+                out,
+                "    {}: {}{}",
+                json_string(kind),
+                json_array(extensions),
+                comma
+            )
+            .unwrap();
+        }
+        out.push_str("  }\n}\n");
+        out
+    }
+
+    fn lists(&self) -> [(&'static str, &[String]); 9] {
+        [
+            ("extensions", &self.extensions),
+            ("disassembly_only", &self.disassembly_only),
+            ("profiles", &self.profiles),
+            ("quirks", &self.quirks),
+            ("executors", &self.executors),
+            ("peripherals", &self.peripherals),
+            ("audio_backends", &self.audio_backends),
+            ("renderers", &self.renderers),
+            ("filters", &self.filters),
+        ]
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version: {}", self.version)?;
+        for (key, values) in self.lists() {
+            let values = match values {
+                [] => "none".to_string(),
+                _ => values.join(", "),
+            };
+            writeln!(f, "{}: {}", key.replace('_', " "), values)?;
+        }
+        writeln!(f, "formats:")?;
+        for (kind, extensions) in &self.formats {
+            writeln!(f, "  {}: {}", kind, extensions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Command line names of the values of an option
+fn value_names<T: ValueEnum>() -> Vec<String> {
+    T::value_variants()
+        .iter()
+        .filter_map(ValueEnum::to_possible_value)
+        .map(|value| value.get_name().to_string())
+        .collect()
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_array(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|value| json_string(value)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = Capabilities::current();
+        assert_eq!(vec!["CHIP-8"], capabilities.extensions);
+        assert_eq!(vec!["SUPER-CHIP", "XO-CHIP"], capabilities.disassembly_only);
+        assert!(capabilities.executors.contains(&"threaded".to_string()));
+        assert_eq!(
+            cfg!(feature = "jit"),
+            capabilities.executors.contains(&"jit".to_string())
+        );
+
+        let json = capabilities.to_json();
+        assert!(json.contains("  \"quirks\": [\"vf_reset\", \"memory_increment\", "));
+        assert!(json.contains("  \"audio_backends\": [],\n"));
+        assert!(json.ends_with("    \"reports\": [\"md\", \"html\"]\n  }\n}\n"));
+        assert_eq!("\"a\\\"b\\u000a\"", json_string("a\"b\n"));
+
+        let text = capabilities.to_string();
+        assert!(text.contains("\naudio backends: none\n"));
+        assert!(text.contains("\ndisassembly only: SUPER-CHIP, XO-CHIP\n"));
+        assert!(text.contains("\n  patches: ips, bps\n"));
+    }
+}
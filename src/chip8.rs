@@ -0,0 +1,709 @@
+use crate::error::{Error, HaltReason};
+use crate::executor::{Executor, Interpreted};
+use crate::font::Fonts;
+use crate::framebuffer::{self, FrameBuffer};
+use crate::interpreter::{Interpreter, KeyEvent};
+use crate::memory::{self, MemInit, Memory, MemoryMap};
+use crate::metrics::Metrics;
+use crate::plugin::Plugin;
+use crate::snapshot;
+use crate::timing::{CycleBudget, Jitter};
+
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+
+/// Which states of the display within a frame `Chip8::captured_display` holds
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DisplayCapture {
+    /// None, the display memory at the end of the frame is the one shown
+    #[default]
+    Off,
+    /// Each row as a CRT beam going down the screen during the frame saw it, copied once its
+    /// part of the frame instructions ran: sprites erased and drawn again within a frame go
+    /// missing from the rows scanned in between, as they flickered on the original hardware
+    Scanout,
+    /// The pixels lit at any point of the frame: at its start, at its end, and the ones DXYN
+    /// erased during it, so sprites erased to be drawn again don't flicker
+    Merged,
+}
+
+/// Interpreter and memory, with the plugins observing them
+pub struct Chip8 {
+    pub interpreter: Interpreter,
+    pub memory: Memory,
+    program: Vec<u8>, // kept to reset
+    fonts: Fonts,     // reloaded on reset
+    breakpoints: BTreeSet<u16>,
+    past_breakpoint: bool, // resumed on a breakpoint, its instruction runs next
+    pc_guard: Option<Vec<RangeInclusive<u16>>>, // regions allowed besides the program
+    executor: Box<dyn Executor>,
+    plugins: Vec<Box<dyn Plugin>>,
+    capture: DisplayCapture,
+    captured: FrameBuffer, // display of the last frame, as `capture` sees it
+    budget: Option<CycleBudget>, // jitter of the cycles of each frame
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        let mut memory = Memory::new();
+        memory.load_fonts(&MemoryMap::default());
+
+        Chip8 {
+            interpreter: Interpreter::new(),
+            memory,
+            program: Vec::new(),
+            fonts: Fonts::default(),
+            breakpoints: BTreeSet::new(),
+            past_breakpoint: false,
+            pc_guard: None,
+            executor: Box::new(Interpreted),
+            plugins: Vec::new(),
+            capture: DisplayCapture::Off,
+            captured: FrameBuffer::new(),
+            budget: None,
+        }
+    }
+
+    /// Change how instructions are executed, `Interpreted` by default
+    pub fn set_executor(&mut self, executor: Box<dyn Executor>) {
+        self.executor = executor;
+    }
+
+    /// Move the fonts, loading them at their new place for FX29 and FX30 to find them
+    pub fn set_memory_map(&mut self, map: MemoryMap) {
+        self.interpreter.set_memory_map(map);
+        self.fonts.load(&mut self.memory, &map);
+    }
+
+    /// Replace the glyphs of the fonts, loading them at once
+    pub fn set_fonts(&mut self, fonts: Fonts) {
+        self.fonts = fonts;
+        self.fonts
+            .load(&mut self.memory, &self.interpreter.memory_map());
+    }
+
+    /// Fill the memory below the display area according to `init` and reload the fonts,
+    /// before loading a program. Resets clear the memory the same way.
+    pub fn set_mem_init(&mut self, init: MemInit) {
+        self.memory.set_init(init);
+        self.fonts
+            .load(&mut self.memory, &self.interpreter.memory_map());
+    }
+
+    /// Halt with `HaltReason::Breakpoint` whenever the program counter reaches `addr`
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Halt with `Error::PcOutOfRegion` as soon as the program counter leaves the loaded
+    /// program and the `allowed` regions, e.g. after a bad BNNN target or a corrupted stack.
+    /// None removes the guard.
+    pub fn set_pc_guard(&mut self, allowed: Option<Vec<RangeInclusive<u16>>>) {
+        self.pc_guard = allowed;
+    }
+
+    /// Vary the instructions run by each `run_frame` randomly, by up to `jitter.percent`
+    /// percent of its cycles. None runs exactly the cycles given.
+    pub fn set_jitter(&mut self, jitter: Option<Jitter>) {
+        self.budget = jitter.map(CycleBudget::new);
+    }
+
+    pub fn jitter(&self) -> Option<Jitter> {
+        self.budget.as_ref().map(CycleBudget::jitter)
+    }
+
+    /// Stop the program with `HaltReason::UserRequested`
+    pub fn halt(&mut self) {
+        self.interpreter.halt(HaltReason::UserRequested);
+    }
+
+    /// Execute instructions again after a breakpoint or `halt`, starting with the one at PC
+    pub fn resume(&mut self) {
+        if matches!(
+            self.interpreter.halt_reason(),
+            Some(HaltReason::Breakpoint { .. } | HaltReason::UserRequested)
+        ) {
+            self.interpreter.resume();
+            self.past_breakpoint = true;
+        }
+    }
+
+    pub fn register_plugin(&mut self, plugin: Box<dyn Plugin>) {
+        self.plugins.push(plugin);
+    }
+
+    pub fn load(&mut self, program: &[u8]) {
+        self.program = program.to_vec();
+        self.memory.load_prog(program);
+        self.executor.invalidate();
+
+        for plugin in &mut self.plugins {
+            plugin.on_load(program, &mut self.memory);
+        }
+    }
+
+    /// The loaded program
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Hash of the machine state, equal across platforms for equal states
+    pub fn state_hash(&self) -> u64 {
+        snapshot::state_hash(&self.interpreter, &self.memory)
+    }
+
+    /// Counters of the work done since the machine was created or `reset_metrics`
+    pub fn metrics(&self) -> &Metrics {
+        self.interpreter.metrics()
+    }
+
+    pub fn reset_metrics(&mut self) {
+        self.interpreter.metrics.reset();
+    }
+
+    /// Restart the program from the memory as it is, as a warm reset: the registers, stack,
+    /// timers and keys are cleared, the RAM and the display are kept, and the settings
+    /// (quirks, fonts location, stack depth...) are kept as with `reset_hard`
+    pub fn reset_soft(&mut self) {
+        self.interpreter.reset();
+        self.past_breakpoint = false;
+    }
+
+    /// Restart the loaded program from a cleared memory, with the fonts and the program
+    /// loaded again
+    pub fn reset_hard(&mut self) {
+        self.interpreter.reset();
+        self.past_breakpoint = false;
+        self.memory.clear();
+        self.fonts
+            .load(&mut self.memory, &self.interpreter.memory_map());
+
+        let program = std::mem::take(&mut self.program);
+        self.load(&program);
+    }
+
+    /// Execute the next instruction, unless the interpreter is stopped.
+    /// Returns why the interpreter halted, if it did during this instruction.
+    pub fn step(&mut self) -> Option<HaltReason> {
+        if self.interpreter.stop() {
+            return None;
+        }
+
+        let pc = self.interpreter.pc;
+        if self.breakpoints.contains(&pc) && !std::mem::take(&mut self.past_breakpoint) {
+            self.interpreter.halt(HaltReason::Breakpoint { pc });
+            return self.interpreter.halt_reason().cloned();
+        }
+        self.past_breakpoint = false;
+
+        let opcode = match self.interpreter.waiting_vblank() {
+            true => None,
+            false => Some(self.interpreter.next(&self.memory)),
+        };
+        if let Some(opcode) = opcode {
+            self.interpreter.metrics.instructions += 1;
+            for plugin in &mut self.plugins {
+                plugin.on_instruction(pc, opcode, &self.interpreter, &self.memory);
+            }
+        }
+
+        self.executor.step(&mut self.interpreter, &mut self.memory);
+        if let Some(opcode) = opcode {
+            for plugin in &mut self.plugins {
+                plugin.after_instruction(pc, opcode, &self.interpreter, &self.memory);
+            }
+        }
+        self.check_pc_guard(pc);
+        self.check_self_jump();
+        self.interpreter.halt_reason().cloned()
+    }
+
+    /// Halt if the instruction at `from` sent the program counter out of the guarded regions
+    fn check_pc_guard(&mut self, from: u16) {
+        let Some(allowed) = &self.pc_guard else {
+            return;
+        };
+
+        let pc = self.interpreter.pc;
+        // as usize, the end of a 64K program being past u16::MAX
+        let program = memory::PROG_LOC as usize..memory::PROG_LOC as usize + self.program.len();
+        if !self.interpreter.stop()
+            && !program.contains(&(pc as usize))
+            && !allowed.iter().any(|region| region.contains(&pc))
+        {
+            self.interpreter
+                .halt(HaltReason::Error(Error::PcOutOfRegion { pc, from }));
+        }
+    }
+
+    /// Halt before a jump to itself, the program would loop there forever
+    fn check_self_jump(&mut self) {
+        let pc = self.interpreter.pc;
+        if !self.interpreter.stop()
+            && pc < 0x1000
+            && self.interpreter.next(&self.memory) == 0x1000 | pc
+        {
+            self.interpreter.halt(HaltReason::SelfJump { pc });
+        }
+    }
+
+    /// Keep a copy of the display of each frame other than its end state, `Off` by default
+    pub fn set_display_capture(&mut self, capture: DisplayCapture) {
+        if capture != self.capture {
+            self.capture = capture;
+            self.captured = FrameBuffer::from_memory(&self.memory);
+        }
+    }
+
+    /// The display of the last frame as set with `set_display_capture`, None when `Off`
+    pub fn captured_display(&self) -> Option<&FrameBuffer> {
+        match self.capture {
+            DisplayCapture::Off => None,
+            DisplayCapture::Scanout | DisplayCapture::Merged => Some(&self.captured),
+        }
+    }
+
+    /// Scan the rows from `row` on whose part of the frame ran after `cycle` of `cycles`
+    /// instructions, returns the next row to scan
+    fn scan_rows(&mut self, mut row: usize, cycle: u32, cycles: u32) -> usize {
+        if self.capture != DisplayCapture::Scanout {
+            return row;
+        }
+
+        while row < framebuffer::HEIGHT && scan_cycle(row, cycles) <= cycle {
+            self.captured.copy_row(row, &self.memory);
+            row += 1;
+        }
+        row
+    }
+
+    /// Start a frame: tick the timers, then sample the keys pressed and released since the
+    /// previous one, right before the instructions of the frame
+    pub fn begin_frame(&mut self) {
+        self.interpreter.decrement_timers();
+        self.interpreter.sample_keys();
+    }
+
+    /// Run one 60 Hz frame: `begin_frame`, then execute `cycles` instructions, give or take
+    /// the jitter. Without plugins observing each instruction, breakpoints nor PC guard, the
+    /// executor may run several at once. Returns why the interpreter halted, if it did during
+    /// this frame.
+    pub fn run_frame(&mut self, cycles: u32) -> Option<HaltReason> {
+        self.begin_frame();
+        let cycles = match &mut self.budget {
+            Some(budget) => budget.next(cycles),
+            None => cycles,
+        };
+        let was_stopped = self.interpreter.stop();
+
+        // pixels erased during the frame are the collisions recorded from here
+        let erased_from = self.interpreter.collisions().len();
+        let recording = self.interpreter.record_collisions();
+        if self.capture == DisplayCapture::Merged {
+            self.captured = FrameBuffer::from_memory(&self.memory);
+            self.interpreter.set_record_collisions(true);
+        }
+
+        let mut cycle = 0;
+        let mut row = 0; // next row of the scanout
+        loop {
+            row = self.scan_rows(row, cycle, cycles);
+            if cycle >= cycles {
+                break;
+            }
+            // the executor stops where the beam scans the next row
+            let until = match self.capture {
+                DisplayCapture::Scanout => scan_cycle(row, cycles),
+                DisplayCapture::Off | DisplayCapture::Merged => cycles,
+            };
+
+            self.interpreter
+                .set_frame_phase(cycle as f32 / cycles as f32);
+
+            if self.plugins.is_empty()
+                && self.breakpoints.is_empty()
+                && self.pc_guard.is_none()
+                && !self.interpreter.stop()
+            {
+                let waiting = self.interpreter.waiting_vblank();
+                let executed =
+                    self.executor
+                        .run(&mut self.interpreter, &mut self.memory, until - cycle);
+                if !waiting {
+                    self.interpreter.metrics.instructions += executed as u64;
+                }
+                cycle += executed;
+                self.check_self_jump();
+            } else {
+                self.step();
+                cycle += 1;
+            }
+        }
+
+        if self.capture == DisplayCapture::Merged {
+            self.captured.merge(&FrameBuffer::from_memory(&self.memory));
+            for &(x, y) in &self.interpreter.collisions()[erased_from..] {
+                self.captured.set_pixel(x, y, true);
+            }
+            // only kept for this frame unless someone else takes them
+            if !recording {
+                self.interpreter.take_collisions();
+            }
+            self.interpreter.set_record_collisions(recording);
+        }
+
+        match was_stopped {
+            true => None,
+            false => self.interpreter.halt_reason().cloned(),
+        }
+    }
+
+    /// Called at the end of every 60 Hz frame
+    pub fn frame(&mut self) {
+        self.interpreter.metrics.frames += 1;
+        for plugin in &mut self.plugins {
+            plugin.on_frame(&mut self.interpreter, &mut self.memory);
+        }
+
+        let events: Vec<KeyEvent> = self
+            .plugins
+            .iter_mut()
+            .flat_map(|plugin| plugin.take_key_events())
+            .collect();
+        for event in events {
+            self.push_key_event(event);
+        }
+    }
+
+    /// Queue a key press or release for the next frame
+    pub fn push_key_event(&mut self, event: KeyEvent) {
+        for plugin in &mut self.plugins {
+            plugin.on_key(event.key as usize, event.down);
+        }
+
+        self.interpreter.push_key_event(event);
+    }
+}
+
+/// Instructions of a frame of `cycles` run before the beam scans `row`, the last row being
+/// scanned at the end of the frame
+fn scan_cycle(row: usize, cycles: u32) -> u32 {
+    ((row as u64 + 1) * cycles as u64 / framebuffer::HEIGHT as u64) as u32
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Chip8::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::AddressSpace;
+    use crate::rom_builder::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder {
+        events: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Plugin for Recorder {
+        fn on_load(&mut self, program: &[u8], _memory: &mut Memory) {
+            self.events
+                .borrow_mut()
+                .push(format!("load {}", program.len()));
+        }
+
+        fn on_frame(&mut self, _interpreter: &mut Interpreter, _memory: &mut Memory) {
+            self.events.borrow_mut().push("frame".to_string());
+        }
+
+        fn on_instruction(
+            &mut self,
+            pc: u16,
+            opcode: u16,
+            _interpreter: &Interpreter,
+            _memory: &Memory,
+        ) {
+            self.events
+                .borrow_mut()
+                .push(format!("{:04X}: {:04X}", pc, opcode));
+        }
+    }
+
+    #[test]
+    fn test_plugin_hooks() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(Recorder {
+            events: Rc::clone(&events),
+        }));
+        chip8.load(&[0x60, 0xC0, 0x00, 0x00]);
+
+        while !chip8.interpreter.stop() {
+            chip8.step();
+        }
+        chip8.step();
+        chip8.frame();
+
+        assert_eq!(
+            vec!["load 4", "0200: 60C0", "0202: 0000", "frame"],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_keys_sampled_before_batch() {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                ld_vx(0, 5), // 200
+                skp(0),      // 202
+                jp(0x202),   // 204: wait for key 5
+                ld_vx(1, 1), // 206
+                halt(),      // 208
+            ]
+            .concat(),
+        );
+        chip8.run_frame(10);
+        assert!(!chip8.interpreter.stop());
+
+        // a tap between two frames is seen by the next one
+        chip8.push_key_event(KeyEvent { key: 5, down: true });
+        chip8.push_key_event(KeyEvent {
+            key: 5,
+            down: false,
+        });
+        chip8.run_frame(10);
+        assert!(chip8.interpreter.stop());
+        assert_eq!(1, chip8.interpreter.vx[1]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut chip8 = Chip8::new();
+        chip8.interpreter.set_stack_depth(1);
+        chip8.load(&[ld_vx(0, 0xC0), ld_i(0x500), store(0), halt()].concat());
+
+        while !chip8.interpreter.stop() {
+            chip8.step();
+        }
+        chip8.reset_hard();
+
+        assert!(!chip8.interpreter.stop());
+        assert_eq!(0x200, chip8.interpreter.pc);
+        assert_eq!(0x00, chip8.memory.read(0x500));
+        assert_eq!(0x60, chip8.memory.read(0x200));
+        assert_eq!(memory::FONT[0], chip8.memory.read(memory::FONT_LOC));
+        assert_eq!(memory::BIG_FONT[0], chip8.memory.read(memory::BIG_FONT_LOC));
+    }
+
+    #[test]
+    fn test_reset_soft() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx(0, 0xC0), ld_i(0x200), store(0), halt()].concat());
+
+        while !chip8.interpreter.stop() {
+            chip8.step();
+        }
+        chip8.reset_soft();
+
+        assert!(!chip8.interpreter.stop());
+        assert_eq!(0x200, chip8.interpreter.pc);
+        assert_eq!(0, chip8.interpreter.vx[0]);
+        assert_eq!(0, chip8.interpreter.index());
+        // the byte the program overwrote in its first instruction is kept
+        assert_eq!(0xC0, chip8.memory.read(0x200));
+
+        chip8.reset_hard();
+        assert_eq!(0x60, chip8.memory.read(0x200));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                ld_i(0x200),  // 200: sprite of a row
+                drw(0, 0, 1), // 202
+                drw(0, 0, 1), // 204: erases the row
+                ld_vx_k(1),   // 206: waits for a key
+            ]
+            .concat(),
+        );
+
+        for _ in 0..6 {
+            chip8.step();
+        }
+        let metrics = chip8.metrics();
+        assert_eq!(6, metrics.instructions);
+        assert_eq!(2, metrics.draws);
+        assert_eq!(1, metrics.collisions);
+        assert_eq!(3, metrics.key_waits);
+
+        // the executor running whole frames counts the same way
+        chip8.run_frame(10);
+        chip8.frame();
+        assert_eq!(16, chip8.metrics().instructions);
+        assert_eq!(13, chip8.metrics().key_waits);
+        assert_eq!(1, chip8.metrics().frames);
+
+        // kept across resets of the program, until the embedder clears them
+        chip8.reset_hard();
+        assert_eq!(16, chip8.metrics().instructions);
+        chip8.reset_metrics();
+        assert_eq!(Metrics::new(), *chip8.metrics());
+    }
+
+    #[test]
+    fn test_scanout() {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                ld_f(0),      // 200: sprite of the digit 0, F0 90 90 90 F0
+                drw(0, 0, 5), // 202
+                drw(0, 0, 5), // 204: erases it
+                jp(0x202),    // 206
+            ]
+            .concat(),
+        );
+        chip8.set_display_capture(DisplayCapture::Scanout);
+
+        // row y is scanned after y + 1 instructions: the digit is drawn after 2, 5, ... 32
+        chip8.run_frame(32);
+        let frame = FrameBuffer::from_memory(&chip8.memory);
+        let scanout = chip8.captured_display().unwrap();
+        assert!((0..5).all(|y| frame.pixel(0, y)));
+        assert_eq!(
+            vec![false, true, false, false, true],
+            (0..5).map(|y| scanout.pixel(0, y)).collect::<Vec<_>>()
+        );
+
+        // the executor runs the same instructions as without the scanout
+        let mut smooth = Chip8::new();
+        smooth.load(chip8.program());
+        smooth.run_frame(32);
+        assert_eq!(chip8.state_hash(), smooth.state_hash());
+
+        chip8.set_display_capture(DisplayCapture::Off);
+        assert_eq!(None, chip8.captured_display());
+    }
+
+    #[test]
+    fn test_merged_display() {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                ld_f(0),      // 200: sprite of the digit 0, F0 90 90 90 F0
+                drw(0, 0, 5), // 202
+                drw(0, 0, 5), // 204: erases it
+                jp(0x202),    // 206
+            ]
+            .concat(),
+        );
+        chip8.set_display_capture(DisplayCapture::Merged);
+        let digit = |frame: &FrameBuffer| (0..5).all(|y| frame.pixel(0, y));
+
+        // drawn and erased within the frame, blank at its end but kept in the merged display
+        chip8.run_frame(3);
+        assert!(!digit(&FrameBuffer::from_memory(&chip8.memory)));
+        assert!(digit(chip8.captured_display().unwrap()));
+
+        // a frame without drawing is blank
+        chip8.run_frame(1);
+        assert_eq!(&FrameBuffer::new(), chip8.captured_display().unwrap());
+
+        // drawn in the frame before and erased in this one
+        chip8.run_frame(1);
+        assert!(digit(&FrameBuffer::from_memory(&chip8.memory)));
+        chip8.run_frame(1);
+        assert!(!digit(&FrameBuffer::from_memory(&chip8.memory)));
+        assert!(digit(chip8.captured_display().unwrap()));
+    }
+
+    #[test]
+    fn test_custom_fonts() {
+        let mut fonts = Fonts::new();
+        fonts.small[0] = 0xAA;
+        fonts.big[0] = 0xBB;
+
+        let mut chip8 = Chip8::new();
+        chip8.set_fonts(fonts);
+        chip8.load(&halt());
+        chip8.reset_hard();
+
+        assert_eq!(0xAA, chip8.memory.read(memory::FONT_LOC));
+        assert_eq!(0xBB, chip8.memory.read(memory::BIG_FONT_LOC));
+    }
+
+    #[test]
+    fn test_halt_reasons() {
+        let mut chip8 = Chip8::new();
+        chip8.add_breakpoint(0x202);
+        chip8.load(&[ld_vx(0, 1), add_vx(0, 1), jp(0x206), jp(0x206)].concat());
+
+        assert_eq!(None, chip8.step());
+        assert_eq!(Some(HaltReason::Breakpoint { pc: 0x202 }), chip8.step());
+        assert_eq!(None, chip8.step());
+        assert_eq!(1, chip8.interpreter.vx[0]);
+
+        chip8.resume();
+        assert_eq!(None, chip8.step());
+        assert_eq!(2, chip8.interpreter.vx[0]);
+        assert_eq!(Some(HaltReason::SelfJump { pc: 0x206 }), chip8.step());
+        assert_eq!(None, chip8.run_frame(10));
+
+        // faults cannot be resumed
+        chip8.remove_breakpoint(0x202);
+        chip8.reset_hard();
+        chip8.memory.load_prog(&ret());
+        assert_eq!(
+            Some(HaltReason::Error(Error::StackUnderflow { pc: 0x200 })),
+            chip8.run_frame(10)
+        );
+        chip8.resume();
+        assert!(chip8.interpreter.stop());
+    }
+
+    #[test]
+    fn test_pc_guard() {
+        // V0 = 0x40 sends BNNN to 0x240, outside the program
+        let program = [ld_vx(0, 0x40), add_vx(1, 1), jp_v0(0x200), jp(0x202)].concat();
+
+        let mut chip8 = Chip8::new();
+        chip8.set_pc_guard(Some(Vec::new()));
+        chip8.load(&program);
+        assert_eq!(
+            Some(HaltReason::Error(Error::PcOutOfRegion {
+                pc: 0x240,
+                from: 0x204
+            })),
+            chip8.run_frame(10)
+        );
+        assert_eq!(1, chip8.interpreter.vx[1]);
+
+        // code copied there on purpose
+        chip8.set_pc_guard(Some(vec![0x240..=0x241]));
+        chip8.reset_hard();
+        chip8.memory.write(0x240, 0x12);
+        chip8.memory.write(0x241, 0x02);
+        assert_eq!(None, chip8.run_frame(10));
+
+        // a program filling the 64K space up to FFFF, run through to its last instruction
+        let mut program = ld_vx(0, 0).repeat(0x7F00);
+        program[0xFFFE - memory::PROG_LOC as usize..].copy_from_slice(&jp(0x200));
+        chip8.memory.set_address_space(AddressSpace::XoChip);
+        chip8.load(&program);
+        chip8.reset_hard();
+        assert_eq!(None, chip8.run_frame(0x7F00));
+        assert_eq!(0x200, chip8.interpreter.pc);
+    }
+}
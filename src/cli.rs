@@ -0,0 +1,610 @@
+use chip8_interpreter::audio::{self, TurboAudio};
+use chip8_interpreter::bus::Extension;
+use chip8_interpreter::demos::DemoKind;
+use chip8_interpreter::executor::ExecutorKind;
+use chip8_interpreter::font;
+use chip8_interpreter::keyconf;
+use chip8_interpreter::memory::{self, DisplayBuffer, MemInit};
+use chip8_interpreter::plugin::cheat::Cheat;
+use chip8_interpreter::plugin::speedrun::Condition;
+use chip8_interpreter::quirks::Profile;
+use chip8_interpreter::rawvideo::PixelFormat;
+use chip8_interpreter::scaler::Filter;
+use chip8_interpreter::tiles::TileSpec;
+use chip8_interpreter::timing::{self, FrameLimit, TimerMode, TimerRate};
+
+use clap::{ArgAction, Parser, Subcommand};
+
+use winit::event::VirtualKeyCode;
+
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// A CHIP-8 interpreter
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+#[command(subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the .ch8 program, or a .c8c container bringing its settings
+    #[arg(required = true)]
+    pub rom: Option<PathBuf>,
+
+    /// Quirk profile
+    #[arg(long, value_enum, default_value_t = Profile::Modern)]
+    pub profile: Profile,
+
+    /// Run a second instance with another quirk profile side by side, with mirrored input.
+    /// Emulation pauses on the first frame where the displays differ (Space resumes).
+    #[arg(long, value_enum, value_name = "PROFILE")]
+    pub dual: Option<Profile>,
+
+    /// Run another ROM in a tile of the same window, with the given quirk profile or the
+    /// one of --profile. Repeatable. Tab moves the keyboard focus to the next tile.
+    #[arg(long, value_name = "ROM[@PROFILE]", conflicts_with = "dual")]
+    pub tile: Vec<TileSpec>,
+
+    /// Instructions executed per frame, at the rate of --timer-hz: the same count runs 5/6 as
+    /// many instructions per second at 50 Hz
+    #[arg(long, value_name = "CYCLES", default_value_t = timing::DEFAULT_CYCLES_PER_FRAME)]
+    pub cycles_per_frame: u32,
+
+    /// Vary the instructions of each frame randomly by up to PERCENT percent of
+    /// --cycles-per-frame, to test programs against the timings of other machines
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub jitter: Option<u8>,
+
+    /// Seed of the --jitter variations, to repeat a run (default: random, printed at start)
+    #[arg(long, value_name = "SEED", requires = "jitter")]
+    pub jitter_seed: Option<u64>,
+
+    /// Run IPS instructions per second (default 2, up to 60) to learn how the program works,
+    /// showing how PC moves, the bytes of each instruction in memory and what it does
+    #[arg(long, value_name = "IPS", value_parser = clap::value_parser!(u32).range(1..=60), conflicts_with_all = ["dual", "tile", "record_movie", "pipe_frames"])]
+    pub teach: Option<Option<u32>>,
+
+    /// Delay timer granularity seen by FX07 (default: from the profile, frame for vip and
+    /// schip, interpolated for the others)
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub timer_mode: Option<TimerMode>,
+
+    /// Rate of the timers and of the display waits, 50 for programs tuned on PAL machines.
+    /// --cycles-per-frame is kept as is, so raise it by 6/5 for the same speed
+    #[arg(long, value_enum, value_name = "HZ", default_value = "60")]
+    pub timer_hz: TimerRate,
+
+    /// Number of stack levels, calls beyond it stop the program (default: from the profile,
+    /// 12 for vip and 16 for the others)
+    #[arg(long, value_name = "LEVELS")]
+    pub stack_depth: Option<usize>,
+
+    /// DXYN sets VF to the number of sprite rows colliding or clipped at the bottom, as
+    /// SUPER-CHIP in high resolution, instead of 1. Off in every profile, for programs made
+    /// for it: there is no high resolution mode, and SUPER-CHIP sets 1 in low resolution.
+    #[arg(long)]
+    pub collision_rows: bool,
+
+    /// IPS or BPS patch applied to the program in memory, the file staying untouched
+    /// (repeatable, applied in order)
+    #[arg(long = "patch", value_name = "FILE")]
+    pub patches: Vec<PathBuf>,
+
+    /// Palette image (.png, the first two or four pixels define the colors) or .hex palette file
+    #[arg(long, value_name = "FILE")]
+    pub palette_file: Option<PathBuf>,
+
+    /// Record the run in a movie file (.c8m) written on exit: the starting state, settings,
+    /// random seed and key presses of each frame, replayed exactly by `play`
+    #[arg(long, value_name = "FILE", conflicts_with = "cheats")]
+    pub record_movie: Option<PathBuf>,
+
+    /// Font replacing the built-in one: 80 bytes for the small font, 240 for the small and
+    /// big fonts
+    #[arg(long, value_name = "FILE")]
+    pub font_file: Option<PathBuf>,
+
+    /// Keep keys held for this many frames after release (debounces flickering input)
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    pub key_release_delay: u8,
+
+    /// How instructions are executed
+    #[arg(long, value_enum, default_value_t = ExecutorKind::Interpreter)]
+    pub executor: ExecutorKind,
+
+    /// Lengthen beeps shorter than this many frames, as the COSMAC VIP buzzer does not respond
+    /// to a sound timer of 1 (0 plays the sound timer as is)
+    #[arg(long, value_name = "FRAMES", default_value_t = audio::DEFAULT_MIN_BEEP_FRAMES)]
+    pub min_beep_frames: u8,
+
+    /// Non-standard peripherals mapped in memory (comma separated): `console` prints what the
+    /// program writes to 0xEF0 (character), 0xEF1 (hexadecimal) and 0xEF2 (decimal)
+    #[arg(long, value_enum, value_name = "EXTENSIONS", value_delimiter = ',')]
+    pub extensions: Vec<Extension>,
+
+    /// Write every frame as raw pixels to stdout, after a header line
+    /// `chip8-rawvideo <format> <width>x<height> <fps>`, e.g. to record video with ffmpeg
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["trace", "coverage", "key_usage", "speedrun", "input_latency", "audit_arithmetic"])]
+    pub pipe_frames: Option<PixelFormat>,
+
+    /// Publish the latest frame and a frame counter in the shared-memory region NAME
+    /// (/dev/shm/NAME on Linux, a file in the temporary directory elsewhere, or a path),
+    /// for capture tools to map
+    #[arg(long, value_name = "NAME")]
+    pub shmem: Option<String>,
+
+    /// Speed multiplier while Backspace is held, timers included
+    #[arg(long, value_name = "N", default_value_t = timing::DEFAULT_TURBO,
+          value_parser = clap::value_parser!(u32).range(2..=timing::MAX_TURBO as i64))]
+    pub turbo: u32,
+
+    /// What the buzzer does in turbo: scale shortens beeps with the timers, mute silences it
+    #[arg(long, value_enum, default_value_t = TurboAudio::Scale)]
+    pub turbo_audio: TurboAudio,
+
+    /// Renders skipped in a row at most while running and drawing the frames takes longer than
+    /// real time, 0 renders every frame even if emulation falls behind
+    #[arg(long, value_name = "N", default_value_t = timing::DEFAULT_MAX_FRAME_SKIP)]
+    pub max_frame_skip: u32,
+
+    /// Window renders: vsync waits for the monitor refresh, off renders as often as possible,
+    /// a number caps the renders per second, e.g. 144 (the programs still run at --timer-hz)
+    #[arg(long, value_name = "LIMIT", default_value = "vsync", value_parser = timing::parse_frame_limit)]
+    pub frame_limit: FrameLimit,
+
+    /// Smooth the edges of the pixels before the palette colors them: scale2x rounds the
+    /// corners of diagonals, scale4x applies it twice
+    #[arg(long, value_enum, default_value_t = Filter::None)]
+    pub filter: Filter,
+
+    /// Show a speaker icon while the buzzer sounds
+    #[arg(long)]
+    pub sound_indicator: bool,
+
+    /// Don't show the title, profile and speed of the program over the display at start
+    #[arg(long)]
+    pub no_splash: bool,
+
+    /// Keep running when the window loses the focus, instead of pausing until it gets it back
+    #[arg(long = "no-pause-on-focus-loss", action = ArgAction::SetFalse)]
+    pub pause_on_focus_loss: bool,
+
+    /// Beep for FRAMES frames on sprite collisions, to hear what some games only show
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    pub collision_cue: u8,
+
+    /// Hide full screen flashes beyond N per second, by inverting the palette along with the
+    /// display (3 is the WCAG seizure threshold, 0 shows them all)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub max_flashes: u8,
+
+    /// Tint the pixels drawn by DXYN during the last FRAMES frames, fading with age, to see
+    /// what each draw call touches
+    #[arg(long, value_name = "FRAMES")]
+    pub draw_tint: Option<u8>,
+
+    /// Overlay a heatmap of the sprite collisions of the last FRAMES frames
+    #[arg(long, value_name = "FRAMES")]
+    pub collision_heatmap: Option<usize>,
+
+    /// Number of distinct frames kept for the filmstrip export (F9)
+    #[arg(long, value_name = "FRAMES", default_value_t = 16)]
+    pub filmstrip_frames: usize,
+
+    /// Key exiting immediately, e.g. escape, f12 or end (by default only the menu quits), best
+    /// not one of the keypad keys. When set to escape, Esc quits instead of opening the menu.
+    #[arg(long, value_name = "KEY", value_parser = keyconf::parse_key)]
+    pub quit_key: Option<VirtualKeyCode>,
+
+    /// Require pressing the quit key twice within a second
+    #[arg(long, requires = "quit_key")]
+    pub confirm_quit: bool,
+
+    /// Keep settings, saves and screenshots in a chip8-data directory next to the executable
+    /// instead of the user directories
+    #[arg(long)]
+    pub portable: bool,
+
+    /// Open paused before the first instruction, F10 stepping one instruction at a time and
+    /// Space resuming
+    #[arg(long, conflicts_with = "record_movie")]
+    pub start_paused: bool,
+
+    /// Print the address and opcode of every executed instruction
+    #[arg(long)]
+    pub trace: bool,
+
+    /// Print the registers, memory and pixels each instruction changed along with the trace,
+    /// e.g. `V3: 0A→0B  I: 200→205  px(12,4): 0→1`
+    #[arg(long, requires = "trace")]
+    pub trace_diff: bool,
+
+    /// Print which program instructions were executed on exit
+    #[arg(long)]
+    pub coverage: bool,
+
+    /// Write the time spent decoding and running each kind of instruction, rendering and playing
+    /// sound to FILE on exit, as folded stacks for inferno-flamegraph or flamegraph.pl.
+    /// Instructions are then timed and run one at a time, several times slower.
+    #[arg(long, value_name = "FILE")]
+    pub perf_out: Option<PathBuf>,
+
+    /// Print on exit how long key events waited for the frame whose instructions see them
+    #[arg(long)]
+    pub input_latency: bool,
+
+    /// Show the keys the program checks (EX9E/EXA1/FX0A) in a corner of the window, and print
+    /// how often each was checked on exit
+    #[arg(long)]
+    pub key_usage: bool,
+
+    /// Show a speedrun timer in the top left corner, started by the first key press
+    #[arg(long)]
+    pub speedrun: bool,
+
+    /// Condition ending the next split of the speedrun, the last one ending the run, e.g.
+    /// `px(32,16)==1` or `mem[0x3F0]==5 && v[3]>2` (also `i`, `dt` and `st`)
+    #[arg(long = "split", value_name = "CONDITION", requires = "speedrun")]
+    pub splits: Vec<Condition>,
+
+    /// Write the split and final times to this file on exit, instead of stdout
+    #[arg(long, value_name = "FILE", requires = "speedrun")]
+    pub speedrun_out: Option<PathBuf>,
+
+    /// Rhai script whose `on_frame()` function runs at the end of every frame, reading and
+    /// changing the registers and memory, pressing keys, stepping frames and taking screenshots
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Warn when FX55/FX33 write into the font or display area, or when sprites are read
+    /// from uninitialized memory. Memory accesses beyond 0xFFF stop the program.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Content of the memory below the display area before the fonts and the program are
+    /// loaded: zero, ff, random(SEED) or pattern (DEADBEEF repeated), to expose programs
+    /// depending on memory they never wrote
+    #[arg(long, value_name = "INIT", default_value = "zero")]
+    pub mem_init: MemInit,
+
+    /// Where the pixels are stored: ram keeps them at 0xF00-0xFFF, where some programs read
+    /// or write them directly, external leaves that memory to the program (always external
+    /// with the 64K memory of xo-chip)
+    #[arg(long, value_enum, value_name = "BUFFER", default_value_t = DisplayBuffer::Ram)]
+    pub display_buffer: DisplayBuffer,
+
+    /// Log the instruction fetches, sprites and FX65 loads reading memory never written
+    /// since the program was loaded, to stderr
+    #[arg(long)]
+    pub log_uninit_reads: bool,
+
+    /// Log the 8XY4 overflows, 8XY5/8XY7 underflows and FX1E moving I past 0xFFF to stderr,
+    /// once per instruction address, and print how often each happened on exit
+    #[arg(long)]
+    pub audit_arithmetic: bool,
+
+    /// Stop the program as soon as the program counter leaves the loaded program, catching
+    /// wild jumps from a corrupted stack or a bad BNNN target
+    #[arg(long)]
+    pub pc_guard: bool,
+
+    /// Region where --pc-guard allows code besides the program, START-END in hexadecimal
+    /// (e.g. 300-3FF, repeatable)
+    #[arg(long = "allow-region", value_name = "START-END", requires = "pc_guard",
+          value_parser = memory::parse_region)]
+    pub allowed_regions: Vec<RangeInclusive<u16>>,
+
+    /// Force a memory byte to a value every frame, ADDR=VALUE in hexadecimal (e.g. 3F0=05)
+    #[arg(long = "cheat", value_name = "ADDR=VALUE")]
+    pub cheats: Vec<Cheat>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the built-in quirk detection program under each profile and print the results
+    Verify,
+
+    /// Print statistics about a program without running it
+    Info {
+        /// Path to the .ch8 program
+        #[arg(required_unless_present = "stats")]
+        rom: Option<PathBuf>,
+
+        /// Print the play time and launch count of the program, or of every program played
+        /// when no program is given
+        #[arg(long)]
+        stats: bool,
+
+        /// Read the play statistics from the portable data directory
+        #[arg(long)]
+        portable: bool,
+
+        /// Also run the program headlessly, pressing every key in turn, and print the keys
+        /// it checks
+        #[arg(long, requires = "rom")]
+        dynamic: bool,
+
+        /// Number of 60 Hz frames to run with --dynamic
+        #[arg(long, default_value_t = 600, requires = "dynamic")]
+        frames: u64,
+    },
+
+    /// Flag suspicious patterns of a program without running it: calls never returning,
+    /// jumps into the middle of instructions, FX55 over the fonts, odd lengths and
+    /// instructions of extensions the profile does not enable. Exits with 1 if any.
+    Lint {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// Profile enabling the extensions, by default the one of a .c8c container or of the
+        /// file extension (.sc8 SUPER-CHIP, .xo8 XO-CHIP, CHIP-8 otherwise)
+        #[arg(long, value_enum)]
+        profile: Option<Profile>,
+    },
+
+    /// Write the control flow graph of a program in Graphviz DOT format, blocks labeled with
+    /// their disassembly
+    Cfg {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// DOT file to write, stdout by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the disassembly of a program, an instruction per line
+    Disasm {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// First address to disassemble, in hexadecimal (e.g. 2A0), the program start by default
+        #[arg(long, value_name = "ADDR", value_parser = memory::parse_addr)]
+        start: Option<u16>,
+
+        /// Last address to disassemble, in hexadecimal, the program end by default. Addresses
+        /// beyond FFF are for programs larger than the 4K of memory (XO-CHIP)
+        #[arg(long, value_name = "ADDR", value_parser = memory::parse_addr)]
+        end: Option<u16>,
+
+        /// Region of data to list as bytes rather than instructions, START-END or START..END in
+        /// hexadecimal, the end included (e.g. 3A0-3FF or 0x3A0..0x3FF, repeatable)
+        #[arg(long, value_name = "START-END", value_parser = memory::parse_region)]
+        data: Vec<RangeInclusive<u16>>,
+
+        /// List the data a byte per line with its pixels, e.g. `F0  ####....`
+        #[arg(long)]
+        sprites_as_art: bool,
+    },
+
+    /// Write the built-in small and big fonts, as loaded in memory, for programs to match
+    /// the interpreter glyphs
+    ExportFont {
+        /// Labeled Octo assembly or raw memory image
+        #[arg(long, value_enum, default_value_t = font::Format::Asm)]
+        format: font::Format,
+
+        /// File to write, stdout by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Replay a movie recorded with --record-movie in the window, with the same program,
+    /// settings and inputs, then hand over to the keyboard
+    Play {
+        /// Path to the .c8m movie
+        movie: PathBuf,
+    },
+
+    /// Catalog of programs to launch by name instead of path, with their settings, kept in
+    /// library.toml of the data directory
+    Library {
+        /// Use the library of the portable data directory
+        #[arg(long, global = true)]
+        portable: bool,
+
+        #[command(subcommand)]
+        command: LibraryCommand,
+    },
+
+    /// Generate programs
+    Gen {
+        #[command(subcommand)]
+        program: GenCommand,
+    },
+
+    /// Run a program headlessly as fast as possible and print the instructions per second
+    Bench {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// Executor to measure, by default every one
+        #[arg(long, value_enum)]
+        executor: Option<ExecutorKind>,
+
+        /// Quirk profile
+        #[arg(long, value_enum, default_value_t = Profile::Modern)]
+        profile: Profile,
+
+        /// Number of 60 Hz frames to run
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+
+        /// Instructions executed per frame
+        #[arg(long, value_name = "CYCLES", default_value_t = 10_000)]
+        cycles_per_frame: u32,
+    },
+
+    /// Check that beeps start in the frame where the matching draw appears, by running a
+    /// program that beeps and draws together (a built-in one by default)
+    AvSync {
+        /// Path to a .ch8 program beeping and drawing together
+        rom: Option<PathBuf>,
+
+        /// Quirk profile
+        #[arg(long, value_enum, default_value_t = Profile::Modern)]
+        profile: Profile,
+
+        /// Number of 60 Hz frames to run
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+
+        /// Instructions executed per frame
+        #[arg(long, value_name = "CYCLES", default_value_t = timing::DEFAULT_CYCLES_PER_FRAME)]
+        cycles_per_frame: u32,
+    },
+
+    /// Debug a program headlessly from a command prompt: step, fast-forward to an address
+    /// or by a number of frames, print the registers (type help for the commands)
+    Debug {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// Quirk profile
+        #[arg(long, value_enum, default_value_t = Profile::Modern)]
+        profile: Profile,
+
+        /// Instructions executed per 60 Hz frame
+        #[arg(long, value_name = "CYCLES", default_value_t = timing::DEFAULT_CYCLES_PER_FRAME)]
+        cycles_per_frame: u32,
+    },
+
+    /// Open the window with no program and execute the instructions typed on stdin one at a
+    /// time (e.g. `6A02`, `DA05`), printing the registers after each
+    Sandbox {
+        /// Quirk profile
+        #[arg(long, value_enum, default_value_t = Profile::Modern)]
+        profile: Profile,
+    },
+
+    /// Run a program headlessly until it halts, exiting with a status for CI: 0 halted
+    /// cleanly, 1 other fault, 2 unknown opcode, 3 stack fault, 4 timeout
+    Run {
+        /// Path to the .ch8 program
+        rom: PathBuf,
+
+        /// Instructions executed before giving up with a timeout
+        #[arg(long, value_name = "CYCLES", default_value_t = 1_000_000)]
+        timeout_cycles: u64,
+
+        /// Quirk profile, by default the one matching the extensions the program uses
+        #[arg(long, value_enum)]
+        profile: Option<Profile>,
+    },
+
+    /// Run every program of a directory headlessly, in parallel, and report which ones
+    /// crashed or reached an unknown opcode
+    Batch {
+        /// Directory of .ch8, .c8, .sc8 and .xo8 programs
+        dir: PathBuf,
+
+        /// Instructions executed per program
+        #[arg(long, default_value_t = 200_000)]
+        cycles: u64,
+
+        /// Quirk profile, by default the one matching the extensions each program uses
+        #[arg(long, value_enum)]
+        profile: Option<Profile>,
+
+        /// Save the final frame of every program as <name>.png in this directory
+        #[arg(long, value_name = "DIR")]
+        screenshots: Option<PathBuf>,
+
+        /// Write a compatibility report with the final frames, Markdown (.md) or HTML (.html)
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+
+    /// Print what this build supports: instruction sets, profiles, quirks, executors,
+    /// peripherals, audio backends, renderers, filters and file formats
+    Capabilities {
+        /// Print a JSON object instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Compare two save states (F6): the registers, memory ranges and display pixels that
+    /// differ, to find when a corruption first appears. Exits with 1 if they differ.
+    Statediff {
+        /// Earlier state
+        a: PathBuf,
+
+        /// Later state
+        b: PathBuf,
+    },
+
+    /// Print the instruction set: pattern, mnemonic, extension defining it and approximate
+    /// COSMAC VIP machine cycles
+    Opcodes,
+
+    /// Print the quirk settings a program runs with
+    Quirks {
+        /// Also describe every quirk and profile
+        #[arg(long)]
+        list: bool,
+
+        /// Profile to show, modern by default as when running
+        #[arg(long, value_enum)]
+        profile: Option<Profile>,
+
+        /// Delay timer granularity, overriding the one of the profile as when running
+        #[arg(long, value_enum, value_name = "MODE")]
+        timer_mode: Option<TimerMode>,
+
+        /// Number of stack levels, overriding the one of the profile as when running
+        #[arg(long, value_name = "LEVELS")]
+        stack_depth: Option<usize>,
+
+        /// Count the colliding sprite rows in VF, as when running with it
+        #[arg(long)]
+        collision_rows: bool,
+
+        /// Path to the .ch8 program
+        rom: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LibraryCommand {
+    /// Add a program, or update it if the name is taken
+    Add {
+        /// Path to the .ch8 program or .c8c container
+        rom: PathBuf,
+
+        /// Name to launch it with, the file name without extension by default
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Quirk profile to run it with, by default the one of its .c8c container
+        #[arg(long, value_enum)]
+        profile: Option<Profile>,
+
+        /// Instructions executed per 60 Hz frame, by default the ones of its .c8c container
+        #[arg(long, value_name = "CYCLES")]
+        cycles_per_frame: Option<u32>,
+    },
+
+    /// Remove a program from the library, its file is kept
+    Remove { name: String },
+
+    /// List the programs with their settings and play time
+    List,
+
+    /// Run a program of the library in the window, by name or the start of a name
+    Run { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum GenCommand {
+    /// Write a small diagnostic program, to check the keymap, display or timing setup
+    Demo {
+        /// Which diagnostic
+        #[arg(long, value_enum)]
+        kind: DemoKind,
+
+        /// File to write, <kind>.ch8 by default
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
@@ -0,0 +1,206 @@
+use crate::palette::Palette;
+use crate::quirks::Profile;
+
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// First bytes of a `.c8c` container: "C8C" and the format version
+const MAGIC: [u8; 4] = *b"C8C\0";
+
+/// Platform ids of the bytecode table, the quirk profile running each
+const PLATFORMS: [(u8, Profile); 4] = [
+    (0x01, Profile::Vip),
+    (0x02, Profile::Modern),
+    (0x03, Profile::Schip),
+    (0x04, Profile::XoChip),
+];
+
+/// Property ids
+const END: u8 = 0x00;
+const CYCLES_PER_FRAME: u8 = 0x01; // u16
+const COLORS: u8 = 0x02; // RGB triplets, background and foreground first
+const TITLE: u8 = 0x03; // UTF-8
+const AUTHOR: u8 = 0x04; // UTF-8
+
+/// Program bundled with the settings it runs with, in the `.c8c` binary format of this
+/// interpreter (big endian), which other emulators do not read:
+///
+/// - magic `C8C` and version 0 (4 bytes)
+/// - offset of the properties (2 bytes)
+/// - bytecode table: entries of a platform id (1 byte), the offset and the size of its
+///   program (2 bytes each), ended by platform 0. The first platform known is run.
+/// - properties: entries of an id (1 byte), the size of the value (1 byte) and the value,
+///   ended by id 0
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Container {
+    pub program: Vec<u8>,
+    pub profile: Option<Profile>,
+    pub cycles_per_frame: Option<u32>,
+    pub colors: Vec<[u8; 3]>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+}
+
+impl Container {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if !is_container(data) {
+            return Err("not a .c8c file".to_string());
+        }
+        let u16_at = |offset: usize| match data.get(offset..offset + 2) {
+            Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]]) as usize),
+            None => Err(format!("truncated at {}", offset)),
+        };
+
+        let mut container = Container::default();
+        let mut entry = MAGIC.len() + 2;
+        loop {
+            let platform = *data.get(entry).ok_or("truncated bytecode table")?;
+            if platform == 0 {
+                break;
+            }
+            let (offset, size) = (u16_at(entry + 1)?, u16_at(entry + 3)?);
+            entry += 5;
+
+            let profile = PLATFORMS.iter().find(|(id, _)| *id == platform);
+            if let (Some(&(_, profile)), None) = (profile, container.profile) {
+                container.program = data
+                    .get(offset..offset + size)
+                    .ok_or("bytecode beyond the end of the file")?
+                    .to_vec();
+                container.profile = Some(profile);
+            }
+        }
+        if container.profile.is_none() {
+            return Err("no bytecode for a supported platform".to_string());
+        }
+
+        let mut property = u16_at(MAGIC.len())?;
+        loop {
+            let id = *data.get(property).ok_or("truncated properties")?;
+            if id == END {
+                break;
+            }
+            let size = *data.get(property + 1).ok_or("truncated properties")? as usize;
+            let value = data
+                .get(property + 2..property + 2 + size)
+                .ok_or("truncated properties")?;
+            property += 2 + size;
+
+            let text = || String::from_utf8_lossy(value).into_owned();
+            match id {
+                CYCLES_PER_FRAME if size == 2 => {
+                    container.cycles_per_frame =
+                        Some(u16::from_be_bytes([value[0], value[1]]) as u32)
+                }
+                COLORS => {
+                    container.colors = value
+                        .chunks_exact(3)
+                        .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+                        .collect()
+                }
+                TITLE => container.title = Some(text()),
+                AUTHOR => container.author = Some(text()),
+                _ => (), // properties of other emulators
+            }
+        }
+
+        Ok(container)
+    }
+
+    /// Palette of the colors, if there are at least two
+    pub fn palette(&self) -> Option<Palette> {
+        let colors: Vec<[u8; 4]> = self
+            .colors
+            .iter()
+            .map(|&[r, g, b]| [r, g, b, 0xFF])
+            .collect();
+        Palette::from_colors(&colors).ok()
+    }
+
+    /// Metadata and settings, one per line
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(title) = &self.title {
+            writeln!(out, "title: {}", title).unwrap();
+        }
+        if let Some(author) = &self.author {
+            writeln!(out, "author: {}", author).unwrap();
+        }
+        if let Some(profile) = self.profile {
+            writeln!(out, "container profile: {}", profile.name()).unwrap();
+        }
+        if let Some(cycles) = self.cycles_per_frame {
+            writeln!(out, "container cycles per frame: {}", cycles).unwrap();
+        }
+        if !self.colors.is_empty() {
+            let colors: Vec<String> = self
+                .colors
+                .iter()
+                .map(|[r, g, b]| format!("{:02X}{:02X}{:02X}", r, g, b))
+                .collect();
+            writeln!(out, "container colors: {}", colors.join(" ")).unwrap();
+        }
+
+        out
+    }
+}
+
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Program of a file: the bytecode of a `.c8c` container with its settings, or the whole
+/// file for the other formats
+pub fn read(path: &Path) -> io::Result<(Vec<u8>, Option<Container>)> {
+    let data = std::fs::read(path)?;
+    if !is_container(&data) {
+        return Ok((data, None));
+    }
+
+    let container =
+        Container::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((container.program.clone(), Some(container)))
+}
+
+/// Program of a file, ignoring the settings of a `.c8c` container
+pub fn read_program(path: &Path) -> io::Result<Vec<u8>> {
+    read(path).map(|(program, _)| program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        #[rustfmt::skip]
+        let data = [
+            b'C', b'8', b'C', 0x00,
+            0x00, 0x13,                   // properties at 0x13
+            0x7F, 0x00, 0x11, 0x00, 0x02, // unknown platform
+            0x03, 0x00, 0x11, 0x00, 0x02, // SUPER-CHIP, program at 0x11
+            0x00,
+            0x12, 0x00,                   // 11: program
+            0x01, 0x02, 0x00, 0x1E,       // 13: 30 cycles per frame
+            0x03, 0x02, b'H', b'i',       // title
+            0x02, 0x06, 0x10, 0x20, 0x30, 0xF0, 0xE0, 0xD0, // colors
+            0x00,
+        ];
+
+        let container = Container::parse(&data).unwrap();
+        assert_eq!(vec![0x12, 0x00], container.program);
+        assert_eq!(Some(Profile::Schip), container.profile);
+        assert_eq!(Some(30), container.cycles_per_frame);
+        assert_eq!(Some("Hi".to_string()), container.title);
+        assert_eq!(
+            [0xF0, 0xE0, 0xD0, 0xFF],
+            container.palette().unwrap().foreground()
+        );
+        assert!(container.report().contains("container profile: schip\n"));
+
+        assert!(Container::parse(&data[..18]).is_err());
+        assert!(Container::parse(&[0x12, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,545 @@
+use crate::chip8::Chip8;
+use crate::disassembler;
+use crate::error::HaltReason;
+use crate::snapshot::Snapshot;
+use crate::timing::FRAME_RATE;
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Longest fast-forward, for `run-until` to give up on addresses never reached
+pub const MAX_RUN_FRAMES: u32 = 10 * 60 * FRAME_RATE;
+
+/// Instructions that can be stepped back
+pub const HISTORY_LEN: usize = 1000;
+
+pub const HELP: &str = "\
+step [N]          execute N instructions (1 by default)
+back [N]          go back N instructions (1 by default), a run-until as one
+run-until ADDR    execute until PC reaches ADDR (hexadecimal)
+run-frames N      execute N frames
+regs              print the registers
+set REG VALUE     set V0-VF, I, PC, DT or ST (hexadecimal)
+set ADDR VALUE    set a memory byte (hexadecimal)
+help              print this help
+quit              exit the debugger";
+
+/// What `set` changes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+    Memory(u16),
+}
+
+impl Target {
+    /// Largest value the target holds
+    fn max(&self) -> u16 {
+        match self {
+            Target::I | Target::Pc => 0xFFFF, // checked against the memory size when set
+            _ => 0xFF,
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "i" => Ok(Target::I),
+            "pc" => Ok(Target::Pc),
+            "dt" => Ok(Target::Dt),
+            "st" => Ok(Target::St),
+            name => {
+                if let Some(index) = name.strip_prefix('v') {
+                    if let Ok(index @ 0..=0xF) = usize::from_str_radix(index, 16) {
+                        return Ok(Target::V(index));
+                    }
+                }
+                match hex(name) {
+                    Ok(addr) => Ok(Target::Memory(addr)),
+                    Err(_) => Err(format!("not a register nor an address: {}", s)),
+                }
+            }
+        }
+    }
+}
+
+fn hex(s: &str) -> Result<u16, String> {
+    u16::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid hexadecimal number: {}", s))
+}
+
+/// Debugger commands, as typed on the command line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Step(u32),
+    StepBack(u32),
+    RunUntil(u16),
+    RunFrames(u32),
+    Registers,
+    Set(Target, u16),
+    Help,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or_default();
+
+        if name == "set" {
+            let (target, value) = match (words.next(), words.next(), words.next()) {
+                (Some(target), Some(value), None) => (target, value),
+                _ => return Err("set needs a register or an address and a value".to_string()),
+            };
+            let value = hex(value)?;
+            if value > target.parse::<Target>()?.max() {
+                return Err(format!("{:X} does not fit in {}", value, target));
+            }
+            return Ok(Command::Set(target.parse()?, value));
+        }
+
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments for {}", name));
+        }
+
+        let number = |argument: Option<&str>| -> Result<u32, String> {
+            let argument = argument.ok_or(format!("{} needs an argument", name))?;
+            argument
+                .parse()
+                .map_err(|_| format!("invalid number: {}", argument))
+        };
+
+        match name {
+            "step" | "s" => Ok(Command::Step(argument.map_or(Ok(1), |_| number(argument))?)),
+            "back" | "b" => Ok(Command::StepBack(
+                argument.map_or(Ok(1), |_| number(argument))?,
+            )),
+            "run-until" | "u" => {
+                let argument = argument.ok_or("run-until needs an address")?;
+                let digits = argument.trim_start_matches("0x");
+                u16::from_str_radix(digits, 16)
+                    .map(Command::RunUntil)
+                    .map_err(|_| format!("invalid address: {}", argument))
+            }
+            "run-frames" | "f" => Ok(Command::RunFrames(number(argument)?)),
+            "regs" | "r" => Ok(Command::Registers),
+            "help" | "h" => Ok(Command::Help),
+            "quit" | "q" => Ok(Command::Quit),
+            _ => Err(format!("unknown command: {} (try help)", name)),
+        }
+    }
+}
+
+/// Executes instructions one at a time, starting and ending frames at their boundaries every
+/// `cycles_per_frame` instructions as `Chip8::run_frame` does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameStepper {
+    cycles_per_frame: u32,
+    cycle: u32,  // instructions executed in the current frame
+    frames: u64, // frames completed
+}
+
+impl FrameStepper {
+    pub fn new(cycles_per_frame: u32) -> Self {
+        FrameStepper {
+            cycles_per_frame: cycles_per_frame.max(1),
+            cycle: 0,
+            frames: 0,
+        }
+    }
+
+    /// Instructions executed in the current frame
+    pub fn cycle(&self) -> u32 {
+        self.cycle
+    }
+
+    /// Frames completed
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Execute one instruction, ending the frame with `Chip8::frame` after its last one.
+    /// Returns why the interpreter halted, if it did.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Option<HaltReason> {
+        let halt = self.execute(chip8);
+        if self.cycle == self.cycles_per_frame {
+            self.cycle = 0;
+            self.frames += 1;
+            chip8.frame();
+        }
+        halt
+    }
+
+    /// Execute the rest of the current frame, or a whole one at full speed if none started,
+    /// leaving the caller to end it with `Chip8::frame` as after `Chip8::run_frame`.
+    /// Returns why the interpreter halted, if it did.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Option<HaltReason> {
+        let mut halt = None;
+        if self.cycle == 0 {
+            halt = chip8.run_frame(self.cycles_per_frame);
+        }
+        while self.cycle > 0 && self.cycle < self.cycles_per_frame {
+            halt = self.execute(chip8).or(halt);
+        }
+        self.cycle = 0;
+        self.frames += 1;
+        halt
+    }
+
+    fn execute(&mut self, chip8: &mut Chip8) -> Option<HaltReason> {
+        if self.cycle == 0 {
+            chip8.begin_frame();
+        }
+        let interpreter = &mut chip8.interpreter;
+        interpreter.set_frame_phase(self.cycle as f32 / self.cycles_per_frame as f32);
+
+        self.cycle += 1;
+        chip8.step()
+    }
+}
+
+/// Runs a program headlessly, instruction by instruction or fast-forwarding, with the
+/// timers ticking every `cycles_per_frame` instructions as in the window.
+/// The states before the last `HISTORY_LEN` instructions are kept to step back, except
+/// across `run_frames` which runs whole frames at full speed.
+pub struct Debugger {
+    chip8: Chip8,
+    stepper: FrameStepper,
+    history: VecDeque<(Snapshot, FrameStepper)>, // state and frame position before each step
+}
+
+impl Debugger {
+    pub fn new(chip8: Chip8, cycles_per_frame: u32) -> Self {
+        Debugger {
+            chip8,
+            stepper: FrameStepper::new(cycles_per_frame),
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// Run a command, returns what to print
+    pub fn execute(&mut self, command: Command) -> String {
+        match command {
+            Command::Step(count) => {
+                for _ in 0..count {
+                    self.step();
+                }
+                self.registers()
+            }
+            Command::StepBack(count) => {
+                let stepped = (0..count).take_while(|_| self.step_back()).count();
+                match stepped {
+                    0 => "no earlier state".to_string(),
+                    _ => self.registers(),
+                }
+            }
+            Command::RunUntil(addr) => {
+                let outcome = match self.run_until(addr) {
+                    Some(instructions) => {
+                        format!("reached {:03X} after {} instructions", addr, instructions)
+                    }
+                    None => match self.chip8.interpreter.halt_reason() {
+                        Some(reason) => format!("program stopped: {}", reason),
+                        None => format!("{:03X} not reached in {} frames", addr, MAX_RUN_FRAMES),
+                    },
+                };
+                format!("{}\n{}", outcome, self.registers())
+            }
+            Command::RunFrames(count) => {
+                self.run_frames(count);
+                self.registers()
+            }
+            Command::Registers => self.registers(),
+            Command::Set(Target::Pc, addr) | Command::Set(Target::Memory(addr), _)
+                if addr as usize >= self.chip8.memory.size() =>
+            {
+                format!(
+                    "{:03X} is beyond the {}K of memory",
+                    addr,
+                    self.chip8.memory.size() / 1024
+                )
+            }
+            Command::Set(target, value) => {
+                self.set(target, value);
+                match target {
+                    Target::Memory(addr) => {
+                        format!("{:03X}: {:02X}", addr, self.chip8.memory.read(addr))
+                    }
+                    _ => self.registers(),
+                }
+            }
+            Command::Help => HELP.to_string(),
+            Command::Quit => String::new(),
+        }
+    }
+
+    /// Execute one instruction, starting and ending frames at their boundaries, and keep the
+    /// state before it to step back
+    pub fn step(&mut self) {
+        self.push_history();
+        self.advance();
+    }
+
+    /// Keep the current state for `step_back`
+    fn push_history(&mut self) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back((Snapshot::take(&self.chip8), self.stepper));
+    }
+
+    /// Execute one instruction without keeping the state before it, for fast-forwards
+    fn advance(&mut self) {
+        self.stepper.step(&mut self.chip8);
+    }
+
+    /// Restore the state before the last instruction stepped, returns false if there is none
+    pub fn step_back(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some((snapshot, stepper)) => {
+                snapshot.restore(&mut self.chip8);
+                self.stepper = stepper;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finish the current frame and run `count` - 1 more, at full speed
+    pub fn run_frames(&mut self, count: u32) {
+        for _ in 0..count {
+            // whole frames are run at once, their instructions not kept
+            if self.stepper.cycle() == 0 {
+                self.history.clear();
+            }
+            self.stepper.run_frame(&mut self.chip8);
+            self.chip8.frame();
+        }
+    }
+
+    /// Execute at least one instruction, until the program counter reaches `addr`.
+    /// Returns the number of instructions executed, None if the program stopped or the
+    /// address was not reached within `MAX_RUN_FRAMES` frames. Stepping back afterwards goes
+    /// back to where it started.
+    pub fn run_until(&mut self, addr: u16) -> Option<u64> {
+        let limit = MAX_RUN_FRAMES as u64 * self.stepper.cycles_per_frame as u64;
+
+        self.push_history();
+        for executed in 1..=limit {
+            if self.chip8.interpreter.stop() {
+                return None;
+            }
+            self.advance();
+            if self.chip8.interpreter.pc == addr {
+                return Some(executed);
+            }
+        }
+
+        None
+    }
+
+    /// Change a register or a memory byte, for the next instructions to see
+    pub fn set(&mut self, target: Target, value: u16) {
+        let interpreter = &mut self.chip8.interpreter;
+        match target {
+            Target::V(index) => interpreter.vx[index] = value as u8,
+            Target::I => interpreter.vi = value,
+            Target::Pc => interpreter.pc = value,
+            Target::Dt => interpreter.set_dt(value as u8),
+            Target::St => interpreter.set_st(value as u8),
+            Target::Memory(addr) => self.chip8.memory.write(addr, value as u8),
+        }
+    }
+
+    /// Registers and the next instruction
+    pub fn registers(&self) -> String {
+        format!(
+            "frame {} cycle {}\n{}",
+            self.stepper.frames(),
+            self.stepper.cycle(),
+            registers(&self.chip8)
+        )
+    }
+}
+
+/// Registers of `chip8` and the instruction it executes next
+pub fn registers(chip8: &Chip8) -> String {
+    let opcode = chip8.interpreter.next(&chip8.memory);
+    format!(
+        "{}\nnext: {:04X} {}",
+        register_values(chip8),
+        opcode,
+        mnemonic(opcode)
+    )
+}
+
+/// PC, I, timers, stack pointer and V0-VF of `chip8`
+pub fn register_values(chip8: &Chip8) -> String {
+    let interpreter = &chip8.interpreter;
+
+    let mut out = String::new();
+    write!(
+        out,
+        "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}  SP {}",
+        interpreter.pc(),
+        interpreter.index(),
+        interpreter.delay_timer(),
+        interpreter.sound_timer(),
+        interpreter.sc()
+    )
+    .unwrap();
+    for (i, chunk) in interpreter.registers().chunks(8).enumerate() {
+        out.push('\n');
+        for (j, value) in chunk.iter().enumerate() {
+            write!(out, "V{:X} {:02X}  ", i * 8 + j, value).unwrap();
+        }
+        out.truncate(out.trim_end().len());
+    }
+
+    out
+}
+
+/// Mnemonic of an opcode, "unknown" if no instruction matches it
+pub fn mnemonic(opcode: u16) -> String {
+    disassembler::decode(opcode).map_or("unknown".to_string(), |instruction| instruction.mnemonic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::*;
+
+    fn debugger() -> Debugger {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                ld_vx(0, 0),   // 200
+                add_vx(0, 1),  // 202
+                se_vx(0, 200), // 204
+                jp(0x202),     // 206
+                jp(0x208),     // 208: loop forever
+            ]
+            .concat(),
+        );
+        Debugger::new(chip8, 10)
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Ok(Command::RunUntil(0x2F0)), "run-until 0x2F0".parse());
+        assert_eq!(Ok(Command::RunFrames(600)), "run-frames 600".parse());
+        assert_eq!(Ok(Command::Step(1)), "step".parse());
+        assert_eq!(Ok(Command::Step(5)), "s 5".parse());
+        assert_eq!(Ok(Command::StepBack(1)), "back".parse());
+        assert!("run-until".parse::<Command>().is_err());
+        assert!("run-until 0x2G0".parse::<Command>().is_err());
+        assert!("jump".parse::<Command>().is_err());
+        assert_eq!(Ok(Command::Set(Target::V(3), 0x10)), "set v3 0x10".parse());
+        assert_eq!(Ok(Command::Set(Target::Pc, 0x2F0)), "set PC 2F0".parse());
+        assert_eq!(
+            Ok(Command::Set(Target::Memory(0x300), 0xFF)),
+            "set 300 FF".parse()
+        );
+        assert!("set v3 100".parse::<Command>().is_err());
+        assert!("set vg 1".parse::<Command>().is_err());
+        assert!("set pc".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_set() {
+        let mut debugger = debugger();
+        debugger.step();
+        debugger.execute(Command::Set(Target::V(0), 199));
+        debugger.execute(Command::Set(Target::Dt, 0x10));
+        let output = debugger.execute(Command::Set(Target::Memory(0x20A), 0x12));
+
+        assert_eq!("20A: 12", output);
+        assert_eq!(0x10, debugger.chip8().interpreter.dt);
+        assert_eq!(
+            "1000 is beyond the 4K of memory",
+            debugger.execute(Command::Set(Target::Memory(0x1000), 0x12))
+        );
+        assert_eq!(Some(2), debugger.run_until(0x208));
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut debugger = debugger();
+
+        assert_eq!(Some(1 + 200 * 3 - 1), debugger.run_until(0x208));
+        assert_eq!(200, debugger.chip8().interpreter.vx[0]);
+        assert_eq!(None, debugger.run_until(0x300));
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut debugger = debugger();
+        debugger.run_until(0x208);
+        let registers = debugger.registers();
+        debugger.step();
+        debugger.step();
+
+        assert!(debugger.step_back());
+        assert!(debugger.step_back());
+        assert_eq!(registers, debugger.registers());
+        // the whole run-until at once
+        assert!(debugger.step_back());
+        assert_eq!(0x200, debugger.chip8().interpreter.pc);
+        assert_eq!(0, debugger.chip8().interpreter.vx[0]);
+        assert!(!debugger.step_back());
+
+        debugger.step();
+
+        debugger.run_frames(2);
+        assert!(!debugger.step_back());
+    }
+
+    #[test]
+    fn test_frame_stepper() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx(0, 5), ld_dt(0), jp(0x204)].concat());
+        let mut stepper = FrameStepper::new(2);
+
+        stepper.step(&mut chip8);
+        stepper.step(&mut chip8);
+        assert_eq!((1, 0), (stepper.frames(), stepper.cycle()));
+        assert_eq!(5, chip8.interpreter.delay_timer());
+
+        // the timers tick when the next frame begins
+        stepper.step(&mut chip8);
+        assert_eq!((1, 1), (stepper.frames(), stepper.cycle()));
+        assert_eq!(4, chip8.interpreter.delay_timer());
+
+        stepper.run_frame(&mut chip8);
+        assert_eq!((2, 0), (stepper.frames(), stepper.cycle()));
+        assert_eq!(4, chip8.interpreter.delay_timer());
+        stepper.run_frame(&mut chip8);
+        assert_eq!((3, 0), (stepper.frames(), stepper.cycle()));
+        assert_eq!(3, chip8.interpreter.delay_timer());
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let mut debugger = debugger();
+        debugger.step();
+        debugger.run_frames(2);
+
+        assert!(debugger.registers().starts_with("frame 2 cycle 0"));
+        assert_eq!(1 + 19 / 3, debugger.chip8().interpreter.vx[0]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::rom_builder::*;
+
+use clap::ValueEnum;
+
+/// Diagnostic programs `gen demo` writes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum DemoKind {
+    /// Shows the digit of each key pressed, with a beep
+    KeypadTest,
+    /// Draws the screen borders and a checkerboard in the center
+    DisplayTest,
+    /// Counts seconds from the 60 Hz delay timer, beeping each second
+    TimingTest,
+}
+
+impl DemoKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DemoKind::KeypadTest => "keypad-test",
+            DemoKind::DisplayTest => "display-test",
+            DemoKind::TimingTest => "timing-test",
+        }
+    }
+}
+
+/// The program of a demo
+pub fn generate(kind: DemoKind) -> Vec<u8> {
+    match kind {
+        DemoKind::KeypadTest => keypad_test(),
+        DemoKind::DisplayTest => display_test(),
+        DemoKind::TimingTest => timing_test(),
+    }
+}
+
+fn keypad_test() -> Vec<u8> {
+    [
+        cls(),        // 200
+        ld_vx(1, 30), // 202: digit position
+        ld_vx(2, 13), // 204
+        ld_vx(3, 4),  // 206: beep length
+        ld_vx_k(0),   // 208: wait for a key
+        cls(),        // 20A
+        ld_f(0),      // 20C
+        drw(1, 2, 5), // 20E: draw its digit
+        ld_st(3),     // 210
+        jp(0x208),    // 212
+    ]
+    .concat()
+}
+
+fn display_test() -> Vec<u8> {
+    const HLINE: u16 = 0x238;
+    const LEFT: u16 = HLINE + 1;
+    const RIGHT: u16 = LEFT + 15;
+    const CHECKER: u16 = RIGHT + 15;
+
+    let mut program = [
+        cls(),         // 200
+        ld_vx(0, 0),   // 202: x
+        ld_vx(1, 0),   // 204: top row
+        ld_vx(2, 31),  // 206: bottom row
+        ld_i(HLINE),   // 208
+        drw(0, 1, 1),  // 20A
+        drw(0, 2, 1),  // 20C
+        add_vx(0, 8),  // 20E
+        se_vx(0, 64),  // 210
+        jp(0x20A),     // 212
+        ld_vx(0, 0),   // 214: left column
+        ld_vx(3, 56),  // 216: right column
+        ld_vx(1, 1),   // 218: y
+        ld_i(LEFT),    // 21A
+        drw(0, 1, 15), // 21C
+        ld_i(RIGHT),   // 21E
+        drw(3, 1, 15), // 220
+        add_vx(1, 15), // 222
+        se_vx(1, 31),  // 224
+        jp(0x21A),     // 226
+        ld_vx(0, 24),  // 228: checkerboard
+        ld_vx(1, 12),  // 22A
+        ld_i(CHECKER), // 22C
+        drw(0, 1, 8),  // 22E
+        add_vx(0, 8),  // 230
+        drw(0, 1, 8),  // 232
+        ld_vx_k(0),    // 234: keep the display, key presses do nothing
+        jp(0x234),     // 236
+    ]
+    .concat();
+
+    program.push(0xFF); // 238: horizontal line
+    program.extend([0x80; 15]); // 239: left border
+    program.extend([0x01; 15]); // 248: right border
+    program.extend([0xAA, 0x55].repeat(4)); // 257: checkerboard
+    program
+}
+
+fn timing_test() -> Vec<u8> {
+    const DIGITS: u16 = 0x230;
+
+    let mut program = [
+        ld_vx(5, 0),  // 200: seconds
+        ld_vx(6, 60), // 202
+        ld_dt(6),     // 204: one second
+        ld_vx(7, 2),  // 206
+        ld_st(7),     // 208: tick
+        cls(),        // 20A
+        ld_i(DIGITS), // 20C
+        ld_b(5),      // 20E
+        load(2),      // 210: V0 to V2 = digits of the seconds
+        ld_vx(3, 22), // 212
+        ld_vx(4, 13), // 214
+        ld_f(0),      // 216
+        drw(3, 4, 5), // 218
+        add_vx(3, 8), // 21A
+        ld_f(1),      // 21C
+        drw(3, 4, 5), // 21E
+        add_vx(3, 8), // 220
+        ld_f(2),      // 222
+        drw(3, 4, 5), // 224
+        ld_vx_dt(6),  // 226: wait for the end of the second
+        se_vx(6, 0),  // 228
+        jp(0x226),    // 22A
+        add_vx(5, 1), // 22C
+        jp(0x202),    // 22E
+    ]
+    .concat();
+
+    program.extend([0; 3]); // 230: digits
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::framebuffer::FrameBuffer;
+    use crate::interpreter::KeyEvent;
+
+    fn run(kind: DemoKind, frames: u32) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.load(&generate(kind));
+        for _ in 0..frames {
+            chip8.run_frame(11);
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_keypad_test() {
+        let mut chip8 = run(DemoKind::KeypadTest, 2);
+        chip8.push_key_event(KeyEvent { key: 1, down: true });
+        chip8.run_frame(11);
+        chip8.push_key_event(KeyEvent {
+            key: 1,
+            down: false,
+        });
+        chip8.run_frame(11);
+
+        assert!(chip8.interpreter.sound_on());
+        let frame = FrameBuffer::from_memory(&chip8.memory);
+        assert!(frame.pixel(32, 13)); // top of the 1
+        assert!(!frame.pixel(30, 13));
+    }
+
+    #[test]
+    fn test_display_test() {
+        let chip8 = run(DemoKind::DisplayTest, 20);
+        let frame = FrameBuffer::from_memory(&chip8.memory);
+
+        for (x, y) in [
+            (0, 0),
+            (63, 0),
+            (0, 31),
+            (63, 31),
+            (0, 15),
+            (63, 20),
+            (24, 12),
+        ] {
+            assert!(frame.pixel(x, y), "({}, {})", x, y);
+        }
+        assert!(!frame.pixel(1, 1));
+        assert!(!frame.pixel(25, 12));
+        assert!(!chip8.interpreter.stop());
+    }
+
+    #[test]
+    fn test_timing_test() {
+        let chip8 = run(DemoKind::TimingTest, 60 * 3 + 10);
+
+        assert_eq!(3, chip8.interpreter.vx[5]);
+    }
+}
@@ -0,0 +1,603 @@
+use crate::memory;
+use crate::opcodes;
+
+pub use crate::opcodes::Extension;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub opcode: u16,
+    pub pattern: &'static str, // e.g. "8XY4"
+    pub mnemonic: String,      // e.g. "ADD V1, V2"
+    pub extension: Extension,
+}
+
+/// How an instruction changes the program counter
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Flow {
+    Next,
+    Skip,
+    Jump(u16),
+    Call(u16),
+    Return,
+    Stop,
+    Indirect(u16), // jump to NNN + register
+}
+
+impl Instruction {
+    /// Size in bytes, F000 NNNN is the only 4 bytes instruction
+    pub fn size(&self) -> u16 {
+        if self.pattern == "F000" {
+            4
+        } else {
+            2
+        }
+    }
+
+    pub fn flow(&self) -> Flow {
+        let nnn = self.opcode & 0x0FFF;
+
+        match self.pattern {
+            "1NNN" => Flow::Jump(nnn),
+            "2NNN" => Flow::Call(nnn),
+            "BNNN" => Flow::Indirect(nnn),
+            "00EE" => Flow::Return,
+            "0000" | "00FD" => Flow::Stop,
+            "3XNN" | "4XNN" | "5XY0" | "9XY0" | "EX9E" | "EXA1" => Flow::Skip,
+            _ => Flow::Next,
+        }
+    }
+}
+
+/// Decode an opcode, returns None for unknown opcodes
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    opcodes::lookup(opcode).map(|row| Instruction {
+        opcode,
+        pattern: row.pattern,
+        mnemonic: row.format(opcode),
+        extension: row.extension,
+    })
+}
+
+/// Follow the control flow from the program start and return the reachable instructions
+/// by address. Computed jumps (BNNN), unknown opcodes and instructions running to the end of
+/// the 64K space end the path being followed.
+pub fn reachable_instructions(program: &[u8]) -> BTreeMap<u16, Instruction> {
+    let start = memory::PROG_LOC;
+    let end = memory::PROG_LOC as usize + program.len();
+
+    let read = |addr: u16| -> Option<u16> {
+        if addr < start || addr as usize + 1 >= end {
+            return None;
+        }
+        let i = (addr - start) as usize;
+        Some((program[i] as u16) << 8 | program[i + 1] as u16)
+    };
+
+    let mut instructions = BTreeMap::new();
+    let mut to_visit = vec![start];
+
+    while let Some(addr) = to_visit.pop() {
+        if instructions.contains_key(&addr) {
+            continue;
+        }
+
+        let instruction = match read(addr).and_then(decode) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let Some(next) = addr.checked_add(instruction.size()) else {
+            continue;
+        };
+
+        match instruction.flow() {
+            Flow::Next => to_visit.push(next),
+            Flow::Skip => {
+                to_visit.push(next);
+                let skipped_size = read(next).and_then(decode).map_or(2, |i| i.size());
+                to_visit.extend(next.checked_add(skipped_size));
+            }
+            Flow::Jump(target) => to_visit.push(target),
+            Flow::Call(target) => {
+                to_visit.push(target);
+                to_visit.push(next);
+            }
+            Flow::Return | Flow::Stop | Flow::Indirect(_) => (),
+        }
+
+        instructions.insert(addr, instruction);
+    }
+
+    instructions
+}
+
+/// Straight-line run of instructions, only entered at its first one and left at its last one
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BasicBlock {
+    pub start: u16,
+    pub end: u16,             // address following the last instruction
+    pub successors: Vec<u16>, // blocks the last instruction can continue to, calls returning
+}
+
+/// Control flow of a program reconstructed without running it, from the program start
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ControlFlowGraph {
+    pub instructions: BTreeMap<u16, Instruction>, // reachable instructions by address
+    pub blocks: BTreeMap<u16, BasicBlock>,        // by start address
+    pub jump_targets: BTreeSet<u16>,
+    pub computed_jumps: BTreeSet<u16>, // base addresses of BNNN
+    pub calls: BTreeMap<u16, BTreeSet<u16>>, // subroutines called by each subroutine
+    pub data_regions: Vec<(u16, u16)>, // inclusive ranges never reached
+}
+
+impl ControlFlowGraph {
+    /// Reachable instructions in address order
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Instruction)> {
+        self.instructions
+            .iter()
+            .map(|(&addr, instruction)| (addr, instruction))
+    }
+
+    /// Entry points of the subroutines, plus the program start
+    pub fn subroutines(&self) -> impl Iterator<Item = u16> + '_ {
+        self.calls.keys().copied()
+    }
+
+    /// Addresses called at least once
+    pub fn call_targets(&self) -> BTreeSet<u16> {
+        self.calls.values().flatten().copied().collect()
+    }
+
+    /// Block containing an instruction
+    pub fn block_at(&self, addr: u16) -> Option<&BasicBlock> {
+        self.blocks
+            .range(..=addr)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| addr < block.end)
+    }
+
+    /// Graphviz DOT source of the graph: blocks labeled with their disassembly, solid edges
+    /// for the control flow ("skip" for taken skips) and dashed ones for calls
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph cfg {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (addr, instruction) in self.instructions.range(block.start..block.end) {
+                write!(label, "{:03X}  {}\\l", addr, instruction.mnemonic).unwrap();
+            }
+            let style = match block.start {
+                memory::PROG_LOC => ", style=bold",
+                _ => "",
+            };
+            writeln!(
+                out,
+                "    b{:03X} [label=\"{}\"{}];",
+                block.start, label, style
+            )
+            .unwrap();
+        }
+        for &(start, end) in &self.data_regions {
+            writeln!(
+                out,
+                "    d{:03X} [label=\"{:03X}-{:03X}  data\", shape=note];",
+                start, start, end
+            )
+            .unwrap();
+        }
+
+        for block in self.blocks.values() {
+            let last = self.instructions.range(..block.end).next_back().unwrap().1;
+            for (i, successor) in block.successors.iter().enumerate() {
+                let label = match (last.flow(), i) {
+                    (Flow::Skip, 1) => " [label=\"skip\"]",
+                    _ => "",
+                };
+                writeln!(
+                    out,
+                    "    b{:03X} -> b{:03X}{};",
+                    block.start, successor, label
+                )
+                .unwrap();
+            }
+            if let Flow::Call(target) = last.flow() {
+                if self.blocks.contains_key(&target) {
+                    writeln!(
+                        out,
+                        "    b{:03X} -> b{:03X} [style=dashed];",
+                        block.start, target
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+/// Split the reachable instructions of a program into basic blocks, and find its jump
+/// targets, call graph and the regions never reached by the control flow (usually sprite data)
+pub fn analyze(program: &[u8]) -> ControlFlowGraph {
+    let instructions = reachable_instructions(program);
+
+    // where the control flow can continue after each instruction, excluding called subroutines
+    let successors = |addr: u16, instruction: &Instruction| -> Vec<u16> {
+        let next = addr + instruction.size();
+        let targets = match instruction.flow() {
+            Flow::Next | Flow::Call(_) => vec![next],
+            Flow::Skip => {
+                let skipped_size = instructions.get(&next).map_or(2, |i| i.size());
+                [Some(next), next.checked_add(skipped_size)]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+            Flow::Jump(target) => vec![target],
+            Flow::Return | Flow::Stop | Flow::Indirect(_) => vec![],
+        };
+        targets
+            .into_iter()
+            .filter(|target| instructions.contains_key(target))
+            .collect()
+    };
+
+    let mut jump_targets = BTreeSet::new();
+    let mut computed_jumps = BTreeSet::new();
+    let mut subroutines = BTreeSet::from([memory::PROG_LOC]);
+    let mut leaders = BTreeSet::from([memory::PROG_LOC]);
+    for (&addr, instruction) in &instructions {
+        match instruction.flow() {
+            Flow::Next => continue,
+            Flow::Jump(target) => {
+                jump_targets.insert(target);
+            }
+            Flow::Call(target) => {
+                subroutines.insert(target);
+                leaders.insert(target);
+            }
+            Flow::Indirect(base) => {
+                computed_jumps.insert(base);
+            }
+            _ => (),
+        }
+        leaders.extend(successors(addr, instruction));
+    }
+
+    let mut blocks: BTreeMap<u16, BasicBlock> = BTreeMap::new();
+    let mut current: Option<BasicBlock> = None;
+    for (&addr, instruction) in &instructions {
+        let next = addr + instruction.size();
+        let mut block = match current.take() {
+            Some(block) if block.end == addr && !leaders.contains(&addr) => block,
+            _ => BasicBlock {
+                start: addr,
+                end: next,
+                successors: Vec::new(),
+            },
+        };
+        block.end = next;
+
+        let falls_through = instruction.flow() == Flow::Next
+            && instructions.contains_key(&next)
+            && !leaders.contains(&next);
+        if falls_through {
+            current = Some(block);
+        } else {
+            block.successors = successors(addr, instruction);
+            blocks.insert(block.start, block);
+        }
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    // call graph, following each subroutine through its blocks
+    let mut calls = BTreeMap::new();
+    for &entry in &subroutines {
+        let mut called = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut to_visit = vec![entry];
+        while let Some(start) = to_visit.pop() {
+            if !visited.insert(start) {
+                continue;
+            }
+            let block = match blocks.get(&start) {
+                Some(block) => block,
+                None => continue,
+            };
+            let last = instructions.range(..block.end).next_back().unwrap().1;
+            if let Flow::Call(target) = last.flow() {
+                called.insert(target);
+            }
+            to_visit.extend(&block.successors);
+        }
+        calls.insert(entry, called);
+    }
+
+    let data_regions = unreachable_regions(program, &instructions);
+
+    ControlFlowGraph {
+        instructions,
+        blocks,
+        jump_targets,
+        computed_jumps,
+        calls,
+        data_regions,
+    }
+}
+
+/// Inclusive address ranges of the program bytes not covered by the given instructions
+fn unreachable_regions(
+    program: &[u8],
+    instructions: &BTreeMap<u16, Instruction>,
+) -> Vec<(u16, u16)> {
+    let mut reached = vec![false; program.len()];
+    for (&addr, instruction) in instructions {
+        let start = (addr - memory::PROG_LOC) as usize;
+        let end = (start + instruction.size() as usize).min(program.len());
+        reached[start..end].fill(true);
+    }
+
+    let mut regions = Vec::new();
+    let mut start = None;
+
+    for (i, &covered) in reached.iter().chain([true].iter()).enumerate() {
+        // as usize, the end of a 64K program being past u16::MAX
+        let addr = memory::PROG_LOC as usize + i;
+        match (covered, start) {
+            (false, None) => start = Some(addr as u16),
+            (true, Some(first)) => {
+                regions.push((first, (addr - 1) as u16));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+
+    regions
+}
+
+/// How `listing` shows data bytes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataStyle {
+    Bytes,   // `db` lines of up to 8 bytes
+    Sprites, // a byte per line with its pixels, e.g. `F0  ####....`
+}
+
+/// Bytes per `db` line
+const DB_BYTES: usize = 8;
+
+/// Disassembly of the program bytes in `range`, one instruction per line in address order.
+/// The bytes in the `data` regions, and the ones not decoding to an instruction, are listed
+/// as data instead.
+pub fn listing(
+    program: &[u8],
+    range: RangeInclusive<u16>,
+    data: &[RangeInclusive<u16>],
+    style: DataStyle,
+) -> String {
+    // addresses as u32, the end of a 64K program being past u16::MAX
+    let program_end = memory::PROG_LOC as u32 + program.len() as u32;
+    let end = (*range.end() as u32 + 1).min(program_end);
+    let byte = |addr: u32| program[(addr - memory::PROG_LOC as u32) as usize];
+    let word = |addr: u32| (byte(addr) as u16) << 8 | byte(addr + 1) as u16;
+
+    let mut out = String::new();
+    let mut data_bytes: Vec<(u16, u8)> = Vec::new(); // pending, listed together
+    let mut addr = (*range.start()).max(memory::PROG_LOC) as u32;
+
+    while addr < end {
+        let in_data = |addr: u32| data.iter().any(|region| region.contains(&(addr as u16)));
+        // instructions must not run into a data region
+        let fits = |size: u32| addr + size <= end && !(addr..addr + size).any(in_data);
+
+        let instruction = match fits(2) {
+            true => decode(word(addr)).filter(|instruction| fits(instruction.size() as u32)),
+            false => None,
+        };
+        let Some(instruction) = instruction else {
+            let size = if fits(2) { 2 } else { 1 };
+            data_bytes.extend((addr..addr + size).map(|addr| (addr as u16, byte(addr))));
+            addr += size;
+            continue;
+        };
+
+        write_data(&mut out, &std::mem::take(&mut data_bytes), style);
+        match instruction.size() {
+            4 => {
+                let long = word(addr + 2);
+                writeln!(
+                    out,
+                    "{:03X}: {:04X} {:04X}  LD I, {:04X}",
+                    addr, instruction.opcode, long, long
+                )
+                .unwrap();
+            }
+            _ => writeln!(
+                out,
+                "{:03X}: {:04X}  {}",
+                addr, instruction.opcode, instruction.mnemonic
+            )
+            .unwrap(),
+        }
+        addr += instruction.size() as u32;
+    }
+    write_data(&mut out, &data_bytes, style);
+
+    out
+}
+
+/// List consecutive data bytes by address
+fn write_data(out: &mut String, bytes: &[(u16, u8)], style: DataStyle) {
+    match style {
+        DataStyle::Bytes => {
+            for line in bytes.chunks(DB_BYTES) {
+                let values: Vec<String> = line.iter().map(|(_, b)| format!("{:02X}", b)).collect();
+                writeln!(out, "{:03X}: db {}", line[0].0, values.join(" ")).unwrap();
+            }
+        }
+        DataStyle::Sprites => {
+            for &(addr, b) in bytes {
+                let pixels: String = (0..8)
+                    .map(|bit| if b & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect();
+                writeln!(out, "{:03X}: {:02X}  {}", addr, b, pixels).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let instruction = decode(0x8124).unwrap();
+        assert_eq!("8XY4", instruction.pattern);
+        assert_eq!("ADD V1, V2", instruction.mnemonic);
+        assert_eq!(Extension::Chip8, instruction.extension);
+
+        assert_eq!("DRW V0, V1, 5", decode(0xD015).unwrap().mnemonic);
+        assert_eq!(Extension::Schip, decode(0x00FF).unwrap().extension);
+        assert_eq!(Extension::XoChip, decode(0xF000).unwrap().extension);
+        assert_eq!(None, decode(0x8008));
+        assert_eq!(None, decode(0xE000));
+    }
+
+    #[test]
+    fn test_reachable_instructions() {
+        let program = [
+            0x22, 0x08, // 200: call 0x208
+            0x12, 0x06, // 202: jump to 0x206
+            0xFF, 0xFF, // 204: data
+            0x00, 0x00, // 206: halt
+            0x30, 0x00, // 208: skip next if V0 == 0
+            0xF0, 0x00, 0x12, 0x34, // 20A: I = 0x1234
+            0x00, 0xEE, // 20E: return
+        ];
+
+        let addresses: Vec<u16> = reachable_instructions(&program).keys().copied().collect();
+        assert_eq!(vec![0x200, 0x202, 0x206, 0x208, 0x20A, 0x20E], addresses);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let program = [
+            0x60, 0x05, // 200: V0 = 5
+            0x22, 0x0C, // 202: call 0x20C
+            0x70, 0xFF, // 204: V0 = V0 - 1
+            0x30, 0x00, // 206: skip next if V0 == 0
+            0x12, 0x02, // 208: jump to 0x202
+            0x00, 0x00, // 20A: halt
+            0x22, 0x12, // 20C: call 0x212
+            0x00, 0xEE, // 20E: return
+            0xF0, 0xF0, // 210: sprite data
+            0x00, 0xEE, // 212: return
+        ];
+
+        let graph = analyze(&program);
+
+        let blocks: Vec<(u16, u16, Vec<u16>)> = graph
+            .blocks
+            .values()
+            .map(|block| (block.start, block.end, block.successors.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                (0x200, 0x202, vec![0x202]),
+                (0x202, 0x204, vec![0x204]),
+                (0x204, 0x208, vec![0x208, 0x20A]),
+                (0x208, 0x20A, vec![0x202]),
+                (0x20A, 0x20C, vec![]),
+                (0x20C, 0x20E, vec![0x20E]),
+                (0x20E, 0x210, vec![]),
+                (0x212, 0x214, vec![]),
+            ],
+            blocks
+        );
+        assert_eq!(Some(0x204), graph.block_at(0x206).map(|block| block.start));
+        assert_eq!(None, graph.block_at(0x210));
+
+        assert_eq!(BTreeSet::from([0x202]), graph.jump_targets);
+        assert_eq!(BTreeSet::from([0x20C]), graph.calls[&0x200]);
+        assert_eq!(BTreeSet::from([0x212]), graph.calls[&0x20C]);
+        assert!(graph.calls[&0x212].is_empty());
+        assert_eq!(BTreeSet::from([0x20C, 0x212]), graph.call_targets());
+        assert_eq!(vec![(0x210, 0x211)], graph.data_regions);
+        assert_eq!(9, graph.iter().count());
+
+        // a program filling the 64K space, its last instruction ending the address space
+        let program = [0x60, 0x05].repeat(0x7F00);
+        let graph = analyze(&program);
+        assert_eq!(Some(&0xFFFC), graph.instructions.keys().next_back());
+        assert_eq!(vec![(0xFFFE, 0xFFFF)], graph.data_regions);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let program = [
+            0x22, 0x06, // 200: call 0x206
+            0x30, 0x00, // 202: skip next if V0 == 0
+            0x12, 0x00, // 204: jump to 0x200
+            0x00, 0xEE, // 206: return
+            0xF0, 0xF0, // 208: sprite data
+        ];
+
+        let dot = analyze(&program).to_dot();
+
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("b200 [label=\"200  CALL 206\\l\", style=bold];"));
+        assert!(dot.contains("b202 [label=\"202  SE V0, 00\\l\"];"));
+        assert!(dot.contains("d208 [label=\"208-209  data\", shape=note];"));
+        assert!(dot.contains("b200 -> b202;"));
+        assert!(dot.contains("b200 -> b206 [style=dashed];"));
+        assert!(dot.contains("b202 -> b206 [label=\"skip\"];"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_listing() {
+        let program = [
+            0xA2, 0x08, // 200: I = 0x208
+            0xFF, 0xFF, // 202: unknown
+            0xF0, 0x00, 0x12, 0x34, // 204: I = 0x1234
+            0x90, 0x90, // 208: sprite data
+            0x00, // 20A: odd byte
+        ];
+        assert_eq!(
+            "200: A208  LD I, 208\n\
+             202: db FF FF\n\
+             204: F000 1234  LD I, 1234\n\
+             208: 9090  SNE V0, V9\n\
+             20A: db 00\n",
+            listing(&program, 0x200..=0xFFF, &[], DataStyle::Bytes)
+        );
+        assert_eq!(
+            "204: F000 1234  LD I, 1234\n\
+             208: db 90 90\n",
+            listing(&program, 0x204..=0x209, &[0x208..=0x209], DataStyle::Bytes)
+        );
+        assert_eq!(
+            "200: A208  LD I, 208\n\
+             202: FF  ########\n\
+             203: FF  ########\n\
+             204: F0  ####....\n\
+             205: 00  ........\n",
+            listing(
+                &program,
+                0x200..=0x205,
+                &[0x204..=0x205],
+                DataStyle::Sprites
+            )
+        );
+    }
+}
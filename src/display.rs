@@ -1,58 +1,269 @@
+use crate::display_settings::{DisplaySettings, ScaleMode};
+use crate::framebuffer::FrameBuffer;
+use crate::geometry::{self, WindowGeometry};
+use crate::heatmap::Heatmap;
 use crate::memory;
+use crate::palette::Palette;
+use crate::scaler::Filter;
+use crate::text;
+use crate::tiles::Layout;
 
-use pixels::{Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
 use winit::event_loop::EventLoop;
-use winit::window::{Window, WindowBuilder};
+use winit::window::{Fullscreen, Window, WindowBuilder};
 
-pub struct Display {
-    pub pixels: Pixels,
+/// Texture pixels per CHIP-8 pixel, leaving room for text overlays
+pub const SCALE: usize = 4;
+
+/// Bytes of one texture row of a 64 pixels wide screen
+const ROW_BYTES: usize = 64 * SCALE * 4;
+
+/// Darkening of the last texture row of each display line with scanlines
+const SCANLINE_DIM: f32 = 0.5;
+
+/// Speaker icon shown while the buzzer sounds, 9 bits per row, most significant bit left
+#[rustfmt::skip]
+const SPEAKER: [u16; 7] = [
+    0b000010000,
+    0b000110010,
+    0b111110001,
+    0b111110001,
+    0b111110001,
+    0b000110010,
+    0b000010000,
+];
+const SPEAKER_WIDTH: usize = 9;
+
+/// RGBA colors of the 8 pixels of every possible display byte, most significant bit first
+struct ByteLut([[[u8; 4]; 8]; 256]);
+
+impl ByteLut {
+    fn new(palette: &Palette) -> Self {
+        let mut lut = [[[0; 4]; 8]; 256];
+
+        for (byte, pixels) in lut.iter_mut().enumerate() {
+            for (bit, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = if (byte >> (7 - bit)) & 1 == 1 {
+                    palette.foreground()
+                } else {
+                    palette.background()
+                };
+            }
+        }
+
+        ByteLut(lut)
+    }
+
+    /// Colors of a 64 pixels display line stored in 8 bytes
+    fn expand(&self, bytes: &[u8], line: &mut [[u8; 4]; 64]) {
+        for (pixels, &byte) in line.chunks_exact_mut(8).zip(bytes) {
+            pixels.copy_from_slice(&self.0[byte as usize]);
+        }
+    }
+}
+
+/// Texture row of a display line, each pixel repeated SCALE times
+fn scale_line(line: &[[u8; 4]; 64], row: &mut [u8; ROW_BYTES]) {
+    for (pixels, color) in row.chunks_exact_mut(SCALE * 4).zip(line) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(color);
+        }
+    }
+}
+
+/// Texture the display draws into and shows, a window through `pixels` when running.
+/// Tests draw into a mock instead, without a window or a GPU surface.
+pub trait FrameSink {
+    /// Texture size in pixels
+    fn size(&self) -> (usize, usize);
+
+    /// Copy rows of `width` RGBA pixels to the texture, the first one at (x, y)
+    fn blit(&mut self, x: usize, y: usize, width: usize, rgba: &[u8]);
+
+    /// RGBA color of a texture pixel
+    fn pixel(&mut self, x: usize, y: usize) -> [u8; 4];
+
+    /// Show the texture
+    fn present(&mut self) -> Result<(), String>;
+}
+
+/// Window showing the texture through `pixels`
+pub struct WindowSink {
+    pixels: Pixels,
     window: Window,
+    texture_size: (usize, usize),
+    windowed_size: PhysicalSize<u32>, // last size outside of fullscreen
+}
+
+impl FrameSink for WindowSink {
+    fn size(&self) -> (usize, usize) {
+        self.texture_size
+    }
+
+    fn blit(&mut self, x: usize, y: usize, width: usize, rgba: &[u8]) {
+        let texture_width = self.texture_size.0;
+        let frame = self.pixels.get_frame();
+
+        for (row, source) in rgba.chunks_exact(width * 4).enumerate() {
+            let offset = ((y + row) * texture_width + x) * 4;
+            frame[offset..offset + width * 4].copy_from_slice(source);
+        }
+    }
+
+    fn pixel(&mut self, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * self.texture_size.0 + x) * 4;
+        let frame = self.pixels.get_frame();
+
+        [
+            frame[offset],
+            frame[offset + 1],
+            frame[offset + 2],
+            frame[offset + 3],
+        ]
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        self.pixels.render().map_err(|e| e.to_string())
+    }
+}
+
+pub struct Display<S: FrameSink = WindowSink> {
+    sink: S,
+    palette: Palette,
+    lut: ByteLut,          // display bytes to colors of the palette
+    layout: Layout,        // grid of 64x32 screens
+    fade: f32,             // brightness kept at each draw by pixels turned off
+    levels: Vec<Vec<f32>>, // brightness of the pixels of each screen, with fade
+    scanlines: bool,
+    scale_mode: ScaleMode,
+    filter: Filter,
+    drawn: Vec<[u8; 256]>, // display bytes of each screen last drawn
+    stale: Vec<bool>,      // screens to draw whole, e.g. under an overlay
 }
 
 impl Display {
-    pub fn new(event_loop: &EventLoop<()>) -> Self {
+    /// `geometry` restores the window of a previous launch, `vsync` makes presenting wait for
+    /// the monitor refresh
+    pub fn new(
+        event_loop: &EventLoop<()>,
+        palette: Palette,
+        layout: Layout,
+        geometry: Option<WindowGeometry>,
+        vsync: bool,
+    ) -> Self {
         let window = {
-            let size = LogicalSize::new(512, 256);
-            WindowBuilder::new()
+            let size = LogicalSize::new(512 * layout.columns as u32, 256 * layout.rows as u32);
+            let mut builder = WindowBuilder::new()
                 .with_title("CHIP-8")
                 .with_inner_size(size)
-                .with_min_inner_size(size)
-                .build(&event_loop)
-                .unwrap()
+                .with_min_inner_size(size);
+
+            if let Some(geometry) = geometry {
+                builder =
+                    builder.with_inner_size(PhysicalSize::new(geometry.width, geometry.height));
+                if let (Some(x), Some(y)) = (geometry.x, geometry.y) {
+                    builder = builder.with_position(PhysicalPosition::new(x, y));
+                }
+                if geometry.fullscreen {
+                    builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+                }
+            }
+
+            builder.build(event_loop).unwrap()
+        };
+        let windowed_size = match geometry {
+            Some(geometry) => PhysicalSize::new(geometry.width, geometry.height),
+            None => window.inner_size(),
         };
 
+        let texture_size = (64 * SCALE * layout.columns, 32 * SCALE * layout.rows);
         let pixels = {
             let window_size = window.inner_size();
             let surface_texture =
                 SurfaceTexture::new(window_size.width, window_size.height, &window);
-            Pixels::new(64, 32, surface_texture).unwrap()
+            PixelsBuilder::new(
+                texture_size.0 as u32,
+                texture_size.1 as u32,
+                surface_texture,
+            )
+            .enable_vsync(vsync)
+            .build()
+            .unwrap()
+        };
+
+        let sink = WindowSink {
+            pixels,
+            window,
+            texture_size,
+            windowed_size,
         };
+        Display::with_sink(sink, palette, layout)
+    }
+
+    /// Resize the surface to the window, and with `ScaleMode::Snap` snap the window to an
+    /// integer multiple of the display when the new size is close to one
+    pub fn resized(&mut self, size: PhysicalSize<u32>) {
+        self.sink.pixels.resize_surface(size.width, size.height);
+
+        if self.sink.window.fullscreen().is_some() {
+            return;
+        }
+        self.sink.windowed_size = size;
+
+        if let Some((width, height)) = self.snapped_size((size.width, size.height)) {
+            self.sink
+                .window
+                .set_inner_size(PhysicalSize::new(width, height));
+        }
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.sink.window.set_title(title);
+    }
 
-        Display { window, pixels }
+    pub fn toggle_fullscreen(&self) {
+        let window = &self.sink.window;
+        if window.fullscreen().is_some() {
+            window.set_fullscreen(None);
+        } else {
+            window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+    }
+
+    /// Current window geometry, with the windowed size when in fullscreen
+    pub fn geometry(&self) -> WindowGeometry {
+        let window = &self.sink.window;
+        let position = window.outer_position().ok();
+
+        WindowGeometry {
+            width: self.sink.windowed_size.width,
+            height: self.sink.windowed_size.height,
+            x: position.map(|position| position.x),
+            y: position.map(|position| position.y),
+            fullscreen: window.fullscreen().is_some(),
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.sink.window
     }
 
     pub fn read_pixel(memory: &memory::Memory, x: u8, y: u8) -> u8 {
-        let byte = memory.read(Display::pos_to_byte_addr(x, y));
+        let byte = memory.display_byte(Display::pos_to_bit_index(x, y) / 8);
         let bit = byte >> (7 - Display::pos_to_bit_offset(x, y));
 
-        return bit & 0b0000_0001;
+        bit & 0b0000_0001
     }
 
     pub fn write_pixel(memory: &mut memory::Memory, x: u8, y: u8) {
-        let byte_addr = Display::pos_to_byte_addr(x, y);
+        let byte_index = Display::pos_to_bit_index(x, y) / 8;
         let bit_offset = Display::pos_to_bit_offset(x, y);
 
         let byte_to_write = 0b1000_0000 >> bit_offset;
-        let current_byte = memory.read(byte_addr);
-
-        memory.write(byte_addr, current_byte ^ byte_to_write);
-    }
+        let current_byte = memory.display_byte(byte_index);
 
-    pub fn pos_to_byte_addr(x: u8, y: u8) -> u16 {
-        let bit_idx = Display::pos_to_bit_index(x, y);
-        let byte_addr = bit_idx / 8;
-        return memory::DISPLAY_LOC + byte_addr;
+        memory.set_display_byte(byte_index, current_byte ^ byte_to_write);
     }
 
     pub fn pos_to_bit_offset(x: u8, y: u8) -> u8 {
@@ -63,28 +274,594 @@ impl Display {
         (x as u16) + (64 * (y as u16)) // x + DISPLAY_WIDTH * y
     }
 
+    /// Positions of the pixels that differ between two displays
+    pub fn diff(a: &memory::Memory, b: &memory::Memory) -> Vec<(u8, u8)> {
+        FrameBuffer::from_memory(a).diff(&FrameBuffer::from_memory(b))
+    }
+}
+
+impl<S: FrameSink> Display<S> {
+    /// Display of `layout.columns` by `layout.rows` screens drawing into `sink`
+    pub fn with_sink(sink: S, palette: Palette, layout: Layout) -> Self {
+        let screens = layout.columns * layout.rows;
+
+        Display {
+            sink,
+            palette,
+            lut: ByteLut::new(&palette),
+            layout,
+            fade: 0.0,
+            levels: vec![vec![0.0; 64 * 32]; screens],
+            scanlines: false,
+            scale_mode: ScaleMode::Snap,
+            filter: Filter::None,
+            drawn: vec![[0; 256]; screens],
+            stale: vec![true; screens],
+        }
+    }
+
+    /// Texture size in pixels, the coordinate space of the overlays
+    pub fn size(&self) -> (usize, usize) {
+        (
+            64 * SCALE * self.layout.columns,
+            32 * SCALE * self.layout.rows,
+        )
+    }
+
+    /// Window size to snap to after a resize to `size`: with `ScaleMode::Snap`, the closest
+    /// integer multiple of the texture when `size` is close to one
+    pub fn snapped_size(&self, size: (u32, u32)) -> Option<(u32, u32)> {
+        if self.scale_mode == ScaleMode::Free {
+            return None;
+        }
+
+        let (width, height) = self.size();
+        geometry::snap(size, (width as u32, height as u32))
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.lut = ByteLut::new(&palette);
+        self.stale.fill(true);
+    }
+
+    /// Fade, scanlines and scale mode of the settings, their colors go through the palette
+    pub fn apply_settings(&mut self, settings: &DisplaySettings) {
+        self.fade = settings.fade_decay();
+        for levels in &mut self.levels {
+            levels.fill(0.0);
+        }
+        self.scanlines = settings.scanlines;
+        self.scale_mode = settings.scale;
+        self.stale.fill(true);
+    }
+
+    /// Smoothing of the pixel edges, the whole screens being drawn at each draw when set
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+        self.stale.fill(true);
+    }
+
+    /// Show what was drawn
+    pub fn present(&mut self) -> Result<(), String> {
+        self.sink.present()
+    }
+
     /// Modify texture pixels according to memory bits.
-    /// Data is translated from binary values to array of RGBA values.
-    /// Since the display is monochrome (0 or 1 in memory), we set the pixel to green (0x00FF00FF in the texture)
-    pub fn draw(&mut self, memory: &memory::Memory) {
-        let frame = self.pixels.get_frame();
+    /// Data is translated from binary values to array of RGBA values, a whole byte
+    /// (8 pixels) at a time through a lookup table of the palette colors.
+    /// Since the display is monochrome (0 or 1 in memory), we set the pixel to the
+    /// palette background or foreground color.
+    /// With a fade, pixels turned off keep part of their brightness at each draw.
+    /// If a heatmap is given, collisions are blended in red over the game image.
+    /// `screen` is the index of the screen to draw when several are tiled.
+    /// Only the lines changed since the last draw of the screen are drawn, unless an overlay
+    /// was drawn over it since, or a fade or a heatmap changes every line.
+    /// With a filter, the edges of the pixels are smoothed before the palette colors them.
+    pub fn draw(&mut self, screen: usize, memory: &memory::Memory, heatmap: Option<&Heatmap>) {
+        self.draw_bytes(screen, |i| memory.display_byte(i as u16), heatmap);
+    }
+
+    /// Like `draw`, from a copy of the display memory such as `Chip8::captured_display`
+    pub fn draw_frame(&mut self, screen: usize, frame: &FrameBuffer, heatmap: Option<&Heatmap>) {
+        self.draw_bytes(screen, |i| frame.byte(i), heatmap);
+    }
 
-        let mut byte_idx = 0;
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let bit_idx = i as u8 % 8;
-            if bit_idx == 0 {
-                byte_idx += 1
+    fn draw_bytes<F: Fn(usize) -> u8>(
+        &mut self,
+        screen: usize,
+        display_byte: F,
+        heatmap: Option<&Heatmap>,
+    ) {
+        if self.filter != Filter::None {
+            return self.draw_filtered(screen, display_byte, heatmap);
+        }
+
+        let intensities = heatmap.map(|heatmap| heatmap.intensities());
+        let whole = std::mem::take(&mut self.stale[screen]) || self.fade > 0.0 || heatmap.is_some();
+        let (origin_x, origin_y) = self.origin(screen);
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        let mut bytes = [0; 8];
+        let mut line = [[0; 4]; 64];
+        let mut row = [0; ROW_BYTES];
+        let mut scanline = [0; ROW_BYTES];
+        for y in 0..32 {
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = display_byte(y * 8 + i);
+            }
+            let drawn = &mut self.drawn[screen][y * 8..(y + 1) * 8];
+            if !whole && drawn == bytes {
+                continue;
             }
+            drawn.copy_from_slice(&bytes);
+            self.lut.expand(&bytes, &mut line);
 
-            let byte = memory.read(memory::DISPLAY_LOC + byte_idx - 1);
+            if self.fade > 0.0 {
+                let levels = &mut self.levels[screen][y * 64..(y + 1) * 64];
+                for (x, (color, level)) in line.iter_mut().zip(levels).enumerate() {
+                    if bytes[x / 8] >> (7 - x % 8) & 1 == 1 {
+                        *level = 1.0;
+                    } else {
+                        *level *= self.fade;
+                        let rgb = [foreground[0], foreground[1], foreground[2]];
+                        *color = blend(background, rgb, *level);
+                    }
+                }
+            }
 
-            let bit = ((byte << bit_idx) & 0b1000_0000) >> 7;
+            if let Some(intensities) = &intensities {
+                for (x, color) in line.iter_mut().enumerate() {
+                    *color = blend(*color, [0xFF, 0x00, 0x00], intensities[y * 64 + x] * 0.75);
+                }
+            }
 
-            pixel.copy_from_slice(&[0x00, bit * 0xFF, 0x00, 0xFF]);
+            scale_line(&line, &mut row);
+            if self.scanlines {
+                for (dark, color) in scanline.chunks_exact_mut(4).zip(row.chunks_exact(4)) {
+                    let color = [color[0], color[1], color[2], color[3]];
+                    dark.copy_from_slice(&blend(color, [0x00, 0x00, 0x00], SCANLINE_DIM));
+                }
+            }
+            for dy in 0..SCALE {
+                let source = match self.scanlines && dy == SCALE - 1 {
+                    true => &scanline,
+                    false => &row,
+                };
+                self.sink
+                    .blit(origin_x, origin_y + y * SCALE + dy, 64 * SCALE, source);
+            }
         }
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    /// Whole screen upscaled by the filter, each sub-pixel of a pixel lit or not taking the
+    /// foreground or background color, faded and heated as its pixel
+    fn draw_filtered<F: Fn(usize) -> u8>(
+        &mut self,
+        screen: usize,
+        display_byte: F,
+        heatmap: Option<&Heatmap>,
+    ) {
+        let intensities = heatmap.map(|heatmap| heatmap.intensities());
+        let (origin_x, origin_y) = self.origin(screen);
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+        let rgb = [foreground[0], foreground[1], foreground[2]];
+
+        let drawn = &mut self.drawn[screen];
+        for (i, byte) in drawn.iter_mut().enumerate() {
+            *byte = display_byte(i);
+        }
+        let lit: Vec<bool> = (0..64 * 32)
+            .map(|i| drawn[i / 8] >> (7 - i % 8) & 1 == 1)
+            .collect();
+        self.stale[screen] = false;
+
+        let levels = &mut self.levels[screen];
+        for (level, &lit) in levels.iter_mut().zip(&lit) {
+            *level = if lit { 1.0 } else { *level * self.fade };
+        }
+
+        let factor = self.filter.factor();
+        let width = 64 * factor;
+        let scaled = self.filter.apply(&lit, 64, 32);
+        let texels = SCALE / factor; // per side of a sub-pixel
+
+        let mut row = [0; ROW_BYTES];
+        for ty in 0..32 * SCALE {
+            let sy = ty / texels;
+            for (tx, texel) in row.chunks_exact_mut(4).enumerate() {
+                let sx = tx / texels;
+                let pixel = (sy / factor) * 64 + sx / factor;
+                let mut color = match (scaled[sy * width + sx], lit[pixel]) {
+                    (true, _) => foreground,
+                    (false, true) => background,
+                    (false, false) => blend(background, rgb, levels[pixel]),
+                };
+                if let Some(intensities) = &intensities {
+                    color = blend(color, [0xFF, 0x00, 0x00], intensities[pixel] * 0.75);
+                }
+                if self.scanlines && ty % SCALE == SCALE - 1 {
+                    color = blend(color, [0x00, 0x00, 0x00], SCANLINE_DIM);
+                }
+                texel.copy_from_slice(&color);
+            }
+            self.sink.blit(origin_x, origin_y + ty, 64 * SCALE, &row);
+        }
+    }
+
+    /// Tint pixels of a screen already drawn with `draw`
+    pub fn highlight(&mut self, screen: usize, pixels: &[(u8, u8)]) {
+        let (origin_x, origin_y) = self.origin(screen);
+
+        for &(x, y) in pixels {
+            let (x, y) = (origin_x + x as usize * SCALE, origin_y + y as usize * SCALE);
+            let color = self.texture_pixel(x, y);
+            self.fill_rect(x, y, SCALE, SCALE, blend(color, [0xFF, 0x00, 0xFF], 0.75));
+        }
+    }
+
+    /// Blend `color` over the pixels of a screen already drawn with `draw`, by the intensity
+    /// of each pixel (64x32 values from 0.0 to 1.0)
+    pub fn tint(&mut self, screen: usize, intensities: &[f32], color: [u8; 3]) {
+        let (origin_x, origin_y) = self.origin(screen);
+
+        for (i, &intensity) in intensities.iter().enumerate() {
+            if intensity <= 0.0 {
+                continue;
+            }
+            let x = origin_x + (i % 64) * SCALE;
+            let y = origin_y + (i / 64) * SCALE;
+            let pixel = self.texture_pixel(x, y);
+            self.fill_rect(x, y, SCALE, SCALE, blend(pixel, color, intensity * 0.75));
+        }
+    }
+
+    /// Frame a screen with a one pixel border, e.g. to show which one receives input
+    pub fn outline(&mut self, screen: usize, color: [u8; 4]) {
+        let (x, y) = self.origin(screen);
+        let (width, height) = (64 * SCALE, 32 * SCALE);
+
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, y + height - 1, width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(x + width - 1, y, 1, height, color);
+    }
+
+    /// Speaker icon in the top right corner of a screen, for the buzzer to be seen
+    pub fn draw_speaker(&mut self, screen: usize) {
+        const MARGIN: usize = 2;
+
+        let (origin_x, origin_y) = self.origin(screen);
+        let (width, height) = (SPEAKER_WIDTH + 2, SPEAKER.len() + 2);
+        let x = origin_x + 64 * SCALE - width - MARGIN;
+        let y = origin_y + MARGIN;
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        self.fill_rect(x, y, width, height, background);
+        for (dy, bits) in SPEAKER.iter().enumerate() {
+            for dx in 0..SPEAKER_WIDTH {
+                if bits >> (SPEAKER_WIDTH - 1 - dx) & 1 == 1 {
+                    self.fill_rect(x + 1 + dx, y + 1 + dy, 1, 1, foreground);
+                }
+            }
+        }
+    }
+
+    /// Line of text in a left corner of a screen, the top one if `top`, e.g. a readout
+    pub fn draw_label(&mut self, screen: usize, top: bool, line: &str) {
+        const MARGIN: usize = 2;
+
+        let (origin_x, origin_y) = self.origin(screen);
+        let (width, height) = (text::width(line) + 2, text::LINE_HEIGHT);
+        let x = origin_x + MARGIN;
+        let y = match top {
+            true => origin_y + MARGIN,
+            false => origin_y + 32 * SCALE - height - MARGIN,
+        };
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        self.fill_rect(x, y, width, height, background);
+        self.draw_text(x + 1, y + 1, line, foreground);
+    }
+
+    /// Lines of text at the bottom left of a screen, e.g. the instruction --teach executed
+    pub fn draw_caption(&mut self, screen: usize, lines: &[String]) {
+        const MARGIN: usize = 2;
+
+        let (origin_x, origin_y) = self.origin(screen);
+        let widest = lines
+            .iter()
+            .map(|line| text::width(line))
+            .max()
+            .unwrap_or(0);
+        let (width, height) = (widest + 2, lines.len() * text::LINE_HEIGHT);
+        let x = origin_x + MARGIN;
+        let y = (origin_y + 32 * SCALE).saturating_sub(height + MARGIN);
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        self.fill_rect(x, y, width, height, background);
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(x + 1, y + 1 + i * text::LINE_HEIGHT, line, foreground);
+        }
+    }
+
+    /// Line of text centered at the bottom of the texture, over every screen
+    pub fn draw_toast(&mut self, line: &str) {
+        const MARGIN: usize = 4;
+
+        let (texture_width, texture_height) = self.size();
+        let (width, height) = (text::width(line) + 4, text::LINE_HEIGHT + 2);
+        let x = texture_width.saturating_sub(width) / 2;
+        let y = texture_height.saturating_sub(height + MARGIN);
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        self.fill_rect(x, y, width, height, foreground);
+        self.fill_rect(x + 1, y + 1, width - 2, height - 2, background);
+        self.draw_text(x + 2, y + 2, line, foreground);
+    }
+
+    /// Top left corner of a screen in texture pixels
+    fn origin(&self, screen: usize) -> (usize, usize) {
+        let (column, row) = self.layout.position(screen);
+        (column * 64 * SCALE, row * 32 * SCALE)
+    }
+
+    /// Darken everything drawn so far, to make an overlay readable
+    pub fn dim(&mut self, alpha: f32) {
+        let (texture_width, texture_height) = self.size();
+
+        let mut row = vec![0; texture_width * 4];
+        for y in 0..texture_height {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let color = self.sink.pixel(x, y);
+                pixel.copy_from_slice(&blend(color, [0x00, 0x00, 0x00], alpha));
+            }
+            self.sink.blit(0, y, texture_width, &row);
+        }
+        self.stale.fill(true);
+    }
+
+    /// Fill a rectangle of the texture, clipped to its bounds
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 4]) {
+        let (texture_width, texture_height) = self.size();
+        let width = (x + width).min(texture_width).saturating_sub(x);
+        let height = (y + height).min(texture_height).saturating_sub(y);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let rgba = color.repeat(width * height);
+        self.sink.blit(x, y, width, &rgba);
+
+        // the screens under the rectangle are drawn whole next time
+        for (screen, stale) in self.stale.iter_mut().enumerate() {
+            let (column, row) = self.layout.position(screen);
+            let (left, top) = (column * 64 * SCALE, row * 32 * SCALE);
+            if x < left + 64 * SCALE && left < x + width && y < top + 32 * SCALE && top < y + height
+            {
+                *stale = true;
+            }
+        }
+    }
+
+    /// Draw a line of text with its top left corner at (x, y) in texture pixels
+    pub fn draw_text(&mut self, x: usize, y: usize, line: &str, color: [u8; 4]) {
+        text::render(line, |dx, dy| self.fill_rect(x + dx, y + dy, 1, 1, color));
+    }
+
+    /// Dim the display and draw the lines of text in a centered box
+    pub fn draw_panel(&mut self, lines: &[String]) {
+        const PADDING: usize = 4;
+
+        let (texture_width, texture_height) = self.size();
+        let text_width = lines
+            .iter()
+            .map(|line| text::width(line))
+            .max()
+            .unwrap_or(0);
+        let width = text_width + 2 * PADDING;
+        let height = lines.len() * text::LINE_HEIGHT + 2 * PADDING - 2;
+        let x = texture_width.saturating_sub(width) / 2;
+        let y = texture_height.saturating_sub(height) / 2;
+
+        let (background, foreground) = (self.palette.background(), self.palette.foreground());
+
+        self.dim(0.6);
+        self.fill_rect(x, y, width, height, foreground);
+        self.fill_rect(x + 1, y + 1, width - 2, height - 2, background);
+
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(
+                x + PADDING,
+                y + PADDING + i * text::LINE_HEIGHT,
+                line,
+                foreground,
+            );
+        }
+    }
+
+    fn texture_pixel(&mut self, x: usize, y: usize) -> [u8; 4] {
+        self.sink.pixel(x, y)
+    }
+}
+
+/// Mix `color` with `with`, alpha being the weight of `with` from 0.0 to 1.0
+fn blend(color: [u8; 4], with: [u8; 3], alpha: f32) -> [u8; 4] {
+    let mut blended = color;
+    for channel in 0..3 {
+        blended[channel] =
+            (color[channel] as f32 * (1.0 - alpha) + with[channel] as f32 * alpha) as u8;
+    }
+    blended
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Texture in memory, recording the rows blitted
+    struct MockSink {
+        size: (usize, usize),
+        texture: Vec<u8>,
+        blits: Vec<(usize, usize, usize)>, // x, y and width of every row
+    }
+
+    impl FrameSink for MockSink {
+        fn size(&self) -> (usize, usize) {
+            self.size
+        }
+
+        fn blit(&mut self, x: usize, y: usize, width: usize, rgba: &[u8]) {
+            for (row, source) in rgba.chunks_exact(width * 4).enumerate() {
+                let offset = ((y + row) * self.size.0 + x) * 4;
+                self.texture[offset..offset + width * 4].copy_from_slice(source);
+                self.blits.push((x, y + row, width));
+            }
+        }
+
+        fn pixel(&mut self, x: usize, y: usize) -> [u8; 4] {
+            let offset = (y * self.size.0 + x) * 4;
+            self.texture[offset..offset + 4].try_into().unwrap()
+        }
+
+        fn present(&mut self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn mock_display(layout: Layout) -> Display<MockSink> {
+        let size = (64 * SCALE * layout.columns, 32 * SCALE * layout.rows);
+        let sink = MockSink {
+            size,
+            texture: vec![0; size.0 * size.1 * 4],
+            blits: Vec::new(),
+        };
+        Display::with_sink(sink, Palette::new(), layout)
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::new();
+        let mut display = mock_display(Layout::single());
+        let mut memory = memory::Memory::new();
+        Display::write_pixel(&mut memory, 3, 2);
+
+        display.draw(0, &memory, None);
+        assert_eq!(
+            palette.foreground(),
+            display.sink.pixel(3 * SCALE, 2 * SCALE)
+        );
+        assert_eq!(
+            palette.background(),
+            display.sink.pixel(4 * SCALE, 2 * SCALE)
+        );
+
+        // every line drawn again with the new colors
+        display.set_palette(palette.inverted());
+        display.sink.blits.clear();
+        display.draw(0, &memory, None);
+        assert_eq!(32 * SCALE, display.sink.blits.len());
+        assert_eq!(
+            palette.background(),
+            display.sink.pixel(3 * SCALE, 2 * SCALE)
+        );
+    }
+
+    #[test]
+    fn test_dirty_lines() {
+        let mut display = mock_display(Layout::for_count(2));
+        let mut memory = memory::Memory::new();
+        display.draw(0, &memory, None);
+        display.draw(1, &memory, None);
+        // the last row of the second screen, on the right
+        assert_eq!(
+            (64 * SCALE, 32 * SCALE - 1, 64 * SCALE),
+            *display.sink.blits.last().unwrap()
+        );
+
+        // only the changed line
+        display.sink.blits.clear();
+        Display::write_pixel(&mut memory, 10, 5);
+        display.draw(0, &memory, None);
+        let rows: Vec<usize> = display.sink.blits.iter().map(|&(_, y, _)| y).collect();
+        assert_eq!((5 * SCALE..6 * SCALE).collect::<Vec<usize>>(), rows);
+
+        display.sink.blits.clear();
+        display.draw(0, &memory, None);
+        assert!(display.sink.blits.is_empty());
+
+        // a label over the second screen has it drawn whole
+        display.draw_label(1, true, "1:00.00");
+        display.sink.blits.clear();
+        display.draw(0, &memory, None);
+        assert!(display.sink.blits.is_empty());
+        display.draw(1, &memory, None);
+        assert_eq!(32 * SCALE, display.sink.blits.len());
+    }
+
+    #[test]
+    fn test_filter() {
+        let palette = Palette::new();
+        let mut display = mock_display(Layout::for_count(2));
+        let mut memory = memory::Memory::new();
+        Display::write_pixel(&mut memory, 3, 2);
+        Display::write_pixel(&mut memory, 4, 3);
+
+        // the corner between the two pixels of the diagonal is filled on the second screen
+        let corner = (4 * SCALE + 64 * SCALE, 2 * SCALE + SCALE / 2);
+        display.set_filter(Filter::Scale2x);
+        display.draw(1, &memory, None);
+        assert_eq!(palette.foreground(), display.sink.pixel(corner.0, corner.1));
+        assert_eq!(
+            palette.background(),
+            display
+                .sink
+                .pixel(corner.0 + SCALE / 2, corner.1 - SCALE / 2)
+        );
+
+        // every line is drawn again, as a pixel changes its neighbors
+        display.sink.blits.clear();
+        display.draw(1, &memory, None);
+        assert_eq!(32 * SCALE, display.sink.blits.len());
+
+        display.set_filter(Filter::None);
+        display.draw(1, &memory, None);
+        assert_eq!(palette.background(), display.sink.pixel(corner.0, corner.1));
+    }
+
+    #[test]
+    fn test_snapped_size() {
+        let mut display = mock_display(Layout::single());
+        assert_eq!(Some((512, 256)), display.snapped_size((520, 250)));
+        assert_eq!(None, display.snapped_size((600, 250)));
+
+        display.apply_settings(&DisplaySettings {
+            scale: ScaleMode::Free,
+            ..DisplaySettings::default()
+        });
+        assert_eq!(None, display.snapped_size((520, 250)));
+    }
+
+    #[test]
+    fn test_byte_lut() {
+        let palette = Palette::new();
+        let (off, on) = (palette.background(), palette.foreground());
+        let lut = ByteLut::new(&palette);
+
+        let mut bytes = [0; 8];
+        bytes[0] = 0b1000_0001;
+        bytes[7] = 0b0000_0001;
+        let mut line = [[0; 4]; 64];
+        lut.expand(&bytes, &mut line);
+
+        for (x, color) in line.iter().enumerate() {
+            let lit = x == 0 || x == 7 || x == 63;
+            assert_eq!(if lit { on } else { off }, *color, "pixel {}", x);
+        }
+
+        let mut row = [0; ROW_BYTES];
+        scale_line(&line, &mut row);
+        assert_eq!(&on, &row[(SCALE - 1) * 4..SCALE * 4]);
+        assert_eq!(&off, &row[SCALE * 4..SCALE * 4 + 4]);
     }
 }
@@ -0,0 +1,160 @@
+use crate::palette::Palette;
+
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::path::Path;
+
+/// Strongest fade of the pixels turned off
+pub const MAX_FADE: u8 = 9;
+
+/// Colors offered in the menu for the foreground and background
+pub const COLORS: [(&str, [u8; 3]); 10] = [
+    ("black", [0x00, 0x00, 0x00]),
+    ("white", [0xFF, 0xFF, 0xFF]),
+    ("gray", [0x80, 0x80, 0x80]),
+    ("green", [0x00, 0xFF, 0x00]),
+    ("amber", [0xFF, 0xB0, 0x00]),
+    ("red", [0xFF, 0x30, 0x30]),
+    ("blue", [0x40, 0x80, 0xFF]),
+    ("cyan", [0x00, 0xFF, 0xFF]),
+    ("lcd-light", [0x9B, 0xBC, 0x0F]),
+    ("lcd-dark", [0x0F, 0x38, 0x0F]),
+];
+
+/// How the display follows window resizes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleMode {
+    /// Windows resized close to an integer multiple of the display snap to it
+    #[default]
+    Snap,
+    /// Any window size, the display centered with borders
+    Free,
+}
+
+impl ScaleMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::Snap => "snap",
+            ScaleMode::Free => "free",
+        }
+    }
+}
+
+/// Which states of the display within a frame are shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FlickerMode {
+    /// The display at the end of each frame, sprites erased and drawn again within a frame
+    /// don't flicker
+    #[default]
+    Smooth,
+    /// Each row as the beam scanned it during the frame, flickering as on the original hardware
+    Authentic,
+    /// The pixels lit at any point of the frame, sprites erased to be drawn again in the next
+    /// frame don't flicker either
+    NoFlicker,
+}
+
+impl FlickerMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlickerMode::Smooth => "smooth",
+            FlickerMode::Authentic => "authentic",
+            FlickerMode::NoFlicker => "no-flicker",
+        }
+    }
+}
+
+/// Display look adjusted in the menu, saved on request
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub foreground: Option<[u8; 3]>, // replaces the palette color
+    pub background: Option<[u8; 3]>,
+    pub fade: u8, // 0 turns pixels off at once, up to MAX_FADE fades them over several frames
+    pub scanlines: bool,
+    pub scale: ScaleMode,
+    pub flicker: FlickerMode,
+}
+
+impl DisplaySettings {
+    /// Returns None if the file is missing or invalid
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// `palette` with the chosen foreground and background
+    pub fn apply(&self, palette: Palette) -> Palette {
+        let mut palette = palette;
+        for (index, color) in [(0, self.background), (1, self.foreground)] {
+            if let Some([r, g, b]) = color {
+                palette.colors[index] = [r, g, b, 0xFF];
+            }
+        }
+        palette
+    }
+
+    /// Part of its brightness a pixel turned off keeps at each frame
+    pub fn fade_decay(&self) -> f32 {
+        self.fade.min(MAX_FADE) as f32 / (MAX_FADE + 1) as f32
+    }
+}
+
+/// Name of a color, its hexadecimal code if it is not one of `COLORS`
+pub fn color_name(color: [u8; 3]) -> String {
+    COLORS
+        .iter()
+        .find(|(_, rgb)| *rgb == color)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]))
+}
+
+/// The color after `color` in `COLORS` (`offset` 1) or before it (`offset` -1), going
+/// through None, the palette color
+pub fn cycle_color(color: Option<[u8; 3]>, offset: isize) -> Option<[u8; 3]> {
+    let choices = COLORS.len() as isize + 1;
+    let index = color
+        .and_then(|color| COLORS.iter().position(|(_, rgb)| *rgb == color))
+        .map_or(0, |index| index as isize + 1);
+
+    match (index + offset).rem_euclid(choices) {
+        0 => None,
+        index => Some(COLORS[index as usize - 1].1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors() {
+        assert_eq!(Some(COLORS[0].1), cycle_color(None, 1));
+        assert_eq!(None, cycle_color(Some(COLORS[0].1), -1));
+        assert_eq!(Some(COLORS[9].1), cycle_color(None, -1));
+        assert_eq!(Some(COLORS[0].1), cycle_color(Some([1, 2, 3]), 1));
+
+        assert_eq!("amber", color_name([0xFF, 0xB0, 0x00]));
+        assert_eq!("#010203", color_name([1, 2, 3]));
+
+        let settings = DisplaySettings {
+            foreground: Some([1, 2, 3]),
+            ..Default::default()
+        };
+        let palette = settings.apply(Palette::new());
+        assert_eq!([1, 2, 3, 0xFF], palette.foreground());
+        assert_eq!(Palette::new().background(), palette.background());
+    }
+}
@@ -0,0 +1,64 @@
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// Remembers how many frames ago DXYN last touched each pixel, to tint recent draws and
+/// show what each draw call changes
+pub struct DrawTracker {
+    frames: u8,                 // how long a draw stays tinted
+    ages: [u8; WIDTH * HEIGHT], // frames since the last draw, `frames` when older
+}
+
+impl DrawTracker {
+    pub fn new(frames: u8) -> Self {
+        let frames = frames.max(1);
+        DrawTracker {
+            frames,
+            ages: [frames; WIDTH * HEIGHT],
+        }
+    }
+
+    /// Add the pixels drawn during the frame that just ended, aging the previous ones
+    pub fn push_frame(&mut self, draws: Vec<(u8, u8)>) {
+        for age in self.ages.iter_mut() {
+            *age = age.saturating_add(1).min(self.frames);
+        }
+        for (x, y) in draws {
+            self.ages[x as usize + WIDTH * y as usize] = 0;
+        }
+    }
+
+    /// How recently each pixel was drawn, from 1.0 (this frame) to 0.0 (not recently)
+    pub fn intensities(&self) -> Vec<f32> {
+        self.ages
+            .iter()
+            .map(|&age| 1.0 - age as f32 / self.frames as f32)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fading() {
+        let mut tracker = DrawTracker::new(4);
+        assert_eq!(0.0, tracker.intensities()[0]);
+
+        tracker.push_frame(vec![(0, 0)]);
+        assert_eq!(1.0, tracker.intensities()[0]);
+        tracker.push_frame(vec![(63, 31)]);
+        assert_eq!(0.75, tracker.intensities()[0]);
+        assert_eq!(1.0, tracker.intensities()[WIDTH * HEIGHT - 1]);
+
+        for _ in 0..5 {
+            tracker.push_frame(vec![]);
+        }
+        assert_eq!(0.0, tracker.intensities()[0]);
+
+        // the longest tint, its ages not overflowing
+        let mut tracker = DrawTracker::new(u8::MAX);
+        tracker.push_frame(vec![]);
+        assert_eq!(0.0, tracker.intensities()[0]);
+    }
+}
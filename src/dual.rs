@@ -0,0 +1,97 @@
+use crate::display::Display;
+use crate::memory::Memory;
+
+/// Compares the displays of two instances frame by frame and remembers
+/// the first frame where they differ
+pub struct DivergenceDetector {
+    frame: u64,
+    divergence: Option<u64>,
+    state_divergence: Option<u64>, // first frame where the states differ, displays matching
+}
+
+impl DivergenceDetector {
+    pub fn new() -> Self {
+        DivergenceDetector {
+            frame: 0,
+            divergence: None,
+            state_divergence: None,
+        }
+    }
+
+    /// Compare the displays at the end of a frame.
+    /// Returns the differing pixels if this is the first frame where the displays diverge.
+    pub fn compare(&mut self, a: &Memory, b: &Memory) -> Option<Vec<(u8, u8)>> {
+        self.frame += 1;
+
+        if self.divergence.is_some() {
+            return None;
+        }
+
+        let pixels = Display::diff(a, b);
+        if pixels.is_empty() {
+            return None;
+        }
+
+        self.divergence = Some(self.frame);
+        Some(pixels)
+    }
+
+    /// Compare the state hashes of the instances, after `compare` for the same frame.
+    /// Returns the frame number if this is the first frame where the states diverge while
+    /// the displays never did, e.g. a register differing before the difference shows.
+    pub fn compare_states(&mut self, a: u64, b: u64) -> Option<u64> {
+        if a == b || self.divergence.is_some() || self.state_divergence.is_some() {
+            return None;
+        }
+
+        self.state_divergence = Some(self.frame);
+        self.state_divergence
+    }
+
+    /// Number of the first frame (starting at 1) where the displays diverged
+    pub fn divergence(&self) -> Option<u64> {
+        self.divergence
+    }
+}
+
+impl Default for DivergenceDetector {
+    fn default() -> Self {
+        DivergenceDetector::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_divergence() {
+        let mut detector = DivergenceDetector::new();
+        let mut a = Memory::new();
+        let mut b = Memory::new();
+
+        assert_eq!(None, detector.compare(&a, &b));
+
+        Display::write_pixel(&mut a, 3, 4);
+        assert_eq!(Some(vec![(3, 4)]), detector.compare(&a, &b));
+        assert_eq!(Some(2), detector.divergence());
+
+        // only the first divergence is reported
+        Display::write_pixel(&mut b, 5, 6);
+        assert_eq!(None, detector.compare(&a, &b));
+        assert_eq!(Some(2), detector.divergence());
+    }
+
+    #[test]
+    fn test_state_divergence() {
+        let mut detector = DivergenceDetector::new();
+        let memory = Memory::new();
+
+        detector.compare(&memory, &memory);
+        assert_eq!(None, detector.compare_states(1, 1));
+        detector.compare(&memory, &memory);
+        assert_eq!(Some(2), detector.compare_states(1, 2));
+        detector.compare(&memory, &memory);
+        assert_eq!(None, detector.compare_states(1, 3));
+    }
+}
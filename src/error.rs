@@ -0,0 +1,102 @@
+use std::fmt;
+
+/// Faults stopping the interpreter, where real hardware would crash
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Error {
+    /// A subroutine call exceeded the stack depth
+    StackOverflow { pc: u16, depth: usize },
+    /// A return happened with an empty stack
+    StackUnderflow { pc: u16 },
+    /// The program reached an opcode no instruction matches
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// An instruction accessed memory outside of the address space (strict mode)
+    Memory { pc: u16, source: MemError },
+    /// The instruction at `from` sent the program counter outside of the program and the
+    /// allowed regions (see `Chip8::set_pc_guard`)
+    PcOutOfRegion { pc: u16, from: u16 },
+}
+
+/// Why the interpreter stopped executing instructions
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum HaltReason {
+    /// The program reached a 0000 opcode, how most test programs end
+    NullOpcode { pc: u16 },
+    /// The program reached a jump to itself, looping forever with nothing left to run
+    SelfJump { pc: u16 },
+    /// The program counter reached a breakpoint (see `Chip8::add_breakpoint`)
+    Breakpoint { pc: u16 },
+    /// A fault, where real hardware would crash
+    Error(Error),
+    /// The frontend stopped the program
+    UserRequested,
+}
+
+/// Invalid memory access
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemError {
+    OutOfBounds {
+        addr: u16,
+    },
+    /// A peripheral claimed an address already claimed by another one
+    AlreadyMapped {
+        addr: u16,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::StackOverflow { pc, depth } => {
+                write!(f, "stack overflow at {:04X} (depth {})", pc, depth)
+            }
+            Error::StackUnderflow { pc } => write!(f, "stack underflow at {:04X}", pc),
+            Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:04X}", opcode, pc)
+            }
+            Error::Memory { pc, source } => write!(f, "{} at {:04X}", source, pc),
+            Error::PcOutOfRegion { pc, from } => {
+                write!(
+                    f,
+                    "jump from {:04X} to {:04X} outside the program",
+                    from, pc
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltReason::NullOpcode { pc } => write!(f, "program ended at {:04X}", pc),
+            HaltReason::SelfJump { pc } => write!(f, "jump to itself at {:04X}", pc),
+            HaltReason::Breakpoint { pc } => write!(f, "breakpoint at {:04X}", pc),
+            HaltReason::Error(error) => error.fmt(f),
+            HaltReason::UserRequested => write!(f, "stopped by the user"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Memory { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemError::OutOfBounds { addr } => {
+                write!(f, "memory access out of bounds ({:04X})", addr)
+            }
+            MemError::AlreadyMapped { addr } => {
+                write!(f, "address already mapped to a peripheral ({:04X})", addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MemError {}
@@ -0,0 +1,170 @@
+use crate::interpreter::Interpreter;
+use crate::memory::{self, Memory};
+use crate::opcodes::{self, Operation};
+
+use clap::ValueEnum;
+
+#[cfg(feature = "jit")]
+pub mod jit;
+
+/// Strategy executing the instructions of a program. The machine state stays in
+/// `Interpreter` and `Memory`, executors only decide how an instruction gets there.
+pub trait Executor {
+    /// Execute the instruction at PC, unless waiting for the next frame
+    fn step(&mut self, interpreter: &mut Interpreter, memory: &mut Memory);
+
+    /// Execute between 1 and `budget` instructions, returns how many were executed.
+    /// Executors running whole blocks at once override it, the default is a single step.
+    fn run(&mut self, interpreter: &mut Interpreter, memory: &mut Memory, budget: u32) -> u32 {
+        let _ = budget;
+        self.step(interpreter, memory);
+        1
+    }
+
+    /// Forget everything cached about the program, called when a new one is loaded
+    fn invalidate(&mut self) {}
+}
+
+/// Available executors
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ExecutorKind {
+    /// Decode every instruction as it is executed
+    Interpreter,
+    /// Decode every instruction once, reused while the opcode is unchanged
+    Threaded,
+    /// Compile basic blocks to native code (experimental)
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl ExecutorKind {
+    pub fn create(&self) -> Box<dyn Executor> {
+        match self {
+            ExecutorKind::Interpreter => Box::new(Interpreted),
+            ExecutorKind::Threaded => Box::new(ThreadedCode::new()),
+            #[cfg(feature = "jit")]
+            ExecutorKind::Jit => Box::new(jit::Jit::new()),
+        }
+    }
+}
+
+/// The reference implementation: `Interpreter::step`
+pub struct Interpreted;
+
+impl Executor for Interpreted {
+    fn step(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        interpreter.step(memory);
+    }
+}
+
+/// Threaded code: each address caches the operation of its last decoded opcode.
+/// Self-modifying programs stay correct since an operation is only reused for the same opcode.
+pub struct ThreadedCode {
+    cache: Vec<Option<(u16, Option<Operation>)>>, // opcode, None if unknown
+}
+
+impl ThreadedCode {
+    pub fn new() -> Self {
+        ThreadedCode {
+            cache: vec![None; memory::MAX_SIZE as usize],
+        }
+    }
+
+    fn decode(opcode: u16) -> (u16, Option<Operation>) {
+        (opcode, opcodes::lookup(opcode).map(|row| row.operation))
+    }
+}
+
+impl Default for ThreadedCode {
+    fn default() -> Self {
+        ThreadedCode::new()
+    }
+}
+
+impl Executor for ThreadedCode {
+    fn step(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        if interpreter.waiting_vblank() {
+            return;
+        }
+
+        let pc = interpreter.pc;
+        let opcode = interpreter.next(memory);
+        interpreter.record_pc(pc, opcode);
+        // XO-CHIP addresses share slots, the opcode check keeps them apart
+        let slot = &mut self.cache[pc as usize % memory::MAX_SIZE as usize];
+        let operation = match slot {
+            Some((decoded, operation)) if *decoded == opcode => *operation,
+            _ => slot.insert(ThreadedCode::decode(opcode)).1,
+        };
+
+        interpreter.pc = interpreter.pc.wrapping_add(2);
+        match operation {
+            Some(operation) => interpreter.execute(operation, opcode, memory),
+            None => interpreter.exec(opcode, memory), // unknown
+        }
+        interpreter.pc &= memory.address_space().mask();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot;
+
+    /// Run `program` with every executor and compare the machine states
+    fn assert_same_state(program: &[u8], steps: u32) {
+        let mut states = Vec::new();
+
+        for kind in ExecutorKind::value_variants() {
+            let mut executor = kind.create();
+            let mut interpreter = Interpreter::new();
+            let mut memory = Memory::new();
+            memory.load_prog(program);
+
+            let mut executed = 0;
+            while executed < steps {
+                executed += executor.run(&mut interpreter, &mut memory, steps - executed);
+            }
+            // the registers for a readable failure, the hash for the rest of the state
+            let hash = snapshot::state_hash(&interpreter, &memory);
+            states.push((interpreter.pc, interpreter.vi, interpreter.vx, hash));
+        }
+
+        for state in &states[1..] {
+            assert_eq!(states[0], *state);
+        }
+    }
+
+    #[test]
+    fn test_loop() {
+        let program = [
+            0x60, 0x00, // 200: V0 = 0
+            0x61, 0x03, // 202: V1 = 3
+            0x70, 0x01, // 204: V0 += 1
+            0x82, 0x00, // 206: V2 = V0
+            0xA3, 0x00, // 208: I = 0x300
+            0x80, 0x14, // 20A: V0 += V1
+            0x30, 0x28, // 20C: skip if V0 == 0x28
+            0x12, 0x04, // 20E: jump to 0x204
+            0x12, 0x10, // 210: jump to itself
+        ];
+
+        assert_same_state(&program, 200);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        let program = [
+            0x60, 0x12, // 200: V0 = 0x12
+            0x61, 0x0A, // 202: V1 = 0x0A
+            0xA2, 0x0A, // 204: I = 0x20A
+            0x12, 0x0A, // 206: jump to 0x20A
+            0x12, 0x08, // 208: jump to itself
+            0x62, 0x01, // 20A: V2 = 1, then overwritten by "jump to 0x20A"
+            0xF1, 0x55, // 20C: store V0, V1 at I
+            0x12, 0x0A, // 20E: jump to 0x20A
+        ];
+
+        assert_same_state(&program, 20);
+    }
+}
@@ -0,0 +1,286 @@
+use super::Executor;
+use crate::bus::Bus;
+use crate::interpreter::Interpreter;
+use crate::memory::{self, Memory};
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlagsData, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::Module;
+
+/// Instructions compiled in a single block at most
+const MAX_BLOCK_LEN: usize = 64;
+
+/// Registers handed to compiled blocks, copied from and back to the interpreter
+#[repr(C)]
+#[derive(Default)]
+struct Registers {
+    vx: [u8; 16],
+    vi: u16,
+    pc: u16,
+}
+
+const VI_OFFSET: i32 = 16;
+const PC_OFFSET: i32 = 18;
+
+type BlockFn = extern "C" fn(*mut Registers);
+
+/// Basic block starting at an address
+struct Block {
+    bytes: Vec<u8>,            // program bytes the block was compiled from
+    checked: u64,              // `Memory::code_writes` when the bytes were last compared
+    len: u32,                  // number of instructions
+    function: Option<BlockFn>, // None if the first instruction is not supported
+}
+
+/// Compiles basic blocks of register and flow instructions to native code with cranelift.
+/// After the program wrote to memory, blocks are compared with the memory before they run
+/// and recompiled if the program modified them. Other instructions go through the interpreter.
+pub struct Jit {
+    module: JITModule,
+    builder_context: FunctionBuilderContext,
+    blocks: Vec<Option<Block>>, // indexed by start address
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").unwrap();
+        flags.set("is_pic", "false").unwrap();
+        let isa = cranelift_native::builder()
+            .expect("JIT not supported on this platform")
+            .finish(settings::Flags::new(flags))
+            .unwrap();
+
+        Jit {
+            module: JITModule::new(JITBuilder::with_isa(
+                isa,
+                cranelift_module::default_libcall_names(),
+            )),
+            builder_context: FunctionBuilderContext::new(),
+            blocks: (0..memory::MAX_SIZE).map(|_| None).collect(),
+        }
+    }
+
+    /// Opcodes of the block starting at `start`, the last one may be a branch
+    fn scan(memory: &Memory, start: u16) -> Vec<u16> {
+        let mut opcodes = Vec::new();
+        let mut addr = start;
+
+        while opcodes.len() < MAX_BLOCK_LEN && (addr as usize) < memory.size() - 1 {
+            let opcode = memory.read_u16(addr);
+            match opcode_kind(opcode) {
+                Kind::Body => opcodes.push(opcode),
+                Kind::Branch => {
+                    opcodes.push(opcode);
+                    break;
+                }
+                Kind::Unsupported => break,
+            }
+            addr += 2;
+        }
+
+        opcodes
+    }
+
+    #[allow(unsafe_code)]
+    fn compile(&mut self, start: u16, opcodes: &[u16]) -> BlockFn {
+        let mut ctx = self.module.make_context();
+        let pointer_type = self.module.target_config().pointer_type();
+        ctx.func.signature.params.push(AbiParam::new(pointer_type));
+
+        {
+            let mut builder = FunctionBuilder::new(&mut ctx.func, &mut self.builder_context);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+
+            let registers = builder.block_params(entry)[0];
+            let flags = MemFlagsData::trusted();
+            let load_vx = |builder: &mut FunctionBuilder, x: u16| -> Value {
+                builder.ins().load(types::I8, flags, registers, x as i32)
+            };
+
+            let mut next = start;
+            let mut pc = None;
+            for &opcode in opcodes {
+                next = next.wrapping_add(2);
+                let (x, y) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF);
+                let (nn, nnn) = ((opcode & 0xFF) as i64, (opcode & 0xFFF) as i64);
+
+                match opcode >> 12 {
+                    0x1 => pc = Some(builder.ins().iconst(types::I16, nnn)),
+                    0x3 | 0x4 | 0x5 | 0x9 => {
+                        let vx = load_vx(&mut builder, x);
+                        let equal = match opcode >> 12 {
+                            0x3 | 0x4 => builder.ins().icmp_imm_u(IntCC::Equal, vx, nn),
+                            _ => {
+                                let vy = load_vx(&mut builder, y);
+                                builder.ins().icmp(IntCC::Equal, vx, vy)
+                            }
+                        };
+                        let skip = builder
+                            .ins()
+                            .iconst(types::I16, next.wrapping_add(2) as i64);
+                        let no_skip = builder.ins().iconst(types::I16, next as i64);
+                        pc = Some(match opcode >> 12 {
+                            0x3 | 0x5 => builder.ins().select(equal, skip, no_skip),
+                            _ => builder.ins().select(equal, no_skip, skip),
+                        });
+                    }
+                    0x6 => {
+                        let value = builder.ins().iconst(types::I8, nn);
+                        builder.ins().store(flags, value, registers, x as i32);
+                    }
+                    0x7 => {
+                        let vx = load_vx(&mut builder, x);
+                        let value = builder.ins().iadd_imm_u(vx, nn);
+                        builder.ins().store(flags, value, registers, x as i32);
+                    }
+                    0x8 => {
+                        let vy = load_vx(&mut builder, y);
+                        builder.ins().store(flags, vy, registers, x as i32);
+                    }
+                    0xA => {
+                        let value = builder.ins().iconst(types::I16, nnn);
+                        builder.ins().store(flags, value, registers, VI_OFFSET);
+                    }
+                    _ => unreachable!("opcode {:04X} is not compiled", opcode),
+                }
+            }
+
+            let pc = pc.unwrap_or_else(|| builder.ins().iconst(types::I16, next as i64));
+            builder.ins().store(flags, pc, registers, PC_OFFSET);
+            builder.ins().return_(&[]);
+            builder.finalize(self.module.target_config());
+        }
+
+        let id = self
+            .module
+            .declare_anonymous_function(&ctx.func.signature)
+            .unwrap();
+        self.module
+            .define_function(id, &mut ctx)
+            .expect("Could not compile block");
+        self.module.clear_context(&mut ctx);
+        self.module.finalize_definitions().unwrap();
+
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the code was compiled with the signature of BlockFn, and stays
+        // allocated as long as the module, which outlives the blocks
+        unsafe { std::mem::transmute::<*const u8, BlockFn>(code) }
+    }
+
+    /// Block at `start`, compiled again if the program bytes changed
+    fn block(&mut self, memory: &Memory, start: u16) -> &Block {
+        let start = start & memory.address_mask();
+        let code_writes = memory.code_writes();
+        if self.blocks.len() < memory.size() {
+            self.blocks.resize_with(memory.size(), || None);
+        }
+
+        let up_to_date = match &mut self.blocks[start as usize] {
+            Some(block) if block.checked == code_writes => true,
+            Some(block) => {
+                let unchanged =
+                    block.bytes.iter().enumerate().all(|(i, &byte)| {
+                        memory.read_wrapped(start.wrapping_add(i as u16)) == byte
+                    });
+                block.checked = code_writes;
+                unchanged
+            }
+            None => false,
+        };
+
+        if !up_to_date {
+            let opcodes = Jit::scan(memory, start);
+            let function = if opcodes.is_empty() {
+                None
+            } else {
+                Some(self.compile(start, &opcodes))
+            };
+            // at least the first opcode, to notice when an unsupported one gets replaced
+            let len = (opcodes.len() * 2).max(2);
+            let bytes = (0..len)
+                .map(|i| memory.read_wrapped(start.wrapping_add(i as u16)))
+                .collect();
+
+            self.blocks[start as usize] = Some(Block {
+                bytes,
+                checked: code_writes,
+                len: opcodes.len() as u32,
+                function,
+            });
+        }
+
+        self.blocks[start as usize].as_ref().unwrap()
+    }
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Jit::new()
+    }
+}
+
+impl Executor for Jit {
+    fn step(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        interpreter.step(memory);
+    }
+
+    /// Chains compiled blocks while they fit in the budget
+    fn run(&mut self, interpreter: &mut Interpreter, memory: &mut Memory, budget: u32) -> u32 {
+        if interpreter.waiting_vblank() {
+            interpreter.step(memory);
+            return 1;
+        }
+
+        let mut registers = Registers {
+            vx: interpreter.vx,
+            vi: interpreter.vi,
+            pc: interpreter.pc,
+        };
+        let mut executed = 0;
+        loop {
+            let block = self.block(memory, registers.pc);
+            match block.function {
+                Some(function) if executed + block.len <= budget => {
+                    interpreter.record_pc(registers.pc, memory.read_u16(registers.pc));
+                    function(&mut registers);
+                    executed += block.len;
+                }
+                _ => break,
+            }
+        }
+        interpreter.vx = registers.vx;
+        interpreter.vi = registers.vi;
+        interpreter.pc = registers.pc;
+
+        if executed == 0 {
+            interpreter.step(memory);
+            executed = 1;
+        }
+        executed
+    }
+
+    fn invalidate(&mut self) {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+    }
+}
+
+enum Kind {
+    Body,        // compiled, execution continues with the next instruction
+    Branch,      // compiled, ends the block
+    Unsupported, // executed by the interpreter
+}
+
+fn opcode_kind(opcode: u16) -> Kind {
+    match (opcode >> 12, opcode & 0xF) {
+        (0x6 | 0x7 | 0xA, _) | (0x8, 0x0) => Kind::Body,
+        (0x1 | 0x3 | 0x4, _) | (0x5 | 0x9, 0x0) => Kind::Branch,
+        _ => Kind::Unsupported,
+    }
+}
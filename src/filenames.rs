@@ -0,0 +1,162 @@
+use crate::playtime;
+
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+/// Values of the placeholders of a file name template
+pub struct TemplateVars<'a> {
+    pub rom: &'a str,   // file name of the program, its extension is dropped
+    pub timestamp: u64, // unix time
+    pub frame: u64,
+}
+
+/// Expand `{rom}`, `{date}` (2024-02-29), `{time}` (235959, UTC), `{frame}` and `{unix}`
+/// in `template`. `{{` and `}}` are literal braces.
+pub fn expand(template: &str, vars: &TemplateVars) -> Result<String, String> {
+    expand_with(template, |placeholder| match placeholder {
+        "rom" => Some(rom_stem(vars.rom)),
+        "date" => Some(playtime::format_date(vars.timestamp)),
+        "time" => {
+            let seconds = vars.timestamp % 86400;
+            Some(format!(
+                "{:02}{:02}{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ))
+        }
+        "frame" => Some(vars.frame.to_string()),
+        "unix" => Some(vars.timestamp.to_string()),
+        _ => None,
+    })
+}
+
+/// Expand the placeholders of `template` with `value`, None for an unknown placeholder.
+/// `{{` and `}}` are literal braces.
+pub fn expand_with(
+    template: &str,
+    value: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find(['{', '}']) {
+        name += &rest[..start];
+        rest = &rest[start..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            name.push(rest.as_bytes()[0] as char);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') => end,
+            _ => return Err(format!("unmatched brace in {:?}", template)),
+        };
+        let placeholder = &rest[1..end];
+        match value(placeholder) {
+            Some(value) => name += &value,
+            None => {
+                return Err(format!(
+                    "unknown placeholder {{{}}} in {:?}",
+                    placeholder, template
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    name += rest;
+
+    Ok(name)
+}
+
+/// Program file name without its extension, usable in a file name
+pub fn rom_stem(rom: &str) -> String {
+    let stem = Path::new(rom)
+        .file_stem()
+        .map_or("rom".into(), |stem| stem.to_string_lossy());
+
+    stem.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Names of the files saved in the screenshots and saves directories
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileNames {
+    pub filmstrip: String, // F9
+    pub state: String,     // F6
+}
+
+impl Default for FileNames {
+    fn default() -> Self {
+        FileNames {
+            filmstrip: "{rom}-{date}-{frame}.png".to_string(),
+            state: "{rom}-{date}-{frame}.state".to_string(),
+        }
+    }
+}
+
+impl FileNames {
+    /// The defaults if the file is missing, an error if it is invalid or has a bad template
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(FileNames::default()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let names: FileNames = toml::from_str(&text).map_err(|e| e.to_string())?;
+
+        let vars = TemplateVars {
+            rom: "",
+            timestamp: 0,
+            frame: 0,
+        };
+        expand(&names.filmstrip, &vars)?;
+        expand(&names.state, &vars)?;
+
+        Ok(names)
+    }
+
+    pub fn filmstrip(&self, vars: &TemplateVars) -> String {
+        // checked by `load`
+        expand(&self.filmstrip, vars).unwrap_or_default()
+    }
+
+    pub fn state(&self, vars: &TemplateVars) -> String {
+        // checked by `load`
+        expand(&self.state, vars).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let vars = TemplateVars {
+            rom: "Space Invaders.ch8",
+            timestamp: 1_709_251_199, // 2024-02-29 23:59:59
+            frame: 600,
+        };
+
+        assert_eq!(
+            Ok("Space Invaders-2024-02-29-600.png".to_string()),
+            expand(&FileNames::default().filmstrip, &vars)
+        );
+        assert_eq!(
+            Ok("{shot} 235959 1709251199".to_string()),
+            expand("{{shot}} {time} {unix}", &vars)
+        );
+        assert!(expand("{rom", &vars).is_err());
+        assert!(expand("rom}", &vars).is_err());
+        assert!(expand("{level}.png", &vars).is_err());
+    }
+}
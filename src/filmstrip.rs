@@ -0,0 +1,143 @@
+use crate::framebuffer::{FrameBuffer, HEIGHT, WIDTH};
+use crate::memory;
+use crate::palette::Palette;
+
+use std::io;
+
+const COLUMNS: usize = 4;
+const SCALE: usize = 2;
+const PADDING: usize = 4;
+const LABEL_HEIGHT: usize = 8; // font characters are 5 pixels high
+
+const CELL_WIDTH: usize = WIDTH * SCALE + PADDING;
+const CELL_HEIGHT: usize = HEIGHT * SCALE + LABEL_HEIGHT + PADDING;
+
+/// Write the frames as a PNG grid of thumbnails, each labeled with its frame number
+pub fn write_png<W: io::Write>(
+    frames: &[(u64, FrameBuffer)],
+    palette: &Palette,
+    writer: W,
+) -> io::Result<()> {
+    let columns = frames.len().clamp(1, COLUMNS);
+    let rows = frames.len().div_ceil(COLUMNS).max(1);
+    let width = columns * CELL_WIDTH + PADDING;
+    let height = rows * CELL_HEIGHT + PADDING;
+
+    // the sheet background is the palette foreground, to separate the thumbnails
+    let mut image: Vec<u8> = palette
+        .foreground()
+        .iter()
+        .copied()
+        .cycle()
+        .take(width * height * 4)
+        .collect();
+
+    let mut put_pixel = |x: usize, y: usize, color: [u8; 4]| {
+        let offset = (y * width + x) * 4;
+        image[offset..offset + 4].copy_from_slice(&color);
+    };
+
+    for (i, (number, frame)) in frames.iter().enumerate() {
+        let left = PADDING + (i % COLUMNS) * CELL_WIDTH;
+        let top = PADDING + (i / COLUMNS) * CELL_HEIGHT;
+
+        for y in 0..HEIGHT * SCALE {
+            for x in 0..WIDTH * SCALE {
+                let on = frame.pixel((x / SCALE) as u8, (y / SCALE) as u8);
+                let color = if on {
+                    palette.foreground()
+                } else {
+                    palette.background()
+                };
+                put_pixel(left + x, top + y, color);
+            }
+        }
+
+        // frame number, drawn with the digits of the built-in font
+        let label_top = top + HEIGHT * SCALE + 2;
+        for (d, digit) in number.to_string().bytes().enumerate() {
+            let glyph_start = (digit - b'0') as usize * memory::FONT_CHAR_SIZE as usize;
+            let glyph = &memory::FONT[glyph_start..glyph_start + memory::FONT_CHAR_SIZE as usize];
+
+            for (row, byte) in glyph.iter().enumerate() {
+                for col in 0..4 {
+                    if byte & (0b1000_0000 >> col) != 0 {
+                        put_pixel(left + d * 5 + col, label_top + row, palette.background());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image)?;
+
+    Ok(())
+}
+
+/// Write a single frame as a PNG image, each CHIP-8 pixel `scale` pixels wide
+pub fn write_frame_png<W: io::Write>(
+    frame: &FrameBuffer,
+    palette: &Palette,
+    scale: usize,
+    writer: W,
+) -> io::Result<()> {
+    let scale = scale.max(1);
+    let (width, height) = (WIDTH * scale, HEIGHT * scale);
+
+    let mut image = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let color = match frame.pixel((x / scale) as u8, (y / scale) as u8) {
+                true => palette.foreground(),
+                false => palette.background(),
+            };
+            image.extend_from_slice(&color);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    #[test]
+    fn test_write_png() {
+        let mut memory = Memory::new();
+        Display::write_pixel(&mut memory, 0, 0);
+        let frame = FrameBuffer::from_memory(&memory);
+        let frames: Vec<(u64, FrameBuffer)> = (0..5).map(|number| (number, frame)).collect();
+
+        let palette = Palette::new();
+        let mut data = Vec::new();
+        write_png(&frames, &palette, &mut data).unwrap();
+
+        let decoder = png::Decoder::new(data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+
+        assert_eq!((4 * CELL_WIDTH + PADDING) as u32, info.width);
+        assert_eq!((2 * CELL_HEIGHT + PADDING) as u32, info.height);
+
+        // top left pixel of the first thumbnail is on
+        let offset = (PADDING * info.width as usize + PADDING) * 4;
+        assert_eq!(palette.foreground(), image[offset..offset + 4]);
+        // its right neighbour (after scaling) is off
+        let offset = offset + SCALE * 4;
+        assert_eq!(palette.background(), image[offset..offset + 4]);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::framebuffer::{self, FrameBuffer};
+
+/// A frame changing more than this many pixels counts as a flash
+const FLASH_PIXELS: usize = framebuffer::WIDTH * framebuffer::HEIGHT / 2;
+
+/// Limits how often the display may flash, for photosensitive players.
+/// Games often flash by inverting the whole screen: flashes coming too soon after the
+/// last one shown are hidden by inverting the palette as well, and the palette is restored
+/// once a flash may be shown again, the restoring counting as one.
+pub struct FlashLimiter {
+    min_interval: u64, // frames between two flashes shown
+    last_flash: Option<u64>,
+    previous: Option<FrameBuffer>,
+    inverted: bool,
+}
+
+impl FlashLimiter {
+    /// Limiter for frames ending `frame_rate` times per second
+    pub fn new(max_per_second: u8, frame_rate: u32) -> Self {
+        FlashLimiter {
+            min_interval: (frame_rate / max_per_second.max(1) as u32) as u64,
+            last_flash: None,
+            previous: None,
+            inverted: false,
+        }
+    }
+
+    /// Look at the frame that just ended.
+    /// Returns whether the palette must now be inverted to hide flashing.
+    pub fn push_frame(&mut self, number: u64, frame: FrameBuffer) -> bool {
+        let flash = self
+            .previous
+            .is_some_and(|previous| previous.count_differences(&frame) > FLASH_PIXELS);
+        self.previous = Some(frame);
+
+        let allowed = self
+            .last_flash
+            .is_none_or(|last| number.saturating_sub(last) >= self.min_interval);
+        if flash && !allowed {
+            self.inverted = !self.inverted;
+        } else if flash || (allowed && self.inverted) {
+            // a flash, or restoring the palette, shown
+            self.last_flash = Some(number);
+            if !flash {
+                self.inverted = false;
+            }
+        }
+
+        self.inverted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{self, Memory};
+    use crate::timing::FRAME_RATE;
+
+    fn frame(lit: bool) -> FrameBuffer {
+        let mut memory = Memory::new();
+        if lit {
+            for addr in memory::DISPLAY_LOC..memory::MAX_SIZE {
+                memory.write(addr, 0xFF);
+            }
+        }
+        FrameBuffer::from_memory(&memory)
+    }
+
+    #[test]
+    fn test_flash_limiter() {
+        let mut limiter = FlashLimiter::new(3, FRAME_RATE);
+
+        assert!(!limiter.push_frame(0, frame(false)));
+        // the first flash is shown, the next ones within 20 frames are hidden
+        assert!(!limiter.push_frame(1, frame(true)));
+        assert!(limiter.push_frame(2, frame(false)));
+        assert!(!limiter.push_frame(3, frame(true)));
+        assert!(!limiter.push_frame(4, frame(true)));
+        // shown again once the interval passed
+        assert!(!limiter.push_frame(21, frame(false)));
+    }
+
+    #[test]
+    fn test_flash_limiter_restores_palette() {
+        let mut limiter = FlashLimiter::new(3, FRAME_RATE);
+
+        assert!(!limiter.push_frame(0, frame(false)));
+        assert!(!limiter.push_frame(1, frame(true)));
+        // flashing back at once is hidden, the screen staying lit
+        assert!(limiter.push_frame(2, frame(false)));
+        assert!(limiter.push_frame(20, frame(false)));
+        // the palette is restored once the interval passed, the only change for 20 frames
+        assert!(!limiter.push_frame(21, frame(false)));
+        assert!(!limiter.push_frame(22, frame(false)));
+        assert!(limiter.push_frame(23, frame(true)));
+    }
+
+    #[test]
+    fn test_flash_limiter_rate() {
+        // 50 frames per second leave 16 frames between flashes at 3 per second
+        let mut limiter = FlashLimiter::new(3, 50);
+
+        assert!(!limiter.push_frame(0, frame(false)));
+        assert!(!limiter.push_frame(1, frame(true)));
+        assert!(limiter.push_frame(16, frame(false)));
+        // shown through the inverted palette, where 60 frames per second would hide it
+        assert!(limiter.push_frame(17, frame(true)));
+    }
+}
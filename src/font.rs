@@ -0,0 +1,152 @@
+use crate::memory::{self, Memory, MemoryMap};
+
+use clap::ValueEnum;
+
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// Glyphs of the small and the big font
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fonts {
+    pub small: [u8; 80],
+    pub big: [u8; 160],
+}
+
+impl Fonts {
+    /// The built-in fonts
+    pub fn new() -> Self {
+        Fonts {
+            small: memory::FONT,
+            big: memory::BIG_FONT,
+        }
+    }
+
+    /// Fonts from a file of 80 bytes replacing the small font, or of 240 bytes replacing
+    /// both fonts (small then big, as `bin` writes them)
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut fonts = Fonts::new();
+        match bytes.len() {
+            80 => fonts.small.copy_from_slice(bytes),
+            240 => {
+                fonts.small.copy_from_slice(&bytes[..80]);
+                fonts.big.copy_from_slice(&bytes[80..]);
+            }
+            len => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "a font file has 80 bytes (small font) or 240 (small and big font), not {}",
+                        len
+                    ),
+                ))
+            }
+        }
+        Ok(fonts)
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Fonts::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Load both fonts where `map` places them
+    pub fn load(&self, memory: &mut Memory, map: &MemoryMap) {
+        memory.load_font(&self.small, map.small_font);
+        memory.load_font(&self.big, map.big_font);
+    }
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts::new()
+    }
+}
+
+/// How `export-font` writes the fonts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    /// Octo assembly, one label per character
+    Asm,
+    /// Memory image from the first font to the end of the last one
+    Bin,
+}
+
+/// Both fonts as Octo assembly, each character labeled and placed at its address with `:org`
+pub fn asm(map: &MemoryMap) -> String {
+    let mut out = String::new();
+    let fonts = [
+        (
+            "font",
+            map.small_font,
+            &memory::FONT[..],
+            memory::FONT_CHAR_SIZE,
+        ),
+        (
+            "bigfont",
+            map.big_font,
+            &memory::BIG_FONT[..],
+            memory::BIG_FONT_CHAR_SIZE,
+        ),
+    ];
+
+    for (name, addr, font, char_size) in fonts {
+        writeln!(out, "# {} bytes per character", char_size).unwrap();
+        writeln!(out, ":org 0x{:03X}", addr).unwrap();
+        for (digit, glyph) in font.chunks(char_size as usize).enumerate() {
+            let bytes: Vec<String> = glyph.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+            writeln!(out, ": {}-{:x}", name, digit).unwrap();
+            writeln!(out, "  {}", bytes.join(" ")).unwrap();
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Memory covering both fonts as loaded by the interpreter, starting at the lowest address
+pub fn bin(map: &MemoryMap) -> Vec<u8> {
+    let mut memory = Memory::new();
+    memory.load_fonts(map);
+
+    let areas = map.font_areas();
+    let start = areas.iter().map(|area| area.start).min().unwrap();
+    let end = areas.iter().map(|area| area.end).max().unwrap();
+    (start..end).map(|addr| memory.read(addr)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asm() {
+        let asm = asm(&MemoryMap::default());
+
+        assert!(asm.starts_with("# 5 bytes per character\n:org 0x050\n: font-0\n  0xF0 0x90"));
+        assert!(asm.contains(":org 0x0A0\n: bigfont-0\n  0xFF 0xFF 0xC3"));
+        assert!(asm.contains(": bigfont-f\n"));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let mut bytes = [0x11; 240];
+        bytes[80..].fill(0x22);
+
+        assert_eq!([0x11; 80], Fonts::from_bytes(&bytes[..80]).unwrap().small);
+        assert_eq!(
+            memory::BIG_FONT,
+            Fonts::from_bytes(&bytes[..80]).unwrap().big
+        );
+        assert_eq!([0x22; 160], Fonts::from_bytes(&bytes).unwrap().big);
+        assert!(Fonts::from_bytes(&bytes[..160]).is_err());
+    }
+
+    #[test]
+    fn test_bin() {
+        let bin = bin(&MemoryMap::default());
+
+        assert_eq!(memory::FONT.len() + memory::BIG_FONT.len(), bin.len());
+        assert_eq!(memory::FONT[..], bin[..80]);
+        assert_eq!(memory::BIG_FONT[..], bin[80..]);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::display::Display;
+use crate::memory::Memory;
+
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+const SIZE: usize = WIDTH * HEIGHT / 8; // bytes
+
+/// Copy of the display memory, one bit per pixel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameBuffer {
+    data: [u8; SIZE],
+}
+
+impl FrameBuffer {
+    /// Blank frame
+    pub fn new() -> Self {
+        FrameBuffer { data: [0; SIZE] }
+    }
+
+    pub fn from_memory(memory: &Memory) -> Self {
+        FrameBuffer {
+            data: memory.display(),
+        }
+    }
+
+    /// Frame of display memory bytes, e.g. of a save state
+    pub fn from_bytes(data: [u8; SIZE]) -> Self {
+        FrameBuffer { data }
+    }
+
+    /// Byte `i` of the display memory it copied, 8 pixels of a row
+    pub fn byte(&self, i: usize) -> u8 {
+        self.data[i]
+    }
+
+    /// Copy row `y` of the display memory
+    pub fn copy_row(&mut self, y: usize, memory: &Memory) {
+        for i in y * WIDTH / 8..(y + 1) * WIDTH / 8 {
+            self.data[i] = memory.display_byte(i as u16);
+        }
+    }
+
+    /// Turn on the pixels lit in `other` too
+    pub fn merge(&mut self, other: &FrameBuffer) {
+        for (byte, other) in self.data.iter_mut().zip(other.data) {
+            *byte |= other;
+        }
+    }
+
+    pub fn pixel(&self, x: u8, y: u8) -> bool {
+        let bit_idx = Display::pos_to_bit_index(x, y);
+        let byte = self.data[bit_idx as usize / 8];
+
+        (byte >> (7 - bit_idx % 8)) & 0b0000_0001 == 1
+    }
+
+    pub fn set_pixel(&mut self, x: u8, y: u8, on: bool) {
+        let bit_idx = Display::pos_to_bit_index(x, y);
+        let mask = 0b1000_0000 >> (bit_idx % 8);
+        let byte = &mut self.data[bit_idx as usize / 8];
+
+        match on {
+            true => *byte |= mask,
+            false => *byte &= !mask,
+        }
+    }
+
+    /// Positions of the pixels differing from another frame, row by row
+    pub fn diff(&self, other: &FrameBuffer) -> Vec<(u8, u8)> {
+        let mut pixels = Vec::new();
+        for y in 0..HEIGHT as u8 {
+            for x in 0..WIDTH as u8 {
+                if self.pixel(x, y) != other.pixel(x, y) {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Number of pixels differing from another frame
+    pub fn count_differences(&self, other: &FrameBuffer) -> usize {
+        self.data
+            .iter()
+            .zip(other.data.iter())
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum()
+    }
+
+    /// Parse a 64x32 PBM image, plain (P1) or raw (P4), black pixels being lit
+    pub fn from_pbm(data: &[u8]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        // header fields: magic number, width and height, comments running to the end of line
+        let mut pos = 0;
+        let mut fields = Vec::new();
+        while fields.len() < 3 {
+            while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+                if data[pos] == b'#' {
+                    while pos < data.len() && data[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    pos += 1;
+                }
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("truncated PBM header"));
+            }
+            fields.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+
+        if fields[1] != WIDTH.to_string() || fields[2] != HEIGHT.to_string() {
+            return Err(invalid("PBM image is not 64x32"));
+        }
+
+        let mut frame = FrameBuffer::new();
+        match fields[0].as_str() {
+            "P1" => {
+                let mut bits = data[pos..]
+                    .iter()
+                    .filter(|byte| !byte.is_ascii_whitespace());
+                for y in 0..HEIGHT as u8 {
+                    for x in 0..WIDTH as u8 {
+                        match bits.next() {
+                            Some(b'0') => (),
+                            Some(b'1') => frame.set_pixel(x, y, true),
+                            Some(_) => return Err(invalid("invalid PBM pixel")),
+                            None => return Err(invalid("truncated PBM data")),
+                        }
+                    }
+                }
+            }
+            "P4" => {
+                // a single whitespace character separates the header from the data
+                let raster = data.get(pos + 1..pos + 1 + SIZE);
+                frame.data = raster
+                    .ok_or_else(|| invalid("truncated PBM data"))?
+                    .try_into()
+                    .unwrap();
+            }
+            _ => return Err(invalid("not a PBM image")),
+        }
+
+        Ok(frame)
+    }
+
+    /// Load an expected frame saved as a PBM image, see `from_pbm`
+    pub fn load_pbm(path: impl AsRef<Path>) -> io::Result<Self> {
+        FrameBuffer::from_pbm(&std::fs::read(path)?)
+    }
+
+    /// Plain PBM image of the frame, one row per line
+    pub fn to_pbm(&self) -> String {
+        let mut out = format!("P1\n{} {}\n", WIDTH, HEIGHT);
+        for y in 0..HEIGHT as u8 {
+            for x in 0..WIDTH as u8 {
+                out.push(if self.pixel(x, y) { '1' } else { '0' });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        FrameBuffer::new()
+    }
+}
+
+/// Describes how `actual` differs from `expected`, None if they are identical.
+/// Used by `assert_frame_eq!`.
+pub fn mismatch(expected: &FrameBuffer, actual: &FrameBuffer) -> Option<String> {
+    let pixels = expected.diff(actual);
+    if pixels.is_empty() {
+        return None;
+    }
+
+    let listed: Vec<String> = pixels
+        .iter()
+        .take(10)
+        .map(|(x, y)| format!("({}, {})", x, y))
+        .collect();
+    let more = match pixels.len() {
+        n if n > listed.len() => format!(" and {} more", n - listed.len()),
+        _ => String::new(),
+    };
+
+    Some(format!(
+        "frames differ at {} pixels: {}{}\nactual frame:\n{}",
+        pixels.len(),
+        listed.join(" "),
+        more,
+        actual.to_pbm()
+    ))
+}
+
+/// Expected frame of a test, from `tests/frames`
+#[cfg(test)]
+pub(crate) fn expected_frame(name: &str) -> FrameBuffer {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/frames")
+        .join(name);
+    FrameBuffer::load_pbm(&path)
+        .unwrap_or_else(|e| panic!("Could not load {}: {}", path.display(), e))
+}
+
+/// Assert that two frames are identical, printing the differing pixels and the actual frame as
+/// a PBM image otherwise, to save as the expected frame once checked
+///
+/// ```
+/// use chip8_interpreter::assert_frame_eq;
+/// use chip8_interpreter::framebuffer::FrameBuffer;
+///
+/// assert_frame_eq!(FrameBuffer::new(), FrameBuffer::default());
+/// ```
+#[macro_export]
+macro_rules! assert_frame_eq {
+    ($expected:expr, $actual:expr $(,)?) => {
+        if let Some(message) = $crate::framebuffer::mismatch(&$expected, &$actual) {
+            panic!("{}", message);
+        }
+    };
+}
+
+/// The last distinct frames, with their frame number
+pub struct FrameHistory {
+    capacity: usize,
+    frames: VecDeque<(u64, FrameBuffer)>,
+}
+
+impl FrameHistory {
+    pub fn new(capacity: usize) -> Self {
+        FrameHistory {
+            capacity: capacity.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Record a frame, unless it is identical to the previous one
+    pub fn push(&mut self, number: u64, frame: FrameBuffer) {
+        if let Some((_, last)) = self.frames.back() {
+            if *last == frame {
+                return;
+            }
+        }
+
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((number, frame));
+    }
+
+    /// Frames from the oldest to the most recent
+    pub fn frames(&self) -> impl Iterator<Item = &(u64, FrameBuffer)> {
+        self.frames.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_memory() {
+        let mut memory = Memory::new();
+        Display::write_pixel(&mut memory, 10, 20);
+
+        let frame = FrameBuffer::from_memory(&memory);
+        assert!(frame.pixel(10, 20));
+        assert!(!frame.pixel(11, 20));
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut frame = FrameBuffer::new();
+        frame.set_pixel(63, 31, true);
+        frame.set_pixel(1, 0, true);
+        frame.set_pixel(1, 0, false);
+        frame.set_pixel(5, 2, true);
+
+        assert_eq!(vec![(5, 2), (63, 31)], FrameBuffer::new().diff(&frame));
+        let message = mismatch(&FrameBuffer::new(), &frame).unwrap();
+        assert!(
+            message.starts_with("frames differ at 2 pixels: (5, 2) (63, 31)\nactual frame:\nP1")
+        );
+        assert_frame_eq!(frame, frame);
+    }
+
+    #[test]
+    fn test_pbm() {
+        let mut frame = FrameBuffer::new();
+        frame.set_pixel(0, 0, true);
+        frame.set_pixel(10, 20, true);
+
+        let plain = frame.to_pbm();
+        assert_frame_eq!(frame, FrameBuffer::from_pbm(plain.as_bytes()).unwrap());
+
+        let mut raw = b"P4\n# comment\n64 32\n".to_vec();
+        raw.extend_from_slice(&frame.data);
+        assert_frame_eq!(frame, FrameBuffer::from_pbm(&raw).unwrap());
+
+        assert!(FrameBuffer::from_pbm(b"P1\n8 8\n").is_err());
+        assert!(FrameBuffer::from_pbm(b"P1\n64 32\n0101").is_err());
+    }
+
+    #[test]
+    fn test_history_keeps_last_distinct_frames() {
+        let mut memory = Memory::new();
+        let mut history = FrameHistory::new(2);
+
+        history.push(1, FrameBuffer::from_memory(&memory));
+        history.push(2, FrameBuffer::from_memory(&memory));
+        assert_eq!(1, history.len());
+
+        Display::write_pixel(&mut memory, 0, 0);
+        history.push(3, FrameBuffer::from_memory(&memory));
+        Display::write_pixel(&mut memory, 0, 0);
+        history.push(4, FrameBuffer::from_memory(&memory));
+
+        let numbers: Vec<u64> = history.frames().map(|(number, _)| *number).collect();
+        assert_eq!(vec![3, 4], numbers);
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::path::Path;
+
+/// Resizes ending within this many pixels of an integer multiple of the display are snapped to it
+pub const SNAP_THRESHOLD: u32 = 16;
+
+/// Window size and position remembered between launches, in physical pixels
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub width: u32,
+    pub height: u32,
+    pub x: Option<i32>, // not available on every platform (e.g. Wayland)
+    pub y: Option<i32>,
+    pub fullscreen: bool,
+}
+
+impl WindowGeometry {
+    /// Returns None if the file is missing or invalid
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+}
+
+/// The closest integer multiple of `base` if `size` is within `SNAP_THRESHOLD` of it
+pub fn snap(size: (u32, u32), base: (u32, u32)) -> Option<(u32, u32)> {
+    let scale = ((size.0 as f32 / base.0 as f32).round() as u32).max(1);
+    let snapped = (base.0 * scale, base.1 * scale);
+
+    let close = size.0.abs_diff(snapped.0) <= SNAP_THRESHOLD
+        && size.1.abs_diff(snapped.1) <= SNAP_THRESHOLD;
+
+    if close && size != snapped {
+        Some(snapped)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap() {
+        assert_eq!(Some((640, 320)), snap((650, 310), (64, 32)));
+        assert_eq!(None, snap((640, 320), (64, 32)));
+        assert_eq!(None, snap((670, 320), (64, 32)));
+        assert_eq!(Some((1024, 256)), snap((1030, 250), (128, 32)));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir()
+            .join("chip8-interpreter-test-geometry")
+            .join("window.toml");
+        let geometry = WindowGeometry {
+            width: 1024,
+            height: 512,
+            x: Some(-20),
+            y: None,
+            fullscreen: true,
+        };
+
+        geometry.save(&path).unwrap();
+        assert_eq!(Some(geometry), WindowGeometry::load(&path));
+
+        std::fs::write(&path, "width = ").unwrap();
+        assert_eq!(None, WindowGeometry::load(&path));
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// Counts DXYN collisions per pixel over the last `frames` frames
+pub struct Heatmap {
+    frames: usize,
+    history: VecDeque<Vec<(u8, u8)>>,
+    counts: [u16; WIDTH * HEIGHT],
+}
+
+impl Heatmap {
+    pub fn new(frames: usize) -> Self {
+        Heatmap {
+            frames: frames.max(1),
+            history: VecDeque::new(),
+            counts: [0; WIDTH * HEIGHT],
+        }
+    }
+
+    /// Add the collisions of the frame that just ended, forgetting the oldest frame if needed
+    pub fn push_frame(&mut self, collisions: Vec<(u8, u8)>) {
+        for &(x, y) in &collisions {
+            self.counts[Heatmap::index(x, y)] += 1;
+        }
+        self.history.push_back(collisions);
+
+        while self.history.len() > self.frames {
+            for (x, y) in self.history.pop_front().unwrap() {
+                self.counts[Heatmap::index(x, y)] -= 1;
+            }
+        }
+    }
+
+    /// Collision count of every pixel relative to the hottest one, from 0.0 to 1.0
+    pub fn intensities(&self) -> Vec<f32> {
+        let max = *self.counts.iter().max().unwrap();
+        if max == 0 {
+            return vec![0.0; WIDTH * HEIGHT];
+        }
+
+        self.counts
+            .iter()
+            .map(|&count| count as f32 / max as f32)
+            .collect()
+    }
+
+    fn index(x: u8, y: u8) -> usize {
+        x as usize + WIDTH * y as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_window() {
+        let mut heatmap = Heatmap::new(2);
+
+        heatmap.push_frame(vec![(1, 2), (1, 2)]);
+        heatmap.push_frame(vec![(1, 2), (3, 4)]);
+        assert_eq!(3, heatmap.counts[Heatmap::index(1, 2)]);
+        assert_eq!(1, heatmap.counts[Heatmap::index(3, 4)]);
+
+        heatmap.push_frame(vec![]);
+        assert_eq!(1, heatmap.counts[Heatmap::index(1, 2)]);
+        assert_eq!(1, heatmap.counts[Heatmap::index(3, 4)]);
+    }
+
+    #[test]
+    fn test_intensities() {
+        let mut heatmap = Heatmap::new(10);
+        assert_eq!(0.0, heatmap.intensities()[0]);
+
+        heatmap.push_frame(vec![(0, 0), (0, 0), (63, 31)]);
+        let intensities = heatmap.intensities();
+        assert_eq!(1.0, intensities[0]);
+        assert_eq!(0.5, intensities[WIDTH * HEIGHT - 1]);
+    }
+}
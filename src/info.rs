@@ -0,0 +1,241 @@
+use crate::chip8::Chip8;
+use crate::disassembler::{self, Extension, Instruction};
+use crate::interpreter::KeyEvent;
+use crate::memory;
+use crate::plugin::keys::KeyUsageTracker;
+use crate::quirks::Profile;
+use crate::timing::DEFAULT_CYCLES_PER_FRAME;
+
+use sha1::{Digest, Sha1};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Largest program fitting in the 4K memory of the original platforms
+const MAX_PROG_SIZE: usize = (memory::MAX_SIZE - memory::PROG_LOC) as usize;
+
+/// Frames each key is held by `dynamic_report`, then released as long
+const KEY_PRESS_FRAMES: u64 = 4;
+
+/// Instructions whose behavior depends on a quirk
+const QUIRK_PATTERNS: [(&str, &[&str]); 5] = [
+    ("vf_reset", &["8XY1", "8XY2", "8XY3"]),
+    ("memory_increment", &["FX55", "FX65"]),
+    ("display_wait", &["DXYN"]),
+    ("shifting", &["8XY6", "8XYE"]),
+    ("jumping", &["BNNN"]),
+];
+
+/// Build a triage report of a program without running it
+pub fn report(program: &[u8]) -> String {
+    let graph = disassembler::analyze(program);
+    let instructions = &graph.instructions;
+    let mut out = String::new();
+
+    writeln!(out, "size: {} bytes", program.len()).unwrap();
+    writeln!(out, "sha1: {}", sha1_hex(program)).unwrap();
+    writeln!(out, "reachable instructions: {}", instructions.len()).unwrap();
+    writeln!(out, "basic blocks: {}", graph.blocks.len()).unwrap();
+
+    writeln!(out, "platform: {}", platform(program, instructions).name()).unwrap();
+    for extension in [Extension::Schip, Extension::XoChip] {
+        let addresses: Vec<String> = instructions
+            .iter()
+            .filter(|(_, instruction)| instruction.extension == extension)
+            .map(|(addr, instruction)| format!("{:03X} ({})", addr, instruction.pattern))
+            .collect();
+        if !addresses.is_empty() {
+            writeln!(out, "{} opcodes: {}", extension.name(), addresses.join(" ")).unwrap();
+        }
+    }
+
+    let quirks: Vec<&str> = QUIRK_PATTERNS
+        .iter()
+        .filter(|(_, patterns)| {
+            instructions
+                .values()
+                .any(|instruction| patterns.contains(&instruction.pattern))
+        })
+        .map(|(name, _)| *name)
+        .collect();
+    if !quirks.is_empty() {
+        writeln!(out, "quirk sensitive: {}", quirks.join(", ")).unwrap();
+    }
+
+    writeln!(out, "jump targets: {}", addresses(&graph.jump_targets)).unwrap();
+    writeln!(out, "call targets: {}", addresses(&graph.call_targets())).unwrap();
+    if !graph.computed_jumps.is_empty() {
+        writeln!(
+            out,
+            "computed jumps from: {}",
+            addresses(&graph.computed_jumps)
+        )
+        .unwrap();
+    }
+    writeln!(out, "call graph:").unwrap();
+    for (entry, called) in &graph.calls {
+        writeln!(out, "  {:03X} -> {}", entry, addresses(called)).unwrap();
+    }
+
+    writeln!(out, "unreachable regions:").unwrap();
+    for &(start, end) in &graph.data_regions {
+        let size = end - start + 1;
+        writeln!(out, "  {:03X}-{:03X} ({} bytes)", start, end, size).unwrap();
+    }
+
+    writeln!(out, "opcode histogram:").unwrap();
+    for (pattern, count) in histogram(instructions) {
+        writeln!(out, "  {}  {}", pattern, count).unwrap();
+    }
+
+    out
+}
+
+/// Run a program headlessly for `frames` frames with the suggested profile, pressing every
+/// key in turn to get past the waits, and report the keys it checks
+pub fn dynamic_report(program: &[u8], frames: u64) -> String {
+    let profile = suggested_profile(program);
+    let mut chip8 = Chip8::new();
+    chip8.interpreter.set_quirks(profile.quirks());
+    chip8.interpreter.set_timer_mode(profile.timer_mode());
+    chip8.interpreter.set_stack_depth(profile.stack_depth());
+    chip8.memory.set_address_space(profile.address_space());
+    let tracker = KeyUsageTracker::new();
+    let usage = tracker.usage();
+    chip8.register_plugin(Box::new(tracker));
+    chip8.load(program);
+
+    for frame in 0..frames {
+        if frame % KEY_PRESS_FRAMES == 0 {
+            let key = (frame / (2 * KEY_PRESS_FRAMES) % 16) as u8;
+            let down = frame % (2 * KEY_PRESS_FRAMES) == 0;
+            chip8.push_key_event(KeyEvent { key, down });
+        }
+        if chip8.run_frame(DEFAULT_CYCLES_PER_FRAME).is_some() {
+            break;
+        }
+        chip8.frame();
+    }
+
+    let report = usage.borrow().report();
+    format!("{}\n", report)
+}
+
+/// Identifies a program, e.g. for the play statistics
+pub fn sha1_hex(program: &[u8]) -> String {
+    Sha1::digest(program)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Most demanding platform suggested by the reachable opcodes and the program size
+fn platform(program: &[u8], instructions: &BTreeMap<u16, Instruction>) -> Extension {
+    let uses = |extension| {
+        instructions
+            .values()
+            .any(|instruction| instruction.extension == extension)
+    };
+
+    if uses(Extension::XoChip) || program.len() > MAX_PROG_SIZE {
+        Extension::XoChip
+    } else if uses(Extension::Schip) {
+        Extension::Schip
+    } else {
+        Extension::Chip8
+    }
+}
+
+/// Platform a program was most likely written for
+pub fn detect_platform(program: &[u8]) -> Extension {
+    platform(program, &disassembler::reachable_instructions(program))
+}
+
+/// Profile matching the extensions a program uses
+pub fn suggested_profile(program: &[u8]) -> Profile {
+    match detect_platform(program) {
+        Extension::Chip8 => Profile::Modern,
+        Extension::Schip => Profile::Schip,
+        Extension::XoChip => Profile::XoChip,
+    }
+}
+
+fn addresses(set: &BTreeSet<u16>) -> String {
+    if set.is_empty() {
+        return "none".to_string();
+    }
+
+    set.iter()
+        .map(|addr| format!("{:03X}", addr))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Count of each opcode pattern, most used first
+fn histogram(instructions: &BTreeMap<u16, Instruction>) -> Vec<(&'static str, usize)> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for instruction in instructions.values() {
+        *counts.entry(instruction.pattern).or_default() += 1;
+    }
+
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let program = [
+            0x60, 0x05, // 200: V0 = 5
+            0x22, 0x0A, // 202: call 0x20A
+            0x70, 0xFF, // 204: V0 = V0 - 1
+            0x12, 0x02, // 206: jump to 0x202
+            0xF0, 0xF0, // 208: sprite data
+            0x00, 0xFF, // 20A: high resolution
+            0x00, 0xEE, // 20C: return
+        ];
+
+        let report = report(&program);
+
+        assert!(report.contains("size: 14 bytes"));
+        assert!(report.contains("sha1: "));
+        assert!(report.contains("platform: SUPER-CHIP"));
+        assert!(report.contains("SUPER-CHIP opcodes: 20A (00FF)"));
+        assert!(report.contains("jump targets: 202"));
+        assert!(report.contains("call targets: 20A"));
+        assert!(report.contains("basic blocks: 4"));
+        assert!(report.contains("  200 -> 20A\n  20A -> none"));
+        assert!(report.contains("  208-209 (2 bytes)"));
+        assert!(report.contains("  6XNN  1"));
+        assert_eq!(Profile::Schip, suggested_profile(&program));
+    }
+
+    #[test]
+    fn test_dynamic_report() {
+        use crate::rom_builder::*;
+
+        let program = [
+            ld_vx(0, 0x05), // 200
+            skp(0),         // 202
+            jp(0x202),      // 204
+            ld_vx_k(1),     // 206
+            jp(0x200),      // 208
+        ]
+        .concat();
+
+        let report = dynamic_report(&program, 120);
+        assert!(report.starts_with("keys checked: 5 ("));
+        // the wait is reached while key 5 is held
+        assert!(report.contains("\nkeys ending a wait: 5 ("));
+        assert!(report.contains("waits for any key"));
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!("a9993e364706816aba3e25717850c26c9cd0d89d", sha1_hex(b"abc"));
+    }
+}
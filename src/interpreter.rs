@@ -1,26 +1,64 @@
+use crate::audio::AudioConfig;
+use crate::bus::Bus;
 use crate::display::Display;
-use crate::keyconf::{COSMACVIP, KEYCONFIG};
-use crate::memory;
-use crate::memory::Memory;
+use crate::error::{Error, HaltReason, MemError};
+use crate::memory::{self, Memory, MemoryMap};
+use crate::metrics::Metrics;
+use crate::opcodes::{self, Operation};
+use crate::pc_history::PcHistory;
+use crate::quirks::Quirks;
+use crate::timing::TimerMode;
 
-use winit_input_helper::WinitInputHelper;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use rand::Rng;
+use std::collections::VecDeque;
 
 const STACK_SIZE: usize = 0xff;
 const NUM_REGISTERS: usize = 16;
-const NUM_KEYS: usize = 16;
+pub const NUM_KEYS: usize = 16;
 
+/// A CHIP-8 key (0x0 to 0xF) going down or up, independent of the frontend
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub down: bool,
+}
+
+#[derive(Clone)]
 pub struct Interpreter {
-    stack: [u16; STACK_SIZE], // stack is here instead of in-memory
-    sc: u8,                   // stack counter
-    vi: u16,                  // index register
-    vx: [u8; NUM_REGISTERS],  // registers V0 to VF
-    pub pc: u16,              // program counter
-    dt: u8,                   // delay timer
-    st: u8,                   // sound timer
+    stack: [u16; STACK_SIZE],           // stack is here instead of in-memory
+    pub(crate) sc: u8,                  // stack counter
+    stack_depth: usize,                 // stack levels available to the program
+    pub(crate) vi: u16,                 // index register
+    pub(crate) vx: [u8; NUM_REGISTERS], // registers V0 to VF
+    pub(crate) pc: u16,                 // program counter
+    pub(crate) dt: u8,                  // delay timer
+    dt_set_phase: f32,                  // frame phase when the delay timer was set
+    dt_expiring: bool,                  // delay timer reached 0 at the last frame boundary
+    pub(crate) st: u8,                  // sound timer
+    beep: u8,                           // frames left of a lengthened short beep
+    short_beep: Option<(u16, u8)>,      // pc and value of a too short FX18
+    cue: u8,                            // frames left of a collision cue
+    audio: AudioConfig,
     key_held: [bool; NUM_KEYS],
-    stop: bool,
+    key_events: VecDeque<KeyEvent>,    // not yet seen by the program
+    key_release_delay: u8,             // frames a key stays held after release
+    key_release_timer: [u8; NUM_KEYS], // remaining frames before a key is released
+    quirks: Quirks,
+    memory_map: MemoryMap, // where FX29 and FX30 find the fonts
+    timer_mode: TimerMode,
+    frame_phase: f32,          // progress through the current frame, from 0.0 to 1.0
+    strict_memory: bool,       // out of bounds accesses stop with an error instead of wrapping
+    vblank_wait: bool,         // waiting for the next frame after a draw (display_wait quirk)
+    collisions: Vec<(u8, u8)>, // pixels erased by DXYN since the last take_collisions()
+    record_collisions: bool,   // whether to record the pixels erased by DXYN
+    record_draws: bool,        // whether to record the pixels touched by DXYN
+    draws: Vec<(u8, u8)>,      // pixels touched by DXYN since the last take_draws()
+    halt: Option<HaltReason>,  // why instructions are no longer executed
+    rng: StdRng,               // for CXNN
+    pc_history: PcHistory,
+    pub(crate) metrics: Metrics,
 }
 
 impl Interpreter {
@@ -30,14 +68,55 @@ impl Interpreter {
             vx: [0; NUM_REGISTERS],
             pc: 0x0200,
             dt: 0,
+            dt_set_phase: 0.0,
+            dt_expiring: false,
             st: 0,
+            beep: 0,
+            short_beep: None,
+            cue: 0,
+            audio: AudioConfig::default(),
             stack: [0; STACK_SIZE],
             sc: 0,
+            stack_depth: STACK_SIZE,
             key_held: [false; NUM_KEYS],
-            stop: false,
+            key_events: VecDeque::new(),
+            key_release_delay: 0,
+            key_release_timer: [0; NUM_KEYS],
+            quirks: Quirks::default(),
+            memory_map: MemoryMap::default(),
+            timer_mode: TimerMode::Frame,
+            frame_phase: 0.0,
+            strict_memory: false,
+            vblank_wait: false,
+            collisions: Vec::new(),
+            record_collisions: false,
+            record_draws: false,
+            draws: Vec::new(),
+            halt: None,
+            rng: StdRng::from_entropy(),
+            pc_history: PcHistory::new(),
+            metrics: Metrics::new(),
         }
     }
 
+    /// Back to the power-on state, keeping the configuration (quirks, timer mode, ...)
+    pub fn reset(&mut self) {
+        *self = Interpreter {
+            key_release_delay: self.key_release_delay,
+            stack_depth: self.stack_depth,
+            quirks: self.quirks,
+            memory_map: self.memory_map,
+            timer_mode: self.timer_mode,
+            strict_memory: self.strict_memory,
+            audio: self.audio,
+            record_collisions: self.record_collisions,
+            record_draws: self.record_draws,
+            rng: self.rng.clone(),
+            metrics: self.metrics,
+            ..Interpreter::new()
+        };
+    }
+
     fn set_vx(&mut self, x: u8, data: u8) {
         self.vx[x as usize] = data;
     }
@@ -50,7 +129,98 @@ impl Interpreter {
         self.vx[15]
     }
 
+    /// Program counter, address of the next instruction
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Registers V0 to VF
+    pub fn registers(&self) -> &[u8; NUM_REGISTERS] {
+        &self.vx
+    }
+
+    /// Index register I
+    pub fn index(&self) -> u16 {
+        self.vi
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    /// Stack levels in use
+    pub fn sc(&self) -> u8 {
+        self.sc
+    }
+
+    /// Return addresses of the stack levels in use, the last one on top
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sc as usize]
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Make CXNN draw the same random numbers on every run
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn memory_map(&self) -> MemoryMap {
+        self.memory_map
+    }
+
+    /// Where FX29 and FX30 find the fonts, which must be loaded there (see `Chip8::set_memory_map`)
+    pub fn set_memory_map(&mut self, map: MemoryMap) {
+        self.memory_map = map;
+    }
+
+    /// Stop with an error on memory accesses beyond the end of memory (0xFFF unless the
+    /// address space is XO-CHIP's) instead of wrapping the address
+    pub fn set_strict_memory(&mut self, strict: bool) {
+        self.strict_memory = strict;
+    }
+
+    /// Limit the stack to `depth` levels (at most 255), deeper calls stop with an error
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack_depth = depth.min(STACK_SIZE);
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+
+    pub fn set_timer_mode(&mut self, mode: TimerMode) {
+        self.timer_mode = mode;
+    }
+
+    pub fn timer_mode(&self) -> TimerMode {
+        self.timer_mode
+    }
+
+    /// Progress through the current frame of the next instruction, from 0.0 to 1.0
+    pub fn set_frame_phase(&mut self, phase: f32) {
+        self.frame_phase = phase;
+    }
+
+    /// Called once per frame (60 Hz by default)
     pub fn decrement_timers(&mut self) {
+        self.vblank_wait = false;
+
+        self.dt_expiring = self.dt == 1;
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -58,22 +228,97 @@ impl Interpreter {
         if self.st > 0 {
             self.st -= 1;
         }
+        self.beep = self.beep.saturating_sub(1);
+        self.cue = self.cue.saturating_sub(1);
     }
 
-    fn set_dt(&mut self, value: u8) {
+    /// Set the delay timer as FX15 does
+    pub fn set_dt(&mut self, value: u8) {
         self.dt = value;
+        self.dt_set_phase = self.frame_phase;
+        self.dt_expiring = false;
+    }
+
+    /// Delay timer as read by FX07
+    fn read_dt(&self) -> u8 {
+        match self.timer_mode {
+            TimerMode::Frame => self.dt,
+            TimerMode::Interpolated => {
+                // the frame boundary came before the timer ran for a whole frame
+                let pending =
+                    (self.dt > 0 || self.dt_expiring) && self.frame_phase < self.dt_set_phase;
+                self.dt.saturating_add(pending as u8)
+            }
+        }
     }
 
-    fn set_st(&mut self, value: u8) {
+    /// Set the sound timer as FX18 does
+    pub fn set_st(&mut self, value: u8) {
         self.st = value;
+        self.beep = 0;
+
+        if value > 0 && value < self.audio.min_beep_frames {
+            self.beep = self.audio.min_beep_frames;
+            self.short_beep = Some((self.pc.wrapping_sub(2), value));
+        }
     }
 
-    pub fn apply_input(&mut self, input: &WinitInputHelper) {
-        self.key_held = [false; NUM_KEYS]; // reset keys
+    pub fn set_audio_config(&mut self, audio: AudioConfig) {
+        self.audio = audio;
+    }
 
-        for (key, virtualkeycode) in KEYCONFIG.iter() {
-            if input.key_held(*virtualkeycode) {
-                self.key_held[*key as usize] = true;
+    /// Whether the buzzer sounds, the sound timer running, a short beep being lengthened
+    /// or a collision cue playing
+    pub fn sound_on(&self) -> bool {
+        self.st > 0 || self.beep > 0 || self.cue > 0
+    }
+
+    /// Returns the address and value of the last sound timer set below the minimum
+    /// beep duration since the last call
+    pub fn take_short_beep(&mut self) -> Option<(u16, u8)> {
+        self.short_beep.take()
+    }
+
+    /// Keep keys logically held for `frames` frames after they are released,
+    /// for games polling the keys less often than every frame
+    pub fn set_key_release_delay(&mut self, frames: u8) {
+        self.key_release_delay = frames;
+    }
+
+    /// Queue a key press or release, seen by the program from the next frame
+    pub fn push_key_event(&mut self, event: KeyEvent) {
+        self.key_events.push_back(event);
+    }
+
+    /// Apply the queued key events, once per frame right before its instructions so that
+    /// EX9E, EXA1 and FX0A see the latest input. A key pressed and released within the same
+    /// frame stays held for the whole frame, its release is applied at the next one.
+    pub fn sample_keys(&mut self) {
+        for key in 0..NUM_KEYS {
+            if self.key_release_timer[key] > 0 {
+                self.key_release_timer[key] -= 1;
+                if self.key_release_timer[key] == 0 {
+                    self.key_held[key] = false;
+                }
+            }
+        }
+
+        let mut changed = [false; NUM_KEYS];
+        while let Some(&KeyEvent { key, down }) = self.key_events.front() {
+            let key = key as usize % NUM_KEYS;
+            if changed[key] {
+                break;
+            }
+            self.key_events.pop_front();
+            changed[key] = true;
+
+            if down {
+                self.key_held[key] = true;
+                self.key_release_timer[key] = 0;
+            } else if self.key_release_delay > 0 {
+                self.key_release_timer[key] = self.key_release_delay;
+            } else {
+                self.key_held[key] = false;
             }
         }
     }
@@ -89,377 +334,569 @@ impl Interpreter {
         None
     }
 
+    /// Positions of the pixel collisions not taken yet
+    pub(crate) fn collisions(&self) -> &[(u8, u8)] {
+        &self.collisions
+    }
+
+    /// Returns the positions of the pixel collisions since the last call,
+    /// when recording them
+    pub fn take_collisions(&mut self) -> Vec<(u8, u8)> {
+        std::mem::take(&mut self.collisions)
+    }
+
+    /// Record the pixels erased by DXYN, for `take_collisions`. Off by default, as the
+    /// positions pile up until taken.
+    pub fn set_record_collisions(&mut self, record: bool) {
+        self.record_collisions = record;
+    }
+
+    pub fn record_collisions(&self) -> bool {
+        self.record_collisions
+    }
+
+    /// Record the pixels touched by DXYN, for `take_draws`
+    pub fn set_record_draws(&mut self, record: bool) {
+        self.record_draws = record;
+    }
+
+    /// Returns the positions of the pixels touched by DXYN since the last call,
+    /// when recording them
+    pub fn take_draws(&mut self) -> Vec<(u8, u8)> {
+        std::mem::take(&mut self.draws)
+    }
+
+    /// Keys held as the program sees them, from the key events of the frames so far
+    pub fn keys(&self) -> &[bool; NUM_KEYS] {
+        &self.key_held
+    }
+
+    /// Whether execution is suspended until the next frame (display_wait quirk)
+    pub fn waiting_vblank(&self) -> bool {
+        self.vblank_wait
+    }
+
     pub fn stop(&self) -> bool {
-        self.stop
+        self.halt.is_some()
+    }
+
+    pub fn halt_reason(&self) -> Option<&HaltReason> {
+        self.halt.as_ref()
+    }
+
+    /// The fault that stopped the interpreter, if any
+    pub fn error(&self) -> Option<&Error> {
+        match &self.halt {
+            Some(HaltReason::Error(error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Stop executing instructions, keeping the first reason when already halted
+    pub fn halt(&mut self, reason: HaltReason) {
+        self.halt.get_or_insert(reason);
+    }
+
+    /// Execute instructions again after a halt, e.g. past a breakpoint
+    pub fn resume(&mut self) {
+        self.halt = None;
+    }
+
+    fn fault(&mut self, error: Error) {
+        self.halt(HaltReason::Error(error));
+    }
+
+    fn unknown_opcode(&mut self, opcode: u16) {
+        self.fault(Error::UnknownOpcode {
+            pc: self.pc.wrapping_sub(2),
+            opcode,
+        });
+    }
+
+    fn memory_fault(&mut self, source: MemError) {
+        self.fault(Error::Memory {
+            pc: self.pc.wrapping_sub(2),
+            source,
+        });
+    }
+
+    fn read_mem(&mut self, memory: &Memory, addr: u16) -> u8 {
+        if !self.strict_memory {
+            return memory.read_wrapped(addr);
+        }
+
+        memory.read_checked(addr).unwrap_or_else(|error| {
+            self.memory_fault(error);
+            0
+        })
+    }
+
+    fn write_mem(&mut self, memory: &mut Memory, addr: u16, value: u8) {
+        if !self.strict_memory {
+            memory.write_wrapped(addr, value);
+        } else if let Err(error) = memory.write_checked(addr, value) {
+            self.memory_fault(error);
+        }
     }
 
     pub fn step(&mut self, memory: &mut Memory) {
+        if self.vblank_wait {
+            return;
+        }
+
         let opcode = self.next(memory);
-        self.pc += 2;
+        self.pc_history.push(self.pc, opcode);
+        self.pc = self.pc.wrapping_add(2);
         self.exec(opcode, memory);
+        // running or skipping past the end of memory continues at 000
+        self.pc &= memory.address_mask();
+    }
+
+    /// The last instructions executed, blocks entered for the JIT
+    pub fn pc_history(&self) -> &PcHistory {
+        &self.pc_history
+    }
+
+    pub(crate) fn record_pc(&mut self, pc: u16, opcode: u16) {
+        self.pc_history.push(pc, opcode);
     }
 
     pub fn next(&self, mem: &Memory) -> u16 {
         mem.read_u16(self.pc)
     }
 
-    pub fn stack_push(&mut self, value: u16) {
+    pub fn stack_push(&mut self, value: u16) -> Result<(), Error> {
+        if self.sc as usize >= self.stack_depth {
+            return Err(Error::StackOverflow {
+                pc: self.pc.wrapping_sub(2),
+                depth: self.stack_depth,
+            });
+        }
+
         self.stack[self.sc as usize] = value;
         self.sc += 1;
+        Ok(())
     }
 
-    pub fn stack_pop(&mut self) -> u16 {
+    pub fn stack_pop(&mut self) -> Result<u16, Error> {
+        if self.sc == 0 {
+            return Err(Error::StackUnderflow {
+                pc: self.pc.wrapping_sub(2),
+            });
+        }
+
         self.sc -= 1;
-        self.stack[self.sc as usize]
+        Ok(self.stack[self.sc as usize])
     }
 
-    fn exec(&mut self, opcode: u16, memory: &mut Memory) {
-        if opcode == 0x0000 {
-            self.stop = true;
-            return;
-        }
+    /// Whether `exec` implements an opcode, rather than halting on it as unknown: found by
+    /// running it on a blank machine
+    pub fn executes(opcode: u16) -> bool {
+        let mut interpreter = Interpreter::new();
+        let mut memory = Memory::new();
+        interpreter.exec(opcode, &mut memory);
+        !matches!(interpreter.error(), Some(Error::UnknownOpcode { .. }))
+    }
 
-        match Interpreter::mode(opcode) {
-            0x0 => {
-                let nnn = Interpreter::nnn(opcode);
-                match nnn {
-                    // clear screen
-                    0x0E0 => {
-                        for pixel_addr in 0x00..0xFF {
-                            memory.write(memory::DISPLAY_LOC + pixel_addr, 0);
-                        }
-                    }
-                    0x0EE => {
-                        self.pc = self.stack_pop();
-                    }
+    /// Execute an instruction already fetched, with PC past it, decoded by the instruction
+    /// table
+    pub(crate) fn exec(&mut self, opcode: u16, memory: &mut Memory) {
+        match opcodes::lookup(opcode) {
+            Some(row) => self.execute(row.operation, opcode, memory),
+            None => self.unknown_opcode(opcode),
+        }
+    }
 
-                    _ => panic!("Unkown opcode"),
+    /// Execute an instruction already decoded to its operation, with PC past it. The
+    /// instructions decoded but not implemented here (most of SUPER-CHIP and XO-CHIP) are
+    /// unknown opcodes.
+    pub(crate) fn execute(&mut self, operation: Operation, opcode: u16, memory: &mut Memory) {
+        match operation {
+            Operation::Halt => self.halt(HaltReason::NullOpcode {
+                pc: self.pc.wrapping_sub(2),
+            }),
+
+            // clear screen
+            Operation::Clear => {
+                for byte in 0..memory::DISPLAY_SIZE {
+                    memory.set_display_byte(byte, 0);
                 }
             }
 
+            // return from a subroutine
+            Operation::Return => match self.stack_pop() {
+                Ok(addr) => self.pc = addr,
+                Err(error) => self.fault(error),
+            },
+
             // jump
-            0x1 => {
+            Operation::Jump => {
                 let nnn = Interpreter::nnn(opcode);
                 self.pc = nnn;
             }
 
             // subroutines
-            0x2 => {
-                self.stack_push(self.pc);
-                self.pc = Interpreter::nnn(opcode);
-            }
+            Operation::Call => match self.stack_push(self.pc) {
+                Ok(()) => self.pc = Interpreter::nnn(opcode),
+                Err(error) => self.fault(error),
+            },
 
             // skip if VX == nn
-            0x3 => {
+            Operation::SkipEqual => {
                 let x = Interpreter::x(opcode);
                 let nn = Interpreter::nn(opcode);
                 if self.vx[x as usize] == nn {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
 
             // skip if VX != nn
-            0x4 => {
+            Operation::SkipNotEqual => {
                 let x = Interpreter::x(opcode);
                 let nn = Interpreter::nn(opcode);
                 if self.vx[x as usize] != nn {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
 
             // skip if VX == VY
-            0x5 => {
-                let n = Interpreter::n(opcode);
-                if n != 0 {
-                    panic!("Unknown instruction");
-                }
-
+            Operation::SkipEqualRegister => {
                 let x = Interpreter::x(opcode);
                 let y = Interpreter::y(opcode);
                 if self.vx[x as usize] == self.vx[y as usize] {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
 
             // set register VX
-            0x6 => {
+            Operation::Set => {
                 let x = Interpreter::x(opcode);
                 let nn = Interpreter::nn(opcode);
                 self.set_vx(x, nn)
             }
 
             // add value to vx
-            0x7 => {
+            Operation::Add => {
                 let x = Interpreter::x(opcode);
                 let nn = Interpreter::nn(opcode);
                 let vx = self.vx[x as usize];
                 self.set_vx(x, vx.wrapping_add(nn));
             }
 
-            // logical and arithmetic instructions
-            0x8 => {
-                let n = Interpreter::n(opcode);
-                let x = Interpreter::x(opcode);
-                let y = Interpreter::y(opcode);
-
-                let vx = self.vx[x as usize];
-                let vy = self.vx[y as usize];
-
-                match n {
-                    // set VX to the value of VY
-                    0x0 => {
-                        self.set_vx(x, vy);
-                    }
-
-                    // binary OR
-                    0x1 => {
-                        self.set_vx(x, vx | vy);
-                    }
+            // set VX to the value of VY
+            Operation::Copy => {
+                let (x, _, vy) = self.xy(opcode);
+                self.set_vx(x, vy);
+            }
 
-                    // binary AND
-                    0x2 => {
-                        self.set_vx(x, vx & vy);
-                    }
+            // binary OR
+            Operation::Or => {
+                let (x, vx, vy) = self.xy(opcode);
+                self.set_vx(x, vx | vy);
+                if self.quirks.vf_reset {
+                    self.set_vf(0);
+                }
+            }
 
-                    // logical XOR
-                    0x3 => {
-                        self.set_vx(x, vx ^ vy);
-                    }
+            // binary AND
+            Operation::And => {
+                let (x, vx, vy) = self.xy(opcode);
+                self.set_vx(x, vx & vy);
+                if self.quirks.vf_reset {
+                    self.set_vf(0);
+                }
+            }
 
-                    // add
-                    0x4 => {
-                        let overflows = vx.checked_add(vy).is_none() as u8;
+            // logical XOR
+            Operation::Xor => {
+                let (x, vx, vy) = self.xy(opcode);
+                self.set_vx(x, vx ^ vy);
+                if self.quirks.vf_reset {
+                    self.set_vf(0);
+                }
+            }
 
-                        self.set_vx(x, vx.wrapping_add(vy));
-                        self.set_vf(overflows);
-                    }
+            // add
+            Operation::AddRegister => {
+                let (x, vx, vy) = self.xy(opcode);
+                let overflows = vx.checked_add(vy).is_none() as u8;
 
-                    // substract VX - VY
-                    0x5 => {
-                        let underflows = vx.checked_sub(vy).is_none() as u8;
+                self.set_vx(x, vx.wrapping_add(vy));
+                self.set_vf(overflows);
+            }
 
-                        self.set_vx(x, vx.wrapping_sub(vy));
-                        self.set_vf(1 - underflows); // 0 if underflows else 1
-                    }
+            // substract VX - VY
+            Operation::Sub => {
+                let (x, vx, vy) = self.xy(opcode);
+                let underflows = vx.checked_sub(vy).is_none() as u8;
 
-                    // substract VY - VX
-                    0x7 => {
-                        let underflows = vy.checked_sub(vx).is_none() as u8;
+                self.set_vx(x, vx.wrapping_sub(vy));
+                self.set_vf(1 - underflows); // 0 if underflows else 1
+            }
 
-                        self.set_vx(x, vy.wrapping_sub(vx));
-                        self.set_vf(1 - underflows); // 0 if underflows else 1
-                    }
+            // substract VY - VX
+            Operation::SubReversed => {
+                let (x, vx, vy) = self.xy(opcode);
+                let underflows = vy.checked_sub(vx).is_none() as u8;
 
-                    // shift 1 bit to the right
-                    0x6 => {
-                        // TODO: optional of configurable: set vx to vy
-                        let shifted_bit = vx & 0b0000_0001;
-                        self.set_vx(x, vx >> 1);
-                        self.set_vf(shifted_bit);
-                    }
+                self.set_vx(x, vy.wrapping_sub(vx));
+                self.set_vf(1 - underflows); // 0 if underflows else 1
+            }
 
-                    // shift 1 bit to the left
-                    0xE => {
-                        // TODO: optional of configurable: set vx to vy
-                        let shifted_bit = (vx & 0b1000_0000) >> 7;
-                        self.set_vx(x, vx << 1);
-                        self.set_vf(shifted_bit);
-                    }
+            // shift 1 bit to the right
+            Operation::ShiftRight => {
+                let (x, vx, vy) = self.xy(opcode);
+                let vx = if self.quirks.shifting { vx } else { vy };
+                let shifted_bit = vx & 0b0000_0001;
+                self.set_vx(x, vx >> 1);
+                self.set_vf(shifted_bit);
+            }
 
-                    _ => panic!("Unknown N for instruction: 0x8XYN"),
-                }
+            // shift 1 bit to the left
+            Operation::ShiftLeft => {
+                let (x, vx, vy) = self.xy(opcode);
+                let vx = if self.quirks.shifting { vx } else { vy };
+                let shifted_bit = (vx & 0b1000_0000) >> 7;
+                self.set_vx(x, vx << 1);
+                self.set_vf(shifted_bit);
             }
 
             // skip if VX != VY
-            0x9 => {
-                let n = Interpreter::n(opcode);
-                if n != 0 {
-                    panic!("Unknown instruction");
-                }
-
+            Operation::SkipNotEqualRegister => {
                 let x = Interpreter::x(opcode);
                 let y = Interpreter::y(opcode);
                 if self.vx[x as usize] != self.vx[y as usize] {
-                    self.pc += 2;
+                    self.pc = self.pc.wrapping_add(2);
                 }
             }
 
             // set index register
-            0xA => {
+            Operation::SetIndex => {
                 let nnn = Interpreter::nnn(opcode);
                 self.vi = nnn;
             }
 
             // jump with offset
-            0xB => {
-                // TODO: configurable instruction with BXNN (see SUPER-CHIP)
+            Operation::JumpOffset => {
                 let nnn = Interpreter::nnn(opcode);
-                self.pc = nnn + self.vx[0] as u16;
+                let offset = if self.quirks.jumping {
+                    self.vx[Interpreter::x(opcode) as usize]
+                } else {
+                    self.vx[0]
+                };
+                self.pc = nnn + offset as u16;
             }
 
             // random
-            0xC => {
+            Operation::Random => {
                 let nn = Interpreter::nn(opcode);
                 let x = Interpreter::x(opcode);
 
-                let r: u8 = rand::thread_rng().gen();
+                let r: u8 = self.rng.gen();
 
                 self.set_vx(x, r & nn);
             }
 
-            // draw to screen
-            0xD => {
+            // draw to screen, DXY0 drawing nothing as on the VIP
+            Operation::DrawLarge | Operation::Draw => {
                 let x = Interpreter::x(opcode);
                 let y = Interpreter::y(opcode);
                 let n = Interpreter::n(opcode);
 
-                let vx = self.vx[x as usize];
-                let vy = self.vx[y as usize];
+                // the starting position wraps around the screen
+                let vx = self.vx[x as usize] % 64;
+                let vy = self.vx[y as usize] % 32;
 
-                let mut row = 0;
-                for sprite_byte_addr in self.vi..(self.vi + n as u16) {
-                    let mut col = 0;
+                self.set_vf(0);
+                self.metrics.draws += 1;
+                let mut rows_hit = 0; // rows colliding or clipped at the bottom
+                let mut collided = false;
 
-                    let sprite_byte = memory.read(sprite_byte_addr);
+                for row in 0..n {
+                    if self.quirks.clipping && vy + row >= 32 {
+                        rows_hit += 1;
+                        continue;
+                    }
 
-                    for sprite_bit_idx in 0..8 {
-                        let sprite_bit = (sprite_byte >> (7 - sprite_bit_idx)) & 0b0000_0001;
+                    let mut row_collided = false;
+                    let sprite_byte = self.read_mem(memory, self.vi.wrapping_add(row as u16));
+
+                    for col in 0..8 {
+                        let sprite_bit = (sprite_byte >> (7 - col)) & 0b0000_0001;
 
                         if sprite_bit == 1 {
-                            let pos_x = vx + col;
-                            let pos_y = vy + row;
-                            // don't display if outside of the screen
-                            if pos_x < 64 && pos_y < 32 {
-                                let curr_pixel = Display::read_pixel(memory, pos_x, pos_y);
-
-                                // pixel collision
-                                if curr_pixel == 1 {
-                                    self.set_vf(1);
+                            let mut pos_x = vx + col;
+                            let mut pos_y = vy + row;
+
+                            if self.quirks.clipping {
+                                // don't display if outside of the screen
+                                if pos_x >= 64 || pos_y >= 32 {
+                                    continue;
                                 }
+                            } else {
+                                pos_x %= 64;
+                                pos_y %= 32;
+                            }
 
-                                Display::write_pixel(memory, pos_x, pos_y);
+                            let curr_pixel = Display::read_pixel(memory, pos_x, pos_y);
+                            if self.record_draws {
+                                self.draws.push((pos_x, pos_y));
+                            }
+
+                            // pixel collision
+                            if curr_pixel == 1 {
+                                row_collided = true;
+                                self.set_vf(1);
+                                if self.record_collisions {
+                                    self.collisions.push((pos_x, pos_y));
+                                }
+                                self.cue = self.audio.collision_cue_frames;
                             }
+
+                            Display::write_pixel(memory, pos_x, pos_y);
                         }
+                    }
 
-                        col += 1;
+                    if row_collided {
+                        rows_hit += 1;
+                        collided = true;
                     }
+                }
 
-                    row += 1;
+                if collided {
+                    self.metrics.collisions += 1;
+                }
+
+                if self.quirks.collision_rows {
+                    self.set_vf(rows_hit);
+                }
+
+                if self.quirks.display_wait {
+                    self.vblank_wait = true;
                 }
             }
 
             // skip if key
-            0xE => {
+            Operation::SkipKey | Operation::SkipNotKey => {
                 let x = Interpreter::x(opcode);
                 let vx = self.vx[x as usize];
 
-                let is_key_pressed_at_vx = self.key_held[vx as usize];
+                // only the low nibble selects a key, as on the VIP
+                let is_key_pressed_at_vx = self.key_held[vx as usize & 0xF];
 
-                let nn = Interpreter::nn(opcode);
+                if is_key_pressed_at_vx == (operation == Operation::SkipKey) {
+                    self.pc = self.pc.wrapping_add(2);
+                }
+            }
 
-                match nn {
-                    0x9E => {
-                        if is_key_pressed_at_vx {
-                            self.pc += 2;
-                        }
-                    }
+            // read delay timer to vx
+            Operation::GetDelay => self.set_vx(Interpreter::x(opcode), self.read_dt()),
 
-                    0xA1 => {
-                        if !is_key_pressed_at_vx {
-                            self.pc += 2;
-                        }
-                    }
+            // set delay timer to vx
+            Operation::SetDelay => self.set_dt(self.vx[Interpreter::x(opcode) as usize]),
+
+            // set sound timer to vx
+            Operation::SetSound => self.set_st(self.vx[Interpreter::x(opcode) as usize]),
 
-                    _ => panic!("Unknown NN for instruction: 0xEXNN"),
+            // add to index
+            Operation::AddIndex => {
+                let vx = self.vx[Interpreter::x(opcode) as usize];
+                self.vi = self.vi.wrapping_add(vx as u16);
+            }
+
+            // get key
+            Operation::WaitKey => {
+                let x = Interpreter::x(opcode);
+                if let Some(key) = self.get_first_key_pressed() {
+                    self.set_vx(x, key as u8);
+                }
+                // go back (e.g. loop) until key press
+                else {
+                    self.pc = self.pc.wrapping_sub(2) & memory.address_mask();
+                    self.metrics.key_waits += 1;
                 }
             }
 
-            // miscellaneous
-            0xF => {
+            // font character
+            Operation::Font => {
                 let x = Interpreter::x(opcode);
                 let vx = self.vx[x as usize];
 
-                let nn = Interpreter::nn(opcode);
-
-                match nn {
-                    // read delay timer to vx
-                    0x07 => self.set_vx(x, self.dt),
-
-                    // set delay timer to vx
-                    0x15 => self.set_dt(vx),
-
-                    // set sound timer to vx
-                    0x18 => self.set_st(vx),
-
-                    // add to index
-                    0x1E => self.vi = self.vi.wrapping_add(vx as u16),
+                self.vi = self.memory_map.small_char(vx);
+            }
 
-                    // get key
-                    0x0A => {
-                        let first_key_pressed = self.get_first_key_pressed();
-                        if first_key_pressed.is_some() {
-                            self.set_vx(x, first_key_pressed.unwrap() as u8);
-                        }
-                        // go back (e.g. loop) until key press
-                        else {
-                            self.pc -= 2;
-                        }
-                    }
+            // big font character (SUPER-CHIP)
+            Operation::BigFont => {
+                let x = Interpreter::x(opcode);
+                let vx = self.vx[x as usize];
 
-                    // font character
-                    0x29 => {
-                        let x = Interpreter::x(opcode);
-                        let vx = self.vx[x as usize];
+                self.vi = self.memory_map.big_char(vx);
+            }
 
-                        let offset = (vx as u16) * memory::FONT_CHAR_SIZE;
-                        self.vi = memory::FONT_LOC + offset;
-                    }
+            // binary-coded decimal conversion
+            Operation::Bcd => {
+                let x = Interpreter::x(opcode);
+                let vx = self.vx[x as usize];
 
-                    // binary-coded decimal conversion
-                    0x33 => {
-                        let x = Interpreter::x(opcode);
-                        let vx = self.vx[x as usize];
+                let right_digit = vx % 10;
+                let mid_digit = (vx / 10) % 10;
+                let left_digit = (vx / 100) % 10;
 
-                        let right_digit = (vx / 1) % 10;
-                        let mid_digit = (vx / 10) % 10;
-                        let left_digit = (vx / 100) % 10;
+                self.write_mem(memory, self.vi, left_digit);
+                self.write_mem(memory, self.vi.wrapping_add(1), mid_digit);
+                self.write_mem(memory, self.vi.wrapping_add(2), right_digit);
+            }
 
-                        memory.write(self.vi, left_digit);
-                        memory.write(self.vi + 1, mid_digit);
-                        memory.write(self.vi + 2, right_digit);
-                    }
+            // write register to mem
+            Operation::Store => {
+                let x_max = Interpreter::x(opcode);
+                for x in 0..(x_max + 1) {
+                    let addr = self.vi.wrapping_add(x as u16);
+                    let value = self.vx[x as usize];
+                    self.write_mem(memory, addr, value);
+                }
 
-                    // write register to mem
-                    0x55 => {
-                        // TODO: configurable instruction
-                        let x_max = Interpreter::x(opcode);
-                        for x in 0..(x_max + 1) {
-                            let addr = self.vi + x as u16;
-                            let value = self.vx[x as usize];
-                            memory.write(addr, value);
-                        }
-                    }
+                if self.quirks.memory_increment {
+                    self.vi = self.vi.wrapping_add(x_max as u16 + 1);
+                }
+            }
 
-                    // read mem to registers
-                    0x65 => {
-                        // TODO: configurable instruction
-                        let x_max = Interpreter::x(opcode);
-                        for x in 0..(x_max + 1) {
-                            let addr = self.vi + x as u16;
-                            self.vx[x as usize] = memory.read(addr);
-                        }
-                    }
+            // read mem to registers
+            Operation::Load => {
+                let x_max = Interpreter::x(opcode);
+                for x in 0..(x_max + 1) {
+                    let addr = self.vi.wrapping_add(x as u16);
+                    self.vx[x as usize] = self.read_mem(memory, addr);
+                }
 
-                    _ => panic!("Unknown NN for instruction: 0xFXNN"),
+                if self.quirks.memory_increment {
+                    self.vi = self.vi.wrapping_add(x_max as u16 + 1);
                 }
             }
 
-            _ => panic!("Unknown mode"),
+            Operation::ScrollDown
+            | Operation::ScrollUp
+            | Operation::ScrollRight
+            | Operation::ScrollLeft
+            | Operation::Exit
+            | Operation::LowRes
+            | Operation::HighRes
+            | Operation::SaveRange
+            | Operation::LoadRange
+            | Operation::SetIndexLong
+            | Operation::Plane
+            | Operation::Audio
+            | Operation::Pitch
+            | Operation::SaveFlags
+            | Operation::LoadFlags => self.unknown_opcode(opcode),
         }
     }
 
-    fn mode(opcode: u16) -> u8 {
-        ((opcode & 0b1111_0000_0000_0000) >> 12) as u8
+    /// X of an 8XY* instruction with the values of VX and VY
+    fn xy(&self, opcode: u16) -> (u8, u8, u8) {
+        let x = Interpreter::x(opcode);
+        let y = Interpreter::y(opcode);
+        (x, self.vx[x as usize], self.vx[y as usize])
     }
 
     fn x(opcode: u16) -> u8 {
@@ -483,9 +920,20 @@ impl Interpreter {
     }
 }
 
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_frame_eq;
+    use crate::framebuffer::{self, FrameBuffer};
+    use crate::memory::{self, AddressSpace, DisplayBuffer};
+    use crate::quirks::Profile;
+    use crate::rom_builder::*;
 
     #[test]
     fn test_set_vx() {
@@ -558,19 +1006,122 @@ mod tests {
     }
 
     #[test]
-    fn test_clear_screen() {
+    fn test_jump_with_offset_wraps() {
         let mut mem = Memory::new();
-        mem.load_prog(&[0x00, 0xE0, 0x00, 0x00]);
+        mem.load_prog(&[
+            0x60, 0x02, // set V0 to 0x02
+            0xBF, 0xFE, // jump to 0xFFE + V0
+        ]);
         let mut interpreter = Interpreter::new();
+        interpreter.step(&mut mem);
+        interpreter.step(&mut mem);
 
-        Display::write_pixel(&mut mem, 2, 3);
+        assert_eq!(0x0000, interpreter.pc);
+    }
 
-        while !interpreter.stop() {
-            interpreter.step(&mut mem);
+    #[test]
+    fn test_executes() {
+        assert!(Interpreter::executes(0x8124));
+        assert!(Interpreter::executes(0x0000)); // halts, but as the program's end
+        assert!(!Interpreter::executes(0x00FF)); // decoded, not executed
+        assert!(!Interpreter::executes(0x5121)); // not decoded
+    }
+
+    #[test]
+    fn test_clear_screen() {
+        for display_buffer in [DisplayBuffer::Ram, DisplayBuffer::External] {
+            let mut mem = Memory::new();
+            mem.set_display_buffer(display_buffer);
+            mem.load_prog(&[0x00, 0xE0, 0x00, 0x00]);
+            let mut interpreter = Interpreter::new();
+
+            Display::write_pixel(&mut mem, 2, 3);
+            Display::write_pixel(&mut mem, 63, 31); // in the last display byte
+
+            while !interpreter.stop() {
+                interpreter.step(&mut mem);
+            }
+
+            assert!(mem.display().iter().all(|&byte| byte == 0));
+            assert_eq!(0, mem.display_byte(memory::DISPLAY_SIZE - 1));
+        }
+    }
+
+    #[test]
+    fn test_address_space() {
+        let mut mem = Memory::new();
+        mem.set_address_space(AddressSpace::XoChip);
+        for (addr, byte) in [0xA0, 0x00, 0xF1, 0x65].into_iter().enumerate() {
+            mem.write(0xFFE + addr as u16, byte); // FFE: I = 0, then at 1000: load V0, V1
         }
+        mem.write(0x1234, 0x42);
+        let mut interpreter = Interpreter::new();
+        interpreter.pc = 0xFFE;
+        interpreter.step(&mut mem);
+
+        // past 0xFFF without wrapping, I reaching all the 64K
+        assert_eq!(0x1000, interpreter.pc);
+        interpreter.vi = 0x1234;
+        interpreter.step(&mut mem);
+        assert_eq!(0x1002, interpreter.pc);
+        assert_eq!([0x42, 0x00], interpreter.vx[..2]);
+
+        // the classic memory wraps at 0xFFF
+        mem.set_address_space(AddressSpace::Classic);
+        interpreter.pc = 0xFFE;
+        interpreter.step(&mut mem);
+        assert_eq!(0x000, interpreter.pc);
+
+        // a key wait at the last address of the 64K goes back across the wrap
+        mem.set_address_space(AddressSpace::XoChip);
+        mem.write(0xFFFE, 0xF0);
+        mem.write(0xFFFF, 0x0A);
+        interpreter.pc = 0xFFFE;
+        interpreter.step(&mut mem);
+        assert_eq!(0xFFFE, interpreter.pc);
+    }
+
+    #[test]
+    fn test_display_buffer() {
+        let program = [
+            ld_vx(0, 0xFF), // 200
+            ld_i(0xF00),    // 202: first byte of the display in RAM
+            store(0),       // 204: 8 pixels at the top left
+            ld_i(0x050),    // 206: font 0, top row F0
+            drw(1, 1, 1),   // 208: at 0, 0, erasing 4 of them
+            ld_vx(0, 0),    // 20A
+            ld_i(0xF00),    // 20C
+            load(0),        // 20E
+            cls(),          // 210
+        ]
+        .concat();
+
+        for (display_buffer, poked, vf, read) in [
+            (DisplayBuffer::Ram, true, 1, 0x0F),
+            (DisplayBuffer::External, false, 0, 0xFF),
+        ] {
+            let mut mem = Memory::new();
+            mem.set_display_buffer(display_buffer);
+            mem.load_fonts(&MemoryMap::default());
+            mem.load_prog(&program);
+            let mut interpreter = Interpreter::new();
+            for _ in 0..5 {
+                interpreter.step(&mut mem);
+            }
+
+            // the sprite is drawn over the pixels poked in RAM, or on a blank display
+            let mut expected = FrameBuffer::new();
+            for x in 0..8 {
+                expected.set_pixel(x, 0, (x < 4) != poked);
+            }
+            assert_frame_eq!(expected, FrameBuffer::from_memory(&mem));
+            assert_eq!(vf, interpreter.vf());
 
-        for pixel_addr in 0x00..0xFF {
-            assert_eq!(0, mem.read(memory::DISPLAY_LOC + pixel_addr));
+            for _ in 0..4 {
+                interpreter.step(&mut mem);
+            }
+            assert_eq!(read, interpreter.vx[0]);
+            assert_frame_eq!(FrameBuffer::new(), FrameBuffer::from_memory(&mem));
         }
     }
 
@@ -595,12 +1146,19 @@ mod tests {
         // this is to check that VF is equal to 1 after the display instruction
         Display::write_pixel(&mut mem, 2, 3);
 
-        mem.load_prog(&[
-            0xA2, 0x0A, 0x60, 0x01, 0x61, 0x02, 0xD0, 0x12, 0x00, 0x00, 0b11111111, 0b11110000,
-            0b11010101, 0b11111111,
-        ]);
+        let mut program = [
+            ld_i(0x20A),  // 200: sprite address
+            ld_vx(0, 1),  // 202
+            ld_vx(1, 2),  // 204
+            drw(0, 1, 2), // 206
+            halt(),       // 208
+        ]
+        .concat();
+        program.extend_from_slice(&[0b11111111, 0b11110000, 0b11010101, 0b11111111]);
+        mem.load_prog(&program);
 
         let mut interpreter = Interpreter::new();
+        interpreter.set_record_collisions(true);
 
         while !interpreter.stop() {
             interpreter.step(&mut mem);
@@ -608,32 +1166,66 @@ mod tests {
 
         // check collision has set VF
         assert_eq!(interpreter.vf(), 1);
+        assert_eq!(vec![(2, 3)], interpreter.take_collisions());
+        assert!(interpreter.take_collisions().is_empty());
+        assert!(interpreter.take_draws().is_empty());
 
-        for row in 0..32 {
-            for col in 0..64 {
-                // this is where collision happens
-                if col == 2 && row == 3 {
-                    assert_eq!(Display::read_pixel(&mem, col, row), 0);
-                    continue;
-                }
+        assert_frame_eq!(
+            framebuffer::expected_frame("display.pbm"),
+            FrameBuffer::from_memory(&mem)
+        );
 
-                // first byte of sprite
-                if col >= 1 && col <= 8 && row == 2 {
-                    assert_eq!(Display::read_pixel(&mem, col, row), 1);
-                // second byte of sprite
-                } else if col >= 1 && col <= 4 && row == 3 {
-                    assert_eq!(Display::read_pixel(&mem, col, row), 1);
-                } else {
-                    assert_eq!(Display::read_pixel(&mem, col, row), 0);
-                }
-            }
+        // not recorded unless asked, they would pile up
+        let mut interpreter = Interpreter::new();
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
         }
+        assert_eq!(interpreter.vf(), 1);
+        assert!(interpreter.take_collisions().is_empty());
+    }
+
+    #[test]
+    fn test_collision_rows() {
+        let program = [
+            ld_i(0x20E),  // 200: sprite address
+            ld_vx(0, 0),  // 202
+            ld_vx(1, 31), // 204
+            drw(0, 0, 3), // 206: three rows at the top
+            drw(0, 0, 3), // 208: erased, colliding on the rows not empty
+            drw(0, 1, 3), // 20A: two rows clipped at the bottom
+            halt(),       // 20C
+            [0xFF, 0x00], // 20E: sprite with an empty second row
+            [0xFF, 0x00], // 210
+        ]
+        .concat();
+
+        // VF after `steps` instructions
+        let run = |collision_rows: bool, steps: usize| {
+            let mut mem = Memory::new();
+            mem.load_prog(&program);
+            let mut interpreter = Interpreter::new();
+            interpreter.set_quirks(Quirks {
+                collision_rows,
+                ..Profile::Schip.quirks()
+            });
+            for _ in 0..steps {
+                interpreter.step(&mut mem);
+            }
+            interpreter.vf()
+        };
+
+        assert_eq!(2, run(true, 5));
+        assert_eq!(1, run(false, 5));
+
+        // the row drawn on screen does not collide
+        assert_eq!(2, run(true, 6));
+        assert_eq!(0, run(false, 6));
     }
 
     #[test]
     fn test_subroutines() {
         let mut mem = Memory::new();
-        mem.load_prog(&[0x00, 0xE0, 0x22, 0x06, 0x00, 0x00, 0xA0, 0xC0, 0x00, 0xEE]); // clear screen, jump to 0x0206 subroutine, set VI and return
+        mem.load_prog(&[cls(), call(0x206), halt(), ld_i(0x0C0), ret()].concat());
         let mut interpreter = Interpreter::new();
 
         while !interpreter.stop() {
@@ -864,6 +1456,86 @@ mod tests {
         assert_eq!(0xCC0, interpreter.vi);
     }
 
+    #[test]
+    fn test_skip_if_key_high_nibble() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x60, 0x1A, // set V0, key 0x0A
+            0xE0, 0x9E, // skip next if key V0 pressed
+        ]);
+        let mut interpreter = Interpreter::new();
+        interpreter.key_held[0x0A] = true;
+        interpreter.step(&mut mem);
+        interpreter.step(&mut mem);
+
+        assert_eq!(0x0206, interpreter.pc);
+    }
+
+    #[test]
+    fn test_key_events() {
+        let mut interpreter = Interpreter::new();
+        let press = KeyEvent {
+            key: 0x05,
+            down: true,
+        };
+        let release = KeyEvent {
+            key: 0x05,
+            down: false,
+        };
+
+        interpreter.push_key_event(press);
+        assert!(!interpreter.key_held[0x05]);
+        interpreter.sample_keys();
+        assert!(interpreter.key_held[0x05]);
+
+        // a tap within a frame is held for the whole frame
+        interpreter.push_key_event(release);
+        interpreter.push_key_event(press);
+        interpreter.push_key_event(release);
+        interpreter.sample_keys();
+        assert!(!interpreter.key_held[0x05]);
+        interpreter.sample_keys();
+        assert!(interpreter.key_held[0x05]);
+        interpreter.sample_keys();
+        assert!(!interpreter.key_held[0x05]);
+    }
+
+    #[test]
+    fn test_key_release_delay() {
+        let mut interpreter = Interpreter::new();
+        let press = KeyEvent {
+            key: 0x05,
+            down: true,
+        };
+        let release = KeyEvent {
+            key: 0x05,
+            down: false,
+        };
+
+        interpreter.set_key_release_delay(2);
+        interpreter.push_key_event(press);
+        interpreter.sample_keys();
+
+        interpreter.push_key_event(release);
+        interpreter.sample_keys();
+        interpreter.sample_keys();
+        assert!(interpreter.key_held[0x05]);
+
+        interpreter.sample_keys();
+        assert!(!interpreter.key_held[0x05]);
+
+        // pressing again cancels the pending release
+        interpreter.push_key_event(press);
+        interpreter.sample_keys();
+        interpreter.push_key_event(release);
+        interpreter.sample_keys();
+        interpreter.push_key_event(press);
+        interpreter.sample_keys();
+        interpreter.sample_keys();
+        interpreter.sample_keys();
+        assert!(interpreter.key_held[0x05]);
+    }
+
     #[test]
     fn test_set_timers() {
         let mut mem = Memory::new();
@@ -884,6 +1556,64 @@ mod tests {
         assert_eq!(0x0A - 1, interpreter.st);
     }
 
+    #[test]
+    fn test_min_beep() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x60, 0x01, // set V0
+            0xF0, 0x18, // sound timer = V0
+            0x00, 0x00,
+        ]);
+        let mut interpreter = Interpreter::new();
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(Some((0x202, 1)), interpreter.take_short_beep());
+        assert_eq!(None, interpreter.take_short_beep());
+        interpreter.decrement_timers();
+        assert!(interpreter.sound_on());
+        interpreter.decrement_timers();
+        assert!(!interpreter.sound_on());
+
+        interpreter.set_audio_config(AudioConfig {
+            min_beep_frames: 0,
+            ..AudioConfig::default()
+        });
+        interpreter.set_st(1);
+        assert_eq!(None, interpreter.take_short_beep());
+        interpreter.decrement_timers();
+        assert!(!interpreter.sound_on());
+    }
+
+    #[test]
+    fn test_collision_cue() {
+        let mut mem = Memory::new();
+        mem.load_fonts(&MemoryMap::default());
+        mem.load_prog(&[
+            0xF0, 0x29, // I = sprite of the digit V0
+            0xD0, 0x05, // draw it
+            0xD0, 0x05, // erase it, colliding
+            0x00, 0x00,
+        ]);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_audio_config(AudioConfig {
+            collision_cue_frames: 2,
+            ..AudioConfig::default()
+        });
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert!(interpreter.sound_on());
+        interpreter.decrement_timers();
+        assert!(interpreter.sound_on());
+        interpreter.decrement_timers();
+        assert!(!interpreter.sound_on());
+    }
+
     #[test]
     fn test_read_delay_timer() {
         let mut mem = Memory::new();
@@ -941,7 +1671,7 @@ mod tests {
         ];
 
         let mut mem = Memory::new();
-        mem.load_font(&font);
+        mem.load_font(&font, memory::FONT_LOC);
 
         mem.load_prog(&[
             0x60, 0x0A, // set V0 to 0x0A
@@ -959,6 +1689,28 @@ mod tests {
         assert_eq!(0xF0, mem.read(interpreter.vi));
     }
 
+    #[test]
+    fn test_big_font_character() {
+        let map = MemoryMap {
+            small_font: 0x000,
+            big_font: 0x060,
+        };
+        let mut mem = Memory::new();
+        mem.load_fonts(&map);
+        mem.load_prog(&[ld_vx(0, 0x0A), ld_hf(0), ld_f(0), halt()].concat());
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_memory_map(map);
+        interpreter.step(&mut mem);
+        interpreter.step(&mut mem);
+        assert_eq!(0x060 + 10 * 0x0A, interpreter.vi);
+        assert_eq!(memory::BIG_FONT[10 * 0x0A], mem.read(interpreter.vi));
+
+        interpreter.step(&mut mem);
+        assert_eq!(5 * 0x0A, interpreter.vi);
+        assert_eq!(memory::FONT[5 * 0x0A], mem.read(interpreter.vi));
+    }
+
     #[test]
     fn test_binary_coded_decimal_conversion() {
         let mut mem = Memory::new();
@@ -1025,4 +1777,170 @@ mod tests {
         assert_eq!(0x9E, interpreter.vx[2]);
         assert_eq!(0x00, interpreter.vx[3]);
     }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x22, 0x00, // call 0x200 recursively
+        ]);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_stack_depth(12);
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(12, interpreter.sc);
+        assert_eq!(
+            Some(&Error::StackOverflow {
+                pc: 0x200,
+                depth: 12
+            }),
+            interpreter.error()
+        );
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x00, 0xEE, // return without call
+        ]);
+        let mut interpreter = Interpreter::new();
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(
+            Some(&Error::StackUnderflow { pc: 0x200 }),
+            interpreter.error()
+        );
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x60, 0x01, // 200: V0 = 1
+            0x50, 0x1F, // 202: 5XY0 with N != 0
+            0x60, 0x02, // 204: never reached
+        ]);
+        let mut interpreter = Interpreter::new();
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(1, interpreter.vx[0]);
+        assert_eq!(
+            Some(&Error::UnknownOpcode {
+                pc: 0x202,
+                opcode: 0x501F
+            }),
+            interpreter.error()
+        );
+    }
+
+    #[test]
+    fn test_inspection() {
+        let mut mem = Memory::new();
+        mem.load_prog(
+            &[
+                ld_vx(3, 0x2A), // 200: V3 = 2A
+                ld_dt(3),       // 202: DT = V3
+                ld_st(3),       // 204: ST = V3
+                ld_i(0x345),    // 206: I = 345
+            ]
+            .concat(),
+        );
+        let mut interpreter = Interpreter::new();
+        interpreter.push_key_event(KeyEvent {
+            key: 0xA,
+            down: true,
+        });
+        interpreter.sample_keys();
+        for _ in 0..4 {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(0x208, interpreter.pc());
+        assert_eq!(0x2A, interpreter.registers()[3]);
+        assert_eq!(0x345, interpreter.index());
+        assert_eq!(0x2A, interpreter.delay_timer());
+        assert_eq!(0x2A, interpreter.sound_timer());
+        assert!(interpreter.keys()[0xA]);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let program = [
+            0xAF, 0xFF, // VI = 0xFFF
+            0x60, 0x2A, // V0 = 0x2A
+            0x61, 0x2B, // V1 = 0x2B
+            0xF1, 0x55, // mem write V0..(V1 + 1) at addr VI
+            0x00, 0x00,
+        ];
+
+        let mut mem = Memory::new();
+        mem.load_prog(&program);
+        let mut interpreter = Interpreter::new();
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        // wraps to 0x000 by default
+        assert_eq!(0x2B, mem.read(0x000));
+        assert_eq!(
+            Some(&HaltReason::NullOpcode { pc: 0x208 }),
+            interpreter.halt_reason()
+        );
+
+        let mut mem = Memory::new();
+        mem.load_prog(&program);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict_memory(true);
+
+        while !interpreter.stop() {
+            interpreter.step(&mut mem);
+        }
+
+        assert_eq!(0x2A, mem.read(0xFFF));
+        assert_eq!(0x00, mem.read(0x000));
+        assert_eq!(
+            Some(&Error::Memory {
+                pc: 0x206,
+                source: MemError::OutOfBounds { addr: 0x1000 }
+            }),
+            interpreter.error()
+        );
+    }
+
+    #[test]
+    fn test_interpolated_delay_timer() {
+        let mut mem = Memory::new();
+        mem.load_prog(&[
+            0x60, 0x02, // V0 = 2
+            0xF0, 0x15, // delay timer = V0
+            0xF1, 0x07, // V1 = delay timer
+            0x00, 0x00,
+        ]);
+
+        for (mode, expected) in [(TimerMode::Frame, 1), (TimerMode::Interpolated, 2)] {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_timer_mode(mode);
+
+            // set the timer late in a frame, read it early in the next one
+            interpreter.set_frame_phase(0.8);
+            interpreter.step(&mut mem);
+            interpreter.step(&mut mem);
+            interpreter.decrement_timers();
+            interpreter.set_frame_phase(0.1);
+            interpreter.step(&mut mem);
+
+            assert_eq!(expected, interpreter.vx[1], "{:?}", mode);
+        }
+    }
 }
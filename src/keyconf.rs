@@ -1,8 +1,14 @@
+use crate::interpreter::KeyEvent;
+
+use serde::{Deserialize, Serialize};
 use winit::event::VirtualKeyCode;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::Path;
 
 /* COSMAC VIP keys */
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 #[repr(usize)]
 pub enum COSMACVIP {
@@ -47,3 +53,300 @@ lazy_static! {
     };
     pub static ref COUNT: usize = KEYCONFIG.len();
 }
+
+/// Key of `KEYCONFIG` for a CHIP-8 key
+fn default_key(key: u8) -> Option<VirtualKeyCode> {
+    KEYCONFIG
+        .iter()
+        .find(|(vip_key, _)| **vip_key as u8 == key)
+        .map(|(_, virtualkeycode)| *virtualkeycode)
+}
+
+/// Physical key bound to a CHIP-8 key, by scancode to stay in place whatever the layout
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Binding {
+    pub scancode: u32,
+    pub name: String, // shown in the menu
+}
+
+/// CHIP-8 keys rebound from the menu, saved on change.
+/// The keys not rebound keep their key of `KEYCONFIG`.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Keymap {
+    bindings: BTreeMap<String, Binding>, // by CHIP-8 key digit, e.g. "A"
+}
+
+impl Keymap {
+    /// Returns None if the file is missing or invalid
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    pub fn binding(&self, key: u8) -> Option<&Binding> {
+        self.bindings.get(&format!("{:X}", key))
+    }
+
+    /// Bind `key` to the physical key of `scancode`, taken from the CHIP-8 key it was bound to
+    pub fn bind(&mut self, key: u8, scancode: u32, name: &str) {
+        self.bindings
+            .retain(|_, binding| binding.scancode != scancode);
+        self.bindings.insert(
+            format!("{:X}", key),
+            Binding {
+                scancode,
+                name: name.to_string(),
+            },
+        );
+    }
+
+    /// Name of the physical key of a CHIP-8 key
+    pub fn key_name(&self, key: u8) -> String {
+        match self.binding(key) {
+            Some(binding) => binding.name.clone(),
+            None => default_key(key)
+                .and_then(key_name)
+                .unwrap_or("?")
+                .to_string(),
+        }
+    }
+
+    /// CHIP-8 key of a physical key: the one bound to its scancode, or the one having its
+    /// `KEYCONFIG` key if not rebound
+    pub fn key(&self, scancode: u32, virtualkeycode: Option<VirtualKeyCode>) -> Option<u8> {
+        let bound = (0..16).find(|&key| {
+            self.binding(key)
+                .is_some_and(|binding| binding.scancode == scancode)
+        });
+        bound.or_else(|| {
+            let virtualkeycode = virtualkeycode?;
+            (0..16).find(|&key| {
+                self.binding(key).is_none() && default_key(key) == Some(virtualkeycode)
+            })
+        })
+    }
+}
+
+/// Turns the keyboard events of the window into CHIP-8 key presses and releases,
+/// ignoring the repeats of held keys
+pub struct KeyReader {
+    keymap: Keymap,
+    down: [bool; 16],
+    events: Vec<KeyEvent>, // since the last `take_events`
+}
+
+impl KeyReader {
+    pub fn new(keymap: Keymap) -> Self {
+        KeyReader {
+            keymap,
+            down: [false; 16],
+            events: Vec::new(),
+        }
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// Use another keymap, releasing the keys held as their physical keys may change
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        for key in 0..16 {
+            self.release(key);
+        }
+        self.keymap = keymap;
+    }
+
+    /// Forget the keys held, released while the window was in the background
+    pub fn reset(&mut self) {
+        self.down = [false; 16];
+    }
+
+    /// A physical key pressed or released
+    pub fn handle(&mut self, scancode: u32, virtualkeycode: Option<VirtualKeyCode>, pressed: bool) {
+        let Some(key) = self.keymap.key(scancode, virtualkeycode) else {
+            return;
+        };
+
+        match pressed {
+            true if !self.down[key as usize] => {
+                self.down[key as usize] = true;
+                self.events.push(KeyEvent { key, down: true });
+            }
+            true => (),
+            false => self.release(key),
+        }
+    }
+
+    /// CHIP-8 key presses and releases since the last call
+    pub fn take_events(&mut self) -> Vec<KeyEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn release(&mut self, key: u8) {
+        if std::mem::take(&mut self.down[key as usize]) {
+            self.events.push(KeyEvent { key, down: false });
+        }
+    }
+}
+
+/// Names accepted for keys given on the command line
+const KEY_NAMES: [(&str, VirtualKeyCode); 64] = [
+    ("a", VirtualKeyCode::A),
+    ("b", VirtualKeyCode::B),
+    ("c", VirtualKeyCode::C),
+    ("d", VirtualKeyCode::D),
+    ("e", VirtualKeyCode::E),
+    ("f", VirtualKeyCode::F),
+    ("g", VirtualKeyCode::G),
+    ("h", VirtualKeyCode::H),
+    ("i", VirtualKeyCode::I),
+    ("j", VirtualKeyCode::J),
+    ("k", VirtualKeyCode::K),
+    ("l", VirtualKeyCode::L),
+    ("m", VirtualKeyCode::M),
+    ("n", VirtualKeyCode::N),
+    ("o", VirtualKeyCode::O),
+    ("p", VirtualKeyCode::P),
+    ("q", VirtualKeyCode::Q),
+    ("r", VirtualKeyCode::R),
+    ("s", VirtualKeyCode::S),
+    ("t", VirtualKeyCode::T),
+    ("u", VirtualKeyCode::U),
+    ("v", VirtualKeyCode::V),
+    ("w", VirtualKeyCode::W),
+    ("x", VirtualKeyCode::X),
+    ("y", VirtualKeyCode::Y),
+    ("z", VirtualKeyCode::Z),
+    ("0", VirtualKeyCode::Key0),
+    ("1", VirtualKeyCode::Key1),
+    ("2", VirtualKeyCode::Key2),
+    ("3", VirtualKeyCode::Key3),
+    ("4", VirtualKeyCode::Key4),
+    ("5", VirtualKeyCode::Key5),
+    ("6", VirtualKeyCode::Key6),
+    ("7", VirtualKeyCode::Key7),
+    ("8", VirtualKeyCode::Key8),
+    ("9", VirtualKeyCode::Key9),
+    ("f1", VirtualKeyCode::F1),
+    ("f2", VirtualKeyCode::F2),
+    ("f3", VirtualKeyCode::F3),
+    ("f4", VirtualKeyCode::F4),
+    ("f5", VirtualKeyCode::F5),
+    ("f6", VirtualKeyCode::F6),
+    ("f7", VirtualKeyCode::F7),
+    ("f8", VirtualKeyCode::F8),
+    ("f9", VirtualKeyCode::F9),
+    ("f10", VirtualKeyCode::F10),
+    ("f11", VirtualKeyCode::F11),
+    ("f12", VirtualKeyCode::F12),
+    ("escape", VirtualKeyCode::Escape),
+    ("space", VirtualKeyCode::Space),
+    ("return", VirtualKeyCode::Return),
+    ("tab", VirtualKeyCode::Tab),
+    ("backspace", VirtualKeyCode::Back),
+    ("insert", VirtualKeyCode::Insert),
+    ("delete", VirtualKeyCode::Delete),
+    ("home", VirtualKeyCode::Home),
+    ("end", VirtualKeyCode::End),
+    ("pageup", VirtualKeyCode::PageUp),
+    ("pagedown", VirtualKeyCode::PageDown),
+    ("pause", VirtualKeyCode::Pause),
+    ("up", VirtualKeyCode::Up),
+    ("down", VirtualKeyCode::Down),
+    ("left", VirtualKeyCode::Left),
+    ("right", VirtualKeyCode::Right),
+];
+
+/// Parse a key name such as "escape", "f10" or "q" (case insensitive)
+pub fn parse_key(name: &str) -> Result<VirtualKeyCode, String> {
+    let name = name.to_ascii_lowercase();
+
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == name)
+        .map(|(_, key)| *key)
+        .ok_or_else(|| format!("unknown key name: {}", name))
+}
+
+/// Name of a key accepted by `parse_key`
+pub fn key_name(key: VirtualKeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, named_key)| *named_key == key)
+        .map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(Ok(VirtualKeyCode::Escape), parse_key("Escape"));
+        assert_eq!(Ok(VirtualKeyCode::F10), parse_key("f10"));
+        assert_eq!(Ok(VirtualKeyCode::Key7), parse_key("7"));
+        assert!(parse_key("hyper").is_err());
+        assert_eq!(Some("q"), key_name(VirtualKeyCode::Q));
+    }
+
+    #[test]
+    fn test_keymap() {
+        let mut keymap = Keymap::default();
+        assert_eq!(Some(0x5), keymap.key(17, Some(VirtualKeyCode::W)));
+        assert_eq!("x", keymap.key_name(0x0));
+
+        // W bound by scancode to 0, its default key 5 no longer reacts to it
+        keymap.bind(0x0, 17, "w");
+        assert_eq!(Some(0x0), keymap.key(17, Some(VirtualKeyCode::W)));
+        assert_eq!(Some(0x0), keymap.key(17, Some(VirtualKeyCode::Z)));
+        assert_eq!(None, keymap.key(45, Some(VirtualKeyCode::X)));
+        assert_eq!(Some(0x5), keymap.key(99, Some(VirtualKeyCode::W)));
+
+        // a physical key moves from one CHIP-8 key to the other
+        keymap.bind(0xA, 17, "w");
+        assert_eq!(None, keymap.binding(0x0));
+        assert_eq!("w", keymap.key_name(0xA));
+
+        let text = toml::to_string(&keymap).unwrap();
+        assert_eq!(keymap, toml::from_str(&text).unwrap());
+    }
+
+    #[test]
+    fn test_key_reader() {
+        let mut reader = KeyReader::new(Keymap::default());
+        reader.handle(17, Some(VirtualKeyCode::W), true);
+        reader.handle(17, Some(VirtualKeyCode::W), true); // repeated while held
+        reader.handle(1, Some(VirtualKeyCode::Escape), true);
+        assert_eq!(vec![KeyEvent { key: 5, down: true }], reader.take_events());
+
+        let mut keymap = Keymap::default();
+        keymap.bind(0x0, 17, "w");
+        reader.set_keymap(keymap);
+        reader.handle(17, Some(VirtualKeyCode::W), true);
+        reader.handle(17, Some(VirtualKeyCode::W), false);
+        assert_eq!(
+            vec![
+                KeyEvent {
+                    key: 5,
+                    down: false
+                },
+                KeyEvent { key: 0, down: true },
+                KeyEvent {
+                    key: 0,
+                    down: false
+                },
+            ],
+            reader.take_events()
+        );
+    }
+}
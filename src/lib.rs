@@ -0,0 +1,68 @@
+// compiled blocks of the JIT executor are called through a function pointer
+#![cfg_attr(not(feature = "jit"), forbid(unsafe_code))]
+#![cfg_attr(feature = "jit", deny(unsafe_code))]
+
+pub mod audio;
+pub mod avsync;
+pub mod batch;
+pub mod bench;
+pub mod bus;
+pub mod capabilities;
+pub mod chip8;
+pub mod container;
+pub mod debugger;
+pub mod demos;
+pub mod disassembler;
+pub mod display;
+pub mod display_settings;
+pub mod draws;
+pub mod dual;
+pub mod error;
+pub mod executor;
+pub mod filenames;
+pub mod filmstrip;
+pub mod flash;
+pub mod font;
+pub mod framebuffer;
+pub mod geometry;
+pub mod heatmap;
+pub mod info;
+pub mod interpreter;
+pub mod keyconf;
+pub mod library;
+pub mod lint;
+pub mod memory;
+pub mod menu;
+pub mod metrics;
+pub mod movie;
+pub mod opcodes;
+pub mod palette;
+pub mod paste;
+pub mod patch;
+pub mod paths;
+pub mod pc_history;
+pub mod playtime;
+pub mod plugin;
+pub mod quirks;
+pub mod quit;
+pub mod rawvideo;
+pub mod report;
+pub mod rom_builder;
+pub mod runtime;
+pub mod sandbox;
+pub mod savestate;
+pub mod scaler;
+pub mod shmem;
+pub mod snapshot;
+pub mod splash;
+pub mod teach;
+pub mod text;
+pub mod tiles;
+pub mod timing;
+pub mod toast;
+pub mod verify;
+pub mod watch;
+pub mod window_title;
+
+#[macro_use]
+extern crate lazy_static;
@@ -0,0 +1,201 @@
+use crate::container;
+use crate::info;
+use crate::playtime::{self, PlayStats};
+use crate::quirks::Profile;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A program of the library, with the settings it is launched with
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub sha1: String, // of the program when added
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub profile: Option<Profile>,
+    pub cycles_per_frame: Option<u32>,
+}
+
+impl Entry {
+    /// Read the program at `path`, its title, author and settings coming from its .c8c
+    /// container unless given
+    pub fn from_file(
+        path: &Path,
+        profile: Option<Profile>,
+        cycles_per_frame: Option<u32>,
+    ) -> io::Result<Self> {
+        let (program, container) = container::read(path)?;
+        let container = container.unwrap_or_default();
+
+        Ok(Entry {
+            path: std::fs::canonicalize(path)?,
+            sha1: info::sha1_hex(&program),
+            title: container.title,
+            author: container.author,
+            profile: profile.or(container.profile),
+            cycles_per_frame: cycles_per_frame.or(container.cycles_per_frame),
+        })
+    }
+}
+
+/// Catalog of programs launched by name, kept in the data directory
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(default)]
+    pub roms: BTreeMap<String, Entry>,
+}
+
+impl Library {
+    /// An empty library if the file is missing, an error if it is invalid so it is not
+    /// overwritten
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Library::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// Entry named `name` ignoring case, or else the only one whose name starts with it
+    pub fn find(&self, name: &str) -> Result<(&str, &Entry), String> {
+        let name = entry_name(name);
+        if let Some((name, entry)) = self.roms.get_key_value(&name) {
+            return Ok((name, entry));
+        }
+
+        let matches: Vec<(&String, &Entry)> = self
+            .roms
+            .iter()
+            .filter(|(other, _)| other.starts_with(&name))
+            .collect();
+        match matches[..] {
+            [(name, entry)] => Ok((name, entry)),
+            [] => Err(format!("no program named {} in the library", name)),
+            _ => {
+                let names: Vec<&str> = matches.iter().map(|(name, _)| name.as_str()).collect();
+                Err(format!("{} matches {}", name, names.join(", ")))
+            }
+        }
+    }
+
+    /// Name, title, settings and play time of each program, and whether its file is missing
+    pub fn list(&self, stats: &PlayStats) -> String {
+        if self.roms.is_empty() {
+            return "the library is empty, add programs with `library add`\n".to_string();
+        }
+
+        let mut out = String::new();
+        for (name, entry) in &self.roms {
+            write!(out, "{}", name).unwrap();
+            if let Some(title) = &entry.title {
+                write!(out, " \"{}\"", title).unwrap();
+            }
+            if let Some(author) = &entry.author {
+                write!(out, " by {}", author).unwrap();
+            }
+            if let Some(profile) = entry.profile {
+                write!(out, ", {}", profile.name()).unwrap();
+            }
+            if let Some(cycles) = entry.cycles_per_frame {
+                write!(out, ", {} cycles per frame", cycles).unwrap();
+            }
+            if let Some(rom_stats) = stats.get(&entry.sha1) {
+                write!(
+                    out,
+                    ", played {}",
+                    playtime::format_duration(rom_stats.seconds)
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+
+            let missing = match entry.path.exists() {
+                true => "",
+                false => " (missing)",
+            };
+            writeln!(out, "    {}{}", entry.path.display(), missing).unwrap();
+        }
+        out
+    }
+}
+
+/// Name as stored in the library: lowercase, spaces replaced with dashes
+pub fn entry_name(name: &str) -> String {
+    name.to_lowercase().replace(' ', "-")
+}
+
+/// Name of a program in the library by default, from its file name without extension
+pub fn default_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| entry_name(&stem.to_string_lossy()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(path),
+            sha1: "da39a3ee".to_string(),
+            title: Some("Pong".to_string()),
+            author: None,
+            profile: Some(Profile::Vip),
+            cycles_per_frame: None,
+        }
+    }
+
+    #[test]
+    fn test_library() {
+        let mut library = Library::default();
+        library
+            .roms
+            .insert("pong".to_string(), entry("/roms/PONG.ch8"));
+        library
+            .roms
+            .insert("pong2".to_string(), entry("/roms/PONG2.ch8"));
+        library.roms.insert(
+            "space-invaders".to_string(),
+            entry("/roms/Space Invaders.ch8"),
+        );
+
+        assert_eq!("pong", library.find("PONG").unwrap().0);
+        assert_eq!("space-invaders", library.find("space").unwrap().0);
+        assert_eq!(
+            Err("po matches pong, pong2".to_string()),
+            library.find("po").map(|(name, _)| name)
+        );
+        assert!(library.find("tetris").is_err());
+
+        let mut stats = PlayStats::default();
+        stats.record_session("da39a3ee", "PONG.ch8", 90, 0);
+        assert!(library
+            .list(&stats)
+            .starts_with("pong \"Pong\", vip, played 0h 01m 30s\n    /roms/PONG.ch8 (missing)\n"));
+
+        let text = toml::to_string(&library).unwrap();
+        assert_eq!(
+            Ok(library),
+            toml::from_str(&text).map_err(|e| e.to_string())
+        );
+        assert_eq!(
+            "space-invaders",
+            default_name(Path::new("/roms/Space Invaders.ch8"))
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::disassembler::{self, ControlFlowGraph, Flow};
+use crate::memory;
+use crate::opcodes::Extension;
+use crate::quirks::Profile;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+/// Suspicious pattern found in a program without running it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Finding {
+    /// The program has an odd number of bytes, its last instruction is cut
+    OddLength { len: usize },
+    /// No path from the subroutine reaches a return (00EE)
+    CallWithoutReturn { pc: u16, target: u16 },
+    /// A jump or call lands inside the instruction at `inside`
+    MisalignedTarget { pc: u16, target: u16, inside: u16 },
+    /// FX55 or FX33 with I set below the program, over the fonts. `vi` is None for an
+    /// address set by FX29 or FX30.
+    FontWrite {
+        pc: u16,
+        opcode: u16,
+        vi: Option<u16>,
+    },
+    /// An instruction defined by an extension the profile does not enable
+    DisabledExtension {
+        pc: u16,
+        opcode: u16,
+        extension: Extension,
+    },
+}
+
+impl Finding {
+    /// Address of the instruction at fault, None for the whole program
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            Finding::OddLength { .. } => None,
+            Finding::CallWithoutReturn { pc, .. }
+            | Finding::MisalignedTarget { pc, .. }
+            | Finding::FontWrite { pc, .. }
+            | Finding::DisabledExtension { pc, .. } => Some(*pc),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::OddLength { len } => {
+                write!(
+                    f,
+                    "program: odd length ({} bytes), the last byte is cut",
+                    len
+                )
+            }
+            Finding::CallWithoutReturn { pc, target } => write!(
+                f,
+                "{:03X}: call to {:03X}, which never returns (no 00EE reached)",
+                pc, target
+            ),
+            Finding::MisalignedTarget { pc, target, inside } => write!(
+                f,
+                "{:03X}: jump to {:03X}, in the middle of the instruction at {:03X}",
+                pc, target, inside
+            ),
+            Finding::FontWrite { pc, opcode, vi } => {
+                let index = match vi {
+                    Some(vi) => format!("I={:03X}", vi),
+                    None => "I set by FX29/FX30".to_string(),
+                };
+                write!(
+                    f,
+                    "{:03X}: {:04X} writes below {:03X} ({}), over the fonts",
+                    pc,
+                    opcode,
+                    memory::PROG_LOC,
+                    index
+                )
+            }
+            Finding::DisabledExtension {
+                pc,
+                opcode,
+                extension,
+            } => write!(
+                f,
+                "{:03X}: {:04X} is a {} instruction, not enabled by the profile",
+                pc,
+                opcode,
+                extension.name()
+            ),
+        }
+    }
+}
+
+/// Profile a program is linted against by default, from its file extension
+pub fn default_profile(path: &Path) -> Profile {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("sc8") => Profile::Schip,
+        Some("xo8") => Profile::XoChip,
+        _ => Profile::Modern,
+    }
+}
+
+/// Extensions whose instructions run as intended under `profile`
+fn enabled(profile: Profile, extension: Extension) -> bool {
+    match profile {
+        Profile::Modern | Profile::Vip => extension == Extension::Chip8,
+        Profile::Schip => extension != Extension::XoChip,
+        Profile::XoChip => true,
+    }
+}
+
+/// Flag the suspicious patterns of the reachable code of a program, ordered by address
+pub fn lint(program: &[u8], profile: Profile) -> Vec<Finding> {
+    let cfg = disassembler::analyze(program);
+    let mut findings = Vec::new();
+
+    if program.len() % 2 == 1 {
+        findings.push(Finding::OddLength { len: program.len() });
+    }
+
+    let mut code = Vec::new();
+    let mut checked_calls = BTreeSet::new();
+    for (pc, instruction) in cfg.iter() {
+        let target = match instruction.flow() {
+            Flow::Jump(target) | Flow::Call(target) => Some(target),
+            _ => None,
+        };
+        if let Some(target) = target {
+            if let Some(inside) = instruction_around(&cfg, target) {
+                code.push(Finding::MisalignedTarget { pc, target, inside });
+            }
+        }
+        if let Flow::Call(target) = instruction.flow() {
+            if checked_calls.insert(target) && !returns(&cfg, target) {
+                code.push(Finding::CallWithoutReturn { pc, target });
+            }
+        }
+        if !enabled(profile, instruction.extension) {
+            code.push(Finding::DisabledExtension {
+                pc,
+                opcode: instruction.opcode,
+                extension: instruction.extension,
+            });
+        }
+    }
+    code.extend(font_writes(&cfg));
+
+    code.sort_by_key(Finding::pc);
+    findings.extend(code);
+    findings
+}
+
+/// Address of the reachable instruction `target` falls inside of, not at its start
+fn instruction_around(cfg: &ControlFlowGraph, target: u16) -> Option<u16> {
+    let (&addr, instruction) = cfg.instructions.range(..target).next_back()?;
+    (target < addr + instruction.size()).then_some(addr)
+}
+
+/// Whether a path from the subroutine at `entry` reaches a return. Subroutines left
+/// through a computed jump or not decoded are given the benefit of the doubt.
+fn returns(cfg: &ControlFlowGraph, entry: u16) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut to_visit = vec![entry];
+
+    while let Some(start) = to_visit.pop() {
+        if !visited.insert(start) {
+            continue;
+        }
+        let Some(block) = cfg.blocks.get(&start) else {
+            return true;
+        };
+        let last = cfg.instructions.range(..block.end).next_back().unwrap().1;
+        if matches!(last.flow(), Flow::Return | Flow::Indirect(_)) {
+            return true;
+        }
+        to_visit.extend(&block.successors);
+    }
+    false
+}
+
+/// FX55 and FX33 run with I below the program, following I through each basic block
+fn font_writes(cfg: &ControlFlowGraph) -> Vec<Finding> {
+    #[derive(Clone, Copy)]
+    enum Index {
+        Unknown,
+        Addr(u16),
+        Glyph, // set by FX29 or FX30
+    }
+
+    let mut findings = Vec::new();
+    for block in cfg.blocks.values() {
+        let mut vi = Index::Unknown;
+        for (&pc, instruction) in cfg.instructions.range(block.start..block.end) {
+            let opcode = instruction.opcode;
+            match instruction.pattern {
+                "ANNN" => vi = Index::Addr(opcode & 0x0FFF),
+                "FX29" | "FX30" => vi = Index::Glyph,
+                "FX55" | "FX33" => {
+                    let vi = match vi {
+                        Index::Addr(addr) if addr < memory::PROG_LOC => Some(Some(addr)),
+                        Index::Glyph => Some(None),
+                        _ => None,
+                    };
+                    if let Some(vi) = vi {
+                        findings.push(Finding::FontWrite { pc, opcode, vi });
+                    }
+                }
+                _ => (),
+            }
+            // FX55 and FX65 may move I depending on the quirks
+            if matches!(instruction.pattern, "FX1E" | "FX55" | "FX65" | "F000") {
+                vi = Index::Unknown;
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_lint() {
+        let program = [
+            &call(0x20E)[..], // 200: never returns
+            &call(0x210),     // 202: returns
+            &ld_i(0x050),     // 204
+            &store(2),        // 206: over the font of 0
+            &scr(),           // 208: SUPER-CHIP
+            &sne_vx(0, 0),    // 20A
+            &jp(0x215),       // 20C: inside the instruction at 214
+            &jp(0x20E),       // 20E: subroutine looping forever
+            &ld_f(0),         // 210
+            &ld_b(0),         // 212: over the font too
+            &ret(),           // 214
+            &[0x00],          // 216: odd length
+        ]
+        .concat();
+
+        let findings: Vec<String> = lint(&program, Profile::Modern)
+            .iter()
+            .map(Finding::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "program: odd length (23 bytes), the last byte is cut",
+                "200: call to 20E, which never returns (no 00EE reached)",
+                "206: F255 writes below 200 (I=050), over the fonts",
+                "208: 00FB is a SUPER-CHIP instruction, not enabled by the profile",
+                "20C: jump to 215, in the middle of the instruction at 214",
+                "212: F033 writes below 200 (I set by FX29/FX30), over the fonts",
+            ],
+            findings
+        );
+
+        // the SUPER-CHIP profile enables the scroll
+        assert_eq!(5, lint(&program, Profile::Schip).len());
+        assert_eq!(
+            Profile::Schip,
+            default_profile(Path::new("roms/Blinky.SC8"))
+        );
+    }
+}
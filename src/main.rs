@@ -1,106 +1,1804 @@
-#[forbid(unsafe_code)]
-mod display;
-mod interpreter;
-mod keyconf;
-mod memory;
+#![forbid(unsafe_code)]
 
-use display::Display;
-use interpreter::Interpreter;
-use memory::Memory;
+mod cli;
 
-use winit::event::{Event, VirtualKeyCode, WindowEvent};
+use chip8_interpreter::audio::{AudioConfig, Buzzer};
+use chip8_interpreter::avsync;
+use chip8_interpreter::batch::{self, Status};
+use chip8_interpreter::bench;
+use chip8_interpreter::capabilities::Capabilities;
+use chip8_interpreter::chip8::{Chip8, DisplayCapture};
+use chip8_interpreter::container;
+use chip8_interpreter::debugger::{self, Command as DebugCommand, Debugger, FrameStepper};
+use chip8_interpreter::demos;
+use chip8_interpreter::disassembler::{self, DataStyle};
+use chip8_interpreter::display::Display;
+use chip8_interpreter::display_settings::{DisplaySettings, FlickerMode};
+use chip8_interpreter::draws::DrawTracker;
+use chip8_interpreter::dual::DivergenceDetector;
+use chip8_interpreter::error::HaltReason;
+use chip8_interpreter::executor::ExecutorKind;
+use chip8_interpreter::filenames::{FileNames, TemplateVars};
+use chip8_interpreter::filmstrip;
+use chip8_interpreter::flash::FlashLimiter;
+use chip8_interpreter::font;
+use chip8_interpreter::framebuffer::{FrameBuffer, FrameHistory};
+use chip8_interpreter::geometry::WindowGeometry;
+use chip8_interpreter::heatmap::Heatmap;
+use chip8_interpreter::info;
+use chip8_interpreter::interpreter::KeyEvent;
+use chip8_interpreter::keyconf::{key_name, KeyReader, Keymap};
+use chip8_interpreter::library::{self, Entry, Library};
+use chip8_interpreter::lint;
+use chip8_interpreter::memory::{self, AddressSpace, MemoryMap};
+use chip8_interpreter::menu::{Menu, MenuAction, MenuKey};
+use chip8_interpreter::movie::{Movie, MoviePlayer};
+use chip8_interpreter::opcodes;
+use chip8_interpreter::palette::{Palette, PRESETS};
+use chip8_interpreter::paste;
+use chip8_interpreter::patch;
+use chip8_interpreter::paths::Paths;
+use chip8_interpreter::playtime::PlayStats;
+use chip8_interpreter::plugin::audit::ArithmeticAudit;
+use chip8_interpreter::plugin::cheat::CheatEngine;
+use chip8_interpreter::plugin::coverage::CoverageTracker;
+use chip8_interpreter::plugin::crash::{self, CrashRecorder};
+use chip8_interpreter::plugin::keys::KeyUsageTracker;
+use chip8_interpreter::plugin::movie::MovieRecorder;
+use chip8_interpreter::plugin::perf::PerfRecorder;
+use chip8_interpreter::plugin::script::ScriptRunner;
+use chip8_interpreter::plugin::speedrun::SpeedrunTimer;
+use chip8_interpreter::plugin::strict::StrictChecker;
+use chip8_interpreter::plugin::trace::TraceLogger;
+use chip8_interpreter::plugin::uninit::UninitializedReads;
+use chip8_interpreter::quirks::{self, Profile, Settings};
+use chip8_interpreter::quit::QuitGuard;
+use chip8_interpreter::rawvideo::FrameWriter;
+use chip8_interpreter::report;
+use chip8_interpreter::sandbox::{self, Input as SandboxInput, Sandbox};
+use chip8_interpreter::savestate::{SaveState, StateDiff};
+use chip8_interpreter::shmem::{self, FrameExport};
+use chip8_interpreter::splash::Splash;
+use chip8_interpreter::teach::{self, Teacher};
+use chip8_interpreter::tiles::Layout;
+use chip8_interpreter::timing::{
+    FrameClock, FrameSkipper, InputLatency, Jitter, RenderPacer, RenderRate, Turbo,
+};
+use chip8_interpreter::toast::Toasts;
+use chip8_interpreter::verify;
+use chip8_interpreter::watch::FileWatcher;
+use chip8_interpreter::window_title::{TitleVars, WindowTitle};
+
+use cli::{Cli, Command, GenCommand, LibraryCommand};
+
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, ValueEnum};
+
+use winit::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit_input_helper::WinitInputHelper;
 
-use std::time::Instant;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-#[macro_use]
-extern crate lazy_static;
+const MENU_KEYS: [(VirtualKeyCode, MenuKey); 6] = [
+    (VirtualKeyCode::Up, MenuKey::Up),
+    (VirtualKeyCode::Down, MenuKey::Down),
+    (VirtualKeyCode::Left, MenuKey::Left),
+    (VirtualKeyCode::Right, MenuKey::Right),
+    (VirtualKeyCode::Return, MenuKey::Select),
+    (VirtualKeyCode::Escape, MenuKey::Back),
+];
 
 fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // settings given on the command line win over the ones of a .c8c container
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    match &cli.command {
+        Some(Command::Verify) => {
+            verify::run();
+            return;
+        }
+        Some(Command::Info {
+            rom,
+            stats,
+            portable,
+            dynamic,
+            frames,
+        }) => {
+            let play_stats = PlayStats::load(&Paths::resolve(*portable).play_stats());
+
+            match rom {
+                Some(rom) => {
+                    let (program, container) =
+                        container::read(rom).expect("Could not read ROM file");
+                    if let Some(container) = &container {
+                        print!("{}", container.report());
+                    }
+                    print!("{}", info::report(&program));
+                    if *dynamic {
+                        print!("{}", info::dynamic_report(&program, *frames));
+                    }
+
+                    if *stats {
+                        match play_stats.get(&info::sha1_hex(&program)) {
+                            Some(rom_stats) => print!("{}", rom_stats.report()),
+                            None => println!("never played"),
+                        }
+                    }
+                }
+                None => {
+                    for (hash, rom_stats) in &play_stats.roms {
+                        println!("{} {}", &hash[..8], rom_stats.name);
+                        print!("{}", rom_stats.report());
+                    }
+                }
+            }
+            return;
+        }
+        Some(Command::Lint { rom, profile }) => {
+            let (program, container) = container::read(rom).expect("Could not read ROM file");
+            let profile = profile
+                .or(container.and_then(|container| container.profile))
+                .unwrap_or_else(|| lint::default_profile(rom));
+            let findings = lint::lint(&program, profile);
+
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            if findings.is_empty() {
+                println!("no issues found with the {} profile", profile.name());
+                return;
+            }
+            std::process::exit(1);
+        }
+        Some(Command::Cfg { rom, output }) => {
+            let program = container::read_program(rom).expect("Could not read ROM file");
+            let dot = disassembler::analyze(&program).to_dot();
+
+            match output {
+                Some(path) => {
+                    if let Err(e) = std::fs::write(path, dot) {
+                        eprintln!("Could not write {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{}", dot),
+            }
+            return;
+        }
+        Some(Command::Disasm {
+            rom,
+            start,
+            end,
+            data,
+            sprites_as_art,
+        }) => {
+            let program = container::read_program(rom).expect("Could not read ROM file");
+            // addresses beyond 0xFFF only in the 64K space of XO-CHIP programs
+            let space = AddressSpace::for_program(program.len());
+            let outside = [*start, *end]
+                .into_iter()
+                .flatten()
+                .chain(data.iter().map(|region| *region.end()))
+                .find(|&addr| addr as usize >= space.size());
+            if let Some(addr) = outside {
+                eprintln!(
+                    "{:03X} is beyond the {} bytes of memory of the program",
+                    addr,
+                    space.size()
+                );
+                std::process::exit(1);
+            }
+            let start = start.unwrap_or(memory::PROG_LOC);
+            // up to the end of the program by default
+            let end = end.unwrap_or(u16::MAX);
+            let style = match sprites_as_art {
+                true => DataStyle::Sprites,
+                false => DataStyle::Bytes,
+            };
+            print!(
+                "{}",
+                disassembler::listing(&program, start..=end, data, style)
+            );
+            return;
+        }
+        Some(Command::ExportFont { format, output }) => {
+            let map = MemoryMap::default();
+            let data = match format {
+                font::Format::Asm => font::asm(&map).into_bytes(),
+                font::Format::Bin => font::bin(&map),
+            };
+
+            let result = match output {
+                Some(path) => std::fs::write(path, data),
+                None => std::io::stdout().write_all(&data),
+            };
+            if let Err(e) = result {
+                eprintln!("Could not write the font: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Gen {
+            program: GenCommand::Demo { kind, output },
+        }) => {
+            let path = output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{}.ch8", kind.name())));
+            match std::fs::write(&path, demos::generate(*kind)) {
+                Ok(()) => println!("Wrote {}", path.display()),
+                Err(e) => {
+                    eprintln!("Could not write {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Bench {
+            rom,
+            executor,
+            profile,
+            frames,
+            cycles_per_frame,
+        }) => {
+            let program = container::read_program(rom).expect("Could not read ROM file");
+            let executors = match executor {
+                Some(executor) => vec![*executor],
+                None => ExecutorKind::value_variants().to_vec(),
+            };
+
+            let mut baseline = None;
+            for executor in executors {
+                let result = bench::run(&program, *profile, executor, *frames, *cycles_per_frame);
+                let speedup = result.mips() / *baseline.get_or_insert(result.mips());
+                println!(
+                    "{:<12} {} frames in {:.3} s, {:.1} MIPS ({:.2}x)",
+                    executor.to_possible_value().unwrap().get_name(),
+                    result.frames,
+                    result.elapsed.as_secs_f64(),
+                    result.mips(),
+                    speedup
+                );
+            }
+            return;
+        }
+        Some(Command::AvSync {
+            rom,
+            profile,
+            frames,
+            cycles_per_frame,
+        }) => {
+            let program = match rom {
+                Some(rom) => container::read_program(rom).expect("Could not read ROM file"),
+                None => avsync::SYNC_ROM.to_vec(),
+            };
+            let monitor = avsync::run(&program, *profile, *cycles_per_frame, *frames);
+
+            let offsets = monitor.offsets();
+            for (onset, offset) in &offsets {
+                match offset {
+                    Some(offset) => println!("beep at frame {}, draw {:+} frames", onset, offset),
+                    None => println!("beep at frame {}, no draw", onset),
+                }
+            }
+            if offsets.is_empty() {
+                println!("no beep in {} frames", frames);
+                std::process::exit(1);
+            } else if monitor.in_sync() {
+                println!("{} beeps in sync with their draws", offsets.len());
+            } else {
+                println!(
+                    "{} beeps, some more than {} frame away from a draw",
+                    offsets.len(),
+                    avsync::MAX_OFFSET
+                );
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Debug {
+            rom,
+            profile,
+            cycles_per_frame,
+        }) => {
+            let program = container::read_program(rom).expect("Could not read ROM file");
+            let mut chip8 = Chip8::new();
+            apply_profile(&mut chip8, *profile, &cli);
+            chip8.load(&program);
+
+            let mut debugger = Debugger::new(chip8, *cycles_per_frame);
+            println!("{}", debugger.registers());
+
+            // an empty line repeats the last command
+            let mut last = None;
+            loop {
+                print!("(chip8) ");
+                std::io::stdout().flush().unwrap();
+
+                let mut line = String::new();
+                if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                let command = match (line.trim(), last) {
+                    ("", Some(command)) => command,
+                    (line, _) => match line.parse() {
+                        Ok(command) => command,
+                        Err(e) => {
+                            println!("{}", e);
+                            continue;
+                        }
+                    },
+                };
+                if command == DebugCommand::Quit {
+                    break;
+                }
+
+                println!("{}", debugger.execute(command));
+                last = Some(command);
+            }
+            return;
+        }
+        Some(Command::Sandbox { profile }) => run_sandbox(&cli, *profile),
+        Some(Command::Run {
+            rom,
+            timeout_cycles,
+            profile,
+        }) => {
+            let (program, container) = container::read(rom).expect("Could not read ROM file");
+            let profile = profile
+                .or(container.and_then(|container| container.profile))
+                .unwrap_or_else(|| info::suggested_profile(&program));
+            let (status, instructions, chip8) =
+                batch::run_machine(&program, profile, *timeout_cycles);
+
+            match &status {
+                Status::Running => println!("timeout after {} instructions", instructions),
+                Status::Exited => println!("halted"),
+                Status::UnknownOpcode { pc, opcode } => {
+                    println!("unknown opcode {:04X} at {:03X}", opcode, pc)
+                }
+                Status::Crashed(error) => println!("crashed: {}", error),
+            }
+            if !status.is_ok() {
+                print!("last instructions:\n{}", chip8.interpreter.pc_history());
+            }
+            std::process::exit(status.exit_code());
+        }
+        Some(Command::Batch {
+            dir,
+            cycles,
+            profile,
+            screenshots,
+            report,
+        }) => {
+            if let Some(path) = report {
+                if report::Format::from_path(path).is_none() {
+                    eprintln!("{} must be a .md or .html file", path.display());
+                    std::process::exit(1);
+                }
+            }
+            let results =
+                batch::run_dir(dir, *profile, *cycles).expect("Could not read ROM directory");
+            let palette = Palette::new();
+
+            for result in &results {
+                let name = result.path.file_name().unwrap().to_string_lossy();
+                let details = match &result.status {
+                    Status::UnknownOpcode { pc, opcode } => {
+                        format!(" {:04X} at {:03X}", opcode, pc)
+                    }
+                    Status::Crashed(error) => format!(" {}", error),
+                    _ => String::new(),
+                };
+                println!(
+                    "{:<14} {:<8} {}{}",
+                    result.status.name(),
+                    result.profile.name(),
+                    name,
+                    details
+                );
+
+                if let Some(dir) = screenshots {
+                    let path = dir.join(Path::new(name.as_ref()).with_extension("png"));
+                    let saved = std::fs::create_dir_all(dir)
+                        .and_then(|()| std::fs::File::create(&path))
+                        .and_then(|file| {
+                            filmstrip::write_frame_png(&result.frame, &palette, 4, file)
+                        });
+                    if let Err(e) = saved {
+                        eprintln!("Could not save {}: {}", path.display(), e);
+                    }
+                }
+            }
+
+            let failed = results
+                .iter()
+                .filter(|result| !result.status.is_ok())
+                .count();
+            println!("{} programs, {} failed", results.len(), failed);
+
+            if let Some(path) = report {
+                match report::write(path, &results, &palette) {
+                    Ok(()) => println!("Report written to {}", path.display()),
+                    Err(e) => {
+                        eprintln!("Could not write report: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if failed > 0 {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Capabilities { json }) => {
+            let capabilities = Capabilities::current();
+            match json {
+                true => print!("{}", capabilities.to_json()),
+                false => print!("{}", capabilities),
+            }
+            return;
+        }
+        Some(Command::Statediff { a, b }) => {
+            let state_a = SaveState::load(a).expect("Could not load the first state");
+            let state_b = SaveState::load(b).expect("Could not load the second state");
+            let diff = StateDiff::new(&state_a, &state_b);
+            print!("{}", diff);
+            if !diff.is_empty() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Opcodes) => {
+            print!("{}", opcodes::list());
+            return;
+        }
+        Some(Command::Quirks {
+            list,
+            profile,
+            timer_mode,
+            stack_depth,
+            collision_rows,
+            rom,
+        }) => {
+            if *list {
+                print!("{}", quirks::list());
+            }
+
+            // the profile a run uses, not guessed from the program
+            let profile = profile.unwrap_or(cli.profile);
+            let settings =
+                Settings::with_overrides(profile, *timer_mode, *stack_depth, *collision_rows);
+            print!("{}", quirks::effective(&settings));
+
+            if let Some(rom) = rom {
+                let program = container::read_program(rom).expect("Could not read ROM file");
+                let suggested = info::suggested_profile(&program);
+                if suggested != profile {
+                    println!(
+                        "profile suggested by the instructions of the program: {}",
+                        suggested.name()
+                    );
+                }
+            }
+            return;
+        }
+        Some(Command::Library { portable, command }) => {
+            let paths = Paths::resolve(*portable);
+            let library_path = paths.library();
+            let mut library = load_library(&library_path);
+
+            match command {
+                LibraryCommand::Add {
+                    rom,
+                    name,
+                    profile,
+                    cycles_per_frame,
+                } => {
+                    let entry = Entry::from_file(rom, *profile, *cycles_per_frame)
+                        .expect("Could not read ROM file");
+                    let name = name
+                        .as_deref()
+                        .map_or_else(|| library::default_name(rom), library::entry_name);
+                    let verb = match library.roms.insert(name.clone(), entry) {
+                        Some(_) => "Updated",
+                        None => "Added",
+                    };
+                    match library.save(&library_path) {
+                        Ok(()) => println!("{} {}", verb, name),
+                        Err(e) => eprintln!("Could not save the library: {}", e),
+                    }
+                }
+                LibraryCommand::Remove { name } => {
+                    let name = library.find(name).map(|(name, _)| name.to_string());
+                    let name = name.unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1)
+                    });
+                    library.roms.remove(&name);
+                    match library.save(&library_path) {
+                        Ok(()) => println!("Removed {}", name),
+                        Err(e) => eprintln!("Could not save the library: {}", e),
+                    }
+                }
+                LibraryCommand::List => {
+                    print!("{}", library.list(&PlayStats::load(&paths.play_stats())));
+                }
+                // runs in the window below
+                LibraryCommand::Run { .. } => (),
+            }
+            if !matches!(command, LibraryCommand::Run { .. }) {
+                return;
+            }
+        }
+        Some(Command::Play { .. }) | None => (),
+    }
+
+    // a program of the library brings its path and the settings it was added with
+    let library_entry = match &cli.command {
+        Some(Command::Library {
+            portable,
+            command: LibraryCommand::Run { name },
+        }) => {
+            let library = load_library(&Paths::resolve(*portable).library());
+            let entry = library.find(name).map(|(_, entry)| entry.clone());
+            let entry = entry.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1)
+            });
+            cli.portable = *portable;
+            Some(entry)
+        }
+        _ => None,
+    };
+    if let Some(entry) = &library_entry {
+        let program = container::read_program(&entry.path).unwrap_or_else(|e| {
+            eprintln!("Could not read {}: {}", entry.path.display(), e);
+            std::process::exit(1)
+        });
+        if info::sha1_hex(&program) != entry.sha1 {
+            eprintln!(
+                "{} changed since it was added to the library",
+                entry.path.display()
+            );
+        }
+        cli.rom = Some(entry.path.clone());
+    }
+
+    // a movie brings its program and the settings it was recorded with
+    let mut movie_player = match &cli.command {
+        Some(Command::Play { movie }) => Some(MoviePlayer::new(
+            Movie::load(movie).expect("Could not load movie"),
+        )),
+        _ => None,
+    };
+    if let Some(player) = &movie_player {
+        cli.profile = player.movie().profile;
+        cli.cycles_per_frame = player.movie().cycles_per_frame;
+    }
+
+    // a .c8c container brings the settings of its program
+    let container = match (&movie_player, &cli.rom) {
+        (None, Some(rom)) => container::read(rom).expect("Could not read ROM file").1,
+        _ => None,
+    };
+    if let Some(container) = &container {
+        if let (Some(profile), false) = (container.profile, given("profile")) {
+            cli.profile = profile;
+        }
+        if let (Some(cycles), false) = (container.cycles_per_frame, given("cycles_per_frame")) {
+            cli.cycles_per_frame = cycles;
+        }
+    }
+    // the settings of the library entry always apply, no window option comes with `library run`
+    if let Some(entry) = &library_entry {
+        cli.profile = entry.profile.unwrap_or(cli.profile);
+        cli.cycles_per_frame = entry.cycles_per_frame.unwrap_or(cli.cycles_per_frame);
+    }
+
+    // the same seed for every instance, printed for the run to be repeated
+    if cli.jitter.is_some() && cli.jitter_seed.is_none() {
+        let seed = rand::random();
+        eprintln!("Jitter seed {} (--jitter-seed to repeat this run)", seed);
+        cli.jitter_seed = Some(seed);
+    }
+
+    let paths = Paths::resolve(cli.portable);
+
+    // palettes selectable in the menu, the one given on the command line first
+    let mut palettes: Vec<(String, Palette)> = PRESETS
+        .iter()
+        .map(|(name, palette)| (name.to_string(), *palette))
+        .collect();
+    if let Some(palette) = container.as_ref().and_then(|container| container.palette()) {
+        palettes.insert(0, ("container".to_string(), palette));
+    }
+    if let Some(path) = &cli.palette_file {
+        let palette = Palette::from_file(path).expect("Could not load palette file");
+        palettes.insert(0, ("custom".to_string(), palette));
+    }
+    let file_names = FileNames::load(&paths.file_names()).unwrap_or_else(|e| {
+        eprintln!(
+            "Invalid file names in {}, using the defaults: {}",
+            paths.file_names().display(),
+            e
+        );
+        FileNames::default()
+    });
+    let display_settings_path = paths.display_settings();
+    let mut display_settings = DisplaySettings::load(&display_settings_path).unwrap_or_default();
+    let mut preset = 0; // palette chosen in the menu, before the display settings colors
+    let mut palette = display_settings.apply(palettes[preset].1);
+
+    let mut chip8 = Chip8::new();
+    chip8.memory.set_display_buffer(cli.display_buffer);
+    chip8.set_mem_init(cli.mem_init);
+    chip8.set_fonts(fonts(&cli));
+    chip8
+        .interpreter
+        .set_key_release_delay(cli.key_release_delay);
+    chip8.interpreter.set_audio_config(audio_config(&cli));
+    chip8.set_executor(cli.executor.create());
+    apply_profile(&mut chip8, cli.profile, &cli);
+
+    // for the bug report bundle written on panic. Observing every instruction would keep
+    // the faster executors from running several at once, they only get the program details.
+    let crash_recorder = CrashRecorder::new();
+    let crash_context = crash_recorder.context();
+    if cli.executor == ExecutorKind::Interpreter {
+        chip8.register_plugin(Box::new(crash_recorder));
+    }
+
+    if cli.trace {
+        let out = std::io::stdout();
+        chip8.register_plugin(Box::new(match cli.trace_diff {
+            true => TraceLogger::with_diffs(out),
+            false => TraceLogger::new(out),
+        }));
+    }
+
+    let coverage = if cli.coverage {
+        let tracker = CoverageTracker::new();
+        let coverage = tracker.coverage();
+        chip8.register_plugin(Box::new(tracker));
+        Some(coverage)
+    } else {
+        None
+    };
+
+    let perf = cli.perf_out.as_ref().map(|_| {
+        let recorder = PerfRecorder::new();
+        let samples = recorder.samples();
+        chip8.register_plugin(Box::new(recorder));
+        samples
+    });
+
+    let key_usage = if cli.key_usage {
+        let tracker = KeyUsageTracker::new();
+        let usage = tracker.usage();
+        chip8.register_plugin(Box::new(tracker));
+        Some(usage)
+    } else {
+        None
+    };
+
+    let speedrun = if cli.speedrun {
+        let timer = SpeedrunTimer::new(cli.splits.clone(), cli.timer_hz.hz());
+        let speedrun = timer.speedrun();
+        chip8.register_plugin(Box::new(timer));
+        Some(speedrun)
+    } else {
+        None
+    };
+
+    if let Some(path) = &cli.script {
+        let script = ScriptRunner::load(path).expect("Could not load script");
+        chip8.register_plugin(Box::new(script));
+    }
+
+    if cli.strict {
+        chip8.interpreter.set_strict_memory(true);
+        chip8.register_plugin(Box::new(StrictChecker::new(std::io::stderr())));
+    }
+
+    if cli.log_uninit_reads {
+        chip8.register_plugin(Box::new(UninitializedReads::new(std::io::stderr())));
+    }
+
+    let arithmetic = cli.audit_arithmetic.then(|| {
+        let audit = ArithmeticAudit::new(std::io::stderr());
+        let counts = audit.counts();
+        chip8.register_plugin(Box::new(audit));
+        counts
+    });
+
+    if cli.pc_guard {
+        chip8.set_pc_guard(Some(cli.allowed_regions.clone()));
+    }
+    chip8.set_jitter(jitter(&cli));
+
+    if !cli.cheats.is_empty() {
+        chip8.register_plugin(Box::new(CheatEngine::new(cli.cheats.clone())));
+    }
+
+    // stdout carries the frames when piping them
+    for extension in &cli.extensions {
+        match cli.pipe_frames {
+            Some(_) => extension.attach(&mut chip8.memory, std::io::stderr()),
+            None => extension.attach(&mut chip8.memory, std::io::stdout()),
+        }
+    }
+
+    let (program, rom_name) = match &movie_player {
+        Some(player) => (
+            player.movie().program.clone(),
+            player.movie().rom_name.clone(),
+        ),
+        None => {
+            let rom_path = cli.rom.as_ref().unwrap();
+            let mut program = container::read_program(rom_path).unwrap();
+            for path in &cli.patches {
+                program = patch::apply_file(path, &program).expect("Could not apply patch");
+            }
+            let rom_name = rom_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (program, rom_name)
+        }
+    };
+
+    chip8.load(&program);
+    if let Some(player) = &movie_player {
+        player.movie().start(&mut chip8);
+    }
+
+    let movie = cli.record_movie.as_ref().map(|_| {
+        let seed = rand::random();
+        chip8.interpreter.set_seed(seed);
+        let recorder = MovieRecorder::new(Movie::new(
+            &chip8,
+            &rom_name,
+            cli.profile,
+            cli.cycles_per_frame,
+            cli.key_release_delay,
+            seed,
+        ));
+        let movie = recorder.movie();
+        chip8.register_plugin(Box::new(recorder));
+        movie
+    });
+    {
+        let mut context = crash_context.lock().unwrap();
+        context.rom_name = rom_name.clone();
+        context.rom_sha1 = info::sha1_hex(&program);
+    }
+    crash::install_panic_hook(Arc::clone(&crash_context));
+
+    // second instance running the same program with another profile
+    let mut dual = cli.dual.map(|profile| {
+        (
+            new_machine(&cli, profile, &program),
+            DivergenceDetector::new(),
+        )
+    });
+
+    // other programs in tiles of the same window, the main one in the first tile
+    let mut tiles: Vec<(String, Chip8)> = cli
+        .tile
+        .iter()
+        .map(|tile| {
+            let program = container::read_program(&tile.rom).expect("Could not read tile ROM file");
+            let name = tile.rom.display().to_string();
+            let profile = tile.profile.unwrap_or(cli.profile);
+            (name, new_machine(&cli, profile, &program))
+        })
+        .collect();
+    let mut focus = 0; // tile receiving the keyboard input
+    let mut paused = cli.start_paused;
+    // F10 steps, the rest of a frame stepped into runs once resumed
+    let mut stepper = FrameStepper::new(cli.cycles_per_frame);
+    if paused {
+        eprintln!("Paused before the first instruction, F10 steps and Space resumes");
+        eprintln!("{}", debugger::registers(&chip8));
+    }
+    let mut unfocused = false; // paused while the window is in the background
+    let mut diverging_pixels = Vec::new();
+
+    let palette_names = palettes.iter().map(|(name, _)| name.clone()).collect();
+    let mut menu = Menu::new(cli.profile, palette_names, display_settings);
+    let keymap_path = paths.keymap();
+    let mut key_reader = KeyReader::new(Keymap::load(&keymap_path).unwrap_or_default());
+    menu.set_keymap(key_reader.keymap().clone());
+    let mut key_bound = false; // the key bound is not a menu key
+    let mut quit_guard = QuitGuard::new(cli.confirm_quit);
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
-    let mut display = Display::new(&event_loop);
-
-    let mut memory = Memory::new();
-    let mut interpreter = Interpreter::new();
-
-    let font = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ];
-
-    memory.load_font(&font);
-
-    let program_path = std::env::args()
-        .nth(1)
-        .expect("Please give path to .ch8 file");
-    let program = std::fs::read(program_path).unwrap();
-
-    memory.load_prog(&program);
-
-    let mut start = Instant::now();
-    let mut delta: f32 = 0.0;
+    let layout = match dual {
+        Some(_) => Layout::for_count(2),
+        None => Layout::for_count(1 + tiles.len()),
+    };
+    let geometry_path = paths.window_geometry();
+    let geometry = WindowGeometry::load(&geometry_path);
+    let mut display = Display::new(
+        &event_loop,
+        palette,
+        layout,
+        geometry,
+        cli.frame_limit.vsync(),
+    );
+    display.apply_settings(&display_settings);
+    display.set_filter(cli.filter);
+    set_flicker(
+        display_settings.flicker,
+        std::iter::once(&mut chip8)
+            .chain(dual.as_mut().map(|(other, _)| other))
+            .chain(tiles.iter_mut().map(|(_, tile)| tile)),
+    );
+    let window_title = {
+        let format = WindowTitle::load(&paths.window_title()).unwrap_or_else(|e| {
+            eprintln!(
+                "Invalid window title in {}, using the default: {}",
+                paths.window_title().display(),
+                e
+            );
+            WindowTitle::default()
+        });
+        let rom = rom_name.clone();
+        let title = container
+            .as_ref()
+            .and_then(|container| container.title.clone());
+        let (profile, ips) = (cli.profile, cli.cycles_per_frame * cli.timer_hz.hz());
+        move |fps| {
+            format.title(&TitleVars {
+                rom: &rom,
+                title: title.as_deref(),
+                profile,
+                ips,
+                fps,
+            })
+        }
+    };
+    let mut fps = 0; // renders of the last second, shown by {fps} in the title
+    let mut render_rate = RenderRate::new(Instant::now());
+    display.set_title(&window_title(fps));
+
+    let mut heatmap = cli.collision_heatmap.map(Heatmap::new);
+    chip8.interpreter.set_record_collisions(heatmap.is_some());
+    let mut draw_tracker = cli.draw_tint.map(DrawTracker::new);
+    chip8.interpreter.set_record_draws(draw_tracker.is_some());
+    // frames go to stdout, the messages printed while running to stderr
+    let mut frame_pipe = cli
+        .pipe_frames
+        .map(|format| FrameWriter::new(std::io::stdout().lock(), format, cli.timer_hz.hz()));
+    let mut frame_export = cli.shmem.as_ref().map(|name| {
+        FrameExport::create(&shmem::region_path(name))
+            .expect("Could not create the shared-memory region")
+    });
+    let mut flash_limiter =
+        (cli.max_flashes > 0).then(|| FlashLimiter::new(cli.max_flashes, cli.timer_hz.hz()));
+    let mut flash_inverted = false; // palette inverted to hide a flash
+    let mut teacher = cli
+        .teach
+        .map(|rate| Teacher::new(rate.unwrap_or(teach::DEFAULT_RATE), cli.cycles_per_frame));
+
+    let mut clock = FrameClock::with_rate(Instant::now(), cli.timer_hz.hz());
+    let mut frame_number: u64 = 0;
+    let mut played_frames: u64 = 0;
+    let mut short_beep_warned = false;
+    let mut history = FrameHistory::new(cli.filmstrip_frames);
+    let mut turbo = Turbo::new(cli.turbo);
+    let mut toasts = Toasts::new();
+    let splash = (!cli.no_splash).then(|| {
+        let title = container
+            .as_ref()
+            .and_then(|container| container.title.as_deref());
+        Splash::new(
+            title.unwrap_or(&rom_name),
+            container
+                .as_ref()
+                .and_then(|container| container.author.as_deref()),
+            cli.profile,
+            cli.cycles_per_frame * cli.timer_hz.hz(),
+            Instant::now(),
+        )
+    });
+    let mut input_latency = cli.input_latency.then(InputLatency::new);
+    let mut frame_skipper = FrameSkipper::new(cli.timer_hz.hz(), cli.max_frame_skip);
+    let mut pacer = RenderPacer::new(cli.frame_limit, Instant::now());
+    // the display settings and palette file are reloaded when edited
+    let mut settings_watcher = FileWatcher::new(&display_settings_path, Instant::now());
+    let mut palette_watcher = cli
+        .palette_file
+        .as_deref()
+        .map(|path| FileWatcher::new(path, Instant::now()));
+    let mut buzzers: Vec<Buzzer> = (0..1 + dual.iter().len() + tiles.len())
+        .map(|_| Buzzer::new())
+        .collect();
+
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
+        // with capped renders, sleep until the next frame or render is due
+        *control_flow = match pacer.next_render() {
+            Some(render) => ControlFlow::WaitUntil(render.min(clock.next_frame())),
+            None => ControlFlow::Poll,
+        };
+
+        // keys are read by scancode, which the input helper does not keep
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { input: keyboard, .. },
+            ..
+        } = &event
+        {
+            let pressed = keyboard.state == ElementState::Pressed;
+            if menu.is_binding() && pressed {
+                key_bound = true;
+                if let Some(MenuAction::Keymap(keymap)) =
+                    menu.bind_key(keyboard.scancode, keyboard.virtual_keycode)
+                {
+                    match keymap.save(&keymap_path) {
+                        Ok(()) => eprintln!("Keys saved to {}", keymap_path.display()),
+                        Err(e) => eprintln!("Could not save keys: {}", e),
+                    }
+                    key_reader.set_keymap(keymap);
+                }
+            } else {
+                key_reader.handle(keyboard.scancode, keyboard.virtual_keycode, pressed);
+            }
+        }
 
         if input.update(&event) {
             // Close events
-            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+            if input.quit() {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
             // Resize the window
             if let Some(size) = input.window_resized() {
-                display.pixels.resize_surface(size.width, size.height);
+                display.resized(size);
             }
 
-            interpreter.apply_input(&input);
-        }
+            if input.key_pressed(VirtualKeyCode::F11) {
+                display.toggle_fullscreen();
+            }
+
+            if input.key_pressed(VirtualKeyCode::F9) {
+                let name = file_names.filmstrip(&TemplateVars {
+                    rom: &rom_name,
+                    timestamp: unix_time(),
+                    frame: frame_number,
+                });
+                let path = paths.screenshot_dir().join(name);
+                let message = match save_filmstrip(&history, &palette, &path) {
+                    true => "Filmstrip saved",
+                    false => "Could not save filmstrip",
+                };
+                toasts.show(message, Instant::now());
+            }
+
+            // F6 saves the state of the focused instance, to compare with `statediff`
+            if input.key_pressed(VirtualKeyCode::F6) && cli.quit_key != Some(VirtualKeyCode::F6) {
+                let (name, instance) = match focus {
+                    0 => (rom_name.as_str(), &chip8),
+                    _ => (tiles[focus - 1].0.as_str(), &tiles[focus - 1].1),
+                };
+                let state = SaveState::new(instance, name, frame_number);
+                let file_name = file_names.state(&TemplateVars {
+                    rom: name,
+                    timestamp: unix_time(),
+                    frame: frame_number,
+                });
+                let message = match save_state(&state, &paths.save_dir().join(file_name)) {
+                    true => "State saved",
+                    false => "Could not save state",
+                };
+                toasts.show(message, Instant::now());
+            }
+
+            // F5 restarts the programs from the memory as they left it, Shift+F5 from a
+            // cleared memory with the programs loaded again
+            if input.key_pressed(VirtualKeyCode::F5) && cli.quit_key != Some(VirtualKeyCode::F5) {
+                let hard = input.held_shift();
+                if !hard && movie.is_some() {
+                    eprintln!("Soft resets are not recorded in movies, Shift+F5 resets");
+                } else {
+                    let instances = std::iter::once(&mut chip8)
+                        .chain(dual.as_mut().map(|(other, _)| other))
+                        .chain(tiles.iter_mut().map(|(_, tile)| tile));
+                    for instance in instances {
+                        match hard {
+                            true => instance.reset_hard(),
+                            false => instance.reset_soft(),
+                        }
+                    }
+                    if let Some((_, detector)) = &mut dual {
+                        *detector = DivergenceDetector::new();
+                    }
+                    display.set_title(&window_title(fps));
+                    paused = false;
+                    let message = if hard { "Hard reset" } else { "Soft reset" };
+                    toasts.show(message, Instant::now());
+                }
+            }
+
+            // Ctrl+V runs the hexadecimal program of the clipboard in the focused instance
+            if input.held_control() && input.key_pressed(VirtualKeyCode::V) {
+                if focus == 0 && movie.is_some() {
+                    eprintln!("Pasted programs are not recorded in movies");
+                    toasts.show("Not while recording a movie", Instant::now());
+                } else {
+                    match paste::from_clipboard() {
+                        Ok(program) => {
+                            if focus > 0 {
+                                let tile = &mut tiles[focus - 1].1;
+                                tile.load(&program);
+                                tile.reset_hard();
+                            } else {
+                                chip8.load(&program);
+                                chip8.reset_hard();
+                                display.set_title(&window_title(fps));
+                                if let Some((other, detector)) = &mut dual {
+                                    other.load(&program);
+                                    other.reset_hard();
+                                    *detector = DivergenceDetector::new();
+                                }
+                            }
+                            let message =
+                                format!("Running {} bytes from the clipboard", program.len());
+                            eprintln!("{}", message);
+                            toasts.show(&message, Instant::now());
+                        }
+                        Err(e) => {
+                            eprintln!("Could not paste a program: {}", e);
+                            toasts.show("Could not paste a program", Instant::now());
+                        }
+                    }
+                }
+            }
+
+            turbo.set_engaged(input.key_held(VirtualKeyCode::Back));
+            if input.key_pressed(VirtualKeyCode::Back) {
+                toasts.show(&format!("Turbo {}x", turbo.multiplier()), Instant::now());
+            }
+
+            if let Some(quit_key) = cli.quit_key {
+                if input.key_pressed(quit_key) {
+                    if quit_guard.press(Instant::now()) {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    let message = format!("Press {} again to quit", key_name(quit_key).unwrap());
+                    eprintln!("{}", message);
+                    toasts.show(&message, Instant::now());
+                }
+            }
+
+            // releases still go through while the menu is open, to not leave keys stuck
+            for event in key_reader.take_events() {
+                if event.down && menu.is_open() {
+                    continue;
+                }
+                if focus > 0 {
+                    tiles[focus - 1].1.push_key_event(event);
+                    continue;
+                }
+                // the movie plays the keys until it is over
+                if movie_player.is_some() {
+                    continue;
+                }
+                chip8.push_key_event(event);
+                if let Some((other, _)) = &mut dual {
+                    other.push_key_event(event);
+                }
+                // frames do not run meanwhile, the wait is not input lag
+                let idle = paused || unfocused || menu.is_open();
+                if let (Some(latency), false) = (&mut input_latency, idle) {
+                    latency.received(Instant::now());
+                }
+            }
+
+            if !tiles.is_empty() && input.key_pressed(VirtualKeyCode::Tab) {
+                // keys held in the tile losing the focus would stay down
+                for key in 0..16 {
+                    let event = KeyEvent { key, down: false };
+                    match focus {
+                        0 => chip8.push_key_event(event),
+                        _ => tiles[focus - 1].1.push_key_event(event),
+                    }
+                }
+                focus = (focus + 1) % (1 + tiles.len());
+            }
 
-        interpreter.decrement_timers();
-        interpreter.step(&mut memory);
+            if menu.is_open() {
+                let action = MENU_KEYS
+                    .iter()
+                    .filter(|(key, _)| input.key_pressed(*key) && !key_bound)
+                    .find_map(|(_, menu_key)| menu.handle_key(*menu_key));
+                key_bound = false;
+
+                match action {
+                    Some(MenuAction::Resume) | None => (),
+                    Some(MenuAction::Reset) => {
+                        chip8.reset_hard();
+                        display.set_title(&window_title(fps));
+                        if let Some((other, detector)) = &mut dual {
+                            other.reset_hard();
+                            *detector = DivergenceDetector::new();
+                        }
+                        for (_, tile) in &mut tiles {
+                            tile.reset_hard();
+                        }
+                        paused = false;
+                        toasts.show("Reset", Instant::now());
+                    }
+                    Some(MenuAction::Profile(_)) if movie.is_some() => {
+                        eprintln!("Profile changes are not recorded in movies");
+                        toasts.show("Not while recording a movie", Instant::now());
+                    }
+                    Some(MenuAction::Profile(profile)) => {
+                        apply_profile(&mut chip8, profile, &cli);
+                        toasts.show(&format!("Profile: {}", profile.name()), Instant::now());
+                    }
+                    Some(MenuAction::Palette(index)) => {
+                        preset = index;
+                        toasts.show(&format!("Palette: {}", palettes[preset].0), Instant::now());
+                        palette = display_settings.apply(palettes[preset].1);
+                        display.set_palette(match flash_inverted {
+                            true => palette.inverted(),
+                            false => palette,
+                        });
+                    }
+                    Some(MenuAction::Display(settings)) => {
+                        display_settings = settings;
+                        palette = display_settings.apply(palettes[preset].1);
+                        display.set_palette(match flash_inverted {
+                            true => palette.inverted(),
+                            false => palette,
+                        });
+                        display.apply_settings(&display_settings);
+                        set_flicker(
+                            display_settings.flicker,
+                            std::iter::once(&mut chip8)
+                                .chain(dual.as_mut().map(|(other, _)| other))
+                                .chain(tiles.iter_mut().map(|(_, tile)| tile)),
+                        );
+                    }
+                    Some(MenuAction::SaveDisplay) => {
+                        match display_settings.save(&display_settings_path) {
+                            Ok(()) => {
+                                eprintln!(
+                                    "Display settings saved to {}",
+                                    display_settings_path.display()
+                                );
+                                toasts.show("Display settings saved", Instant::now());
+                            }
+                            Err(e) => {
+                                eprintln!("Could not save display settings: {}", e);
+                                toasts.show("Could not save display settings", Instant::now());
+                            }
+                        }
+                    }
+                    // bound and saved as soon as the key is pressed
+                    Some(MenuAction::Keymap(_)) => (),
+                    Some(MenuAction::Quit) => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                }
+            } else {
+                if input.key_pressed(VirtualKeyCode::Escape)
+                    && cli.quit_key != Some(VirtualKeyCode::Escape)
+                {
+                    menu.open();
+                }
+
+                if paused && input.key_pressed(VirtualKeyCode::Space) {
+                    paused = false;
+                }
+
+                if paused
+                    && input.key_pressed(VirtualKeyCode::F10)
+                    && cli.quit_key != Some(VirtualKeyCode::F10)
+                {
+                    if let Some(reason) = stepper.step(&mut chip8) {
+                        report_stop("Program stopped", &reason, &chip8);
+                        display.set_title(&format!("{} - {}", window_title(fps), reason));
+                    }
+                    eprintln!("{}", debugger::registers(&chip8));
+                }
+            }
+        }
 
         match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::Focused(focused),
+                ..
+            } => {
+                unfocused = !focused && cli.pause_on_focus_loss;
+
+                // the releases happening elsewhere are never seen
+                if !focused {
+                    key_reader.reset();
+                    let instances = std::iter::once(&mut chip8)
+                        .filter(|_| movie_player.is_none())
+                        .chain(dual.as_mut().map(|(other, _)| other))
+                        .chain(tiles.iter_mut().map(|(_, tile)| tile));
+                    for instance in instances {
+                        for key in 0..16 {
+                            instance.push_key_event(KeyEvent { key, down: false });
+                        }
+                    }
+                }
+            }
             Event::MainEventsCleared => {
-                display.draw(&memory);
+                let now = Instant::now();
+                let mut palette_changed = false;
+                if settings_watcher.changed(now) {
+                    match DisplaySettings::load(settings_watcher.path()) {
+                        Some(settings) if settings != display_settings => {
+                            display_settings = settings;
+                            menu.set_display(settings);
+                            display.apply_settings(&display_settings);
+                            set_flicker(
+                                display_settings.flicker,
+                                std::iter::once(&mut chip8)
+                                    .chain(dual.as_mut().map(|(other, _)| other))
+                                    .chain(tiles.iter_mut().map(|(_, tile)| tile)),
+                            );
+                            palette_changed = true;
+                            eprintln!(
+                                "Display settings reloaded from {}",
+                                settings_watcher.path().display()
+                            );
+                            toasts.show("Display settings reloaded", now);
+                        }
+                        Some(_) => (),
+                        None => eprintln!(
+                            "Invalid display settings in {}, kept the current ones",
+                            settings_watcher.path().display()
+                        ),
+                    }
+                }
+                if let Some(watcher) = &mut palette_watcher {
+                    if watcher.changed(now) {
+                        match Palette::from_file(watcher.path()) {
+                            Ok(custom) => {
+                                // the palette file is the first choice of the menu
+                                palettes[0].1 = custom;
+                                palette_changed = true;
+                                eprintln!("Palette reloaded from {}", watcher.path().display());
+                                toasts.show("Palette reloaded", now);
+                            }
+                            Err(e) => eprintln!("Could not reload the palette file: {}", e),
+                        }
+                    }
+                }
+                if palette_changed {
+                    palette = display_settings.apply(palettes[preset].1);
+                    display.set_palette(match flash_inverted {
+                        true => palette.inverted(),
+                        false => palette,
+                    });
+                }
+
+                let due = if paused || unfocused || menu.is_open() {
+                    0
+                } else {
+                    clock.frames_due(Instant::now())
+                };
+                let started = Instant::now();
+
+                for _ in 0..turbo.frames(due) {
+                    played_frames += 1;
+                    if let Some(latency) = &mut input_latency {
+                        latency.sampled(Instant::now());
+                    }
+                    if let Some(player) = &mut movie_player {
+                        if !player.play_frame(&mut chip8) {
+                            eprintln!(
+                                "Movie over after {} frames, the keyboard controls the program",
+                                player.movie().frames
+                            );
+                            movie_player = None;
+                        }
+                    }
+                    let emulate = Instant::now();
+                    let halt = match &mut teacher {
+                        Some(teacher) => teacher.frame(&mut chip8).and_then(|step| {
+                            println!("{}", step);
+                            step.halt.clone()
+                        }),
+                        None => stepper.run_frame(&mut chip8),
+                    };
+                    if let Some(reason) = halt {
+                        report_stop("Program stopped", &reason, &chip8);
+                        display.set_title(&format!("{} - {}", window_title(fps), reason));
+                    }
+                    if let Some((pc, value)) = chip8.interpreter.take_short_beep() {
+                        if !short_beep_warned {
+                            eprintln!(
+                                "Sound timer set to {} at {:03X}, lengthened to {} frames (--min-beep-frames)",
+                                value, pc, cli.min_beep_frames
+                            );
+                            short_beep_warned = true;
+                        }
+                    }
+                    chip8.frame();
+                    if let Some(perf) = &perf {
+                        perf.borrow_mut().record("frame;emulate", emulate.elapsed());
+                    }
+                    if let Ok(mut context) = crash_context.lock() {
+                        context.set_pc_history(chip8.interpreter.pc_history());
+                    }
+
+                    frame_number += 1;
+                    let frame = FrameBuffer::from_memory(&chip8.memory);
+                    history.push(frame_number, frame);
+
+                    if let Some(pipe) = &mut frame_pipe {
+                        if let Err(e) = pipe.write_frame(&frame, &palette) {
+                            eprintln!("Stopped writing frames to stdout: {}", e);
+                            frame_pipe = None;
+                        }
+                    }
+
+                    if let Some(export) = &mut frame_export {
+                        if let Err(e) = export.publish(&frame, &palette) {
+                            eprintln!(
+                                "Stopped publishing frames to {}: {}",
+                                export.path().display(),
+                                e
+                            );
+                            frame_export = None;
+                        }
+                    }
+
+                    if let Some(limiter) = &mut flash_limiter {
+                        let inverted = limiter.push_frame(frame_number, frame);
+                        if inverted != flash_inverted {
+                            flash_inverted = inverted;
+                            display.set_palette(match inverted {
+                                true => palette.inverted(),
+                                false => palette,
+                            });
+                        }
+                    }
+
+                    buzzers[0].push_frame(chip8.interpreter.sound_on());
+
+                    let collisions = chip8.interpreter.take_collisions();
+                    if let Some(heatmap) = &mut heatmap {
+                        heatmap.push_frame(collisions);
+                    }
+                    if let Some(tracker) = &mut draw_tracker {
+                        tracker.push_frame(chip8.interpreter.take_draws());
+                    }
+
+                    let tile_buzzers = &mut buzzers[1 + dual.iter().len()..];
+                    for ((name, tile), buzzer) in tiles.iter_mut().zip(tile_buzzers) {
+                        if let Some(reason) = tile.run_frame(cli.cycles_per_frame) {
+                            let what = format!("Program stopped in the tile of {}", name);
+                            report_stop(&what, &reason, tile);
+                        }
+                        tile.frame();
+                        buzzer.push_frame(tile.interpreter.sound_on());
+                    }
+
+                    if let Some((other, detector)) = &mut dual {
+                        if let Some(reason) = other.run_frame(cli.cycles_per_frame) {
+                            report_stop("Program stopped on the second screen", &reason, other);
+                        }
+                        other.frame();
+                        buzzers[1].push_frame(other.interpreter.sound_on());
+
+                        if let Some(pixels) = detector.compare(&chip8.memory, &other.memory) {
+                            eprintln!(
+                                "Displays diverge at frame {}, press Space to resume",
+                                detector.divergence().unwrap()
+                            );
+                            diverging_pixels = pixels;
+                            paused = true;
+                            break;
+                        }
+                        if let Some(frame) =
+                            detector.compare_states(chip8.state_hash(), other.state_hash())
+                        {
+                            eprintln!("States diverge at frame {}, the displays still match", frame);
+                        }
+                    }
+                }
+
+                let audio = Instant::now();
+                let turbo_audio = turbo.engaged().then_some(cli.turbo_audio);
+                let sounding: Vec<bool> = std::iter::once(&chip8)
+                    .chain(dual.as_ref().map(|(other, _)| other))
+                    .chain(tiles.iter().map(|(_, tile)| tile))
+                    .map(|instance| instance.interpreter.sound_on())
+                    .zip(&mut buzzers)
+                    .map(|(sound_on, buzzer)| buzzer.present(sound_on, turbo_audio))
+                    .collect();
+                if let Some(perf) = &perf {
+                    perf.borrow_mut().record("frame;audio", audio.elapsed());
+                }
+
+                if !pacer.render(Instant::now()) || !frame_skipper.render(due, started.elapsed()) {
+                    return;
+                }
+
+                let render = Instant::now();
+                draw_instance(&mut display, 0, &chip8, heatmap.as_ref());
+                if let Some(tracker) = &draw_tracker {
+                    display.tint(0, &tracker.intensities(), [0x00, 0xC0, 0xFF]);
+                }
+
+                if let Some((other, _)) = &dual {
+                    draw_instance(&mut display, 1, other, None);
+
+                    if paused {
+                        display.highlight(0, &diverging_pixels);
+                        display.highlight(1, &diverging_pixels);
+                    }
+                }
+
+                for (screen, (_, tile)) in tiles.iter().enumerate() {
+                    draw_instance(&mut display, screen + 1, tile, None);
+                }
+                if !tiles.is_empty() {
+                    display.outline(focus, palette.colors[1]);
+                }
+
+                for (screen, &sound_on) in sounding.iter().enumerate() {
+                    if sound_on && cli.sound_indicator {
+                        display.draw_speaker(screen);
+                    }
+                }
+
+                if let Some(usage) = &key_usage {
+                    display.draw_label(0, false, &usage.borrow().summary());
+                }
+                if let Some(speedrun) = &speedrun {
+                    display.draw_label(0, true, &speedrun.borrow().summary());
+                }
+                if let Some(teacher) = &teacher {
+                    display.draw_caption(0, &teacher.lines(&chip8));
+                }
+
+                if menu.is_open() {
+                    display.draw_panel(&menu.lines());
+                } else if let Some(lines) = splash.as_ref().and_then(|s| s.lines(Instant::now())) {
+                    display.draw_panel(lines);
+                }
+                if let Some(message) = toasts.current(Instant::now()) {
+                    display.draw_toast(message);
+                }
+                // measured before presenting, waiting for the monitor refresh is not load
+                frame_skipper.rendered(render.elapsed());
 
                 if display
-                    .pixels
-                    .render()
-                    .map_err(|e| println!("pixels.render() failed: {}", e))
+                    .present()
+                    .map_err(|e| eprintln!("pixels.render() failed: {}", e))
                     .is_err()
                 {
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
+                if let Some(perf) = &perf {
+                    perf.borrow_mut().record("frame;render", render.elapsed());
+                }
+                if let Some(rate) = render_rate.render(Instant::now()) {
+                    // the title shows why the program stopped instead
+                    if rate != fps && chip8.interpreter.halt_reason().is_none() {
+                        fps = rate;
+                        display.set_title(&window_title(fps));
+                    }
+                }
 
                 display.window().request_redraw();
+            }
+            Event::LoopDestroyed => {
+                if let Err(e) = display.geometry().save(&geometry_path) {
+                    eprintln!("Could not save window geometry: {}", e);
+                }
+
+                // reloaded to keep the sessions of other instances ended meanwhile
+                let stats_path = paths.play_stats();
+                let mut play_stats = PlayStats::load(&stats_path);
+                play_stats.record_session(
+                    &info::sha1_hex(&program),
+                    &rom_name,
+                    played_frames / cli.timer_hz.hz() as u64,
+                    unix_time(),
+                );
+                if let Err(e) = play_stats.save(&stats_path) {
+                    eprintln!("Could not save play statistics: {}", e);
+                }
 
-                let elapsed = start.elapsed();
-                delta = (elapsed.as_micros() as f32) / 1000_000.0;
-                start = Instant::now();
+                if let Some(coverage) = &coverage {
+                    println!("{}", coverage.borrow().report());
+                }
+                if let Some(usage) = &key_usage {
+                    println!("{}", usage.borrow().report());
+                }
+                if let Some(latency) = &input_latency {
+                    println!("{}", latency.report());
+                }
+                if let Some(arithmetic) = &arithmetic {
+                    println!("{}", arithmetic.borrow().report());
+                }
+                if let (Some(perf), Some(path)) = (&perf, &cli.perf_out) {
+                    let written = File::create(path)
+                        .and_then(|file| perf.borrow().write_folded(BufWriter::new(file)));
+                    match written {
+                        Ok(()) => eprintln!("Profile written to {}", path.display()),
+                        Err(e) => eprintln!("Could not write profile: {}", e),
+                    }
+                }
+                if let Some(speedrun) = &speedrun {
+                    let report = speedrun.borrow().report();
+                    match &cli.speedrun_out {
+                        Some(path) => match std::fs::write(path, report) {
+                            Ok(()) => eprintln!("Speedrun times written to {}", path.display()),
+                            Err(e) => eprintln!("Could not write speedrun times: {}", e),
+                        },
+                        None => print!("{}", report),
+                    }
+                }
+
+                if let (Some(movie), Some(path)) = (&movie, &cli.record_movie) {
+                    match movie.borrow().save(path) {
+                        Ok(()) => eprintln!("Movie written to {}", path.display()),
+                        Err(e) => eprintln!("Could not write movie: {}", e),
+                    }
+                }
             }
             _ => (),
         }
     })
 }
+
+/// Window of the `sandbox` command, executing the instructions typed on stdin as they come
+fn run_sandbox(cli: &Cli, profile: Profile) -> ! {
+    let mut chip8 = Chip8::new();
+    apply_profile(&mut chip8, profile, cli);
+    let mut sandbox = Sandbox::new(chip8);
+
+    // stdin is read on its own thread, the window needing the main one
+    let (lines, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let event_loop = EventLoop::new();
+    let mut display = Display::new(&event_loop, Palette::new(), Layout::single(), None, true);
+    display.set_title("CHIP-8 sandbox");
+    let keymap = Keymap::load(&Paths::resolve(cli.portable).keymap()).unwrap_or_default();
+    let mut key_reader = KeyReader::new(keymap);
+    let mut input = WinitInputHelper::new();
+    let mut clock = FrameClock::new(Instant::now());
+
+    let prompt = || {
+        print!("(sandbox) ");
+        std::io::stdout().flush().unwrap();
+    };
+    println!("{}", sandbox::HELP);
+    prompt();
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(clock.next_frame());
+
+        if let Event::WindowEvent {
+            event: WindowEvent::KeyboardInput {
+                input: keyboard, ..
+            },
+            ..
+        } = &event
+        {
+            let pressed = keyboard.state == ElementState::Pressed;
+            key_reader.handle(keyboard.scancode, keyboard.virtual_keycode, pressed);
+        }
+
+        if !input.update(&event) {
+            return;
+        }
+        if input.quit() {
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+        if let Some(size) = input.window_resized() {
+            display.resized(size);
+        }
+
+        // the timers tick and the keys are sampled at 60 Hz, as when a program runs
+        for event in key_reader.take_events() {
+            sandbox.chip8_mut().push_key_event(event);
+        }
+        for _ in 0..clock.frames_due(Instant::now()) {
+            sandbox.chip8_mut().begin_frame();
+        }
+
+        loop {
+            let line = match received.try_recv() {
+                Ok(line) => line,
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+            };
+            match line.parse() {
+                Ok(SandboxInput::Quit) => {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                Ok(input) => println!("{}", sandbox.execute(input)),
+                Err(e) => println!("{}", e),
+            }
+            prompt();
+        }
+
+        display.draw(0, &sandbox.chip8().memory, None);
+        if let Err(e) = display.present() {
+            eprintln!("pixels.render() failed: {}", e);
+            *control_flow = ControlFlow::Exit;
+        }
+    })
+}
+
+fn fonts(cli: &Cli) -> font::Fonts {
+    match &cli.font_file {
+        Some(path) => font::Fonts::from_file(path).expect("Could not load font file"),
+        None => font::Fonts::default(),
+    }
+}
+
+/// Instance set up from the command line, without the plugins of the main one
+fn new_machine(cli: &Cli, profile: Profile, program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.memory.set_display_buffer(cli.display_buffer);
+    chip8.set_mem_init(cli.mem_init);
+    chip8.set_fonts(fonts(cli));
+    chip8
+        .interpreter
+        .set_key_release_delay(cli.key_release_delay);
+    chip8.interpreter.set_audio_config(audio_config(cli));
+    chip8.set_executor(cli.executor.create());
+    apply_profile(&mut chip8, profile, cli);
+    if cli.pc_guard {
+        chip8.set_pc_guard(Some(cli.allowed_regions.clone()));
+    }
+    chip8.set_jitter(jitter(cli));
+    if !cli.cheats.is_empty() {
+        chip8.register_plugin(Box::new(CheatEngine::new(cli.cheats.clone())));
+    }
+    chip8.load(program);
+
+    chip8
+}
+
+fn jitter(cli: &Cli) -> Option<Jitter> {
+    cli.jitter.map(|percent| Jitter {
+        percent,
+        seed: cli.jitter_seed.unwrap_or_default(),
+    })
+}
+
+fn audio_config(cli: &Cli) -> AudioConfig {
+    AudioConfig {
+        min_beep_frames: cli.min_beep_frames,
+        collision_cue_frames: cli.collision_cue,
+    }
+}
+
+/// Configure the quirks, timer mode, stack depth and address space of a profile,
+/// unless overridden on the command line
+fn apply_profile(chip8: &mut Chip8, profile: Profile, cli: &Cli) {
+    chip8.memory.set_address_space(profile.address_space());
+    let settings =
+        Settings::with_overrides(profile, cli.timer_mode, cli.stack_depth, cli.collision_rows);
+    let interpreter = &mut chip8.interpreter;
+    interpreter.set_quirks(settings.quirks);
+    interpreter.set_timer_mode(settings.timer_mode);
+    interpreter.set_stack_depth(settings.stack_depth);
+}
+
+/// The library at `path`, exits if it cannot be read
+fn load_library(path: &Path) -> Library {
+    Library::load(path).unwrap_or_else(|e| {
+        eprintln!("Could not read the library {}: {}", path.display(), e);
+        std::process::exit(1)
+    })
+}
+
+/// Print why an instance stopped, with the instructions leading to it after an error
+fn report_stop(what: &str, reason: &HaltReason, instance: &Chip8) {
+    eprintln!("{}: {}", what, reason);
+    if let HaltReason::Error(_) = reason {
+        eprint!("Last instructions:\n{}", instance.interpreter.pc_history());
+    }
+}
+
+/// Capture the displays of the instances during the frames as the flicker mode shows them
+fn set_flicker<'a>(mode: FlickerMode, instances: impl Iterator<Item = &'a mut Chip8>) {
+    let capture = match mode {
+        FlickerMode::Smooth => DisplayCapture::Off,
+        FlickerMode::Authentic => DisplayCapture::Scanout,
+        FlickerMode::NoFlicker => DisplayCapture::Merged,
+    };
+    for instance in instances {
+        instance.set_display_capture(capture);
+    }
+}
+
+/// Draw the display of an instance, as captured during the last frame if it was
+fn draw_instance(
+    display: &mut Display,
+    screen: usize,
+    instance: &Chip8,
+    heatmap: Option<&Heatmap>,
+) {
+    match instance.captured_display() {
+        Some(frame) => display.draw_frame(screen, frame, heatmap),
+        None => display.draw(screen, &instance.memory, heatmap),
+    }
+}
+
+/// Export the frame history as a PNG contact sheet, returns whether it was saved
+fn save_filmstrip(history: &FrameHistory, palette: &Palette, path: &Path) -> bool {
+    let frames: Vec<(u64, FrameBuffer)> = history.frames().copied().collect();
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::File::create(path))
+        .and_then(|file| filmstrip::write_png(&frames, palette, std::io::BufWriter::new(file)));
+
+    match result {
+        Ok(()) => {
+            eprintln!("Saved {} frames to {}", frames.len(), path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("Could not save filmstrip: {}", e);
+            false
+        }
+    }
+}
+
+fn save_state(state: &SaveState, path: &Path) -> bool {
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| state.save(path));
+
+    match result {
+        Ok(()) => {
+            eprintln!("Saved state of frame {} to {}", state.frame, path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("Could not save state: {}", e);
+            false
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
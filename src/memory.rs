@@ -1,73 +1,597 @@
-const MAX_SIZE: u16 = 0x1000;
+use crate::bus::{Bus, Mapping, Peripheral};
+use crate::error::MemError;
 
-const PROG_LOC: u16 = 0x0200;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+use std::ops::{Range, RangeInclusive};
+use std::str::FromStr;
+
+/// Size of the classic address space, addresses are masked to 12 bits
+pub const MAX_SIZE: u16 = 0x1000;
+/// Size of the XO-CHIP address space
+pub const XO_CHIP_SIZE: usize = 0x10000;
+
+pub const PROG_LOC: u16 = 0x0200;
 pub const DISPLAY_LOC: u16 = 0x0F00;
+pub const DISPLAY_SIZE: u16 = MAX_SIZE - DISPLAY_LOC; // 64x32 pixels, 1 bit each
 pub const FONT_LOC: u16 = 0x0050;
 pub const FONT_CHAR_SIZE: u16 = 5; // bytes
+pub const BIG_FONT_LOC: u16 = FONT_LOC + FONT.len() as u16;
+pub const BIG_FONT_CHAR_SIZE: u16 = 10; // bytes
+
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// 8x10 digits of the SUPER-CHIP big font, with the XO-CHIP letters
+pub const BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Where the fonts are loaded, for FX29 and FX30 to point at their characters
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryMap {
+    pub small_font: u16, // 5 bytes per character
+    pub big_font: u16,   // 10 bytes per character
+}
+
+impl MemoryMap {
+    /// Both fonts below the program, the small one at 0x050 as most interpreters do
+    pub fn new() -> Self {
+        MemoryMap {
+            small_font: FONT_LOC,
+            big_font: BIG_FONT_LOC,
+        }
+    }
+
+    /// Address of the small sprite of a hexadecimal digit, the low nibble of `digit`
+    pub fn small_char(&self, digit: u8) -> u16 {
+        self.small_font + (digit & 0xF) as u16 * FONT_CHAR_SIZE
+    }
+
+    /// Address of the big sprite of a hexadecimal digit
+    pub fn big_char(&self, digit: u8) -> u16 {
+        self.big_font + (digit & 0xF) as u16 * BIG_FONT_CHAR_SIZE
+    }
+
+    /// Addresses taken by the small and the big font
+    pub fn font_areas(&self) -> [Range<u16>; 2] {
+        [
+            self.small_font..self.small_font + FONT.len() as u16,
+            self.big_font..self.big_font + BIG_FONT.len() as u16,
+        ]
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> Self {
+        MemoryMap::new()
+    }
+}
+
+/// Bytes 0xDEADBEEF repeated by `MemInit::Pattern`, easy to spot in a hexdump
+const INIT_PATTERN: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+
+/// Content of the RAM below the display area before the fonts and the program are loaded,
+/// to expose programs depending on memory they never wrote
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MemInit {
+    Zero,
+    Ff,
+    Random(u64), // seed
+    Pattern,
+}
+
+impl MemInit {
+    fn fill(&self, data: &mut [u8]) {
+        match self {
+            MemInit::Zero => data.fill(0),
+            MemInit::Ff => data.fill(0xFF),
+            MemInit::Random(seed) => StdRng::seed_from_u64(*seed).fill_bytes(data),
+            MemInit::Pattern => {
+                for (byte, pattern) in data.iter_mut().zip(INIT_PATTERN.iter().cycle()) {
+                    *byte = *pattern;
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for MemInit {
+    type Err = String;
+
+    /// `zero`, `ff`, `random(SEED)` or `pattern`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zero" => Ok(MemInit::Zero),
+            "ff" => Ok(MemInit::Ff),
+            "pattern" => Ok(MemInit::Pattern),
+            _ => s
+                .strip_prefix("random(")
+                .and_then(|rest| rest.strip_suffix(')'))
+                .and_then(|seed| seed.parse().ok())
+                .map(MemInit::Random)
+                .ok_or_else(|| format!("expected zero, ff, random(SEED) or pattern: {}", s)),
+        }
+    }
+}
+
+/// Addressable memory, the display area staying at 0xF00
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AddressSpace {
+    /// 4K with addresses masked to 12 bits, as on the VIP
+    Classic,
+    /// 64K with addresses of 16 bits, as XO-CHIP programs expect
+    XoChip,
+}
+
+impl AddressSpace {
+    /// Size in bytes
+    pub fn size(self) -> usize {
+        match self {
+            AddressSpace::Classic => MAX_SIZE as usize,
+            AddressSpace::XoChip => XO_CHIP_SIZE,
+        }
+    }
+
+    /// Bits kept of an address
+    pub fn mask(self) -> u16 {
+        (self.size() - 1) as u16
+    }
+
+    /// Smallest address space holding a program of `len` bytes loaded at PROG_LOC
+    pub fn for_program(len: usize) -> Self {
+        match PROG_LOC as usize + len <= MAX_SIZE as usize {
+            true => AddressSpace::Classic,
+            false => AddressSpace::XoChip,
+        }
+    }
 
+    /// Address space of `size` bytes, None if there is none of that size
+    pub fn from_size(size: usize) -> Option<Self> {
+        [AddressSpace::Classic, AddressSpace::XoChip]
+            .into_iter()
+            .find(|space| space.size() == size)
+    }
+}
+
+/// Where the pixels of the display are stored
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum DisplayBuffer {
+    /// In RAM at 0xF00, as on the VIP: programs can read and write the pixels directly
+    Ram,
+    /// Outside of the RAM, leaving 0xF00-0xFFF to the program
+    External,
+}
+
+/// RAM, with address ranges that peripherals can claim
 pub struct Memory {
-    data: [u8; MAX_SIZE as usize],
+    data: Box<[u8]>, // as large as the address space
+    space: AddressSpace,
+    init: MemInit,    // content of the RAM once cleared
+    code_writes: u64, // writes outside of the display area
+    peripherals: Vec<Mapping>,
+    display_buffer: DisplayBuffer, // as set, the 64K address space always uses External
+    external: [u8; DISPLAY_SIZE as usize], // the pixels with DisplayBuffer::External
 }
 
 impl Memory {
     pub fn new() -> Self {
         Memory {
-            data: [0; MAX_SIZE as usize],
+            data: vec![0; MAX_SIZE as usize].into_boxed_slice(),
+            space: AddressSpace::Classic,
+            init: MemInit::Zero,
+            code_writes: 0,
+            peripherals: Vec::new(),
+            display_buffer: DisplayBuffer::Ram,
+            external: [0; DISPLAY_SIZE as usize],
+        }
+    }
+
+    /// Fill the RAM outside of the display area according to `init`, now and whenever cleared
+    pub fn set_init(&mut self, init: MemInit) {
+        self.init = init;
+        self.fill_init();
+        self.code_writes += 1;
+    }
+
+    /// Fill the RAM with `init`, keeping the display area when the pixels are in RAM
+    fn fill_init(&mut self) {
+        let display = DISPLAY_LOC as usize..MAX_SIZE as usize;
+        let pixels = (self.display_buffer() == DisplayBuffer::Ram)
+            .then(|| self.data[display.clone()].to_vec());
+
+        self.init.fill(&mut self.data);
+        if let Some(pixels) = pixels {
+            self.data[display].copy_from_slice(&pixels);
+        }
+    }
+
+    /// Grow or shrink the RAM, keeping its content below the new size. Added memory is filled
+    /// as set by `set_init`. The pixels move out of the RAM with the 64K address space, where
+    /// 0xF00-0xFFF is in the middle of the programs, keeping the current frame.
+    pub fn set_address_space(&mut self, space: AddressSpace) {
+        let frame = self.display();
+        let mut data = vec![0; space.size()];
+        let kept = self.data.len().min(data.len());
+        data[..kept].copy_from_slice(&self.data[..kept]);
+        self.init.fill(&mut data[kept..]);
+
+        self.data = data.into_boxed_slice();
+        self.space = space;
+        self.code_writes += 1;
+        for (i, &byte) in frame.iter().enumerate() {
+            self.set_display_byte(i as u16, byte);
+        }
+    }
+
+    pub fn address_space(&self) -> AddressSpace {
+        self.space
+    }
+
+    /// Size of the RAM in bytes
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Move the pixels in or out of the RAM, keeping the current frame
+    pub fn set_display_buffer(&mut self, display_buffer: DisplayBuffer) {
+        let frame = self.display();
+        self.display_buffer = display_buffer;
+        for (i, &byte) in frame.iter().enumerate() {
+            self.set_display_byte(i as u16, byte);
         }
     }
 
+    /// Where the pixels are stored, External with the 64K address space whatever was set
+    pub fn display_buffer(&self) -> DisplayBuffer {
+        match self.space {
+            AddressSpace::Classic => self.display_buffer,
+            AddressSpace::XoChip => DisplayBuffer::External,
+        }
+    }
+
+    /// Byte `i` of the display, 8 pixels of a row with the leftmost in the highest bit
+    pub fn display_byte(&self, i: u16) -> u8 {
+        match self.display_buffer() {
+            DisplayBuffer::Ram => self.read(DISPLAY_LOC + i),
+            DisplayBuffer::External => self.external[i as usize],
+        }
+    }
+
+    pub fn set_display_byte(&mut self, i: u16, byte: u8) {
+        match self.display_buffer() {
+            DisplayBuffer::Ram => self.write(DISPLAY_LOC + i, byte),
+            DisplayBuffer::External => self.external[i as usize] = byte,
+        }
+    }
+
+    /// Every byte of the display
+    pub fn display(&self) -> [u8; DISPLAY_SIZE as usize] {
+        let mut bytes = [0; DISPLAY_SIZE as usize];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.display_byte(i as u16);
+        }
+        bytes
+    }
+
+    /// Route the reads and writes of `range` to a peripheral instead of RAM
+    pub fn map(
+        &mut self,
+        range: RangeInclusive<u16>,
+        device: Box<dyn Peripheral>,
+    ) -> Result<(), MemError> {
+        if *range.end() as usize >= self.size() {
+            return Err(MemError::OutOfBounds { addr: *range.end() });
+        }
+        if let Some(addr) = range.clone().find(|&addr| self.mapping(addr).is_some()) {
+            return Err(MemError::AlreadyMapped { addr });
+        }
+
+        self.peripherals.push(Mapping { range, device });
+        Ok(())
+    }
+
+    /// Address ranges claimed by peripherals, with their names
+    pub fn mappings(&self) -> impl Iterator<Item = (RangeInclusive<u16>, &str)> {
+        self.peripherals
+            .iter()
+            .map(|mapping| (mapping.range.clone(), mapping.device.name()))
+    }
+
+    fn mapping(&self, addr: u16) -> Option<&Mapping> {
+        self.peripherals
+            .iter()
+            .find(|mapping| mapping.contains(addr))
+    }
+
+    /// Content of the RAM, whatever peripherals are mapped over it
+    pub fn ram(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Replace the content of the RAM, e.g. with a snapshot. The address space follows the
+    /// size of `data`, which must be the one of an `AddressSpace`.
+    pub fn restore_ram(&mut self, data: &[u8]) {
+        self.space = AddressSpace::from_size(data.len()).expect("RAM of 4K or 64K");
+        self.data = data.into();
+        self.code_writes += 1;
+    }
+
+    /// Clear the RAM as set by `set_init` (zeroed by default) and reset the peripherals,
+    /// which stay mapped
+    pub fn clear(&mut self) {
+        self.data.fill(0);
+        self.external = [0; DISPLAY_SIZE as usize];
+        self.fill_init();
+        self.code_writes += 1;
+        for mapping in &mut self.peripherals {
+            mapping.device.reset();
+        }
+    }
+
+    /// Number of writes outside of the display area so far, lets executors caching
+    /// decoded instructions notice when a program may have modified itself
+    pub fn code_writes(&self) -> u64 {
+        self.code_writes
+    }
+
+    fn count_write(&mut self, addr: u16) {
+        if !(DISPLAY_LOC..MAX_SIZE).contains(&addr)
+            || self.display_buffer() == DisplayBuffer::External
+        {
+            self.code_writes += 1;
+        }
+    }
+
+    /// Print `len` bytes from `from`, stopping at the end of the RAM
     pub fn hexdump(&self, from: u16, len: u16) {
+        let end = (from as usize + len as usize).min(self.size());
         print!("hexdump from 0x{:04x}: ", from - from % 2);
-        for addr in (from - from % 2)..(from + len) {
+        for addr in (from - from % 2) as usize..end {
             if (addr) % 0x10 == 0 {
                 print!("\n{:04x}: ", addr);
             }
             if addr % 2 == 0 {
-                print!("{:04x} ", self.read_u16(addr));
+                print!("{:04x} ", self.read_u16(addr as u16));
             }
         }
     }
 
     pub fn load_prog(&mut self, prgm: &[u8]) {
         for (i, byte) in prgm.iter().enumerate() {
-            self.data[(PROG_LOC as usize + i)] = *byte;
+            self.data[PROG_LOC as usize + i] = *byte;
         }
+        self.code_writes += 1;
     }
 
-    pub fn load_font(&mut self, font: &[u8]) {
+    pub fn load_font(&mut self, font: &[u8], addr: u16) {
         for (i, byte) in font.iter().enumerate() {
-            self.data[(FONT_LOC as usize + i)] = *byte;
+            self.data[(addr as usize + i) % self.size()] = *byte;
         }
+        self.code_writes += 1;
+    }
+
+    /// Load the small and the big font where `map` places them
+    pub fn load_fonts(&mut self, map: &MemoryMap) {
+        self.load_font(&FONT, map.small_font);
+        self.load_font(&BIG_FONT, map.big_font);
     }
 
     pub fn read(&self, addr: u16) -> u8 {
+        if !self.peripherals.is_empty() {
+            if let Some(mapping) = self.mapping(addr) {
+                return mapping.device.read(mapping.offset(addr));
+            }
+        }
         self.data[addr as usize]
     }
 
-    pub fn read_u16(&self, addr: u16) -> u16 {
-        let lo = self.read(addr) as u16;
-        let hi = self.read(addr + 1) as u16;
+    pub fn read_checked(&self, addr: u16) -> Result<u8, MemError> {
+        if addr as usize >= self.size() {
+            return Err(MemError::OutOfBounds { addr });
+        }
+        Ok(self.read(addr))
+    }
 
-        lo << 8 | hi
+    /// Read with the address masked to the address space, 12 bits as the original hardware
+    pub fn read_wrapped(&self, addr: u16) -> u8 {
+        self.read(addr & self.space.mask())
     }
 
     pub fn write(&mut self, addr: u16, data: u8) {
+        if !self.peripherals.is_empty() {
+            if let Some(mapping) = self.peripherals.iter_mut().find(|m| m.contains(addr)) {
+                let offset = mapping.offset(addr);
+                mapping.device.write(offset, data);
+                return;
+            }
+        }
         self.data[addr as usize] = data;
+        self.count_write(addr);
+    }
+
+    pub fn write_checked(&mut self, addr: u16, data: u8) -> Result<(), MemError> {
+        if addr as usize >= self.size() {
+            return Err(MemError::OutOfBounds { addr });
+        }
+        self.write(addr, data);
+        Ok(())
     }
 
-    pub fn write_u16(&mut self, addr: u16, data: u16) {
-        let lo = (data >> 8) as u8;
-        let hi = data as u8;
+    /// Write with the address masked to the address space, 12 bits as the original hardware
+    pub fn write_wrapped(&mut self, addr: u16, data: u8) {
+        self.write(addr & self.space.mask(), data);
+    }
+}
+
+impl Bus for Memory {
+    fn read(&self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        Memory::write(self, addr, data)
+    }
+
+    fn address_mask(&self) -> u16 {
+        self.space.mask()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new()
+    }
+}
+
+/// Parse an address in hexadecimal (e.g. 3A0 or 0x3A0), up to FFFF: the users check it
+/// against the address space, which is not known yet
+pub fn parse_addr(s: &str) -> Result<u16, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address: {}", s))
+}
 
-        self.write(addr, lo);
-        self.write(addr + 1, hi);
+/// Parse an inclusive address range in hexadecimal, START-END or START..END (e.g. 300-3FF or
+/// 0x300..0x3FF, the end included either way), or a single address
+pub fn parse_region(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = match s.split_once("..").or_else(|| s.split_once('-')) {
+        Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+        None => (parse_addr(s)?, parse_addr(s)?),
+    };
+    if end < start {
+        return Err(format!("region ends before it starts: {}", s));
     }
+
+    Ok(start..=end)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Remembers the writes, reads the last value written
+    struct Latch(Rc<RefCell<Vec<(u16, u8)>>>);
+
+    impl Peripheral for Latch {
+        fn name(&self) -> &str {
+            "latch"
+        }
+
+        fn read(&self, _offset: u16) -> u8 {
+            self.0.borrow().last().map_or(0, |&(_, data)| data)
+        }
+
+        fn write(&mut self, offset: u16, data: u8) {
+            self.0.borrow_mut().push((offset, data));
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(Ok(0x300..=0x3FF), parse_region("300-3FF"));
+        assert_eq!(Ok(0xEA0..=0xEA0), parse_region("ea0"));
+        assert_eq!(Ok(0xFF00..=0xFFFF), parse_region("FF00-FFFF"));
+        assert_eq!(Ok(0x3A0..=0x3FF), parse_region("0x3A0..0x3FF"));
+        assert_eq!(Ok(0x3A0..=0x3A0), parse_region("0X3a0"));
+        assert!(parse_region("3FF-300").is_err());
+        assert!(parse_region("300-10000").is_err());
+        assert!(parse_region("jump").is_err());
+    }
+
+    #[test]
+    fn test_mem_init() {
+        assert_eq!(Ok(MemInit::Random(42)), "random(42)".parse());
+        assert_eq!(Ok(MemInit::Ff), "ff".parse());
+        assert!("random".parse::<MemInit>().is_err());
+
+        let mut mem = Memory::new();
+        mem.set_init(MemInit::Pattern);
+        assert_eq!([0xDE, 0xAD, 0xBE, 0xEF, 0xDE], mem.ram()[0x200..0x205]);
+        assert_eq!(0, mem.read(DISPLAY_LOC));
+
+        // cleared to the same content
+        mem.set_init(MemInit::Random(7));
+        let random = mem.ram().to_vec();
+        mem.write(0x300, !random[0x300]);
+        mem.clear();
+        assert_eq!(random, mem.ram());
+    }
+
+    #[test]
+    fn test_address_space() {
+        let mut mem = Memory::new();
+        mem.set_init(MemInit::Ff);
+        mem.write(0x300, 0x12);
+        mem.write(0xFFF, 0x34);
+        assert_eq!(0x12, mem.read_wrapped(0x1300));
+        assert!(mem.read_checked(0x1300).is_err());
+
+        mem.set_address_space(AddressSpace::XoChip);
+        assert_eq!(XO_CHIP_SIZE, mem.size());
+        // the pixels left the RAM, which is the program's
+        assert_eq!(DisplayBuffer::External, mem.display_buffer());
+        assert_eq!(0x34, mem.display_byte(0xFF));
+        mem.set_display_byte(0xFF, 0);
+        assert_eq!(0x34, mem.read(0xFFF));
+        assert_eq!(0x12, mem.read(0x300));
+        assert_eq!(0xFF, mem.read(0x1300));
+        mem.write_wrapped(0x1300, 0x56);
+        assert_eq!(0x56, mem.read_checked(0x1300).unwrap());
+        assert_eq!(0x12, mem.read(0x300));
+
+        // words wrap at the end of the address space
+        mem.write(0xFFFF, 0x78);
+        mem.write(0x0000, 0x9A);
+        assert_eq!(0x789A, mem.read_u16(0xFFFF));
+        assert_eq!(0x34FF, mem.read_u16(0xFFF));
+
+        let ram = mem.ram().to_vec();
+        mem.set_address_space(AddressSpace::Classic);
+        assert_eq!(0x12, mem.read_wrapped(0x1300));
+        mem.restore_ram(&ram);
+        assert_eq!(AddressSpace::XoChip, mem.address_space());
+        assert_eq!(0x56, mem.read(0x1300));
+    }
+
     #[test]
     fn test_read_write() {
         let mut mem = Memory::new();
@@ -82,14 +606,67 @@ mod tests {
         assert_eq!(0xC042, mem.read_u16(0x0004));
     }
 
+    #[test]
+    fn test_read_write_checked() {
+        let mut mem = Memory::new();
+        assert_eq!(Ok(()), mem.write_checked(0x0FFF, 0x42));
+        assert_eq!(Ok(0x42), mem.read_checked(0x0FFF));
+        assert_eq!(
+            Err(MemError::OutOfBounds { addr: 0x1000 }),
+            mem.read_checked(0x1000)
+        );
+        assert_eq!(
+            Err(MemError::OutOfBounds { addr: 0x1000 }),
+            mem.write_checked(0x1000, 0x42)
+        );
+    }
+
+    #[test]
+    fn test_read_write_wrapped() {
+        let mut mem = Memory::new();
+        mem.write_wrapped(0x1004, 0xC0);
+        assert_eq!(0xC0, mem.read(0x0004));
+        assert_eq!(0xC0, mem.read_wrapped(0xF004));
+    }
+
+    #[test]
+    fn test_read_write_u16_boundary() {
+        let mut mem = Memory::new();
+        mem.write_u16(0x0FFF, 0xC042);
+        assert_eq!(0xC0, mem.read(0x0FFF));
+        assert_eq!(0x42, mem.read(0x0000));
+        assert_eq!(0xC042, mem.read_u16(0x0FFF));
+    }
+
+    #[test]
+    fn test_peripheral() {
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        let mut mem = Memory::new();
+        mem.map(0x0E00..=0x0E0F, Box::new(Latch(writes.clone())))
+            .unwrap();
+
+        mem.write_u16(0x0E02, 0xC042);
+        assert_eq!(vec![(2, 0xC0), (3, 0x42)], *writes.borrow());
+        assert_eq!(0x42, mem.read(0x0E05));
+        assert_eq!(0, mem.read(0x0E10));
+        assert_eq!(
+            Err(MemError::AlreadyMapped { addr: 0x0E0F }),
+            mem.map(0x0E0F..=0x0E1F, Box::new(Latch(writes.clone())))
+        );
+        assert_eq!(
+            vec![(0x0E00..=0x0E0F, "latch")],
+            mem.mappings().collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn test_load_prgm() {
         let data = [0x01, 0x02, 0x42, 0x04];
         let mut mem = Memory::new();
         mem.load_prog(&data);
 
-        for i in 0..data.len() {
-            assert_eq!(data[i], mem.read(PROG_LOC + i as u16));
+        for (i, byte) in data.iter().enumerate() {
+            assert_eq!(*byte, mem.read(PROG_LOC + i as u16));
         }
     }
 
@@ -115,10 +692,31 @@ mod tests {
         ];
 
         let mut mem = Memory::new();
-        mem.load_font(&font);
+        mem.load_font(&font, FONT_LOC);
 
-        for i in 0..font.len() {
-            assert_eq!(font[i], mem.read(FONT_LOC + i as u16));
+        for (i, byte) in font.iter().enumerate() {
+            assert_eq!(*byte, mem.read(FONT_LOC + i as u16));
         }
     }
+
+    #[test]
+    fn test_load_fonts() {
+        let map = MemoryMap {
+            small_font: 0x000,
+            big_font: 0x100,
+        };
+        let mut mem = Memory::new();
+        mem.load_fonts(&map);
+
+        assert_eq!(FONT[5], mem.read(map.small_char(1)));
+        assert_eq!(BIG_FONT[10], mem.read(map.big_char(1)));
+        assert_eq!(0x196, map.big_char(0xF));
+        // only the low nibble counts, FX29 with VX = FF points at the F glyph
+        assert_eq!(map.small_char(0xF), map.small_char(0xFF));
+
+        // the default fonts are next to each other
+        let [small, big] = MemoryMap::default().font_areas();
+        assert_eq!(small.end, big.start);
+        assert!(big.end <= PROG_LOC);
+    }
 }
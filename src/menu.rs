@@ -0,0 +1,492 @@
+use crate::display_settings::{self, DisplaySettings, FlickerMode, ScaleMode, MAX_FADE};
+use crate::keyconf::{self, Keymap};
+use crate::quirks::Profile;
+
+use winit::event::VirtualKeyCode;
+
+/// Keys navigating the menu
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+/// What the emulator should do after a menu key press
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MenuAction {
+    Resume,
+    Reset,
+    Profile(Profile),
+    Palette(usize), // index in the palette names given to `Menu::new`
+    Display(DisplaySettings),
+    SaveDisplay,
+    Keymap(Keymap), // to use and save
+    Quit,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Item {
+    Resume,
+    Reset,
+    Profile,
+    Palette,
+    Foreground,
+    Background,
+    Fade,
+    Scanlines,
+    Scale,
+    Flicker,
+    SaveDisplay,
+    Keys,
+    Quit,
+}
+
+const ITEMS: [Item; 13] = [
+    Item::Resume,
+    Item::Reset,
+    Item::Profile,
+    Item::Palette,
+    Item::Foreground,
+    Item::Background,
+    Item::Fade,
+    Item::Scanlines,
+    Item::Scale,
+    Item::Flicker,
+    Item::SaveDisplay,
+    Item::Keys,
+    Item::Quit,
+];
+
+/// CHIP-8 keys as laid out on the COSMAC VIP keypad
+const KEYPAD: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+/// Screen rebinding the keys, opened from the KEYS item
+struct Keypad {
+    selected: usize, // in KEYPAD
+    listening: bool, // for the physical key of the selected CHIP-8 key
+}
+
+/// Pause menu opened with Esc
+pub struct Menu {
+    open: bool,
+    selected: usize,
+    profile: Profile,
+    palette: usize,
+    palettes: Vec<String>,
+    display: DisplaySettings,
+    keymap: Keymap,
+    keypad: Option<Keypad>,
+}
+
+impl Menu {
+    pub fn new(profile: Profile, palettes: Vec<String>, display: DisplaySettings) -> Self {
+        Menu {
+            open: false,
+            selected: 0,
+            profile,
+            palette: 0,
+            palettes,
+            display,
+            keymap: Keymap::default(),
+            keypad: None,
+        }
+    }
+
+    /// Show display settings changed outside of the menu
+    pub fn set_display(&mut self, display: DisplaySettings) {
+        self.display = display;
+    }
+
+    /// Show the keys bound outside of the menu
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.selected = 0;
+        self.keypad = None;
+    }
+
+    /// Whether the next physical key pressed goes to `bind_key` rather than `handle_key`
+    pub fn is_binding(&self) -> bool {
+        self.keypad.as_ref().is_some_and(|keypad| keypad.listening)
+    }
+
+    /// Bind the CHIP-8 key selected on the keypad to a physical key, Esc cancels
+    pub fn bind_key(
+        &mut self,
+        scancode: u32,
+        virtualkeycode: Option<VirtualKeyCode>,
+    ) -> Option<MenuAction> {
+        let keypad = self.keypad.as_mut().filter(|keypad| keypad.listening)?;
+        keypad.listening = false;
+        if virtualkeycode == Some(VirtualKeyCode::Escape) {
+            return None;
+        }
+
+        let name = virtualkeycode
+            .and_then(keyconf::key_name)
+            .map_or_else(|| format!("key {}", scancode), str::to_string);
+        self.keymap.bind(KEYPAD[keypad.selected], scancode, &name);
+        Some(MenuAction::Keymap(self.keymap.clone()))
+    }
+
+    pub fn handle_key(&mut self, key: MenuKey) -> Option<MenuAction> {
+        if let Some(keypad) = &mut self.keypad {
+            match key {
+                _ if keypad.listening => (),
+                MenuKey::Back => self.keypad = None,
+                MenuKey::Up => keypad.selected = (keypad.selected + 12) % 16,
+                MenuKey::Down => keypad.selected = (keypad.selected + 4) % 16,
+                MenuKey::Left => {
+                    keypad.selected = keypad.selected / 4 * 4 + (keypad.selected + 3) % 4
+                }
+                MenuKey::Right => {
+                    keypad.selected = keypad.selected / 4 * 4 + (keypad.selected + 1) % 4
+                }
+                MenuKey::Select => keypad.listening = true,
+            }
+            return None;
+        }
+
+        let item = ITEMS[self.selected];
+
+        let action = match (key, item) {
+            (MenuKey::Back, _) => Some(MenuAction::Resume),
+            (MenuKey::Up, _) => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                None
+            }
+            (MenuKey::Down, _) => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+                None
+            }
+            (MenuKey::Select, Item::Resume) => Some(MenuAction::Resume),
+            (MenuKey::Select, Item::Reset) => Some(MenuAction::Reset),
+            (MenuKey::Select, Item::Quit) => Some(MenuAction::Quit),
+            (MenuKey::Select | MenuKey::Right, Item::Profile) => Some(self.cycle_profile(1)),
+            (MenuKey::Left, Item::Profile) => Some(self.cycle_profile(Profile::ALL.len() - 1)),
+            (MenuKey::Select | MenuKey::Right, Item::Palette) => self.cycle_palette(1),
+            (MenuKey::Left, Item::Palette) => {
+                self.cycle_palette(self.palettes.len().saturating_sub(1))
+            }
+            (MenuKey::Select | MenuKey::Right, Item::Foreground) => {
+                self.adjust(|d| d.foreground = display_settings::cycle_color(d.foreground, 1))
+            }
+            (MenuKey::Left, Item::Foreground) => {
+                self.adjust(|d| d.foreground = display_settings::cycle_color(d.foreground, -1))
+            }
+            (MenuKey::Select | MenuKey::Right, Item::Background) => {
+                self.adjust(|d| d.background = display_settings::cycle_color(d.background, 1))
+            }
+            (MenuKey::Left, Item::Background) => {
+                self.adjust(|d| d.background = display_settings::cycle_color(d.background, -1))
+            }
+            (MenuKey::Select | MenuKey::Right, Item::Fade) => {
+                self.adjust(|d| d.fade = (d.fade + 1).min(MAX_FADE))
+            }
+            (MenuKey::Left, Item::Fade) => self.adjust(|d| d.fade = d.fade.saturating_sub(1)),
+            (MenuKey::Select | MenuKey::Left | MenuKey::Right, Item::Scanlines) => {
+                self.adjust(|d| d.scanlines = !d.scanlines)
+            }
+            (MenuKey::Select | MenuKey::Left | MenuKey::Right, Item::Scale) => self.adjust(|d| {
+                d.scale = match d.scale {
+                    ScaleMode::Snap => ScaleMode::Free,
+                    ScaleMode::Free => ScaleMode::Snap,
+                }
+            }),
+            (MenuKey::Select | MenuKey::Right, Item::Flicker) => self.adjust(|d| {
+                d.flicker = match d.flicker {
+                    FlickerMode::Smooth => FlickerMode::Authentic,
+                    FlickerMode::Authentic => FlickerMode::NoFlicker,
+                    FlickerMode::NoFlicker => FlickerMode::Smooth,
+                }
+            }),
+            (MenuKey::Left, Item::Flicker) => self.adjust(|d| {
+                d.flicker = match d.flicker {
+                    FlickerMode::Smooth => FlickerMode::NoFlicker,
+                    FlickerMode::Authentic => FlickerMode::Smooth,
+                    FlickerMode::NoFlicker => FlickerMode::Authentic,
+                }
+            }),
+            (MenuKey::Select, Item::SaveDisplay) => Some(MenuAction::SaveDisplay),
+            (MenuKey::Select, Item::Keys) => {
+                self.keypad = Some(Keypad {
+                    selected: 0,
+                    listening: false,
+                });
+                None
+            }
+            (MenuKey::Left | MenuKey::Right, _) => None,
+        };
+
+        if let Some(MenuAction::Resume | MenuAction::Reset | MenuAction::Quit) = action {
+            self.open = false;
+        }
+
+        action
+    }
+
+    /// Text of the menu, the selected item is marked with '>'
+    pub fn lines(&self) -> Vec<String> {
+        if let Some(keypad) = &self.keypad {
+            return self.keypad_lines(keypad);
+        }
+
+        let mut lines = vec!["PAUSED".to_string(), String::new()];
+
+        for (i, item) in ITEMS.iter().enumerate() {
+            let label = match item {
+                Item::Resume => "RESUME".to_string(),
+                Item::Reset => "RESET".to_string(),
+                Item::Profile => format!("PROFILE < {} >", self.profile.name()),
+                Item::Palette => format!("PALETTE < {} >", self.palettes[self.palette]),
+                Item::Foreground => format!("FOREGROUND < {} >", color(self.display.foreground)),
+                Item::Background => format!("BACKGROUND < {} >", color(self.display.background)),
+                Item::Fade => format!("FADE < {} >", self.display.fade),
+                Item::Scanlines => format!("SCANLINES < {} >", on_off(self.display.scanlines)),
+                Item::Scale => format!("SCALE < {} >", self.display.scale.name()),
+                Item::Flicker => format!("FLICKER < {} >", self.display.flicker.name()),
+                Item::SaveDisplay => "SAVE DISPLAY".to_string(),
+                Item::Keys => "KEYS".to_string(),
+                Item::Quit => "QUIT".to_string(),
+            };
+            let marker = if i == self.selected { "> " } else { "  " };
+            lines.push(format!("{}{}", marker, label));
+        }
+
+        lines
+    }
+
+    /// The keypad with the physical key of each CHIP-8 key
+    fn keypad_lines(&self, keypad: &Keypad) -> Vec<String> {
+        let mut lines = vec!["KEYS".to_string(), String::new()];
+
+        for row in 0..4 {
+            let cells: Vec<String> = (row * 4..row * 4 + 4)
+                .map(|i| {
+                    let marker = if i == keypad.selected { '>' } else { ' ' };
+                    let name: String = self.keymap.key_name(KEYPAD[i]).chars().take(5).collect();
+                    format!("{}{:X} {:<5}", marker, KEYPAD[i], name.to_uppercase())
+                })
+                .collect();
+            lines.push(cells.join(" ").trim_end().to_string());
+        }
+
+        lines.push(String::new());
+        lines.push(match keypad.listening {
+            true => format!("PRESS A KEY FOR {:X}", KEYPAD[keypad.selected]),
+            false => "ENTER: REBIND  ESC: BACK".to_string(),
+        });
+        lines
+    }
+
+    /// Change the display settings, applied at once
+    fn adjust<F: FnOnce(&mut DisplaySettings)>(&mut self, change: F) -> Option<MenuAction> {
+        change(&mut self.display);
+        Some(MenuAction::Display(self.display))
+    }
+
+    fn cycle_profile(&mut self, offset: usize) -> MenuAction {
+        let index = Profile::ALL
+            .iter()
+            .position(|p| *p == self.profile)
+            .unwrap();
+        self.profile = Profile::ALL[(index + offset) % Profile::ALL.len()];
+
+        MenuAction::Profile(self.profile)
+    }
+
+    fn cycle_palette(&mut self, offset: usize) -> Option<MenuAction> {
+        if self.palettes.is_empty() {
+            return None;
+        }
+
+        self.palette = (self.palette + offset) % self.palettes.len();
+        Some(MenuAction::Palette(self.palette))
+    }
+}
+
+/// Color of the display settings, None keeping the one of the palette
+fn color(color: Option<[u8; 3]>) -> String {
+    color.map_or("PALETTE".to_string(), display_settings::color_name)
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_navigation() {
+        let mut menu = Menu::new(
+            Profile::Modern,
+            vec!["green".into(), "amber".into()],
+            DisplaySettings::default(),
+        );
+        menu.open();
+
+        assert_eq!(None, menu.handle_key(MenuKey::Down));
+        assert_eq!(None, menu.handle_key(MenuKey::Down));
+        assert_eq!(
+            Some(MenuAction::Profile(Profile::Vip)),
+            menu.handle_key(MenuKey::Right)
+        );
+        assert_eq!(
+            Some(MenuAction::Profile(Profile::Modern)),
+            menu.handle_key(MenuKey::Left)
+        );
+        assert_eq!(
+            Some(MenuAction::Profile(Profile::XoChip)),
+            menu.handle_key(MenuKey::Left)
+        );
+
+        menu.handle_key(MenuKey::Down);
+        assert_eq!(
+            Some(MenuAction::Palette(1)),
+            menu.handle_key(MenuKey::Select)
+        );
+        assert!(menu.lines().contains(&"> PALETTE < amber >".to_string()));
+        assert!(menu.is_open());
+
+        // wraps from the first item to the last one
+        menu.open();
+        menu.handle_key(MenuKey::Up);
+        assert_eq!(Some(MenuAction::Quit), menu.handle_key(MenuKey::Select));
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn test_display_settings() {
+        let mut menu = Menu::new(
+            Profile::Modern,
+            vec!["green".into()],
+            DisplaySettings::default(),
+        );
+        menu.open();
+        for _ in 0..4 {
+            menu.handle_key(MenuKey::Down);
+        }
+
+        let Some(MenuAction::Display(settings)) = menu.handle_key(MenuKey::Right) else {
+            panic!("foreground not changed");
+        };
+        assert_eq!(Some(display_settings::COLORS[0].1), settings.foreground);
+        assert!(menu.lines().contains(&"> FOREGROUND < black >".to_string()));
+
+        menu.handle_key(MenuKey::Down);
+        menu.handle_key(MenuKey::Down);
+        assert_eq!(
+            Some(MenuAction::Display(DisplaySettings {
+                fade: 0,
+                ..settings
+            })),
+            menu.handle_key(MenuKey::Left)
+        );
+        for _ in 0..12 {
+            menu.handle_key(MenuKey::Right);
+        }
+        assert!(menu.lines().contains(&"> FADE < 9 >".to_string()));
+
+        menu.handle_key(MenuKey::Down);
+        menu.handle_key(MenuKey::Select);
+        menu.handle_key(MenuKey::Down);
+        let Some(MenuAction::Display(settings)) = menu.handle_key(MenuKey::Left) else {
+            panic!("scale mode not changed");
+        };
+        assert!(settings.scanlines);
+        assert_eq!(ScaleMode::Free, settings.scale);
+
+        menu.handle_key(MenuKey::Down);
+        let Some(MenuAction::Display(settings)) = menu.handle_key(MenuKey::Right) else {
+            panic!("flicker mode not changed");
+        };
+        assert_eq!(FlickerMode::Authentic, settings.flicker);
+        assert!(menu
+            .lines()
+            .contains(&"> FLICKER < authentic >".to_string()));
+        let Some(MenuAction::Display(settings)) = menu.handle_key(MenuKey::Left) else {
+            panic!("flicker mode not changed");
+        };
+        assert_eq!(FlickerMode::Smooth, settings.flicker);
+        let Some(MenuAction::Display(settings)) = menu.handle_key(MenuKey::Left) else {
+            panic!("flicker mode not changed");
+        };
+        assert_eq!(FlickerMode::NoFlicker, settings.flicker);
+
+        menu.handle_key(MenuKey::Down);
+        assert_eq!(
+            Some(MenuAction::SaveDisplay),
+            menu.handle_key(MenuKey::Select)
+        );
+        assert!(menu.is_open());
+    }
+
+    #[test]
+    fn test_keypad() {
+        let mut menu = Menu::new(
+            Profile::Modern,
+            vec!["green".into()],
+            DisplaySettings::default(),
+        );
+        menu.open();
+        menu.handle_key(MenuKey::Up);
+        menu.handle_key(MenuKey::Up);
+        assert_eq!(None, menu.handle_key(MenuKey::Select));
+        assert_eq!(">1 1      2 2      3 3      C 4", menu.lines()[2]);
+
+        // from 1 to 5
+        menu.handle_key(MenuKey::Down);
+        menu.handle_key(MenuKey::Right);
+        menu.handle_key(MenuKey::Select);
+        assert!(menu.is_binding());
+        assert_eq!("PRESS A KEY FOR 5", menu.lines()[7]);
+        assert_eq!(None, menu.handle_key(MenuKey::Back));
+
+        let Some(MenuAction::Keymap(keymap)) = menu.bind_key(57, Some(VirtualKeyCode::Space))
+        else {
+            panic!("key not bound");
+        };
+        assert_eq!("space", keymap.key_name(0x5));
+        assert!(!menu.is_binding());
+        assert_eq!(" 4 Q     >5 SPACE  6 E      D R", menu.lines()[3]);
+
+        // Esc cancels, then goes back to the menu
+        menu.handle_key(MenuKey::Select);
+        assert_eq!(None, menu.bind_key(1, Some(VirtualKeyCode::Escape)));
+        menu.handle_key(MenuKey::Back);
+        assert!(menu.lines().contains(&"> KEYS".to_string()));
+        assert!(menu.is_open());
+    }
+
+    #[test]
+    fn test_back_resumes() {
+        let mut menu = Menu::new(
+            Profile::Modern,
+            vec!["green".into()],
+            DisplaySettings::default(),
+        );
+        menu.open();
+
+        assert_eq!(Some(MenuAction::Resume), menu.handle_key(MenuKey::Back));
+        assert!(!menu.is_open());
+    }
+}
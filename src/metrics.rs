@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// Counters of the work done by a machine since it was created or the counters were reset.
+/// Resets of the program keep them, only `Chip8::reset_metrics` clears them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Metrics {
+    pub instructions: u64, // executed, the cycles waiting for the next frame excluded
+    pub frames: u64,       // 60 Hz frames ended
+    pub draws: u64,        // DXYN executed
+    pub collisions: u64,   // DXYN erasing at least a pixel
+    pub key_waits: u64,    // FX0A executed without a key, each one a cycle waiting
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Metrics::new();
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} instructions, {} frames, {} draws, {} collisions, {} key waits",
+            self.instructions, self.frames, self.draws, self.collisions, self.key_waits
+        )
+    }
+}
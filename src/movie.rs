@@ -0,0 +1,274 @@
+use crate::chip8::Chip8;
+use crate::info;
+use crate::interpreter::KeyEvent;
+use crate::memory::AddressSpace;
+use crate::quirks::Profile;
+use crate::timing::{Jitter, TimerMode};
+
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::path::Path;
+
+/// Version of the movie format, movies of another version are refused
+pub const VERSION: u32 = 1;
+
+/// A recorded run to reproduce a bug exactly: the machine at the start (memory, settings,
+/// random seed) and the key presses and resets of each frame. Movies start from power-on.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Movie {
+    pub version: u32,
+    pub rom_name: String,
+    pub rom_sha1: String,
+    pub profile: Profile,
+    pub timer_mode: TimerMode,
+    pub stack_depth: usize,
+    pub cycles_per_frame: u32,
+    pub key_release_delay: u8,
+    pub seed: u64,
+    #[serde(default)]
+    pub jitter: Option<Jitter>,
+    pub frames: u64, // recorded length
+    #[serde(with = "hex")]
+    pub program: Vec<u8>, // reloaded on reset
+    #[serde(with = "hex")]
+    pub ram: Vec<u8>, // at the start
+    #[serde(default)]
+    pub events: Vec<MovieEvent>,
+}
+
+/// Input of a movie, applied before the frame after `frame` frames were played
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MovieEvent {
+    Key { frame: u64, key: u8, down: bool },
+    Reset { frame: u64 },
+}
+
+impl MovieEvent {
+    pub fn frame(&self) -> u64 {
+        match self {
+            MovieEvent::Key { frame, .. } | MovieEvent::Reset { frame } => *frame,
+        }
+    }
+}
+
+impl Movie {
+    /// Movie starting from the current state of `chip8`, a program just loaded, whose
+    /// interpreter was seeded with `seed`
+    pub fn new(
+        chip8: &Chip8,
+        rom_name: &str,
+        profile: Profile,
+        cycles_per_frame: u32,
+        key_release_delay: u8,
+        seed: u64,
+    ) -> Self {
+        Movie {
+            version: VERSION,
+            rom_name: rom_name.to_string(),
+            rom_sha1: info::sha1_hex(chip8.program()),
+            profile,
+            timer_mode: chip8.interpreter.timer_mode(),
+            stack_depth: chip8.interpreter.stack_depth(),
+            cycles_per_frame,
+            key_release_delay,
+            seed,
+            jitter: chip8.jitter(),
+            frames: 0,
+            program: chip8.program().to_vec(),
+            ram: chip8.memory.ram().to_vec(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let text = std::fs::read_to_string(path)?;
+        let movie: Movie = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if movie.version != VERSION {
+            return Err(invalid(format!(
+                "movie format version {}, expected {}",
+                movie.version, VERSION
+            )));
+        }
+        if AddressSpace::from_size(movie.ram.len()).is_none() {
+            return Err(invalid(format!("{} bytes of memory", movie.ram.len())));
+        }
+
+        Ok(movie)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// Put `chip8` in the state the movie starts from, keeping the settings that do not
+    /// change how the program runs (executor, audio, ...)
+    pub fn start(&self, chip8: &mut Chip8) {
+        chip8.interpreter.set_quirks(self.profile.quirks());
+        chip8.interpreter.set_timer_mode(self.timer_mode);
+        chip8.interpreter.set_stack_depth(self.stack_depth);
+        chip8
+            .interpreter
+            .set_key_release_delay(self.key_release_delay);
+        chip8.interpreter.set_seed(self.seed);
+        chip8.set_jitter(self.jitter);
+        chip8.memory.set_address_space(self.profile.address_space());
+        chip8.load(&self.program);
+        self.reset(chip8);
+    }
+
+    /// Reset `chip8` as the recording did, to the memory it started with: the fonts and
+    /// memory initialization of the player may differ from the recorder's
+    fn reset(&self, chip8: &mut Chip8) {
+        chip8.reset_hard();
+        chip8.memory.restore_ram(&self.ram);
+    }
+}
+
+/// Feeds the inputs of a movie to a machine started with `Movie::start`, frame by frame
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: u64,  // frames played
+    next: usize, // next event
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer {
+            movie,
+            frame: 0,
+            next: 0,
+        }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Apply the inputs coming before the next frame, returns false once the movie is over
+    pub fn play_frame(&mut self, chip8: &mut Chip8) -> bool {
+        if self.frame >= self.movie.frames {
+            return false;
+        }
+
+        while let Some(event) = self.movie.events.get(self.next) {
+            if event.frame() > self.frame {
+                break;
+            }
+            match *event {
+                MovieEvent::Key { key, down, .. } => chip8.push_key_event(KeyEvent { key, down }),
+                MovieEvent::Reset { .. } => self.movie.reset(chip8),
+            }
+            self.next += 1;
+        }
+
+        self.frame += 1;
+        true
+    }
+}
+
+/// Bytes as a hexadecimal string, to keep movies readable text
+pub(crate) mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let text: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        serializer.serialize_str(&text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        if text.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hexadecimal digits"));
+        }
+        (0..text.len())
+            .step_by(2)
+            .map(|i| {
+                text.get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| D::Error::custom("invalid hexadecimal digits"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Fonts;
+    use crate::framebuffer::FrameBuffer;
+    use crate::plugin::movie::MovieRecorder;
+    use crate::rom_builder::*;
+
+    // draws a random sprite at the position of the last key pressed
+    fn program() -> Vec<u8> {
+        [
+            ld_vx_k(0),   // 200
+            rnd(1, 0xFF), // 202
+            ld_i(0x300),  // 204
+            store(1),     // 206: sprite = V0, V1
+            drw(0, 0, 2), // 208
+            jp(0x200),    // 20A
+        ]
+        .concat()
+    }
+
+    fn press(chip8: &mut Chip8, key: u8) {
+        chip8.push_key_event(KeyEvent { key, down: true });
+        chip8.run_frame(11);
+        chip8.frame();
+        chip8.push_key_event(KeyEvent { key, down: false });
+        chip8.run_frame(11);
+        chip8.frame();
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut chip8 = Chip8::new();
+        chip8.interpreter.set_seed(42);
+        let jitter = Jitter {
+            percent: 50,
+            seed: 3,
+        };
+        chip8.set_jitter(Some(jitter));
+        chip8.load(&program());
+        let recorder =
+            MovieRecorder::new(Movie::new(&chip8, "test.ch8", Profile::Modern, 11, 0, 42));
+        let movie = recorder.movie();
+        chip8.register_plugin(Box::new(recorder));
+
+        for key in [3, 7, 12] {
+            press(&mut chip8, key);
+        }
+        chip8.reset_hard();
+        press(&mut chip8, 5);
+        let expected = FrameBuffer::from_memory(&chip8.memory);
+
+        let path = std::env::temp_dir().join(format!("chip8-test-{}.c8m", std::process::id()));
+        movie.borrow().save(&path).unwrap();
+        let movie = Movie::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(8, movie.frames);
+        assert_eq!(MovieEvent::Reset { frame: 6 }, movie.events[6]);
+
+        // the fonts of the player are not the ones of the movie, resets included
+        let mut replay = Chip8::new();
+        replay.set_fonts(Fonts::from_bytes(&[0xFF; 80]).unwrap());
+        movie.start(&mut replay);
+        let mut player = MoviePlayer::new(movie);
+        while player.play_frame(&mut replay) {
+            replay.run_frame(11);
+            replay.frame();
+        }
+
+        assert_eq!(expected, FrameBuffer::from_memory(&replay.memory));
+        assert_eq!(chip8.interpreter.vx, replay.interpreter.vx);
+        assert_eq!(chip8.memory.ram(), replay.memory.ram());
+        assert_eq!(Some(jitter), replay.jitter());
+    }
+}
@@ -0,0 +1,416 @@
+use std::fmt::Write;
+
+/// Instruction set defining an instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Extension {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl Extension {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Extension::Chip8 => "CHIP-8",
+            Extension::Schip => "SUPER-CHIP",
+            Extension::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+/// Opcode with its operands extracted
+#[derive(Clone, Copy, Debug)]
+pub struct Operands {
+    pub opcode: u16,
+    pub x: usize,
+    pub y: usize,
+    pub n: u8,
+    pub nn: u8,
+    pub nnn: u16,
+}
+
+impl Operands {
+    pub fn new(opcode: u16) -> Self {
+        Operands {
+            opcode,
+            x: ((opcode >> 8) & 0xF) as usize,
+            y: ((opcode >> 4) & 0xF) as usize,
+            n: (opcode & 0xF) as u8,
+            nn: (opcode & 0xFF) as u8,
+            nnn: opcode & 0xFFF,
+        }
+    }
+}
+
+/// What an instruction does, one per row of the instruction table, for `Interpreter::execute`
+/// to dispatch on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Halt,
+    Clear,
+    Return,
+    ScrollDown,
+    ScrollUp,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    LowRes,
+    HighRes,
+    Jump,
+    Call,
+    SkipEqual,
+    SkipNotEqual,
+    SkipEqualRegister,
+    SaveRange,
+    LoadRange,
+    Set,
+    Add,
+    Copy,
+    Or,
+    And,
+    Xor,
+    AddRegister,
+    Sub,
+    ShiftRight,
+    SubReversed,
+    ShiftLeft,
+    SkipNotEqualRegister,
+    SetIndex,
+    JumpOffset,
+    Random,
+    DrawLarge,
+    Draw,
+    SkipKey,
+    SkipNotKey,
+    SetIndexLong,
+    Plane,
+    Audio,
+    GetDelay,
+    WaitKey,
+    SetDelay,
+    SetSound,
+    AddIndex,
+    Font,
+    BigFont,
+    Bcd,
+    Pitch,
+    Store,
+    Load,
+    SaveFlags,
+    LoadFlags,
+}
+
+/// Row of the instruction table
+pub struct Opcode {
+    pub mask: u16,             // bits fixed by the instruction
+    pub value: u16,            // their value
+    pub pattern: &'static str, // e.g. "8XY4"
+    /// e.g. "ADD {vx}, {vy}": {vx} and {vy} are registers, {nnn} and {nn} hexadecimal,
+    /// {n} and {x} decimal
+    pub mnemonic: &'static str,
+    pub extension: Extension,
+    /// Approximate COSMAC VIP machine cycles, without the part varying with the operands
+    /// (sprite rows, registers stored...), None for the instructions the VIP did not have
+    pub cycles: Option<u16>,
+    pub operation: Operation,
+}
+
+impl Opcode {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+
+    /// Mnemonic with the operands of `opcode`, e.g. "ADD V1, V2"
+    pub fn format(&self, opcode: u16) -> String {
+        format_operands(self.mnemonic, opcode)
+    }
+
+    /// Mnemonic with the operands named, e.g. "ADD VX, VY"
+    pub fn syntax(&self) -> String {
+        self.mnemonic
+            .replace("{vx}", "VX")
+            .replace("{vy}", "VY")
+            .replace("{nnn}", "NNN")
+            .replace("{nn}", "NN")
+            .replace("{n}", "N")
+            .replace("{x}", "X")
+    }
+}
+
+/// `template` with the operands of `opcode` in place of its {vx}, {vy}, {nnn}, {nn}, {n}
+/// and {x}, as in the mnemonics
+pub fn format_operands(template: &str, opcode: u16) -> String {
+    let op = Operands::new(opcode);
+    template
+        .replace("{vx}", &format!("V{:X}", op.x))
+        .replace("{vy}", &format!("V{:X}", op.y))
+        .replace("{nnn}", &format!("{:03X}", op.nnn))
+        .replace("{nn}", &format!("{:02X}", op.nn))
+        .replace("{n}", &op.n.to_string())
+        .replace("{x}", &op.x.to_string())
+}
+
+/// Shorthand for the table rows
+const fn op(
+    mask: u16,
+    value: u16,
+    pattern: &'static str,
+    mnemonic: &'static str,
+    extension: Extension,
+    cycles: Option<u16>,
+    operation: Operation,
+) -> Opcode {
+    Opcode {
+        mask,
+        value,
+        pattern,
+        mnemonic,
+        extension,
+        cycles,
+        operation,
+    }
+}
+
+use Extension::*;
+use Operation::*;
+
+/// Every known instruction. The first row matching an opcode decodes it, so the more
+/// specific rows come first (DXY0 before DXYN).
+#[rustfmt::skip]
+pub const OPCODES: [Opcode; 52] = [
+    op(0xFFFF, 0x0000, "0000", "HALT", Chip8, None, Halt),
+    op(0xFFFF, 0x00E0, "00E0", "CLS", Chip8, Some(24), Clear),
+    op(0xFFFF, 0x00EE, "00EE", "RET", Chip8, Some(10), Return),
+    op(0xFFF0, 0x00C0, "00CN", "SCD {n}", Schip, None, ScrollDown),
+    op(0xFFF0, 0x00D0, "00DN", "SCU {n}", XoChip, None, ScrollUp),
+    op(0xFFFF, 0x00FB, "00FB", "SCR", Schip, None, ScrollRight),
+    op(0xFFFF, 0x00FC, "00FC", "SCL", Schip, None, ScrollLeft),
+    op(0xFFFF, 0x00FD, "00FD", "EXIT", Schip, None, Exit),
+    op(0xFFFF, 0x00FE, "00FE", "LOW", Schip, None, LowRes),
+    op(0xFFFF, 0x00FF, "00FF", "HIGH", Schip, None, HighRes),
+    op(0xF000, 0x1000, "1NNN", "JP {nnn}", Chip8, Some(12), Jump),
+    op(0xF000, 0x2000, "2NNN", "CALL {nnn}", Chip8, Some(26), Call),
+    op(0xF000, 0x3000, "3XNN", "SE {vx}, {nn}", Chip8, Some(10), SkipEqual),
+    op(0xF000, 0x4000, "4XNN", "SNE {vx}, {nn}", Chip8, Some(10), SkipNotEqual),
+    op(0xF00F, 0x5000, "5XY0", "SE {vx}, {vy}", Chip8, Some(14), SkipEqualRegister),
+    op(0xF00F, 0x5002, "5XY2", "SAVE {vx}-{vy}", XoChip, None, SaveRange),
+    op(0xF00F, 0x5003, "5XY3", "LOAD {vx}-{vy}", XoChip, None, LoadRange),
+    op(0xF000, 0x6000, "6XNN", "LD {vx}, {nn}", Chip8, Some(6), Set),
+    op(0xF000, 0x7000, "7XNN", "ADD {vx}, {nn}", Chip8, Some(10), Add),
+    op(0xF00F, 0x8000, "8XY0", "LD {vx}, {vy}", Chip8, Some(44), Copy),
+    op(0xF00F, 0x8001, "8XY1", "OR {vx}, {vy}", Chip8, Some(44), Or),
+    op(0xF00F, 0x8002, "8XY2", "AND {vx}, {vy}", Chip8, Some(44), And),
+    op(0xF00F, 0x8003, "8XY3", "XOR {vx}, {vy}", Chip8, Some(44), Xor),
+    op(0xF00F, 0x8004, "8XY4", "ADD {vx}, {vy}", Chip8, Some(44), AddRegister),
+    op(0xF00F, 0x8005, "8XY5", "SUB {vx}, {vy}", Chip8, Some(44), Sub),
+    op(0xF00F, 0x8006, "8XY6", "SHR {vx}, {vy}", Chip8, Some(44), ShiftRight),
+    op(0xF00F, 0x8007, "8XY7", "SUBN {vx}, {vy}", Chip8, Some(44), SubReversed),
+    op(0xF00F, 0x800E, "8XYE", "SHL {vx}, {vy}", Chip8, Some(44), ShiftLeft),
+    op(0xF00F, 0x9000, "9XY0", "SNE {vx}, {vy}", Chip8, Some(14), SkipNotEqualRegister),
+    op(0xF000, 0xA000, "ANNN", "LD I, {nnn}", Chip8, Some(12), SetIndex),
+    op(0xF000, 0xB000, "BNNN", "JP V0, {nnn}", Chip8, Some(22), JumpOffset),
+    op(0xF000, 0xC000, "CXNN", "RND {vx}, {nn}", Chip8, Some(36), Random),
+    op(0xF00F, 0xD000, "DXY0", "DRW {vx}, {vy}, 0", Schip, None, DrawLarge),
+    op(0xF000, 0xD000, "DXYN", "DRW {vx}, {vy}, {n}", Chip8, Some(22), Draw),
+    op(0xF0FF, 0xE09E, "EX9E", "SKP {vx}", Chip8, Some(14), SkipKey),
+    op(0xF0FF, 0xE0A1, "EXA1", "SKNP {vx}", Chip8, Some(14), SkipNotKey),
+    op(0xFFFF, 0xF000, "F000", "LD I, long", XoChip, None, SetIndexLong),
+    op(0xF0FF, 0xF001, "FN01", "PLANE {x}", XoChip, None, Plane),
+    op(0xFFFF, 0xF002, "F002", "AUDIO", XoChip, None, Audio),
+    op(0xF0FF, 0xF007, "FX07", "LD {vx}, DT", Chip8, Some(10), GetDelay),
+    op(0xF0FF, 0xF00A, "FX0A", "LD {vx}, K", Chip8, Some(10), WaitKey),
+    op(0xF0FF, 0xF015, "FX15", "LD DT, {vx}", Chip8, Some(10), SetDelay),
+    op(0xF0FF, 0xF018, "FX18", "LD ST, {vx}", Chip8, Some(10), SetSound),
+    op(0xF0FF, 0xF01E, "FX1E", "ADD I, {vx}", Chip8, Some(16), AddIndex),
+    op(0xF0FF, 0xF029, "FX29", "LD F, {vx}", Chip8, Some(16), Font),
+    op(0xF0FF, 0xF030, "FX30", "LD HF, {vx}", Schip, None, BigFont),
+    op(0xF0FF, 0xF033, "FX33", "LD B, {vx}", Chip8, Some(80), Bcd),
+    op(0xF0FF, 0xF03A, "FX3A", "PITCH {vx}", XoChip, None, Pitch),
+    op(0xF0FF, 0xF055, "FX55", "LD [I], {vx}", Chip8, Some(14), Store),
+    op(0xF0FF, 0xF065, "FX65", "LD {vx}, [I]", Chip8, Some(14), Load),
+    op(0xF0FF, 0xF075, "FX75", "LD R, {vx}", Schip, None, SaveFlags),
+    op(0xF0FF, 0xF085, "FX85", "LD {vx}, R", Schip, None, LoadFlags),
+];
+
+lazy_static! {
+    /// Index of the row decoding each opcode, OPCODES.len() for the unknown ones
+    static ref ROWS: Vec<u8> = (0..=u16::MAX)
+        .map(|opcode| {
+            OPCODES
+                .iter()
+                .position(|row| row.matches(opcode))
+                .unwrap_or(OPCODES.len()) as u8
+        })
+        .collect();
+}
+
+/// Index in OPCODES of the row decoding an opcode, None for unknown opcodes
+pub fn index(opcode: u16) -> Option<usize> {
+    let row = ROWS[opcode as usize] as usize;
+    (row < OPCODES.len()).then_some(row)
+}
+
+/// Table row decoding an opcode, None for unknown opcodes
+pub fn lookup(opcode: u16) -> Option<&'static Opcode> {
+    index(opcode).map(|row| &OPCODES[row])
+}
+
+/// The instruction table, one instruction per line
+pub fn list() -> String {
+    let mut out = String::new();
+
+    for row in &OPCODES {
+        let cycles = row
+            .cycles
+            .map_or("-".to_string(), |cycles| cycles.to_string());
+        writeln!(
+            out,
+            "{}  {:<18} {:<10} {:>3}",
+            row.pattern,
+            row.syntax(),
+            row.extension.name(),
+            cycles
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::memory::Memory;
+
+    /// Pattern of an opcode decoded nibble by nibble, independently of the table
+    fn reference_pattern(opcode: u16) -> Option<&'static str> {
+        let [hi, lo] = opcode.to_be_bytes();
+        let nibbles = (hi >> 4, hi & 0xF, lo >> 4, lo & 0xF);
+
+        let pattern = match nibbles {
+            (0x0, 0x0, 0x0, 0x0) => "0000",
+            (0x0, 0x0, 0xE, 0x0) => "00E0",
+            (0x0, 0x0, 0xE, 0xE) => "00EE",
+            (0x0, 0x0, 0xC, _) => "00CN",
+            (0x0, 0x0, 0xD, _) => "00DN",
+            (0x0, 0x0, 0xF, 0xB) => "00FB",
+            (0x0, 0x0, 0xF, 0xC) => "00FC",
+            (0x0, 0x0, 0xF, 0xD) => "00FD",
+            (0x0, 0x0, 0xF, 0xE) => "00FE",
+            (0x0, 0x0, 0xF, 0xF) => "00FF",
+            (0x1, ..) => "1NNN",
+            (0x2, ..) => "2NNN",
+            (0x3, ..) => "3XNN",
+            (0x4, ..) => "4XNN",
+            (0x5, _, _, 0x0) => "5XY0",
+            (0x5, _, _, 0x2) => "5XY2",
+            (0x5, _, _, 0x3) => "5XY3",
+            (0x6, ..) => "6XNN",
+            (0x7, ..) => "7XNN",
+            (0x8, _, _, 0x0) => "8XY0",
+            (0x8, _, _, 0x1) => "8XY1",
+            (0x8, _, _, 0x2) => "8XY2",
+            (0x8, _, _, 0x3) => "8XY3",
+            (0x8, _, _, 0x4) => "8XY4",
+            (0x8, _, _, 0x5) => "8XY5",
+            (0x8, _, _, 0x6) => "8XY6",
+            (0x8, _, _, 0x7) => "8XY7",
+            (0x8, _, _, 0xE) => "8XYE",
+            (0x9, _, _, 0x0) => "9XY0",
+            (0xA, ..) => "ANNN",
+            (0xB, ..) => "BNNN",
+            (0xC, ..) => "CXNN",
+            (0xD, _, _, 0x0) => "DXY0",
+            (0xD, ..) => "DXYN",
+            (0xE, _, 0x9, 0xE) => "EX9E",
+            (0xE, _, 0xA, 0x1) => "EXA1",
+            (0xF, 0x0, 0x0, 0x0) => "F000",
+            (0xF, _, 0x0, 0x1) => "FN01",
+            (0xF, 0x0, 0x0, 0x2) => "F002",
+            (0xF, ..) => match lo {
+                0x07 => "FX07",
+                0x0A => "FX0A",
+                0x15 => "FX15",
+                0x18 => "FX18",
+                0x1E => "FX1E",
+                0x29 => "FX29",
+                0x30 => "FX30",
+                0x33 => "FX33",
+                0x3A => "FX3A",
+                0x55 => "FX55",
+                0x65 => "FX65",
+                0x75 => "FX75",
+                0x85 => "FX85",
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(pattern)
+    }
+
+    /// Every opcode decodes to the same instruction as the reference, with the same operands,
+    /// and the interpreter rejects exactly the opcodes the table does not have as CHIP-8 ones
+    #[test]
+    fn test_decode_all_opcodes() {
+        for opcode in 0..=u16::MAX {
+            let row = lookup(opcode);
+            assert_eq!(
+                reference_pattern(opcode),
+                row.map(|row| row.pattern),
+                "{:04X}",
+                opcode
+            );
+
+            let [hi, lo] = opcode.to_be_bytes();
+            let op = Operands::new(opcode);
+            assert_eq!(opcode, op.opcode);
+            assert_eq!((hi & 0xF) as usize, op.x, "{:04X}", opcode);
+            assert_eq!((lo >> 4) as usize, op.y, "{:04X}", opcode);
+            assert_eq!(lo & 0xF, op.n, "{:04X}", opcode);
+            assert_eq!(lo, op.nn, "{:04X}", opcode);
+            assert_eq!(opcode % 0x1000, op.nnn, "{:04X}", opcode);
+
+            let mut interpreter = Interpreter::new();
+            let mut memory = Memory::new();
+            interpreter.pc = 0x202;
+            interpreter.exec(opcode, &mut memory);
+            let unknown = matches!(
+                interpreter.error(),
+                Some(crate::error::Error::UnknownOpcode { .. })
+            );
+            // the extensions are decoded for the disassembler, the interpreter only executes
+            // DXY0 (drawing no rows) and FX30 of them
+            let executed = row.is_some_and(|row| {
+                row.extension == Chip8 || matches!(row.pattern, "DXY0" | "FX30")
+            });
+            assert_eq!(!executed, unknown, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!("8XY4", lookup(0x8124).unwrap().pattern);
+        assert_eq!("DXY0", lookup(0xD120).unwrap().pattern);
+        assert_eq!("DXYN", lookup(0xD125).unwrap().pattern);
+        assert!(lookup(0x8008).is_none());
+        assert!(lookup(0xF100).is_none());
+
+        let row = lookup(0x5123).unwrap();
+        assert_eq!("LOAD V1-V2", row.format(0x5123));
+        assert_eq!("LOAD VX-VY", row.syntax());
+        assert_eq!("PLANE 3", lookup(0xF301).unwrap().format(0xF301));
+        assert_eq!("JP V0, 2A4", lookup(0xB2A4).unwrap().format(0xB2A4));
+
+        // every pattern is reached by its own row, with N = 1 for DXYN, and has its own operation
+        for (i, row) in OPCODES.iter().enumerate() {
+            let opcode = row.value | (!row.mask & 0x0001);
+            assert_eq!(row.pattern, lookup(opcode).unwrap().pattern);
+            assert_eq!(Some(i), index(opcode));
+            assert!(OPCODES[..i]
+                .iter()
+                .all(|other| other.operation != row.operation));
+        }
+    }
+}
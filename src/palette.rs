@@ -0,0 +1,260 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+pub const NUM_COLORS: usize = 4;
+
+/// RGBA colors used to draw the display.
+/// Index 0 is the background (pixel off) and index 1 the foreground (pixel on).
+/// Indexes 2 and 3 are only meaningful for programs drawing on two planes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Palette {
+    pub colors: [[u8; 4]; NUM_COLORS],
+}
+
+/// Built-in palettes, the first one is the default
+pub const PRESETS: [(&str, Palette); 6] = [
+    (
+        "green",
+        Palette {
+            colors: [
+                [0x00, 0x00, 0x00, 0xFF],
+                [0x00, 0xFF, 0x00, 0xFF],
+                [0x00, 0x80, 0x00, 0xFF],
+                [0x80, 0xFF, 0x80, 0xFF],
+            ],
+        },
+    ),
+    (
+        "amber",
+        Palette {
+            colors: [
+                [0x1A, 0x10, 0x00, 0xFF],
+                [0xFF, 0xB0, 0x00, 0xFF],
+                [0x80, 0x58, 0x00, 0xFF],
+                [0xFF, 0xD8, 0x80, 0xFF],
+            ],
+        },
+    ),
+    (
+        "white",
+        Palette {
+            colors: [
+                [0x00, 0x00, 0x00, 0xFF],
+                [0xFF, 0xFF, 0xFF, 0xFF],
+                [0x80, 0x80, 0x80, 0xFF],
+                [0xC0, 0xC0, 0xC0, 0xFF],
+            ],
+        },
+    ),
+    (
+        "lcd",
+        Palette {
+            colors: [
+                [0x9B, 0xBC, 0x0F, 0xFF],
+                [0x0F, 0x38, 0x0F, 0xFF],
+                [0x30, 0x62, 0x30, 0xFF],
+                [0x8B, 0xAC, 0x0F, 0xFF],
+            ],
+        },
+    ),
+    (
+        "high-contrast",
+        Palette {
+            colors: [
+                [0x00, 0x00, 0x00, 0xFF],
+                [0xFF, 0xFF, 0x00, 0xFF],
+                [0x00, 0xFF, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0xFF, 0xFF],
+            ],
+        },
+    ),
+    (
+        // Okabe-Ito colors, distinguishable with the common color vision deficiencies
+        "colorblind",
+        Palette {
+            colors: [
+                [0x00, 0x00, 0x00, 0xFF],
+                [0xE6, 0x9F, 0x00, 0xFF],
+                [0x56, 0xB4, 0xE9, 0xFF],
+                [0xF0, 0xE4, 0x42, 0xFF],
+            ],
+        },
+    ),
+];
+
+impl Palette {
+    pub fn new() -> Self {
+        PRESETS[0].1
+    }
+
+    pub fn background(&self) -> [u8; 4] {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> [u8; 4] {
+        self.colors[1]
+    }
+
+    /// Background and foreground swapped, as well as the two plane colors
+    pub fn inverted(&self) -> Self {
+        let [off, on, plane, both] = self.colors;
+        Palette {
+            colors: [on, off, both, plane],
+        }
+    }
+
+    /// Load a palette from an image (.png) or a text file (.hex)
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => Palette::from_png(File::open(path)?),
+            Some("hex") => Palette::from_hex(&std::fs::read_to_string(path)?),
+            _ => Err(invalid_data("palette file must be a .png or .hex file")),
+        }
+    }
+
+    /// The first two (or four) pixels of the image, in reading order, define the colors
+    pub fn from_png<R: io::Read>(reader: R) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(invalid_data)?;
+
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(invalid_data)?;
+        let data = &buf[..info.buffer_size()];
+
+        let colors: Vec<[u8; 4]> = match info.color_type {
+            png::ColorType::Rgba => data
+                .chunks_exact(4)
+                .map(|c| [c[0], c[1], c[2], c[3]])
+                .collect(),
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .map(|c| [c[0], c[1], c[2], 0xFF])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .map(|c| [c[0], c[0], c[0], c[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().map(|&g| [g, g, g, 0xFF]).collect(),
+            png::ColorType::Indexed => return Err(invalid_data("indexed png was not expanded")),
+        };
+
+        Palette::from_colors(&colors)
+    }
+
+    /// Parse the `.hex` palette format: one `RRGGBB` color per line, `#` prefix optional
+    pub fn from_hex(text: &str) -> io::Result<Self> {
+        let mut colors = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim().trim_start_matches('#');
+            if line.is_empty() {
+                continue;
+            }
+
+            let rgb = Some(line)
+                .filter(|line| line.len() == 6)
+                .and_then(|line| u32::from_str_radix(line, 16).ok())
+                .ok_or_else(|| invalid_data(format!("invalid color in .hex palette: {}", line)))?;
+
+            colors.push([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF]);
+        }
+
+        Palette::from_colors(&colors)
+    }
+
+    /// Use four colors if available, otherwise the first two.
+    /// Missing colors keep their default value.
+    pub fn from_colors(colors: &[[u8; 4]]) -> io::Result<Self> {
+        let count = match colors.len() {
+            0 | 1 => return Err(invalid_data("palette needs at least two colors")),
+            2 | 3 => 2,
+            _ => NUM_COLORS,
+        };
+
+        let mut palette = Palette::new();
+        palette.colors[..count].copy_from_slice(&colors[..count]);
+
+        Ok(palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new()
+    }
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut data, width, height);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(rgb).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let palette = Palette::from_hex("1a1c2c\n#F4F4F4\n\n").unwrap();
+
+        assert_eq!([0x1A, 0x1C, 0x2C, 0xFF], palette.background());
+        assert_eq!([0xF4, 0xF4, 0xF4, 0xFF], palette.foreground());
+        assert_eq!(Palette::new().colors[2], palette.colors[2]);
+    }
+
+    #[test]
+    fn test_from_hex_four_colors() {
+        let palette = Palette::from_hex("000000\n111111\n222222\n333333\n444444").unwrap();
+
+        assert_eq!([0x33, 0x33, 0x33, 0xFF], palette.colors[3]);
+    }
+
+    #[test]
+    fn test_from_hex_invalid() {
+        assert!(Palette::from_hex("000000").is_err());
+        assert!(Palette::from_hex("000000\nGGGGGG").is_err());
+    }
+
+    #[test]
+    fn test_from_png() {
+        let png = encode_png(2, 1, &[0x10, 0x20, 0x30, 0xA0, 0xB0, 0xC0]);
+        let palette = Palette::from_png(png.as_slice()).unwrap();
+
+        assert_eq!([0x10, 0x20, 0x30, 0xFF], palette.background());
+        assert_eq!([0xA0, 0xB0, 0xC0, 0xFF], palette.foreground());
+    }
+
+    #[test]
+    fn test_from_png_three_pixels_uses_two() {
+        let png = encode_png(
+            3,
+            1,
+            &[0x10, 0x10, 0x10, 0x20, 0x20, 0x20, 0x30, 0x30, 0x30],
+        );
+        let palette = Palette::from_png(png.as_slice()).unwrap();
+
+        assert_eq!([0x20, 0x20, 0x20, 0xFF], palette.foreground());
+        assert_eq!(Palette::new().colors[2], palette.colors[2]);
+    }
+}
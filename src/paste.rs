@@ -0,0 +1,59 @@
+use crate::memory::{MAX_SIZE, PROG_LOC};
+
+/// Program from hexadecimal bytes separated by whitespace or commas, as printed in tutorials:
+/// `00E0 A22A`, `00 E0 A2 2A` or `0x00, 0xE0, 0xA2, 0x2A`
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut program = Vec::new();
+
+    for word in text.split(|c: char| c.is_whitespace() || c == ',') {
+        if word.is_empty() {
+            continue;
+        }
+
+        let digits = word.trim_start_matches("0x");
+        if digits.is_empty()
+            || digits.len() % 2 != 0
+            || !digits.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(format!("not hexadecimal bytes: {}", word));
+        }
+        for i in (0..digits.len()).step_by(2) {
+            program.push(u8::from_str_radix(&digits[i..i + 2], 16).unwrap());
+        }
+    }
+
+    if program.is_empty() {
+        return Err("no hexadecimal bytes".to_string());
+    }
+    if program.len() > (MAX_SIZE - PROG_LOC) as usize {
+        return Err(format!("{} bytes do not fit in memory", program.len()));
+    }
+
+    Ok(program)
+}
+
+/// Program from the hexadecimal text of the clipboard
+pub fn from_clipboard() -> Result<Vec<u8>, String> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| e.to_string())?;
+    parse_hex(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        let program = vec![0x00, 0xE0, 0xA2, 0x2A];
+
+        assert_eq!(Ok(program.clone()), parse_hex("00E0 A22A\n"));
+        assert_eq!(Ok(program.clone()), parse_hex("00 e0\ta2 2a"));
+        assert_eq!(Ok(program), parse_hex("0x00, 0xE0, 0xA2, 0x2A,"));
+        assert!(parse_hex("00E").is_err());
+        assert!(parse_hex("jump 200").is_err());
+        assert!(parse_hex(" \n").is_err());
+        assert!(parse_hex(&"00".repeat(0x1000)).is_err());
+    }
+}
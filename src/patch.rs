@@ -0,0 +1,279 @@
+use std::io;
+use std::path::Path;
+
+/// Apply an IPS or BPS patch to a program, the format being detected from the header
+pub fn apply(patch: &[u8], program: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(patch, program)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(patch, program)
+    } else {
+        Err(invalid("not an IPS nor BPS patch"))
+    }
+}
+
+pub fn apply_file(path: &Path, program: &[u8]) -> io::Result<Vec<u8>> {
+    apply(&std::fs::read(path)?, program)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads the fields of a patch, failing on a truncated one
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("truncated patch"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Big endian number of `len` bytes (IPS)
+    fn number(&mut self, len: usize) -> io::Result<usize> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    /// Variable length number (BPS)
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.bytes(1)?[0] as usize;
+            value = value
+                .checked_add((byte & 0x7F) * shift)
+                .ok_or_else(|| invalid("number too large"))?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_mul(128)
+                .ok_or_else(|| invalid("number too large"))?;
+            value += shift;
+        }
+    }
+}
+
+/// IPS: records of an offset and bytes to write there (or a byte repeated), up to `EOF`,
+/// optionally followed by the size to truncate to
+fn apply_ips(patch: &[u8], program: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = Reader {
+        data: patch,
+        pos: b"PATCH".len(),
+    };
+    let mut output = program.to_vec();
+
+    loop {
+        if reader.bytes(3)? == b"EOF" {
+            break;
+        }
+        reader.pos -= 3;
+
+        let offset = reader.number(3)?;
+        let bytes = match reader.number(2)? {
+            0 => {
+                let len = reader.number(2)?;
+                vec![reader.bytes(1)?[0]; len]
+            }
+            len => reader.bytes(len)?.to_vec(),
+        };
+
+        if output.len() < offset + bytes.len() {
+            output.resize(offset + bytes.len(), 0);
+        }
+        output[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    if let Ok(size) = reader.number(3) {
+        output.truncate(size);
+    }
+
+    Ok(output)
+}
+
+/// BPS: the output built by copying from the source, the patch or the output itself, checked
+/// against the CRC32 of the source and the output stored at the end
+fn apply_bps(patch: &[u8], source: &[u8]) -> io::Result<Vec<u8>> {
+    const FOOTER: usize = 12;
+    if patch.len() < b"BPS1".len() + FOOTER {
+        return Err(invalid("truncated patch"));
+    }
+
+    let footer = &patch[patch.len() - FOOTER..];
+    let crc = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != crc(8) {
+        return Err(invalid("corrupted patch"));
+    }
+    if crc32(source) != crc(0) {
+        return Err(invalid("patch made for another program"));
+    }
+
+    let mut reader = Reader {
+        data: &patch[..patch.len() - FOOTER],
+        pos: b"BPS1".len(),
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != source.len() {
+        return Err(invalid("patch made for another program"));
+    }
+
+    let mut output = Vec::with_capacity(target_size);
+    let (mut source_pos, mut target_pos) = (0usize, 0usize);
+    // relative offsets of the copies, signed
+    let offset = |reader: &mut Reader, pos: usize| -> io::Result<usize> {
+        let data = reader.varint()?;
+        let delta = (data >> 1) as isize * if data & 1 == 1 { -1 } else { 1 };
+        pos.checked_add_signed(delta)
+            .ok_or_else(|| invalid("copy before the start"))
+    };
+
+    while reader.pos < reader.data.len() {
+        let data = reader.varint()?;
+        let len = (data >> 2) + 1;
+        match data & 3 {
+            // source read
+            0 => {
+                let start = output.len();
+                let bytes = source
+                    .get(start..start + len)
+                    .ok_or_else(|| invalid("read beyond the source"))?;
+                output.extend_from_slice(bytes);
+            }
+            // target read
+            1 => output.extend_from_slice(reader.bytes(len)?),
+            // source copy
+            2 => {
+                source_pos = offset(&mut reader, source_pos)?;
+                let bytes = source
+                    .get(source_pos..source_pos + len)
+                    .ok_or_else(|| invalid("copy beyond the source"))?;
+                output.extend_from_slice(bytes);
+                source_pos += len;
+            }
+            // target copy, byte by byte as it may overlap what it writes
+            _ => {
+                target_pos = offset(&mut reader, target_pos)?;
+                for _ in 0..len {
+                    let byte = *output
+                        .get(target_pos)
+                        .ok_or_else(|| invalid("copy beyond the output"))?;
+                    output.push(byte);
+                    target_pos += 1;
+                }
+            }
+        }
+        if output.len() > target_size {
+            return Err(invalid("output larger than announced"));
+        }
+    }
+
+    if output.len() != target_size || crc32(&output) != crc(4) {
+        return Err(invalid("patched program does not match the patch"));
+    }
+
+    Ok(output)
+}
+
+/// CRC-32 (IEEE) as stored in BPS patches
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte | 0x80);
+                return bytes;
+            }
+            bytes.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(target.len()));
+        patch.extend(varint(0));
+        patch.extend_from_slice(actions);
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn test_ips() {
+        let program = [0x00, 0xE0, 0x12, 0x00];
+        #[rustfmt::skip]
+        let patch = [
+            b'P', b'A', b'T', b'C', b'H',
+            0x00, 0x00, 0x02, 0x00, 0x02, 0x12, 0x04, // 002: 1204
+            0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x02, 0xAA, // 004: AA repeated twice
+            b'E', b'O', b'F',
+        ];
+
+        assert_eq!(
+            vec![0x00, 0xE0, 0x12, 0x04, 0xAA, 0xAA],
+            apply(&patch, &program).unwrap()
+        );
+        assert!(apply(&patch[..patch.len() - 3], &program).is_err());
+
+        // truncated to 2 bytes
+        let truncate = [&patch[..], &[0x00, 0x00, 0x02]].concat();
+        assert_eq!(vec![0x00, 0xE0], apply(&truncate, &program).unwrap());
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = [0x00, 0xE0, 0x12, 0x00];
+        let target = [0x00, 0xE0, 0x00, 0xE0, 0x12, 0x00, 0x02, 0x02];
+        let actions = [
+            varint(1 << 2),     // source read of 2 bytes: 00E0
+            varint(1 << 2 | 3), // target copy of 2 bytes,
+            varint(0),          // from the start of the output: 00E0
+            varint(1 << 2 | 2), // source copy of 2 bytes,
+            varint(2 << 1),     // 2 bytes further in the source: 1200
+            varint(1 << 2 | 1), // target read of 2 bytes:
+            vec![0x02, 0x02],   // 0202
+        ]
+        .concat();
+        let mut patch = bps(&source, &target, &actions);
+
+        assert_eq!(target.to_vec(), apply(&patch, &source).unwrap());
+        assert!(apply(&patch, &[0x00, 0xE0, 0x12, 0x02]).is_err());
+
+        let last_action = patch.len() - 13;
+        patch[last_action] ^= 1;
+        assert!(apply(&patch, &source).is_err());
+    }
+}
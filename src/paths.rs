@@ -0,0 +1,167 @@
+use directories::ProjectDirs;
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory created next to the executable in portable mode
+const PORTABLE_DIR: &str = "chip8-data";
+
+/// Where files written by the interpreter go
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Paths {
+    config: PathBuf,
+    saves: PathBuf,
+    screenshots: PathBuf,
+    rpl: PathBuf, // SUPER-CHIP RPL user flags (FX75/FX85)
+}
+
+impl Paths {
+    /// Per-user directories: XDG on Linux, Application Support on macOS, AppData on Windows
+    pub fn user() -> Option<Self> {
+        let dirs = ProjectDirs::from("", "", "chip8-interpreter")?;
+        let data = dirs.data_dir();
+
+        Some(Paths {
+            config: dirs.config_dir().to_path_buf(),
+            saves: data.join("saves"),
+            screenshots: data.join("screenshots"),
+            rpl: data.join("rpl"),
+        })
+    }
+
+    /// Everything in one directory next to the executable (or next to the AppImage file,
+    /// since the executable itself is on a read-only mount)
+    pub fn portable() -> io::Result<Self> {
+        let executable = match std::env::var_os("APPIMAGE") {
+            Some(appimage) => PathBuf::from(appimage),
+            None => std::env::current_exe()?,
+        };
+        let dir = executable
+            .parent()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no executable directory"))?;
+
+        Ok(Paths::portable_in(dir))
+    }
+
+    pub fn portable_in(dir: &Path) -> Self {
+        let root = dir.join(PORTABLE_DIR);
+
+        Paths {
+            config: root.join("config"),
+            saves: root.join("saves"),
+            screenshots: root.join("screenshots"),
+            rpl: root.join("rpl"),
+        }
+    }
+
+    /// Portable directories if asked, otherwise the user directories.
+    /// Falls back to the working directory if neither can be found.
+    pub fn resolve(portable: bool) -> Self {
+        let paths = if portable {
+            Paths::portable().ok()
+        } else {
+            Paths::user()
+        };
+
+        paths.unwrap_or_else(|| Paths::portable_in(Path::new(".")))
+    }
+
+    pub fn config_dir(&self) -> &Path {
+        &self.config
+    }
+
+    pub fn save_dir(&self) -> &Path {
+        &self.saves
+    }
+
+    pub fn screenshot_dir(&self) -> &Path {
+        &self.screenshots
+    }
+
+    pub fn rpl_dir(&self) -> &Path {
+        &self.rpl
+    }
+
+    pub fn window_geometry(&self) -> PathBuf {
+        self.config.join("window.toml")
+    }
+
+    /// Colors, fade, scanlines and scale mode saved from the menu
+    pub fn display_settings(&self) -> PathBuf {
+        self.config.join("display.toml")
+    }
+
+    /// Templates of the names of the files saved in the screenshots directory
+    pub fn file_names(&self) -> PathBuf {
+        self.config.join("filenames.toml")
+    }
+
+    /// Format of the window title, for every program and for some of them
+    pub fn window_title(&self) -> PathBuf {
+        self.config.join("title.toml")
+    }
+
+    /// CHIP-8 keys rebound from the menu
+    pub fn keymap(&self) -> PathBuf {
+        self.config.join("keymap.toml")
+    }
+
+    /// Programs launched by name with `library run`, next to the saves directory
+    pub fn library(&self) -> PathBuf {
+        self.saves.with_file_name("library.toml")
+    }
+
+    /// Play time and launch counts, next to the saves directory
+    pub fn play_stats(&self) -> PathBuf {
+        self.saves.with_file_name("playtime.toml")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_layout() {
+        let paths = Paths::portable_in(Path::new("/opt/chip8"));
+
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config"),
+            paths.config_dir()
+        );
+        assert_eq!(Path::new("/opt/chip8/chip8-data/saves"), paths.save_dir());
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/screenshots"),
+            paths.screenshot_dir()
+        );
+        assert_eq!(Path::new("/opt/chip8/chip8-data/rpl"), paths.rpl_dir());
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config/window.toml"),
+            paths.window_geometry()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config/display.toml"),
+            paths.display_settings()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config/keymap.toml"),
+            paths.keymap()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config/title.toml"),
+            paths.window_title()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/config/filenames.toml"),
+            paths.file_names()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/playtime.toml"),
+            paths.play_stats()
+        );
+        assert_eq!(
+            Path::new("/opt/chip8/chip8-data/library.toml"),
+            paths.library()
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use crate::disassembler;
+
+use std::fmt;
+
+/// Instructions kept by `PcHistory`
+pub const PC_HISTORY_LEN: usize = 64;
+
+/// Address and opcode of the last instructions executed, cheap enough to always be kept.
+/// Shows how a program got to a wild jump or a fault without tracing every instruction.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PcHistory {
+    entries: [(u16, u16); PC_HISTORY_LEN],
+    next: usize, // where the next entry goes, the oldest one once full
+    len: usize,
+}
+
+impl PcHistory {
+    pub fn new() -> Self {
+        PcHistory {
+            entries: [(0, 0); PC_HISTORY_LEN],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, pc: u16, opcode: u16) {
+        self.entries[self.next] = (pc, opcode);
+        self.next = (self.next + 1) % PC_HISTORY_LEN;
+        self.len = (self.len + 1).min(PC_HISTORY_LEN);
+    }
+
+    /// Address and opcode of the instructions, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let start = (self.next + PC_HISTORY_LEN - self.len) % PC_HISTORY_LEN;
+        (0..self.len).map(move |i| self.entries[(start + i) % PC_HISTORY_LEN])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Default for PcHistory {
+    fn default() -> Self {
+        PcHistory::new()
+    }
+}
+
+/// One instruction per line, oldest first: `0204: 2300 CALL 300`
+impl fmt::Display for PcHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (pc, opcode) in self.iter() {
+            let mnemonic = disassembler::decode(opcode)
+                .map_or("unknown".to_string(), |instruction| instruction.mnemonic);
+            writeln!(f, "{:04X}: {:04X} {}", pc, opcode, mnemonic)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pc_history() {
+        let mut history = PcHistory::new();
+        assert!(history.is_empty());
+
+        for i in 0..PC_HISTORY_LEN as u16 + 2 {
+            history.push(0x200 + 2 * i, 0x6000 + i);
+        }
+        assert_eq!(PC_HISTORY_LEN, history.len());
+        assert_eq!(Some((0x204, 0x6002)), history.iter().next());
+        assert_eq!(Some((0x282, 0x6041)), history.iter().last());
+
+        let text = history.to_string();
+        assert!(text.starts_with("0204: 6002 LD V0, 02\n"));
+        assert_eq!(PC_HISTORY_LEN, text.lines().count());
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// Play statistics of one ROM
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct RomStats {
+    pub name: String, // file name of the last launch
+    pub launches: u32,
+    pub seconds: u64,     // emulated time, pauses excluded
+    pub last_played: u64, // unix timestamp
+}
+
+/// Play statistics of every launched ROM, keyed by the SHA-1 of the program.
+/// Only stored locally, nothing is ever sent anywhere.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct PlayStats {
+    #[serde(default)]
+    pub roms: BTreeMap<String, RomStats>,
+}
+
+impl PlayStats {
+    /// Returns empty statistics if the file is missing or invalid
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    /// Count a launch of `seconds` of play ending at `now` (unix timestamp)
+    pub fn record_session(&mut self, hash: &str, name: &str, seconds: u64, now: u64) {
+        let stats = self.roms.entry(hash.to_string()).or_default();
+
+        stats.name = name.to_string();
+        stats.launches += 1;
+        stats.seconds += seconds;
+        stats.last_played = now;
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&RomStats> {
+        self.roms.get(hash)
+    }
+}
+
+impl RomStats {
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "launches: {}", self.launches).unwrap();
+        writeln!(out, "play time: {}", format_duration(self.seconds)).unwrap();
+        writeln!(out, "last played: {}", format_date(self.last_played)).unwrap();
+
+        out
+    }
+}
+
+/// e.g. "1h 02m 03s"
+pub fn format_duration(seconds: u64) -> String {
+    format!(
+        "{}h {:02}m {:02}s",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// UTC date of a unix timestamp, e.g. "2024-02-29"
+pub fn format_date(timestamp: u64) -> String {
+    // days since 1970-01-01 to civil date (Howard Hinnant's algorithm)
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_session() {
+        let mut stats = PlayStats::default();
+        stats.record_session("abc", "pong.ch8", 90, 1000);
+        stats.record_session("abc", "pong2.ch8", 30, 2000);
+
+        let rom = stats.get("abc").unwrap();
+        assert_eq!("pong2.ch8", rom.name);
+        assert_eq!(2, rom.launches);
+        assert_eq!(120, rom.seconds);
+        assert_eq!(2000, rom.last_played);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let path = std::env::temp_dir()
+            .join("chip8-interpreter-test-playtime")
+            .join("playtime.toml");
+        let mut stats = PlayStats::default();
+        stats.record_session("0123abcd", "tetris.ch8", 3723, 1_709_164_800);
+
+        stats.save(&path).unwrap();
+        assert_eq!(stats, PlayStats::load(&path));
+        assert_eq!(
+            "launches: 1\nplay time: 1h 02m 03s\nlast played: 2024-02-29\n",
+            stats.get("0123abcd").unwrap().report()
+        );
+    }
+}
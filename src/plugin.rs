@@ -0,0 +1,54 @@
+use crate::interpreter::{Interpreter, KeyEvent};
+use crate::memory::Memory;
+
+pub mod audit;
+pub mod cheat;
+pub mod coverage;
+pub mod crash;
+pub mod keys;
+pub mod movie;
+pub mod perf;
+pub mod script;
+pub mod speedrun;
+pub mod strict;
+pub mod trace;
+pub mod uninit;
+
+/// Hooks called by the emulator (see `Chip8::register_plugin`).
+/// Every hook does nothing by default.
+pub trait Plugin {
+    /// Called after a program is loaded in memory
+    fn on_load(&mut self, _program: &[u8], _memory: &mut Memory) {}
+
+    /// Called at the end of every 60 Hz frame
+    fn on_frame(&mut self, _interpreter: &mut Interpreter, _memory: &mut Memory) {}
+
+    /// Called before the instruction at `pc` is executed
+    fn on_instruction(
+        &mut self,
+        _pc: u16,
+        _opcode: u16,
+        _interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+    }
+
+    /// Called after the instruction at `pc` is executed, with the state it left
+    fn after_instruction(
+        &mut self,
+        _pc: u16,
+        _opcode: u16,
+        _interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+    }
+
+    /// Called when a CHIP-8 key is pressed or released, before the program sees it
+    fn on_key(&mut self, _key: usize, _pressed: bool) {}
+
+    /// Keys the plugin pressed or released during `on_frame`, pushed right after it as the
+    /// keyboard's are, the other plugins seeing them through `on_key`
+    fn take_key_events(&mut self) -> Vec<KeyEvent> {
+        Vec::new()
+    }
+}
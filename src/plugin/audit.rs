@@ -0,0 +1,163 @@
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Arithmetic edge behavior whose result differs between interpreters or quirk settings
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ArithmeticEdge {
+    AddOverflow,   // 8XY4 carries past 0xFF
+    SubUnderflow,  // 8XY5 borrows, VX < VY
+    SubnUnderflow, // 8XY7 borrows, VY < VX
+    IndexOverflow, // FX1E sets I past the end of memory
+}
+
+impl fmt::Display for ArithmeticEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ArithmeticEdge::AddOverflow => "8XY4 overflow",
+            ArithmeticEdge::SubUnderflow => "8XY5 underflow",
+            ArithmeticEdge::SubnUnderflow => "8XY7 underflow",
+            ArithmeticEdge::IndexOverflow => "FX1E past the end of memory",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// How often each instruction hit an arithmetic edge, by address
+#[derive(Default)]
+pub struct EdgeCounts {
+    counts: BTreeMap<(u16, ArithmeticEdge), u64>,
+}
+
+impl EdgeCounts {
+    pub fn count(&self, pc: u16, edge: ArithmeticEdge) -> u64 {
+        self.counts.get(&(pc, edge)).copied().unwrap_or(0)
+    }
+
+    pub fn report(&self) -> String {
+        if self.counts.is_empty() {
+            return "no arithmetic edge hit".to_string();
+        }
+
+        let lines: Vec<String> = self
+            .counts
+            .iter()
+            .map(|((pc, edge), count)| format!("{:04X}: {} x{}", pc, edge, count))
+            .collect();
+        lines.join("\n")
+    }
+}
+
+/// Logs the instructions relying on wrapping arithmetic: 8XY4 overflows, 8XY5/8XY7
+/// underflows and FX1E moving I past the end of memory, once per instruction address and
+/// edge, with the operands. The counts can be read through the handle returned by `counts()`.
+pub struct ArithmeticAudit<W: Write> {
+    out: W,
+    counts: Rc<RefCell<EdgeCounts>>,
+}
+
+impl<W: Write> ArithmeticAudit<W> {
+    pub fn new(out: W) -> Self {
+        ArithmeticAudit {
+            out,
+            counts: Rc::new(RefCell::new(EdgeCounts::default())),
+        }
+    }
+
+    pub fn counts(&self) -> Rc<RefCell<EdgeCounts>> {
+        Rc::clone(&self.counts)
+    }
+}
+
+impl<W: Write> Plugin for ArithmeticAudit<W> {
+    fn on_instruction(&mut self, pc: u16, opcode: u16, interpreter: &Interpreter, memory: &Memory) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let (vx, vy, vi) = (interpreter.vx[x], interpreter.vx[y], interpreter.index());
+        let last_addr = memory.address_space().mask();
+
+        let hit = match opcode & 0xF00F {
+            0x8004 if vx as u16 + vy as u16 > 0xFF => Some((
+                ArithmeticEdge::AddOverflow,
+                format!("V{:X}={:02X} + V{:X}={:02X}", x, vx, y, vy),
+            )),
+            0x8005 if vx < vy => Some((
+                ArithmeticEdge::SubUnderflow,
+                format!("V{:X}={:02X} - V{:X}={:02X}", x, vx, y, vy),
+            )),
+            0x8007 if vy < vx => Some((
+                ArithmeticEdge::SubnUnderflow,
+                format!("V{:X}={:02X} - V{:X}={:02X}", y, vy, x, vx),
+            )),
+            _ if opcode & 0xF0FF == 0xF01E && vi as u32 + vx as u32 > last_addr as u32 => Some((
+                ArithmeticEdge::IndexOverflow,
+                format!("I={:03X} + V{:X}={:02X}", vi, x, vx),
+            )),
+            _ => None,
+        };
+
+        if let Some((edge, operands)) = hit {
+            let mut counts = self.counts.borrow_mut();
+            let count = counts.counts.entry((pc, edge)).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                // diagnostics must not stop the emulation
+                let _ = writeln!(
+                    self.out,
+                    "audit: {:04X}: {:04X} {}: {}",
+                    pc, opcode, edge, operands
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_arithmetic_audit() {
+        let audit = ArithmeticAudit::new(Vec::new());
+        let counts = audit.counts();
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(audit));
+
+        let program = [
+            ld_vx(0, 0xF0),  // 200: V0 = F0
+            ld_vx(1, 0x20),  // 202: V1 = 20
+            add_vx_vy(0, 1), // 204: V0 += V1, overflows once
+            add_vx_vy(1, 0), // 206: V1 += V0, 20 + 10 fits
+            sub(0, 1),       // 208: V0 -= V1, 10 - 30 underflows
+            subn(0, 1),      // 20A: V0 = V1 - V0, 30 - E0 underflows
+            ld_i(0xFF8),     // 20C: I = FF8
+            add_i(1),        // 20E: I += V1, FF8 + 30 passes FFF
+            halt(),          // 210: stop
+        ]
+        .concat();
+        chip8.load(&program);
+        chip8.run_frame(100);
+
+        let counts = counts.borrow();
+        assert_eq!(1, counts.count(0x204, ArithmeticEdge::AddOverflow));
+        assert_eq!(0, counts.count(0x206, ArithmeticEdge::AddOverflow));
+        assert_eq!(1, counts.count(0x208, ArithmeticEdge::SubUnderflow));
+        assert_eq!(1, counts.count(0x20A, ArithmeticEdge::SubnUnderflow));
+        assert_eq!(1, counts.count(0x20E, ArithmeticEdge::IndexOverflow));
+        assert_eq!(
+            "0204: 8XY4 overflow x1\n\
+             0208: 8XY5 underflow x1\n\
+             020A: 8XY7 underflow x1\n\
+             020E: FX1E past the end of memory x1",
+            counts.report()
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::plugin::Plugin;
+
+use std::str::FromStr;
+
+/// Memory byte forced to a value, written as `ADDR=VALUE` in hexadecimal (e.g. `3F0=05`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cheat {
+    pub addr: u16,
+    pub value: u8,
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_hex = |s: &str| {
+            let s = s.trim();
+            let s = s.strip_prefix("0x").unwrap_or(s);
+            u16::from_str_radix(s, 16).ok()
+        };
+
+        let (addr, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected ADDR=VALUE, got {}", s))?;
+
+        match (parse_hex(addr), parse_hex(value)) {
+            (Some(addr), Some(value)) if addr < 0x1000 && value <= 0xFF => Ok(Cheat {
+                addr,
+                value: value as u8,
+            }),
+            _ => Err(format!("invalid cheat {}", s)),
+        }
+    }
+}
+
+/// Writes the cheat values in memory every frame
+pub struct CheatEngine {
+    cheats: Vec<Cheat>,
+}
+
+impl CheatEngine {
+    pub fn new(cheats: Vec<Cheat>) -> Self {
+        CheatEngine { cheats }
+    }
+}
+
+impl Plugin for CheatEngine {
+    fn on_frame(&mut self, _interpreter: &mut Interpreter, memory: &mut Memory) {
+        for cheat in &self.cheats {
+            memory.write(cheat.addr, cheat.value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Ok(Cheat {
+                addr: 0x3F0,
+                value: 0x05
+            }),
+            "0x3F0=05".parse()
+        );
+        assert!("3F0".parse::<Cheat>().is_err());
+        assert!("1000=00".parse::<Cheat>().is_err());
+        assert!("3F0=100".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn test_frame_writes_memory() {
+        let mut engine = CheatEngine::new(vec!["3F0=05".parse().unwrap()]);
+        let mut interpreter = Interpreter::new();
+        let mut memory = Memory::new();
+
+        engine.on_frame(&mut interpreter, &mut memory);
+        assert_eq!(0x05, memory.read(0x3F0));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::disassembler;
+use crate::interpreter::Interpreter;
+use crate::memory::{self, Memory};
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Addresses of the executed instructions
+pub struct Coverage {
+    executed: Vec<bool>, // by address
+    program_len: u16,
+    blocks: Vec<u16>, // start of the basic blocks reachable from the program start
+}
+
+impl Coverage {
+    pub fn executed(&self, addr: u16) -> bool {
+        self.executed.get(addr as usize).copied().unwrap_or(false)
+    }
+
+    /// Program ranges (inclusive) that were never executed, instructions being 2 bytes long
+    pub fn unexecuted_ranges(&self) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+
+        let program_end = memory::PROG_LOC + self.program_len;
+        for addr in (memory::PROG_LOC..program_end).step_by(2) {
+            if self.executed(addr) {
+                continue;
+            }
+
+            let end = (addr + 1).min(program_end - 1);
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == addr => range.1 = end,
+                _ => ranges.push((addr, end)),
+            }
+        }
+
+        ranges
+    }
+
+    pub fn report(&self) -> String {
+        let total = (self.program_len as usize).div_ceil(2);
+        let executed = (memory::PROG_LOC..memory::PROG_LOC + self.program_len)
+            .step_by(2)
+            .filter(|&addr| self.executed(addr))
+            .count();
+
+        let executed_blocks = self
+            .blocks
+            .iter()
+            .filter(|&&addr| self.executed(addr))
+            .count();
+
+        let mut report = format!("executed {} of {} program instructions", executed, total);
+        report += &format!(
+            "\nexecuted {} of {} reachable basic blocks",
+            executed_blocks,
+            self.blocks.len()
+        );
+        for (start, end) in self.unexecuted_ranges() {
+            report += &format!("\nnever executed: {:04X}-{:04X}", start, end);
+        }
+
+        report
+    }
+}
+
+/// Records which instructions are executed.
+/// The coverage can be read through the handle returned by `coverage()`.
+pub struct CoverageTracker {
+    coverage: Rc<RefCell<Coverage>>,
+}
+
+impl CoverageTracker {
+    pub fn new() -> Self {
+        CoverageTracker {
+            coverage: Rc::new(RefCell::new(Coverage {
+                executed: vec![false; memory::MAX_SIZE as usize],
+                program_len: 0,
+                blocks: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn coverage(&self) -> Rc<RefCell<Coverage>> {
+        Rc::clone(&self.coverage)
+    }
+}
+
+impl Default for CoverageTracker {
+    fn default() -> Self {
+        CoverageTracker::new()
+    }
+}
+
+impl Plugin for CoverageTracker {
+    fn on_load(&mut self, program: &[u8], memory: &mut Memory) {
+        let mut coverage = self.coverage.borrow_mut();
+        coverage.executed = vec![false; memory.size()];
+        coverage.program_len = program.len() as u16;
+        coverage.blocks = disassembler::analyze(program).blocks.into_keys().collect();
+    }
+
+    fn on_instruction(
+        &mut self,
+        pc: u16,
+        _opcode: u16,
+        _interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        self.coverage.borrow_mut().executed[pc as usize] = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unexecuted_ranges() {
+        let mut tracker = CoverageTracker::new();
+        let interpreter = Interpreter::new();
+        let mut memory = Memory::new();
+
+        let mut program = [0; 11];
+        program[0..4].copy_from_slice(&[0x12, 0x06, 0x12, 0x02]); // jump 206, jump 202
+        tracker.on_load(&program, &mut memory);
+        tracker.on_instruction(0x0200, 0x0000, &interpreter, &memory);
+        tracker.on_instruction(0x0206, 0x0000, &interpreter, &memory);
+
+        let coverage = tracker.coverage();
+        assert_eq!(
+            vec![(0x0202, 0x0205), (0x0208, 0x020A)],
+            coverage.borrow().unexecuted_ranges()
+        );
+        let report = coverage.borrow().report();
+        assert!(report.starts_with("executed 2 of 6"));
+        assert!(report.contains("executed 2 of 2 reachable basic blocks"));
+    }
+}
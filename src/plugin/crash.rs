@@ -0,0 +1,216 @@
+use crate::info;
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::pc_history::PcHistory;
+use crate::plugin::Plugin;
+use crate::quirks::Quirks;
+use crate::snapshot::Snapshot;
+use crate::timing::TimerMode;
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Instructions kept for the bug report
+pub const TRACE_LEN: usize = 100;
+
+/// What a bug report needs to reproduce a crash, kept up to date by `CrashRecorder`
+#[derive(Default)]
+pub struct CrashContext {
+    pub rom_name: String,
+    pub rom_sha1: String,
+    trace: VecDeque<(u16, u16)>, // address and opcode of the last instructions
+    state: Option<Snapshot>,     // at the start of the current frame
+    pc_history: PcHistory,       // of the interpreter at the end of the last frame
+}
+
+impl CrashContext {
+    /// Keep the history of the interpreter, copied at the end of every frame since the
+    /// faster executors run without `CrashRecorder`
+    pub fn set_pc_history(&mut self, history: &PcHistory) {
+        self.pc_history.clone_from(history);
+    }
+
+    /// Write the bundle files in `dir`: `report.txt` (panic message, program and settings),
+    /// `trace.txt` (last instructions), `history.txt` (last instructions of the interpreter
+    /// history, decoded), `registers.txt` and `memory.bin` (RAM)
+    pub fn write_bundle(&self, dir: &Path, message: &str) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+
+        let mut report = String::new();
+        writeln!(report, "panic: {}", message).unwrap();
+        writeln!(report, "version: {}", env!("CARGO_PKG_VERSION")).unwrap();
+        writeln!(report, "rom: {}", self.rom_name).unwrap();
+        writeln!(report, "sha1: {}", self.rom_sha1).unwrap();
+        if let Some(state) = &self.state {
+            let interpreter = state.interpreter();
+            let values = interpreter.quirks().values();
+            for (name, value) in Quirks::NAMES.iter().zip(values) {
+                writeln!(report, "{}: {}", name, if value { "on" } else { "off" }).unwrap();
+            }
+            writeln!(report, "stack depth: {}", interpreter.stack_depth()).unwrap();
+            let timer_mode = match interpreter.timer_mode() {
+                TimerMode::Frame => "frame",
+                TimerMode::Interpolated => "interpolated",
+            };
+            writeln!(report, "timer mode: {}", timer_mode).unwrap();
+        }
+        std::fs::write(dir.join("report.txt"), report)?;
+
+        let trace: String = self
+            .trace
+            .iter()
+            .map(|(pc, opcode)| format!("{:04X}: {:04X}\n", pc, opcode))
+            .collect();
+        std::fs::write(dir.join("trace.txt"), trace)?;
+        std::fs::write(dir.join("history.txt"), self.pc_history.to_string())?;
+
+        if let Some(state) = &self.state {
+            std::fs::write(dir.join("registers.txt"), registers(state.interpreter()))?;
+            std::fs::write(dir.join("memory.bin"), state.ram())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Registers at the start of the frame, the trace shows the instructions executed since
+fn registers(interpreter: &Interpreter) -> String {
+    let mut out = format!(
+        "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}  SP {}\n",
+        interpreter.pc, interpreter.vi, interpreter.dt, interpreter.st, interpreter.sc
+    );
+    for (i, value) in interpreter.vx.iter().enumerate() {
+        writeln!(out, "V{:X} {:02X}", i, value).unwrap();
+    }
+    out
+}
+
+/// Records the last instructions and the state at each frame, for the panic hook
+/// installed by `install_panic_hook` to write a bug report bundle.
+pub struct CrashRecorder {
+    context: Arc<Mutex<CrashContext>>,
+}
+
+impl CrashRecorder {
+    pub fn new() -> Self {
+        CrashRecorder {
+            context: Arc::new(Mutex::new(CrashContext::default())),
+        }
+    }
+
+    pub fn context(&self) -> Arc<Mutex<CrashContext>> {
+        Arc::clone(&self.context)
+    }
+}
+
+impl Default for CrashRecorder {
+    fn default() -> Self {
+        CrashRecorder::new()
+    }
+}
+
+impl Plugin for CrashRecorder {
+    fn on_load(&mut self, program: &[u8], _memory: &mut Memory) {
+        let mut context = self.context.lock().unwrap();
+        context.rom_sha1 = info::sha1_hex(program);
+        context.trace.clear();
+        context.state = None;
+    }
+
+    fn on_frame(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        let mut context = self.context.lock().unwrap();
+        context.state = Some(Snapshot::from_parts(interpreter, memory));
+    }
+
+    fn on_instruction(&mut self, pc: u16, opcode: u16, interpreter: &Interpreter, memory: &Memory) {
+        let mut context = self.context.lock().unwrap();
+        if context.state.is_none() {
+            context.state = Some(Snapshot::from_parts(interpreter, memory));
+        }
+        if context.trace.len() == TRACE_LEN {
+            context.trace.pop_front();
+        }
+        context.trace.push_back((pc, opcode));
+    }
+}
+
+/// After the default panic message, write a bug report bundle from `context` to a new
+/// directory of the temporary directory and print its path
+pub fn install_panic_hook(context: Arc<Mutex<CrashContext>>) {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |panic_info| {
+        default_hook(panic_info);
+
+        // the panic may come from a thread holding the lock
+        let context = match context.try_lock() {
+            Ok(context) => context,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return,
+        };
+
+        let dir = bundle_dir();
+        match context.write_bundle(&dir, &panic_info.to_string()) {
+            Ok(()) => eprintln!(
+                "A bug report bundle was written to {}, please attach it to an issue at \
+                 https://github.com/leopnt/chip8-interpreter/issues",
+                dir.display()
+            ),
+            Err(e) => eprintln!("Could not write a bug report bundle: {}", e),
+        }
+    }));
+}
+
+fn bundle_dir() -> PathBuf {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    std::env::temp_dir().join(format!("chip8-crash-{}-{}", time, std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_bundle() {
+        let mut recorder = CrashRecorder::new();
+        let mut interpreter = Interpreter::new();
+        let mut memory = Memory::new();
+
+        recorder.on_load(&[0x60, 0x05], &mut memory);
+        recorder.on_frame(&mut interpreter, &mut memory);
+        for i in 0..TRACE_LEN as u16 + 1 {
+            recorder.on_instruction(0x200 + 2 * i, 0x6005, &interpreter, &memory);
+        }
+
+        let dir = std::env::temp_dir().join(format!("chip8-test-bundle-{}", std::process::id()));
+        let context = recorder.context();
+        let mut history = PcHistory::new();
+        history.push(0x200, 0x2300);
+        context.lock().unwrap().set_pc_history(&history);
+        context.lock().unwrap().write_bundle(&dir, "oops").unwrap();
+
+        let report = std::fs::read_to_string(dir.join("report.txt")).unwrap();
+        assert!(report.starts_with("panic: oops\n"));
+        assert!(report.contains(&format!("sha1: {}", info::sha1_hex(&[0x60, 0x05]))));
+        assert!(report.contains("vf_reset: off"));
+        let trace = std::fs::read_to_string(dir.join("trace.txt")).unwrap();
+        assert_eq!(TRACE_LEN, trace.lines().count());
+        assert!(trace.starts_with("0202: 6005\n"));
+        assert_eq!(
+            "0200: 2300 CALL 300\n",
+            std::fs::read_to_string(dir.join("history.txt")).unwrap()
+        );
+        assert!(std::fs::read_to_string(dir.join("registers.txt"))
+            .unwrap()
+            .starts_with("PC 200"));
+        assert_eq!(4096, std::fs::read(dir.join("memory.bin")).unwrap().len());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
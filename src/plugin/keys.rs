@@ -0,0 +1,163 @@
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// How often a program looked at each key
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct KeyUsage {
+    pub checks: [u64; 16], // EX9E and EXA1 executed on the key
+    pub waits: [u64; 16],  // FX0A waits the key ended
+    pub waited: bool,      // FX0A executed, any key being accepted
+}
+
+impl KeyUsage {
+    /// Keys checked or having ended a wait
+    pub fn used(&self) -> Vec<u8> {
+        (0..16)
+            .filter(|&key| self.checks[key] > 0 || self.waits[key] > 0)
+            .map(|key| key as u8)
+            .collect()
+    }
+
+    /// One line readout, e.g. `keys 4 5 6 + any`, the `+ any` for programs waiting on any key
+    pub fn summary(&self) -> String {
+        let mut keys: Vec<String> = self.used().iter().map(|key| format!("{:X}", key)).collect();
+        if self.waited {
+            keys.push("+ any".to_string());
+        }
+
+        match keys.is_empty() {
+            true => "keys none".to_string(),
+            false => format!("keys {}", keys.join(" ")),
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let counts = |counts: &[u64; 16]| {
+            let keys: Vec<String> = (0..16)
+                .filter(|&key| counts[key] > 0)
+                .map(|key| format!("{:X} ({})", key, counts[key]))
+                .collect();
+            match keys.is_empty() {
+                true => "none".to_string(),
+                false => keys.join(" "),
+            }
+        };
+
+        let mut report = format!("keys checked: {}", counts(&self.checks));
+        report += &format!("\nkeys ending a wait: {}", counts(&self.waits));
+        if self.waited {
+            report += "\nwaits for any key (FX0A)";
+        }
+
+        report
+    }
+}
+
+/// Counts the keys checked by EX9E/EXA1 and those ending FX0A waits.
+/// The usage can be read through the handle returned by `usage()`.
+pub struct KeyUsageTracker {
+    usage: Rc<RefCell<KeyUsage>>,
+}
+
+impl KeyUsageTracker {
+    pub fn new() -> Self {
+        KeyUsageTracker {
+            usage: Rc::new(RefCell::new(KeyUsage::default())),
+        }
+    }
+
+    pub fn usage(&self) -> Rc<RefCell<KeyUsage>> {
+        Rc::clone(&self.usage)
+    }
+}
+
+impl Default for KeyUsageTracker {
+    fn default() -> Self {
+        KeyUsageTracker::new()
+    }
+}
+
+impl Plugin for KeyUsageTracker {
+    fn on_load(&mut self, _program: &[u8], _memory: &mut Memory) {
+        *self.usage.borrow_mut() = KeyUsage::default();
+    }
+
+    fn on_instruction(
+        &mut self,
+        _pc: u16,
+        opcode: u16,
+        interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        let x = (opcode >> 8 & 0xF) as usize;
+        match opcode & 0xF0FF {
+            0xE09E | 0xE0A1 => {
+                let key = (interpreter.vx[x] & 0xF) as usize;
+                self.usage.borrow_mut().checks[key] += 1;
+            }
+            0xF00A => self.usage.borrow_mut().waited = true,
+            _ => (),
+        }
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        // FX0A goes back to itself until a key is pressed
+        if opcode & 0xF0FF == 0xF00A && interpreter.pc != pc {
+            let key = (interpreter.vx[(opcode >> 8 & 0xF) as usize] & 0xF) as usize;
+            self.usage.borrow_mut().waits[key] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::interpreter::KeyEvent;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_key_usage() {
+        let mut chip8 = Chip8::new();
+        let tracker = KeyUsageTracker::new();
+        let usage = tracker.usage();
+        chip8.register_plugin(Box::new(tracker));
+        chip8.load(
+            &[
+                ld_vx(0, 0x15), // 200
+                skp(0),         // 202: key 5, high nibble ignored
+                ld_vx(1, 0x0C), // 204
+                sknp(1),        // 206: key C, skips 208
+                jp(0x208),      // 208
+                ld_vx_k(2),     // 20A
+            ]
+            .concat(),
+        );
+        for _ in 0..6 {
+            chip8.step();
+        }
+        assert_eq!(1, usage.borrow().checks[0x5]);
+        assert_eq!(1, usage.borrow().checks[0xC]);
+        assert_eq!("keys 5 C + any", usage.borrow().summary());
+
+        chip8.push_key_event(KeyEvent { key: 7, down: true });
+        chip8.begin_frame();
+        chip8.step();
+        assert_eq!(1, usage.borrow().waits[0x7]);
+        assert_eq!(vec![0x5, 0x7, 0xC], usage.borrow().used());
+        assert!(usage
+            .borrow()
+            .report()
+            .contains("keys checked: 5 (1) C (1)\nkeys ending a wait: 7 (1)"));
+    }
+}
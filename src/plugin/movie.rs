@@ -0,0 +1,48 @@
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::movie::{Movie, MovieEvent};
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Records the key presses and resets of each frame into a movie, to register right
+/// after the program is loaded
+pub struct MovieRecorder {
+    movie: Rc<RefCell<Movie>>,
+}
+
+impl MovieRecorder {
+    /// `movie` gives the starting state, see `Movie::new`
+    pub fn new(movie: Movie) -> Self {
+        MovieRecorder {
+            movie: Rc::new(RefCell::new(movie)),
+        }
+    }
+
+    pub fn movie(&self) -> Rc<RefCell<Movie>> {
+        Rc::clone(&self.movie)
+    }
+}
+
+impl Plugin for MovieRecorder {
+    fn on_load(&mut self, _program: &[u8], _memory: &mut Memory) {
+        let mut movie = self.movie.borrow_mut();
+        let frame = movie.frames;
+        movie.events.push(MovieEvent::Reset { frame });
+    }
+
+    fn on_frame(&mut self, _interpreter: &mut Interpreter, _memory: &mut Memory) {
+        self.movie.borrow_mut().frames += 1;
+    }
+
+    fn on_key(&mut self, key: usize, pressed: bool) {
+        let mut movie = self.movie.borrow_mut();
+        let frame = movie.frames;
+        movie.events.push(MovieEvent::Key {
+            frame,
+            key: key as u8,
+            down: pressed,
+        });
+    }
+}
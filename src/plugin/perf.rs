@@ -0,0 +1,204 @@
+use crate::interpreter::Interpreter;
+use crate::memory::Memory;
+use crate::opcodes::{self, OPCODES};
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Stack of the time spent executing the instructions of a table row
+const EXEC_STACK: &str = "frame;emulate;exec";
+
+/// Stack of the time spent decoding the instructions through the table
+const DECODE_STACK: &str = "frame;emulate;decode";
+
+/// Time spent in the parts of the emulator, by stack of nested parts separated by `;`
+/// (e.g. `frame;emulate;exec;8XY4`)
+#[derive(Default)]
+pub struct PerfSamples {
+    nanos: BTreeMap<String, u64>, // including the stacks below
+}
+
+impl PerfSamples {
+    pub fn new() -> Self {
+        PerfSamples::default()
+    }
+
+    /// Add `elapsed` to the time of `stack`, the stacks below it included
+    pub fn record(&mut self, stack: &str, elapsed: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        match self.nanos.get_mut(stack) {
+            Some(total) => *total += nanos,
+            None => {
+                self.nanos.insert(stack.to_string(), nanos);
+            }
+        }
+    }
+
+    /// Stacks recorded so far, in order
+    pub fn stacks(&self) -> impl Iterator<Item = &str> {
+        self.nanos.keys().map(String::as_str)
+    }
+
+    /// Total time of `stack`, the stacks below it included
+    pub fn total(&self, stack: &str) -> Duration {
+        Duration::from_nanos(self.nanos.get(stack).copied().unwrap_or(0))
+    }
+
+    /// Folded stacks as read by inferno and flamegraph.pl: one `stack nanoseconds` line per
+    /// stack, with the time spent in it outside of the stacks below
+    pub fn write_folded<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut own = self.nanos.clone();
+        for (stack, &nanos) in &self.nanos {
+            // the closest recorded parent, the ones in between may be missing
+            let mut parent = stack.as_str();
+            while let Some((prefix, _)) = parent.rsplit_once(';') {
+                if let Some(total) = own.get_mut(prefix) {
+                    *total = total.saturating_sub(nanos);
+                    break;
+                }
+                parent = prefix;
+            }
+        }
+
+        for (stack, nanos) in own {
+            if nanos > 0 {
+                writeln!(out, "{} {}", stack, nanos)?;
+            }
+        }
+        out.flush()
+    }
+}
+
+/// Times the decoding of every instruction (`frame;emulate;decode`), on the table lookup `exec`
+/// does too, and its execution by row of the instruction table (`frame;emulate;exec;8XY4`).
+/// The samples are read and completed by the frontend through the handle returned by
+/// `samples()`.
+///
+/// The overhead is large: three clock reads per instruction, and observing each instruction keeps
+/// the executors from running several at once, so the emulation runs several times slower than
+/// unprofiled. The parts are to be compared with each other, not with an unprofiled run.
+pub struct PerfRecorder {
+    samples: Rc<RefCell<PerfSamples>>,
+    started: Option<Instant>, // of the execution of the current instruction
+    row: usize,               // of the current instruction in OPCODES, its length if unknown
+    decode: Option<Duration>, // since the last frame
+    exec: Vec<Option<Duration>>, // by row of OPCODES, then unknown opcodes, since the last frame
+}
+
+impl PerfRecorder {
+    pub fn new() -> Self {
+        PerfRecorder {
+            samples: Rc::new(RefCell::new(PerfSamples::new())),
+            started: None,
+            row: OPCODES.len(),
+            decode: None,
+            exec: vec![None; OPCODES.len() + 1],
+        }
+    }
+
+    pub fn samples(&self) -> Rc<RefCell<PerfSamples>> {
+        Rc::clone(&self.samples)
+    }
+}
+
+impl Default for PerfRecorder {
+    fn default() -> Self {
+        PerfRecorder::new()
+    }
+}
+
+impl Plugin for PerfRecorder {
+    fn on_frame(&mut self, _interpreter: &mut Interpreter, _memory: &mut Memory) {
+        let mut samples = self.samples.borrow_mut();
+        if let Some(elapsed) = self.decode.take() {
+            samples.record(DECODE_STACK, elapsed);
+        }
+        for (row, elapsed) in self.exec.iter_mut().enumerate() {
+            if let Some(elapsed) = elapsed.take() {
+                let pattern = OPCODES.get(row).map_or("unknown", |opcode| opcode.pattern);
+                samples.record(&format!("{};{}", EXEC_STACK, pattern), elapsed);
+            }
+        }
+    }
+
+    fn on_instruction(
+        &mut self,
+        _pc: u16,
+        opcode: u16,
+        _interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        let decoding = Instant::now();
+        self.row = opcodes::index(opcode).unwrap_or(OPCODES.len());
+        let started = Instant::now();
+        *self.decode.get_or_insert(Duration::ZERO) += started - decoding;
+        self.started = Some(started);
+    }
+
+    fn after_instruction(
+        &mut self,
+        _pc: u16,
+        _opcode: u16,
+        _interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        let Some(started) = self.started.take() else {
+            return;
+        };
+        *self.exec[self.row].get_or_insert(Duration::ZERO) += started.elapsed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_write_folded() {
+        let mut samples = PerfSamples::new();
+        samples.record("frame;emulate", Duration::from_nanos(700));
+        samples.record("frame;emulate;exec;8XY4", Duration::from_nanos(200));
+        samples.record("frame;emulate;exec;6XNN", Duration::from_nanos(100));
+        samples.record("frame;render", Duration::from_nanos(300));
+        samples.record("frame;render", Duration::from_nanos(100));
+
+        let mut out = Vec::new();
+        samples.write_folded(&mut out).unwrap();
+        assert_eq!(
+            "frame;emulate 400\n\
+             frame;emulate;exec;6XNN 100\n\
+             frame;emulate;exec;8XY4 200\n\
+             frame;render 400\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_recorder() {
+        let recorder = PerfRecorder::new();
+        let samples = recorder.samples();
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(recorder));
+        chip8.load(&[ld_vx(0, 1), add_vx(0, 1), jp(0x202)].concat());
+
+        chip8.run_frame(10);
+        chip8.frame();
+
+        let samples = samples.borrow();
+        assert_eq!(
+            vec![
+                "frame;emulate;decode",
+                "frame;emulate;exec;1NNN",
+                "frame;emulate;exec;6XNN",
+                "frame;emulate;exec;7XNN"
+            ],
+            samples.stacks().collect::<Vec<_>>()
+        );
+    }
+}
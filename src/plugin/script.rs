@@ -0,0 +1,350 @@
+use crate::filmstrip;
+use crate::framebuffer::FrameBuffer;
+use crate::interpreter::{Interpreter, KeyEvent};
+use crate::memory::Memory;
+use crate::palette::Palette;
+use crate::plugin::Plugin;
+
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Name of the script function called at the end of every frame
+const FRAME_HOOK: &str = "on_frame";
+
+/// Operations a script may run at once, its top level or a hook call, so that an endless
+/// loop stops the script instead of freezing the emulator
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What scripts see and change during a frame, copied from the machine before the hook and
+/// written back after it
+#[derive(Default)]
+struct Host {
+    frame: u64,
+    vx: [u8; 16],
+    vi: u16,
+    pc: u16,
+    dt: u8,
+    st: u8,
+    ram: Vec<u8>,
+    writes: Vec<(u16, u8)>,
+    keys: Vec<KeyEvent>,
+    screenshots: Vec<PathBuf>,
+    skipped: u64, // frames left to run before the next hook call
+}
+
+/// Runs a rhai script for bots, autosplitters and trainers. The top level of the script runs
+/// once when loaded, then its `on_frame()` function at the end of every frame, `this` being
+/// a map kept between frames. The functions bound to the machine are:
+///
+/// - `frame()`: frames run so far
+/// - `v(x)`, `i()`, `pc()`, `dt()`, `st()` and `set_v(x, n)`, `set_i(n)`, `set_pc(n)`,
+///   `set_dt(n)`, `set_st(n)`: registers and timers
+/// - `peek(addr)` and `poke(addr, n)`: memory
+/// - `press(key)` and `release(key)`: keys, seen by the program from the next frame
+/// - `step_frames(n)`: let the program run `n` frames before the next `on_frame()` call
+/// - `screenshot(path)`: the display as a PNG image
+///
+/// A script running more than `MAX_OPERATIONS` operations at once stops with an error.
+pub struct ScriptRunner {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    this: Dynamic,
+    host: Rc<RefCell<Host>>,
+    keys: Vec<KeyEvent>, // pressed or released by the last hook call
+    hook: bool,          // the script has an `on_frame` function
+    stopped: bool,       // after an error
+}
+
+impl ScriptRunner {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let host = Rc::new(RefCell::new(Host::default()));
+        let engine = engine(&host);
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        let hook = ast
+            .iter_functions()
+            .any(|function| function.name == FRAME_HOOK && function.params.is_empty());
+
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| e.to_string())?;
+
+        Ok(ScriptRunner {
+            engine,
+            ast,
+            scope,
+            this: Dynamic::from_map(Map::new()),
+            host,
+            keys: Vec::new(),
+            hook,
+            stopped: false,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ScriptRunner::new(&source)
+    }
+
+    /// Call the frame hook, returns the error stopping the script if any
+    fn run_hook(
+        &mut self,
+        interpreter: &mut Interpreter,
+        memory: &mut Memory,
+    ) -> Result<(), String> {
+        {
+            let mut host = self.host.borrow_mut();
+            host.vx = interpreter.vx;
+            host.vi = interpreter.vi;
+            host.pc = interpreter.pc;
+            host.dt = interpreter.dt;
+            host.st = interpreter.st;
+            host.ram.clear();
+            host.ram.extend_from_slice(memory.ram());
+        }
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .rewind_scope(false)
+            .bind_this_ptr(&mut self.this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            FRAME_HOOK,
+            (),
+        );
+
+        let mut host = self.host.borrow_mut();
+        interpreter.vx = host.vx;
+        interpreter.vi = host.vi;
+        interpreter.pc = host.pc;
+        if host.dt != interpreter.dt {
+            interpreter.set_dt(host.dt);
+        }
+        if host.st != interpreter.st {
+            interpreter.set_st(host.st);
+        }
+        for (addr, value) in host.writes.drain(..) {
+            memory.write(addr, value);
+        }
+        self.keys.append(&mut host.keys);
+        let frame = FrameBuffer::from_memory(memory);
+        for path in host.screenshots.drain(..) {
+            let written = std::fs::File::create(&path).and_then(|file| {
+                let writer = std::io::BufWriter::new(file);
+                filmstrip::write_frame_png(&frame, &Palette::new(), 4, writer)
+            });
+            if let Err(e) = written {
+                eprintln!("Script could not write {}: {}", path.display(), e);
+            }
+        }
+
+        result.map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+impl Plugin for ScriptRunner {
+    fn on_load(&mut self, _program: &[u8], _memory: &mut Memory) {
+        let mut host = self.host.borrow_mut();
+        host.frame = 0;
+        host.skipped = 0;
+    }
+
+    fn on_frame(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        {
+            let mut host = self.host.borrow_mut();
+            host.frame += 1;
+            if host.skipped > 0 {
+                host.skipped -= 1;
+                return;
+            }
+        }
+        if !self.hook || self.stopped {
+            return;
+        }
+
+        if let Err(e) = self.run_hook(interpreter, memory) {
+            eprintln!("Script stopped: {}", e);
+            self.stopped = true;
+        }
+    }
+
+    fn take_key_events(&mut self) -> Vec<KeyEvent> {
+        std::mem::take(&mut self.keys)
+    }
+}
+
+/// Engine with the functions bound to the machine, printing to stderr
+fn engine(host: &Rc<RefCell<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| eprintln!("{}", text));
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    // registers are masked to their size, like the interpreter does
+    let h = Rc::clone(host);
+    engine.register_fn("frame", move || h.borrow().frame as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("v", move |x: i64| h.borrow().vx[x as usize & 0xF] as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("set_v", move |x: i64, n: i64| {
+        h.borrow_mut().vx[x as usize & 0xF] = n as u8
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("i", move || h.borrow().vi as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("set_i", move |n: i64| h.borrow_mut().vi = n as u16);
+    let h = Rc::clone(host);
+    engine.register_fn("pc", move || h.borrow().pc as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("set_pc", move |n: i64| {
+        let mut host = h.borrow_mut();
+        host.pc = (n as usize % host.ram.len()) as u16;
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("dt", move || h.borrow().dt as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("set_dt", move |n: i64| h.borrow_mut().dt = n as u8);
+    let h = Rc::clone(host);
+    engine.register_fn("st", move || h.borrow().st as i64);
+    let h = Rc::clone(host);
+    engine.register_fn("set_st", move |n: i64| h.borrow_mut().st = n as u8);
+
+    let h = Rc::clone(host);
+    engine.register_fn("peek", move |addr: i64| {
+        let host = h.borrow();
+        host.ram[addr as usize % host.ram.len()] as i64
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("poke", move |addr: i64, n: i64| {
+        let mut host = h.borrow_mut();
+        let addr = addr as usize % host.ram.len();
+        host.ram[addr] = n as u8;
+        host.writes.push((addr as u16, n as u8));
+    });
+
+    let h = Rc::clone(host);
+    engine.register_fn("press", move |key: i64| {
+        let key = key as u8 & 0xF;
+        h.borrow_mut().keys.push(KeyEvent { key, down: true });
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("release", move |key: i64| {
+        let key = key as u8 & 0xF;
+        h.borrow_mut().keys.push(KeyEvent { key, down: false });
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("step_frames", move |n: i64| {
+        h.borrow_mut().skipped = n.max(0) as u64
+    });
+    let h = Rc::clone(host);
+    engine.register_fn("screenshot", move |path: &str| {
+        h.borrow_mut().screenshots.push(PathBuf::from(path))
+    });
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::rom_builder::*;
+
+    /// Keys seen by the plugins
+    struct KeyLog(Rc<RefCell<Vec<(usize, bool)>>>);
+
+    impl Plugin for KeyLog {
+        fn on_key(&mut self, key: usize, pressed: bool) {
+            self.0.borrow_mut().push((key, pressed));
+        }
+    }
+
+    fn run(script: &str, frames: usize) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(ScriptRunner::new(script).unwrap()));
+        chip8.load(
+            &[
+                ld_vx(0, 0x05), // 200
+                sknp(0),        // 202
+                add_vx(1, 1),   // 204
+                jp(0x202),      // 206
+            ]
+            .concat(),
+        );
+        for _ in 0..frames {
+            chip8.run_frame(4);
+            chip8.frame();
+        }
+        chip8
+    }
+
+    #[test]
+    fn test_script() {
+        let chip8 = run(
+            r#"
+            fn on_frame() {
+                this.frames = (this.frames ?? 0) + 1;
+                if frame() == 2 { press(5); }
+                if frame() == 3 { release(5); }
+                set_v(2, this.frames);
+                poke(0x300, v(1) + 1);
+            }
+            "#,
+            4,
+        );
+
+        // key 5 held during the third frame only, V1 incremented once
+        assert_eq!(1, chip8.interpreter.vx[1]);
+        assert_eq!(4, chip8.interpreter.vx[2]);
+        assert_eq!(2, chip8.memory.read(0x300));
+    }
+
+    #[test]
+    fn test_script_errors() {
+        assert!(ScriptRunner::new("fn on_frame() {").is_err());
+
+        // the script stops at its first error, the program keeps running
+        let chip8 = run("fn on_frame() { set_v(2, v(2) + 1); undefined(); }", 3);
+        assert_eq!(1, chip8.interpreter.vx[2]);
+
+        // endless loops too
+        let error = ScriptRunner::new("loop {}").err().unwrap();
+        assert!(error.contains("Too many operations"), "{}", error);
+        let chip8 = run("fn on_frame() { set_v(2, v(2) + 1); loop {} }", 3);
+        assert_eq!(1, chip8.interpreter.vx[2]);
+    }
+
+    #[test]
+    fn test_script_step_frames() {
+        let chip8 = run(
+            "fn on_frame() { set_v(2, v(2) + 1); set_v(3, frame()); step_frames(2); }",
+            7,
+        );
+
+        // called at frames 1, 4 and 7
+        assert_eq!(3, chip8.interpreter.vx[2]);
+        assert_eq!(7, chip8.interpreter.vx[3]);
+    }
+
+    #[test]
+    fn test_script_keys() {
+        let keys = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(
+            ScriptRunner::new("fn on_frame() { if frame() == 1 { press(5); release(5); } }")
+                .unwrap(),
+        ));
+        chip8.register_plugin(Box::new(KeyLog(Rc::clone(&keys))));
+        chip8.frame();
+        chip8.frame();
+
+        // pushed like the keyboard's, the other plugins seeing them
+        assert_eq!(vec![(5, true), (5, false)], *keys.borrow());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::framebuffer::{self, FrameBuffer};
+use crate::interpreter::Interpreter;
+use crate::memory::{self, Memory};
+use crate::plugin::Plugin;
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Value compared by a split condition
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operand {
+    Number(u16),
+    Pixel(u8, u8), // px(X,Y), 1 when lit
+    Memory(u16),   // mem[ADDR]
+    Register(u8),  // v[X]
+    Index,         // i
+    Delay,         // dt
+    Sound,         // st
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+/// Condition ending a split: comparisons joined by `&&`, e.g. `px(32,16)==1 && mem[0x3F0]==5`.
+/// Numbers are decimal, or hexadecimal with `0x`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Condition {
+    source: String,
+    terms: Vec<(Operand, Comparison, Operand)>,
+}
+
+impl Condition {
+    pub fn eval(&self, interpreter: &Interpreter, memory: &Memory) -> bool {
+        let display = FrameBuffer::from_memory(memory);
+        let value = |operand: Operand| match operand {
+            Operand::Number(n) => n,
+            Operand::Pixel(x, y) => display.pixel(x, y) as u16,
+            Operand::Memory(addr) => memory.read(addr) as u16,
+            Operand::Register(x) => interpreter.vx[x as usize] as u16,
+            Operand::Index => interpreter.vi,
+            Operand::Delay => interpreter.dt as u16,
+            Operand::Sound => interpreter.st as u16,
+        };
+
+        self.terms.iter().all(|&(left, comparison, right)| {
+            let (left, right) = (value(left), value(right));
+            match comparison {
+                Comparison::Eq => left == right,
+                Comparison::Ne => left != right,
+                Comparison::Le => left <= right,
+                Comparison::Ge => left >= right,
+                Comparison::Lt => left < right,
+                Comparison::Gt => left > right,
+            }
+        })
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let compact: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut parser = Parser { rest: &compact };
+
+        let mut terms = Vec::new();
+        loop {
+            let left = parser.operand()?;
+            let comparison = parser.comparison()?;
+            let right = parser.operand()?;
+            terms.push((left, comparison, right));
+
+            if parser.rest.is_empty() {
+                break;
+            }
+            parser.expect("&&")?;
+        }
+
+        Ok(Condition {
+            source: s.trim().to_string(),
+            terms,
+        })
+    }
+}
+
+/// Reads a condition without whitespace from the start
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, token: &str) -> bool {
+        match self.rest.strip_prefix(token) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.eat(token) {
+            true => Ok(()),
+            false => Err(format!("expected {} at {}", token, self.rest)),
+        }
+    }
+
+    fn number(&mut self, max: u16) -> Result<u16, String> {
+        let radix = if self.eat("0x") { 16 } else { 10 };
+        let len = self
+            .rest
+            .find(|c: char| !c.is_digit(radix))
+            .unwrap_or(self.rest.len());
+        let (digits, rest) = self.rest.split_at(len);
+
+        match u16::from_str_radix(digits, radix) {
+            Ok(n) if n <= max => {
+                self.rest = rest;
+                Ok(n)
+            }
+            Ok(n) => Err(format!("{} is above {}", n, max)),
+            Err(_) => Err(format!("expected a number at {}", self.rest)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if self.eat("px(") {
+            let x = self.number(framebuffer::WIDTH as u16 - 1)? as u8;
+            self.expect(",")?;
+            let y = self.number(framebuffer::HEIGHT as u16 - 1)? as u8;
+            self.expect(")")?;
+            Ok(Operand::Pixel(x, y))
+        } else if self.eat("mem[") {
+            let addr = self.number(memory::MAX_SIZE - 1)?;
+            self.expect("]")?;
+            Ok(Operand::Memory(addr))
+        } else if self.eat("v[") {
+            let x = self.number(0xF)? as u8;
+            self.expect("]")?;
+            Ok(Operand::Register(x))
+        } else if self.eat("dt") {
+            Ok(Operand::Delay)
+        } else if self.eat("st") {
+            Ok(Operand::Sound)
+        } else if self.eat("i") {
+            Ok(Operand::Index)
+        } else {
+            Ok(Operand::Number(self.number(u16::MAX)?))
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Comparison, String> {
+        let comparisons = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+
+        comparisons
+            .into_iter()
+            .find(|(token, _)| self.eat(token))
+            .map(|(_, comparison)| comparison)
+            .ok_or_else(|| format!("expected a comparison at {}", self.rest))
+    }
+}
+
+/// Time of a number of frames, e.g. `1:02.50`
+pub fn format_time(frames: u64, hz: u32) -> String {
+    let hundredths = frames * 100 / hz as u64;
+    format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
+
+/// A run timed in frames, started by the first key press and split each time the next
+/// condition holds at the end of a frame. The last split ends the run.
+pub struct Speedrun {
+    splits: Vec<Condition>,
+    hz: u32,            // frames per second
+    frame: u64,         // frames since the program was loaded
+    start: Option<u64>, // frame of the first key press
+    times: Vec<u64>,    // frames from the start to each split
+}
+
+impl Speedrun {
+    /// Frames since the start, the final time once every split is done
+    pub fn elapsed(&self) -> u64 {
+        match (self.start, self.finished()) {
+            (Some(_), true) => *self.times.last().unwrap(),
+            (Some(start), false) => self.frame - start,
+            (None, _) => 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        !self.splits.is_empty() && self.times.len() == self.splits.len()
+    }
+
+    /// One line readout, e.g. `1:02.50 2/5`
+    pub fn summary(&self) -> String {
+        let time = format_time(self.elapsed(), self.hz);
+        match self.splits.is_empty() {
+            true => time,
+            false => format!("{} {}/{}", time, self.times.len(), self.splits.len()),
+        }
+    }
+
+    /// Time of each split done and the final time
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let mut previous = 0;
+        for (i, (condition, &time)) in self.splits.iter().zip(&self.times).enumerate() {
+            writeln!(
+                report,
+                "split {}  {}  {} (+{})",
+                i + 1,
+                condition.source,
+                format_time(time, self.hz),
+                format_time(time - previous, self.hz)
+            )
+            .unwrap();
+            previous = time;
+        }
+
+        let end = match self.finished() {
+            true => "final",
+            false => "unfinished",
+        };
+        writeln!(report, "{}  {}", end, format_time(self.elapsed(), self.hz)).unwrap();
+
+        report
+    }
+}
+
+/// Times a run of the program, see `Speedrun`.
+/// The run can be read through the handle returned by `speedrun()`.
+pub struct SpeedrunTimer {
+    speedrun: Rc<RefCell<Speedrun>>,
+}
+
+impl SpeedrunTimer {
+    /// `hz` frames per second
+    pub fn new(splits: Vec<Condition>, hz: u32) -> Self {
+        SpeedrunTimer {
+            speedrun: Rc::new(RefCell::new(Speedrun {
+                splits,
+                hz,
+                frame: 0,
+                start: None,
+                times: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn speedrun(&self) -> Rc<RefCell<Speedrun>> {
+        Rc::clone(&self.speedrun)
+    }
+}
+
+impl Plugin for SpeedrunTimer {
+    // a reset starts a new run
+    fn on_load(&mut self, _program: &[u8], _memory: &mut Memory) {
+        let mut speedrun = self.speedrun.borrow_mut();
+        speedrun.frame = 0;
+        speedrun.start = None;
+        speedrun.times.clear();
+    }
+
+    fn on_frame(&mut self, interpreter: &mut Interpreter, memory: &mut Memory) {
+        let mut speedrun = self.speedrun.borrow_mut();
+        speedrun.frame += 1;
+
+        let Some(start) = speedrun.start else {
+            return;
+        };
+        while let Some(condition) = speedrun.splits.get(speedrun.times.len()) {
+            if !condition.eval(interpreter, memory) {
+                break;
+            }
+            let time = speedrun.frame - start;
+            speedrun.times.push(time);
+        }
+    }
+
+    fn on_key(&mut self, _key: usize, pressed: bool) {
+        let mut speedrun = self.speedrun.borrow_mut();
+        if pressed && speedrun.start.is_none() {
+            speedrun.start = Some(speedrun.frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::interpreter::KeyEvent;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_parse() {
+        let condition: Condition = "px(32, 16) == 1 && mem[0x3F0]>=5".parse().unwrap();
+        assert_eq!(
+            vec![
+                (Operand::Pixel(32, 16), Comparison::Eq, Operand::Number(1)),
+                (Operand::Memory(0x3F0), Comparison::Ge, Operand::Number(5)),
+            ],
+            condition.terms
+        );
+        assert_eq!(
+            vec![(Operand::Register(0xA), Comparison::Ne, Operand::Index)],
+            "v[0xA]!=i".parse::<Condition>().unwrap().terms
+        );
+
+        assert!("px(64,0)==1".parse::<Condition>().is_err());
+        assert!("mem[0x3F0]".parse::<Condition>().is_err());
+        assert!("dt==0 &&".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!("0:00.00", format_time(0, 60));
+        assert_eq!("1:02.50", format_time(3750, 60));
+    }
+
+    fn frame(chip8: &mut Chip8) {
+        chip8.run_frame(4);
+        chip8.frame();
+    }
+
+    #[test]
+    fn test_splits() {
+        let mut chip8 = Chip8::new();
+        let splits = vec!["v[1]>=2".parse().unwrap(), "mem[0x301]==3".parse().unwrap()];
+        let timer = SpeedrunTimer::new(splits, 60);
+        let speedrun = timer.speedrun();
+        chip8.register_plugin(Box::new(timer));
+        chip8.load(
+            &[
+                add_vx(1, 1), // 200
+                ld_i(0x300),  // 202
+                store(1),     // 204: [300] = V0, [301] = V1
+                jp(0x200),    // 206
+            ]
+            .concat(),
+        );
+
+        // not started before the first key press
+        frame(&mut chip8);
+        assert_eq!("0:00.00 0/2", speedrun.borrow().summary());
+
+        // V1 incremented and stored once per frame
+        chip8.push_key_event(KeyEvent { key: 0, down: true });
+        frame(&mut chip8);
+        assert_eq!(vec![1], speedrun.borrow().times);
+        assert!(!speedrun.borrow().finished());
+        frame(&mut chip8);
+        assert!(speedrun.borrow().finished());
+
+        // the final time stays
+        frame(&mut chip8);
+        assert_eq!(2, speedrun.borrow().elapsed());
+        assert_eq!(
+            "split 1  v[1]>=2  0:00.01 (+0:00.01)\n\
+             split 2  mem[0x301]==3  0:00.03 (+0:00.01)\n\
+             final  0:00.03\n",
+            speedrun.borrow().report()
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::interpreter::Interpreter;
+use crate::memory::{self, DisplayBuffer, Memory, MemoryMap};
+use crate::plugin::Plugin;
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::ops::Range;
+
+const DISPLAY_AREA: Range<u16> = memory::DISPLAY_LOC..memory::MAX_SIZE;
+
+/// Warns about memory accesses that only work thanks to the fixed memory map:
+/// FX55/FX33 writing into the font or display area, and DXYN reading sprite data
+/// from memory that was never initialized.
+pub struct StrictChecker<W: Write> {
+    out: W,
+    initialized: Vec<bool>,
+    memory_map: MemoryMap,            // of the interpreter, for the font areas
+    display_area: Option<Range<u16>>, // None with an external display buffer
+    reported: HashSet<u16>,           // warn once per instruction address
+}
+
+impl<W: Write> StrictChecker<W> {
+    pub fn new(out: W) -> Self {
+        StrictChecker {
+            out,
+            initialized: vec![false; memory::MAX_SIZE as usize],
+            memory_map: MemoryMap::default(),
+            display_area: Some(DISPLAY_AREA),
+            reported: HashSet::new(),
+        }
+    }
+
+    fn warn(&mut self, pc: u16, opcode: u16, message: String) {
+        if self.reported.insert(pc) {
+            // diagnostics must not stop the emulation
+            let _ = writeln!(self.out, "strict: {:04X}: {:04X} {}", pc, opcode, message);
+        }
+    }
+
+    fn check_write(&mut self, pc: u16, opcode: u16, addrs: Range<u16>) {
+        let [small_font, big_font] = self.memory_map.font_areas();
+        let areas = [
+            Some(("font", small_font)),
+            Some(("big font", big_font)),
+            self.display_area.clone().map(|area| ("display", area)),
+        ];
+        for (name, area) in areas.into_iter().flatten() {
+            if addrs.start < area.end && area.start < addrs.end {
+                let message = format!(
+                    "writes {:03X}-{:03X} into the {} area",
+                    addrs.start,
+                    addrs.end - 1,
+                    name
+                );
+                self.warn(pc, opcode, message);
+            }
+        }
+
+        self.mark_initialized(addrs);
+    }
+
+    fn check_sprite_read(&mut self, pc: u16, opcode: u16, addrs: Range<u16>) {
+        let uninitialized = addrs.clone().find(|&addr| {
+            !self
+                .initialized
+                .get(addr as usize)
+                .copied()
+                .unwrap_or(false)
+        });
+
+        if let Some(addr) = uninitialized {
+            let message = format!(
+                "reads sprite data from uninitialized memory at {:03X}",
+                addr
+            );
+            self.warn(pc, opcode, message);
+        }
+    }
+
+    fn mark_initialized(&mut self, addrs: Range<u16>) {
+        for addr in addrs {
+            if let Some(initialized) = self.initialized.get_mut(addr as usize) {
+                *initialized = true;
+            }
+        }
+    }
+}
+
+impl<W: Write> Plugin for StrictChecker<W> {
+    fn on_load(&mut self, program: &[u8], memory: &mut Memory) {
+        self.initialized.resize(memory.size(), false);
+        for area in self.memory_map.font_areas() {
+            self.mark_initialized(area);
+        }
+        self.display_area = match memory.display_buffer() {
+            DisplayBuffer::Ram => Some(DISPLAY_AREA),
+            DisplayBuffer::External => None,
+        };
+        if let Some(area) = self.display_area.clone() {
+            self.mark_initialized(area);
+        }
+        self.mark_initialized(memory::PROG_LOC..memory::PROG_LOC + program.len() as u16);
+    }
+
+    fn on_instruction(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        // fonts moved after the program was loaded
+        if interpreter.memory_map() != self.memory_map {
+            self.memory_map = interpreter.memory_map();
+            for area in self.memory_map.font_areas() {
+                self.mark_initialized(area);
+            }
+        }
+
+        let vi = interpreter.index();
+        let x = (opcode & 0x0F00) >> 8;
+        let n = opcode & 0x000F;
+
+        match opcode & 0xF0FF {
+            0xF055 => self.check_write(pc, opcode, vi..vi.saturating_add(x + 1)),
+            0xF033 => self.check_write(pc, opcode, vi..vi.saturating_add(3)),
+            _ if opcode & 0xF000 == 0xD000 => {
+                self.check_sprite_read(pc, opcode, vi..vi.saturating_add(n))
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strict_warnings() {
+        let program = [
+            0xA0, 0x50, // 200: I = 0x050 (font)
+            0xF1, 0x55, // 202: mem write V0-V1 at addr I
+            0xA6, 0x00, // 204: I = 0x600 (uninitialized)
+            0xD0, 0x02, // 206: draw 2 rows sprite
+            0xF0, 0x33, // 208: BCD of V0 at addr I
+            0xD0, 0x03, // 20A: draw 3 rows sprite, initialized by FX33
+            0x00, 0x00, // 20C: stop
+        ];
+
+        let mut checker = StrictChecker::new(Vec::new());
+        let mut interpreter = Interpreter::new();
+        let mut memory = Memory::new();
+        memory.load_prog(&program);
+        checker.on_load(&program, &mut memory);
+
+        while !interpreter.stop() {
+            let opcode = interpreter.next(&memory);
+            checker.on_instruction(interpreter.pc, opcode, &interpreter, &memory);
+            interpreter.step(&mut memory);
+        }
+
+        assert_eq!(
+            "strict: 0202: F155 writes 050-051 into the font area\n\
+             strict: 0206: D002 reads sprite data from uninitialized memory at 600\n",
+            String::from_utf8(checker.out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::framebuffer::FrameBuffer;
+use crate::interpreter::Interpreter;
+use crate::memory::{self, Memory};
+use crate::opcodes;
+use crate::plugin::Plugin;
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Changed pixels listed one by one at most, beyond only their number is printed
+const MAX_LISTED_PIXELS: usize = 8;
+
+/// Writes the address, opcode and mnemonic of every executed instruction
+pub struct TraceLogger<W: Write> {
+    out: W,
+    before: Option<State>, // with diffs, the state before the current instruction
+}
+
+/// What an instruction may change
+struct State {
+    vx: [u8; 16],
+    vi: u16,
+    sc: u8,
+    dt: u8,
+    st: u8,
+    ram: Box<[u8]>,
+    display: FrameBuffer,
+}
+
+impl State {
+    fn new(interpreter: &Interpreter, memory: &Memory) -> Self {
+        State {
+            vx: interpreter.vx,
+            vi: interpreter.vi,
+            sc: interpreter.sc,
+            dt: interpreter.dt,
+            st: interpreter.st,
+            ram: memory.ram().into(),
+            display: FrameBuffer::from_memory(memory),
+        }
+    }
+
+    /// What changed from this state, e.g. `V3: 0A→0B  I: 200→205  px(12,4): 0→1`
+    fn diff(&self, after: &State) -> String {
+        let mut out = String::new();
+
+        for (x, (before, after)) in self.vx.iter().zip(after.vx.iter()).enumerate() {
+            if before != after {
+                write!(out, "  V{:X}: {:02X}→{:02X}", x, before, after).unwrap();
+            }
+        }
+        let registers = [
+            ("I", self.vi, after.vi, 3),
+            ("SP", self.sc as u16, after.sc as u16, 1),
+            ("DT", self.dt as u16, after.dt as u16, 2),
+            ("ST", self.st as u16, after.st as u16, 2),
+        ];
+        for (name, before, after, width) in registers {
+            if before != after {
+                write!(out, "  {}: {:0w$X}→{:0w$X}", name, before, after, w = width).unwrap();
+            }
+        }
+
+        // the display area is listed as pixels, the memory above it is XO-CHIP memory
+        let ram_end = self.ram.len().min(after.ram.len());
+        let addrs = (0..memory::DISPLAY_LOC as usize).chain(memory::MAX_SIZE as usize..ram_end);
+        for addr in addrs {
+            if self.ram[addr] != after.ram[addr] {
+                write!(
+                    out,
+                    "  [{:03X}]: {:02X}→{:02X}",
+                    addr, self.ram[addr], after.ram[addr]
+                )
+                .unwrap();
+            }
+        }
+
+        let pixels = self.display.diff(&after.display);
+        if pixels.len() > MAX_LISTED_PIXELS {
+            write!(out, "  px: {} changed", pixels.len()).unwrap();
+        } else {
+            for (x, y) in pixels {
+                let on = after.display.pixel(x, y) as u8;
+                write!(out, "  px({},{}): {}→{}", x, y, 1 - on, on).unwrap();
+            }
+        }
+
+        out
+    }
+}
+
+impl<W: Write> TraceLogger<W> {
+    pub fn new(out: W) -> Self {
+        TraceLogger { out, before: None }
+    }
+
+    /// Also write the registers, memory and pixels each instruction changed
+    pub fn with_diffs(out: W) -> Self {
+        TraceLogger {
+            out,
+            before: Some(State::new(&Interpreter::new(), &Memory::new())),
+        }
+    }
+}
+
+/// e.g. `ADD V1, V2`
+fn mnemonic(opcode: u16) -> String {
+    opcodes::lookup(opcode).map_or("unknown".to_string(), |row| row.format(opcode))
+}
+
+impl<W: Write> Plugin for TraceLogger<W> {
+    fn on_instruction(&mut self, pc: u16, opcode: u16, interpreter: &Interpreter, memory: &Memory) {
+        match &mut self.before {
+            Some(before) => *before = State::new(interpreter, memory),
+            // tracing must not stop the emulation (e.g. closed pipe)
+            None => {
+                let _ = writeln!(self.out, "{:04X}: {:04X}  {}", pc, opcode, mnemonic(opcode));
+            }
+        }
+    }
+
+    fn after_instruction(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        interpreter: &Interpreter,
+        memory: &Memory,
+    ) {
+        if let Some(before) = &self.before {
+            let diff = before.diff(&State::new(interpreter, memory));
+            let _ = writeln!(
+                self.out,
+                "{:04X}: {:04X}  {}{}",
+                pc,
+                opcode,
+                mnemonic(opcode),
+                diff
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::rom_builder::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_trace() {
+        let mut trace = TraceLogger::new(Vec::new());
+        let interpreter = Interpreter::new();
+        let memory = Memory::new();
+
+        trace.on_instruction(0x0200, 0x6A02, &interpreter, &memory);
+        trace.on_instruction(0x0202, 0x00E0, &interpreter, &memory);
+
+        assert_eq!(
+            "0200: 6A02  LD VA, 02\n0202: 00E0  CLS\n",
+            String::from_utf8(trace.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trace_diffs() {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::new();
+        chip8.register_plugin(Box::new(TraceLogger::with_diffs(SharedOut(Rc::clone(
+            &out,
+        )))));
+        chip8.load(
+            &[
+                ld_vx(3, 0x0A), // 200
+                add_vx(3, 1),   // 202
+                ld_i(0x300),    // 204
+                store(3),       // 206
+                ld_vx(0, 12),   // 208
+                ld_vx(1, 4),    // 20A
+                ld_i(0x050),    // 20C: font 0, top row F0
+                drw(0, 1, 1),   // 20E
+            ]
+            .concat(),
+        );
+        for _ in 0..8 {
+            chip8.step();
+        }
+
+        assert_eq!(
+            "0200: 630A  LD V3, 0A  V3: 00→0A\n\
+             0202: 7301  ADD V3, 01  V3: 0A→0B\n\
+             0204: A300  LD I, 300  I: 000→300\n\
+             0206: F355  LD [I], V3  [303]: 00→0B\n\
+             0208: 600C  LD V0, 0C  V0: 00→0C\n\
+             020A: 6104  LD V1, 04  V1: 00→04\n\
+             020C: A050  LD I, 050  I: 300→050\n\
+             020E: D011  DRW V0, V1, 1  px(12,4): 0→1  px(13,4): 0→1  px(14,4): 0→1  px(15,4): 0→1\n",
+            String::from_utf8(out.borrow().clone()).unwrap()
+        );
+    }
+
+    struct SharedOut(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOut {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::interpreter::Interpreter;
+use crate::memory::{self, DisplayBuffer, Memory, MemoryMap};
+use crate::plugin::Plugin;
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::ops::Range;
+
+/// Logs the reads of memory never written since the program was loaded: instructions
+/// fetched, DXYN sprite data and FX65 loads. The fonts, the display area in RAM and the
+/// program count as written. Run with `MemInit` patterns to see what the program does with them.
+pub struct UninitializedReads<W: Write> {
+    out: W,
+    written: Vec<bool>,
+    memory_map: MemoryMap,  // of the interpreter, for the font areas
+    reported: HashSet<u16>, // log once per instruction address
+}
+
+impl<W: Write> UninitializedReads<W> {
+    pub fn new(out: W) -> Self {
+        UninitializedReads {
+            out,
+            written: vec![false; memory::MAX_SIZE as usize],
+            memory_map: MemoryMap::default(),
+            reported: HashSet::new(),
+        }
+    }
+
+    fn mark_written(&mut self, addrs: Range<u16>) {
+        for addr in addrs {
+            let size = self.written.len();
+            self.written[addr as usize % size] = true;
+        }
+    }
+
+    fn check_read(&mut self, pc: u16, opcode: u16, addrs: Range<u16>) {
+        let unwritten = addrs
+            .map(|addr| addr as usize % self.written.len())
+            .find(|&addr| !self.written[addr]);
+
+        if let Some(addr) = unwritten {
+            if self.reported.insert(pc) {
+                // diagnostics must not stop the emulation
+                let _ = writeln!(
+                    self.out,
+                    "uninit: {:04X}: {:04X} reads {:03X}, never written",
+                    pc, opcode, addr
+                );
+            }
+        }
+    }
+}
+
+impl<W: Write> Plugin for UninitializedReads<W> {
+    fn on_load(&mut self, program: &[u8], memory: &mut Memory) {
+        self.written = vec![false; memory.size()];
+        for area in self.memory_map.font_areas() {
+            self.mark_written(area);
+        }
+        if memory.display_buffer() == DisplayBuffer::Ram {
+            self.mark_written(memory::DISPLAY_LOC..memory::MAX_SIZE);
+        }
+        self.mark_written(memory::PROG_LOC..memory::PROG_LOC + program.len() as u16);
+    }
+
+    fn on_instruction(
+        &mut self,
+        pc: u16,
+        opcode: u16,
+        interpreter: &Interpreter,
+        _memory: &Memory,
+    ) {
+        // fonts moved after the program was loaded
+        if interpreter.memory_map() != self.memory_map {
+            self.memory_map = interpreter.memory_map();
+            for area in self.memory_map.font_areas() {
+                self.mark_written(area);
+            }
+        }
+
+        let vi = interpreter.index();
+        let x = (opcode & 0x0F00) >> 8;
+        let n = opcode & 0x000F;
+
+        self.check_read(pc, opcode, pc..pc.saturating_add(2));
+        match opcode & 0xF0FF {
+            0xF065 => self.check_read(pc, opcode, vi..vi.saturating_add(x + 1)),
+            0xF055 => self.mark_written(vi..vi.saturating_add(x + 1)),
+            0xF033 => self.mark_written(vi..vi.saturating_add(3)),
+            _ if opcode & 0xF000 == 0xD000 => self.check_read(pc, opcode, vi..vi.saturating_add(n)),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::memory::MemInit;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_uninitialized_reads() {
+        let mut chip8 = Chip8::new();
+        chip8.set_mem_init(MemInit::Ff);
+        let program = [
+            ld_i(0x600),  // 200
+            load(1),      // 202: never written
+            store(1),     // 204
+            load(1),      // 206: written by the previous instruction
+            drw(0, 0, 3), // 208: the third row never written
+            jp(0x700),    // 20A: runs FFFF, an unknown opcode
+        ]
+        .concat();
+        chip8.load(&program);
+        let mut reads = UninitializedReads::new(Vec::new());
+        reads.on_load(&program, &mut chip8.memory);
+
+        while !chip8.interpreter.stop() {
+            let opcode = chip8.interpreter.next(&chip8.memory);
+            reads.on_instruction(
+                chip8.interpreter.pc,
+                opcode,
+                &chip8.interpreter,
+                &chip8.memory,
+            );
+            chip8.step();
+        }
+
+        assert_eq!(
+            "uninit: 0202: F165 reads 600, never written\n\
+             uninit: 0208: D003 reads 602, never written\n\
+             uninit: 0700: FFFF reads 700, never written\n",
+            String::from_utf8(reads.out).unwrap()
+        );
+    }
+}
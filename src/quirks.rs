@@ -0,0 +1,311 @@
+use crate::memory::AddressSpace;
+use crate::timing::TimerMode;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use std::fmt::Write;
+
+/// Behaviors that differ between CHIP-8 implementations
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Quirks {
+    pub vf_reset: bool,         // 8XY1, 8XY2, 8XY3 reset VF to 0
+    pub memory_increment: bool, // FX55, FX65 increment I
+    pub display_wait: bool,     // DXYN waits for the next frame
+    pub clipping: bool,         // sprites are clipped at the screen edges instead of wrapping
+    pub shifting: bool,         // 8XY6, 8XYE shift VX in place instead of VY
+    pub jumping: bool,          // BXNN jumps to XNN + VX instead of BNNN to NNN + V0
+    pub collision_rows: bool,   // DXYN sets VF to the rows colliding or clipped at the bottom
+}
+
+impl Quirks {
+    pub const NAMES: [&'static str; 7] = [
+        "vf_reset",
+        "memory_increment",
+        "display_wait",
+        "clipping",
+        "shifting",
+        "jumping",
+        "collision_rows",
+    ];
+
+    /// What each quirk changes when enabled, in the same order as `Quirks::NAMES`
+    pub const DESCRIPTIONS: [&'static str; 7] = [
+        "8XY1, 8XY2 and 8XY3 reset VF to 0",
+        "FX55 and FX65 leave I incremented past the last register",
+        "DXYN waits for the next frame (60 sprites per second at most)",
+        "sprites are clipped at the screen edges instead of wrapping around",
+        "8XY6 and 8XYE shift VX in place, ignoring VY",
+        "BNNN becomes BXNN, jumping to XNN + VX instead of NNN + V0",
+        "DXYN sets VF to the number of sprite rows colliding or clipped at the bottom, not 1",
+    ];
+
+    /// Well-known programs that break when the quirk has the wrong value,
+    /// in the same order as `Quirks::NAMES`
+    pub const KNOWN_PROGRAMS: [&'static str; 7] = [
+        "on: original COSMAC VIP games using logic operations on VF",
+        "on: VIP games storing register tables with consecutive FX55; off: SUPER-CHIP games",
+        "on: VIP games paced by their drawing speed, which run too fast without it",
+        "on: Blitz (draws garbage at the top when wrapping); off: XO-CHIP programs scrolling across edges",
+        "on: Space Invaders (David Winter) and most SCHIP games; off: VIP games",
+        "on: Spacefight 2091! and other SCHIP games using BXNN; off: VIP jump tables",
+        "on: SCHIP games counting the rows hit from VF; off: games comparing VF with 1",
+    ];
+
+    /// Quirk values in the same order as `Quirks::NAMES`
+    pub fn values(&self) -> [bool; 7] {
+        [
+            self.vf_reset,
+            self.memory_increment,
+            self.display_wait,
+            self.clipping,
+            self.shifting,
+            self.jumping,
+            self.collision_rows,
+        ]
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Profile::Modern.quirks()
+    }
+}
+
+/// Built-in quirk profiles
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    /// Behavior most modern CHIP-8 programs expect
+    Modern,
+    /// Original COSMAC VIP interpreter
+    Vip,
+    /// SUPER-CHIP 1.1
+    Schip,
+    /// XO-CHIP
+    XoChip,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 4] = [
+        Profile::Modern,
+        Profile::Vip,
+        Profile::Schip,
+        Profile::XoChip,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Modern => "modern",
+            Profile::Vip => "vip",
+            Profile::Schip => "schip",
+            Profile::XoChip => "xo-chip",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Profile::Modern => "behavior most modern CHIP-8 programs expect",
+            Profile::Vip => "original COSMAC VIP interpreter",
+            Profile::Schip => "SUPER-CHIP 1.1",
+            Profile::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// Number of nested subroutine calls supported
+    pub fn stack_depth(&self) -> usize {
+        match self {
+            Profile::Vip => 12,
+            Profile::Modern | Profile::Schip | Profile::XoChip => 16,
+        }
+    }
+
+    /// 64K of memory for XO-CHIP, 4K with 12-bit addresses for the others
+    pub fn address_space(&self) -> AddressSpace {
+        match self {
+            Profile::XoChip => AddressSpace::XoChip,
+            Profile::Modern | Profile::Vip | Profile::Schip => AddressSpace::Classic,
+        }
+    }
+
+    /// Delay timer granularity: frame accurate for the original platforms
+    pub fn timer_mode(&self) -> TimerMode {
+        match self {
+            Profile::Vip | Profile::Schip => TimerMode::Frame,
+            Profile::Modern | Profile::XoChip => TimerMode::Interpolated,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Profile::Modern => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: false,
+                collision_rows: false,
+            },
+            Profile::Vip => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                display_wait: true,
+                clipping: true,
+                shifting: false,
+                jumping: false,
+                collision_rows: false,
+            },
+            Profile::Schip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+                collision_rows: false, // SUPER-CHIP only does it in high resolution
+            },
+            Profile::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+                jumping: false,
+                collision_rows: false,
+            },
+        }
+    }
+}
+
+/// Every quirk with its effect and the programs depending on it, and the value of
+/// each quirk in every profile
+pub fn list() -> String {
+    let mut out = String::new();
+
+    writeln!(out, "quirks:").unwrap();
+    for (i, name) in Quirks::NAMES.iter().enumerate() {
+        writeln!(out, "  {}: {}", name, Quirks::DESCRIPTIONS[i]).unwrap();
+        writeln!(out, "    {}", Quirks::KNOWN_PROGRAMS[i]).unwrap();
+    }
+
+    writeln!(out, "profiles:").unwrap();
+    for profile in Profile::ALL {
+        writeln!(out, "  {}: {}", profile.name(), profile.description()).unwrap();
+    }
+
+    let width = Quirks::NAMES.iter().map(|name| name.len()).max().unwrap();
+    write!(out, "{:width$}", "", width = width + 2).unwrap();
+    for profile in Profile::ALL {
+        write!(out, " {:>8}", profile.name()).unwrap();
+    }
+    writeln!(out).unwrap();
+    for (i, name) in Quirks::NAMES.iter().enumerate() {
+        write!(out, "  {:width$}", name, width = width).unwrap();
+        for profile in Profile::ALL {
+            let value = if profile.quirks().values()[i] {
+                "on"
+            } else {
+                "off"
+            };
+            write!(out, " {:>8}", value).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    out
+}
+
+/// Quirks, stack depth and timer mode a program runs with: the ones of its profile, unless
+/// overridden on the command line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Settings {
+    pub profile: Profile,
+    pub quirks: Quirks,
+    pub stack_depth: usize,
+    pub timer_mode: TimerMode,
+}
+
+impl Settings {
+    /// The settings of `profile`, none overridden
+    pub fn new(profile: Profile) -> Self {
+        Settings {
+            profile,
+            quirks: profile.quirks(),
+            stack_depth: profile.stack_depth(),
+            timer_mode: profile.timer_mode(),
+        }
+    }
+
+    /// The settings of `profile`, with the overrides given on the command line
+    pub fn with_overrides(
+        profile: Profile,
+        timer_mode: Option<TimerMode>,
+        stack_depth: Option<usize>,
+        collision_rows: bool,
+    ) -> Self {
+        let mut settings = Settings::new(profile);
+        settings.quirks.collision_rows |= collision_rows;
+        settings.timer_mode = timer_mode.unwrap_or(settings.timer_mode);
+        settings.stack_depth = stack_depth.unwrap_or(settings.stack_depth);
+        settings
+    }
+}
+
+/// Settings applied when running with `settings`, described
+pub fn effective(settings: &Settings) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "profile: {}", settings.profile.name()).unwrap();
+    let values = settings.quirks.values();
+    for (name, value) in Quirks::NAMES.iter().zip(values) {
+        writeln!(out, "  {}: {}", name, if value { "on" } else { "off" }).unwrap();
+    }
+    writeln!(out, "  stack depth: {}", settings.stack_depth).unwrap();
+    let timer_mode = match settings.timer_mode {
+        TimerMode::Frame => "frame",
+        TimerMode::Interpolated => "interpolated",
+    };
+    writeln!(out, "  timer mode: {}", timer_mode).unwrap();
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list() {
+        let list = list();
+
+        for name in Quirks::NAMES {
+            assert!(list.contains(&format!("  {}: ", name)));
+        }
+        assert!(list.contains("   modern      vip    schip  xo-chip"));
+        assert!(list.contains("  jumping               off      off       on      off"));
+        assert!(list.contains("  collision_rows        off      off      off      off"));
+    }
+
+    #[test]
+    fn test_effective() {
+        let settings = effective(&Settings::new(Profile::Vip));
+
+        assert!(settings.starts_with("profile: vip\n"));
+        assert!(settings.contains("  vf_reset: on\n"));
+        assert!(settings.contains("  shifting: off\n"));
+        assert!(settings.contains("  stack depth: 12\n"));
+        assert!(settings.contains("  timer mode: frame\n"));
+
+        // with overrides
+        let settings = effective(&Settings::with_overrides(
+            Profile::Vip,
+            Some(TimerMode::Interpolated),
+            Some(32),
+            true,
+        ));
+        assert!(settings.contains("  collision_rows: on\n"));
+        assert!(settings.contains("  stack depth: 32\n"));
+        assert!(settings.contains("  timer mode: interpolated\n"));
+    }
+}
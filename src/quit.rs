@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Delay within which the quit key must be pressed again when confirmation is required
+pub const CONFIRM_WINDOW: Duration = Duration::from_secs(1);
+
+/// Decides whether a press of the quit key exits, optionally asking for a second press
+pub struct QuitGuard {
+    confirm: bool,
+    last_press: Option<Instant>,
+}
+
+impl QuitGuard {
+    pub fn new(confirm: bool) -> Self {
+        QuitGuard {
+            confirm,
+            last_press: None,
+        }
+    }
+
+    /// Returns true if the emulator should exit
+    pub fn press(&mut self, now: Instant) -> bool {
+        if !self.confirm {
+            return true;
+        }
+
+        let confirmed = self
+            .last_press
+            .is_some_and(|last| now.duration_since(last) <= CONFIRM_WINDOW);
+        self.last_press = Some(now);
+
+        confirmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_press() {
+        let start = Instant::now();
+
+        let mut guard = QuitGuard::new(false);
+        assert!(guard.press(start));
+
+        let mut guard = QuitGuard::new(true);
+        assert!(!guard.press(start));
+        assert!(!guard.press(start + CONFIRM_WINDOW * 2));
+        assert!(guard.press(start + CONFIRM_WINDOW * 2 + CONFIRM_WINDOW / 2));
+    }
+}
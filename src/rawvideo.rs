@@ -0,0 +1,98 @@
+use crate::framebuffer::{FrameBuffer, HEIGHT, WIDTH};
+use crate::palette::Palette;
+
+use clap::ValueEnum;
+
+use std::io;
+
+/// Pixel formats of the raw video stream
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum PixelFormat {
+    /// 4 bytes per pixel, as ffmpeg's rgba pixel format
+    Rgba,
+}
+
+/// Writes every frame as raw pixels, for encoders reading raw video such as ffmpeg.
+/// The stream starts with a header line `chip8-rawvideo <format> <width>x<height> <fps>`
+/// for the reader to know how to decode the frames that follow.
+pub struct FrameWriter<W: io::Write> {
+    writer: W,
+    format: PixelFormat,
+    frame_rate: u32,
+    header_written: bool,
+}
+
+impl<W: io::Write> FrameWriter<W> {
+    pub fn new(writer: W, format: PixelFormat, frame_rate: u32) -> Self {
+        FrameWriter {
+            writer,
+            format,
+            frame_rate,
+            header_written: false,
+        }
+    }
+
+    pub fn header(&self) -> String {
+        let format = match self.format {
+            PixelFormat::Rgba => "rgba",
+        };
+        format!(
+            "chip8-rawvideo {} {}x{} {}\n",
+            format, WIDTH, HEIGHT, self.frame_rate
+        )
+    }
+
+    /// Write a frame, preceded by the header on the first one
+    pub fn write_frame(&mut self, frame: &FrameBuffer, palette: &Palette) -> io::Result<()> {
+        if !self.header_written {
+            self.writer.write_all(self.header().as_bytes())?;
+            self.header_written = true;
+        }
+
+        self.writer.write_all(&rgba(frame, palette))?;
+        self.writer.flush()
+    }
+}
+
+/// RGBA pixels of a frame in the palette colors, row after row
+pub fn rgba(frame: &FrameBuffer, palette: &Palette) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 4);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let color = match frame.pixel(x as u8, y as u8) {
+                true => palette.foreground(),
+                false => palette.background(),
+            };
+            pixels.extend_from_slice(&color);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+    use crate::timing::FRAME_RATE;
+
+    #[test]
+    fn test_write_frames() {
+        let mut memory = Memory::new();
+        Display::write_pixel(&mut memory, 1, 0);
+        let frame = FrameBuffer::from_memory(&memory);
+        let palette = Palette::new();
+
+        let mut out = Vec::new();
+        let mut writer = FrameWriter::new(&mut out, PixelFormat::Rgba, FRAME_RATE);
+        writer.write_frame(&frame, &palette).unwrap();
+        writer.write_frame(&frame, &palette).unwrap();
+
+        let header = "chip8-rawvideo rgba 64x32 60\n";
+        assert!(out.starts_with(header.as_bytes()));
+        assert_eq!(header.len() + 2 * 64 * 32 * 4, out.len());
+        let pixels = &out[header.len()..];
+        assert_eq!(palette.background(), pixels[0..4]);
+        assert_eq!(palette.foreground(), pixels[4..8]);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::batch::{RomResult, Status};
+use crate::filmstrip;
+use crate::palette::Palette;
+
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+/// Scale of the final frames in reports
+const FRAME_SCALE: usize = 4;
+
+/// Compatibility report formats, chosen from the file extension
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// Final frames saved as PNG files in a directory next to the report
+    Markdown,
+    /// Self-contained, with the final frames embedded
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(Format::Markdown),
+            "html" | "htm" => Some(Format::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Write a compatibility report of a batch run to `path`, in the format of its extension
+pub fn write(path: &Path, results: &[RomResult], palette: &Palette) -> io::Result<()> {
+    let format = Format::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "report file must be a .md or .html file",
+        )
+    })?;
+
+    let report = match format {
+        Format::Markdown => {
+            // frames go to <report name>-frames/ next to the report
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let frames_dir_name = format!("{}-frames", stem);
+            let frames_dir = path.with_file_name(&frames_dir_name);
+            std::fs::create_dir_all(&frames_dir)?;
+            for result in results {
+                let file = std::fs::File::create(frames_dir.join(frame_file_name(result)))?;
+                filmstrip::write_frame_png(&result.frame, palette, FRAME_SCALE, file)?;
+            }
+            markdown(results, &frames_dir_name)
+        }
+        Format::Html => {
+            let mut frames = Vec::new();
+            for result in results {
+                let mut png = Vec::new();
+                filmstrip::write_frame_png(&result.frame, palette, FRAME_SCALE, &mut png)?;
+                frames.push(png);
+            }
+            html(results, &frames)
+        }
+    };
+
+    std::fs::write(path, report)
+}
+
+/// Markdown table, with the final frames read from `frames_dir`
+pub fn markdown(results: &[RomResult], frames_dir: &str) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "# Compatibility report\n").unwrap();
+    writeln!(out, "{}\n", summary(results)).unwrap();
+    writeln!(
+        out,
+        "| Program | Status | Platform | Profile | Failure | Final frame |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---|---|---|---|---|").unwrap();
+    for result in results {
+        let name = program_name(result).replace('|', "\\|");
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | ![{}]({}/{}) |",
+            name,
+            result.status.name(),
+            result.platform.name(),
+            result.profile.name(),
+            failure(&result.status),
+            name,
+            percent_encode(frames_dir),
+            percent_encode(&frame_file_name(result))
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+/// Self-contained HTML page, `frames` being the PNG final frames in the order of `results`
+pub fn html(results: &[RomResult], frames: &[Vec<u8>]) -> String {
+    let mut out = String::new();
+
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<title>Compatibility report</title>\n<style>\n");
+    out.push_str("table { border-collapse: collapse; }\n");
+    out.push_str("td, th { border: 1px solid #ccc; padding: 4px 8px; }\n");
+    out.push_str(".failed { background: #fdd; }\n");
+    out.push_str("img { image-rendering: pixelated; }\n");
+    out.push_str("</style>\n</head>\n<body>\n<h1>Compatibility report</h1>\n");
+    writeln!(out, "<p>{}</p>", escape(&summary(results))).unwrap();
+    out.push_str("<table>\n<tr><th>Program</th><th>Status</th><th>Platform</th>");
+    out.push_str("<th>Profile</th><th>Failure</th><th>Final frame</th></tr>\n");
+
+    for (result, frame) in results.iter().zip(frames) {
+        let class = match result.status.is_ok() {
+            true => "",
+            false => " class=\"failed\"",
+        };
+        writeln!(
+            out,
+            "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td><img src=\"data:image/png;base64,{}\" alt=\"{}\"></td></tr>",
+            class,
+            escape(&program_name(result)),
+            result.status.name(),
+            result.platform.name(),
+            result.profile.name(),
+            escape(&failure(&result.status)),
+            base64(frame),
+            escape(&program_name(result))
+        )
+        .unwrap();
+    }
+
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+fn summary(results: &[RomResult]) -> String {
+    let failed = results
+        .iter()
+        .filter(|result| !result.status.is_ok())
+        .count();
+    format!(
+        "chip8-interpreter {}: {} programs, {} failed",
+        env!("CARGO_PKG_VERSION"),
+        results.len(),
+        failed
+    )
+}
+
+fn program_name(result: &RomResult) -> String {
+    result
+        .path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn frame_file_name(result: &RomResult) -> String {
+    format!("{}.png", program_name(result))
+}
+
+/// Failing opcode or fault, empty for programs that ran fine
+fn failure(status: &Status) -> String {
+    match status {
+        Status::UnknownOpcode { pc, opcode } => format!("{:04X} at {:03X}", opcode, pc),
+        Status::Crashed(error) => error.to_string(),
+        Status::Running | Status::Exited => String::new(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape a path for a Markdown link, where spaces and parentheses would end it
+fn percent_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => write!(out, "%{:02X}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::Extension;
+    use crate::framebuffer::FrameBuffer;
+    use crate::memory::Memory;
+    use crate::quirks::Profile;
+
+    use std::path::PathBuf;
+
+    fn results() -> Vec<RomResult> {
+        let frame = FrameBuffer::from_memory(&Memory::new());
+        vec![
+            RomResult {
+                path: PathBuf::from("roms/pong.ch8"),
+                platform: Extension::Chip8,
+                profile: Profile::Modern,
+                status: Status::Running,
+                instructions: 1000,
+                frame,
+            },
+            RomResult {
+                path: PathBuf::from("roms/a<b>.ch8"),
+                platform: Extension::Schip,
+                profile: Profile::Schip,
+                status: Status::UnknownOpcode {
+                    pc: 0x204,
+                    opcode: 0x00E1,
+                },
+                instructions: 2,
+                frame,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_markdown() {
+        let report = markdown(&results(), "report-frames");
+
+        assert!(report.contains("2 programs, 1 failed"));
+        assert!(report.contains(
+            "| pong.ch8 | running | CHIP-8 | modern |  | ![pong.ch8](report-frames/pong.ch8.png) |"
+        ));
+        assert!(report.contains("| unknown opcode | SUPER-CHIP | schip | 00E1 at 204 |"));
+
+        let report = markdown(&results(), "my report-frames");
+        assert!(report.contains("![pong.ch8](my%20report-frames/pong.ch8.png)"));
+        assert!(report.contains("(my%20report-frames/a%3Cb%3E.ch8.png)"));
+    }
+
+    #[test]
+    fn test_html() {
+        let report = html(&results(), &[b"Man".to_vec(), b"Ma".to_vec()]);
+
+        assert!(report.contains("<td>a&lt;b&gt;.ch8</td>"));
+        assert!(report.contains("data:image/png;base64,TWFu\""));
+        assert!(report.contains("data:image/png;base64,TWE=\""));
+        assert!(report.contains("<tr class=\"failed\">"));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            Some(Format::Html),
+            Format::from_path(Path::new("report.HTML"))
+        );
+        assert_eq!(
+            Some(Format::Markdown),
+            Format::from_path(Path::new("report.md"))
+        );
+        assert_eq!(None, Format::from_path(Path::new("report.txt")));
+    }
+}
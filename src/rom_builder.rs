@@ -0,0 +1,317 @@
+//! Opcode constructors, to write test programs as instructions instead of hexadecimal:
+//!
+//! ```
+//! use chip8_interpreter::rom_builder::*;
+//!
+//! let program = [ld_vx(0, 0xAA), add_vx(0, 1), jp(0x202)].concat();
+//! assert_eq!(vec![0x60, 0xAA, 0x70, 0x01, 0x12, 0x02], program);
+//! ```
+//!
+//! Registers are 0x0 to 0xF and addresses 0x000 to 0xFFF, higher bits are dropped.
+
+fn op(opcode: u16) -> [u8; 2] {
+    opcode.to_be_bytes()
+}
+
+fn nnn(prefix: u16, addr: u16) -> [u8; 2] {
+    op(prefix << 12 | addr & 0x0FFF)
+}
+
+fn xnn(prefix: u16, x: u8, nn: u8) -> [u8; 2] {
+    op(prefix << 12 | (x as u16 & 0xF) << 8 | nn as u16)
+}
+
+fn xyn(prefix: u16, x: u8, y: u8, n: u8) -> [u8; 2] {
+    op(prefix << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n as u16 & 0xF)
+}
+
+/// 0000: stop the program
+pub fn halt() -> [u8; 2] {
+    op(0x0000)
+}
+
+/// 00E0: clear the display
+pub fn cls() -> [u8; 2] {
+    op(0x00E0)
+}
+
+/// 00EE: return from a subroutine
+pub fn ret() -> [u8; 2] {
+    op(0x00EE)
+}
+
+/// 1NNN: jump to `addr`
+pub fn jp(addr: u16) -> [u8; 2] {
+    nnn(0x1, addr)
+}
+
+/// 2NNN: call the subroutine at `addr`
+pub fn call(addr: u16) -> [u8; 2] {
+    nnn(0x2, addr)
+}
+
+/// 3XNN: skip the next instruction if VX == NN
+pub fn se_vx(x: u8, nn: u8) -> [u8; 2] {
+    xnn(0x3, x, nn)
+}
+
+/// 4XNN: skip the next instruction if VX != NN
+pub fn sne_vx(x: u8, nn: u8) -> [u8; 2] {
+    xnn(0x4, x, nn)
+}
+
+/// 5XY0: skip the next instruction if VX == VY
+pub fn se_vx_vy(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x5, x, y, 0x0)
+}
+
+/// 6XNN: VX = NN
+pub fn ld_vx(x: u8, nn: u8) -> [u8; 2] {
+    xnn(0x6, x, nn)
+}
+
+/// 7XNN: VX += NN, without carry
+pub fn add_vx(x: u8, nn: u8) -> [u8; 2] {
+    xnn(0x7, x, nn)
+}
+
+/// 8XY0: VX = VY
+pub fn ld_vx_vy(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x0)
+}
+
+/// 8XY1: VX |= VY
+pub fn or(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x1)
+}
+
+/// 8XY2: VX &= VY
+pub fn and(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x2)
+}
+
+/// 8XY3: VX ^= VY
+pub fn xor(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x3)
+}
+
+/// 8XY4: VX += VY, VF = carry
+pub fn add_vx_vy(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x4)
+}
+
+/// 8XY5: VX -= VY, VF = no borrow
+pub fn sub(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x5)
+}
+
+/// 8XY6: shift right, VF = shifted out bit
+pub fn shr(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x6)
+}
+
+/// 8XY7: VX = VY - VX, VF = no borrow
+pub fn subn(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0x7)
+}
+
+/// 8XYE: shift left, VF = shifted out bit
+pub fn shl(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x8, x, y, 0xE)
+}
+
+/// 9XY0: skip the next instruction if VX != VY
+pub fn sne_vx_vy(x: u8, y: u8) -> [u8; 2] {
+    xyn(0x9, x, y, 0x0)
+}
+
+/// ANNN: I = `addr`
+pub fn ld_i(addr: u16) -> [u8; 2] {
+    nnn(0xA, addr)
+}
+
+/// BNNN: jump to `addr` + V0
+pub fn jp_v0(addr: u16) -> [u8; 2] {
+    nnn(0xB, addr)
+}
+
+/// CXNN: VX = random byte & NN
+pub fn rnd(x: u8, nn: u8) -> [u8; 2] {
+    xnn(0xC, x, nn)
+}
+
+/// DXYN: draw the `n` bytes sprite at I at (VX, VY), VF = collision
+pub fn drw(x: u8, y: u8, n: u8) -> [u8; 2] {
+    xyn(0xD, x, y, n)
+}
+
+/// EX9E: skip the next instruction if the key VX is held
+pub fn skp(x: u8) -> [u8; 2] {
+    xnn(0xE, x, 0x9E)
+}
+
+/// EXA1: skip the next instruction if the key VX is not held
+pub fn sknp(x: u8) -> [u8; 2] {
+    xnn(0xE, x, 0xA1)
+}
+
+/// FX07: VX = delay timer
+pub fn ld_vx_dt(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x07)
+}
+
+/// FX0A: wait for a key press, VX = key
+pub fn ld_vx_k(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x0A)
+}
+
+/// FX15: delay timer = VX
+pub fn ld_dt(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x15)
+}
+
+/// FX18: sound timer = VX
+pub fn ld_st(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x18)
+}
+
+/// FX1E: I += VX
+pub fn add_i(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x1E)
+}
+
+/// FX29: I = font character VX
+pub fn ld_f(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x29)
+}
+
+/// FX33: store the decimal digits of VX at I, I + 1 and I + 2
+pub fn ld_b(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x33)
+}
+
+/// FX55: store V0 to VX from I
+pub fn store(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x55)
+}
+
+/// FX65: load V0 to VX from I
+pub fn load(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x65)
+}
+
+/// 00CN: scroll down `n` lines (SUPER-CHIP)
+pub fn scd(n: u8) -> [u8; 2] {
+    op(0x00C0 | n as u16 & 0xF)
+}
+
+/// 00FB: scroll right 4 pixels (SUPER-CHIP)
+pub fn scr() -> [u8; 2] {
+    op(0x00FB)
+}
+
+/// 00FC: scroll left 4 pixels (SUPER-CHIP)
+pub fn scl() -> [u8; 2] {
+    op(0x00FC)
+}
+
+/// 00FD: exit the interpreter (SUPER-CHIP)
+pub fn exit() -> [u8; 2] {
+    op(0x00FD)
+}
+
+/// 00FE: low resolution (SUPER-CHIP)
+pub fn low() -> [u8; 2] {
+    op(0x00FE)
+}
+
+/// 00FF: high resolution (SUPER-CHIP)
+pub fn high() -> [u8; 2] {
+    op(0x00FF)
+}
+
+/// FX30: I = big font character VX (SUPER-CHIP)
+pub fn ld_hf(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x30)
+}
+
+/// FX75: store V0 to VX in the flag registers (SUPER-CHIP)
+pub fn ld_r(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x75)
+}
+
+/// FX85: load V0 to VX from the flag registers (SUPER-CHIP)
+pub fn ld_vx_r(x: u8) -> [u8; 2] {
+    xnn(0xF, x, 0x85)
+}
+
+/// F000 NNNN: I = `addr`, a 16-bit address (XO-CHIP)
+pub fn ld_i_long(addr: u16) -> [u8; 4] {
+    let [high, low] = addr.to_be_bytes();
+    [0xF0, 0x00, high, low]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler;
+
+    #[test]
+    fn test_opcodes() {
+        let instructions = [
+            (halt(), "0000"),
+            (cls(), "00E0"),
+            (ret(), "00EE"),
+            (jp(0x208), "1NNN"),
+            (call(0x208), "2NNN"),
+            (se_vx(1, 2), "3XNN"),
+            (sne_vx(1, 2), "4XNN"),
+            (se_vx_vy(1, 2), "5XY0"),
+            (ld_vx(1, 2), "6XNN"),
+            (add_vx(1, 2), "7XNN"),
+            (ld_vx_vy(1, 2), "8XY0"),
+            (or(1, 2), "8XY1"),
+            (and(1, 2), "8XY2"),
+            (xor(1, 2), "8XY3"),
+            (add_vx_vy(1, 2), "8XY4"),
+            (sub(1, 2), "8XY5"),
+            (shr(1, 2), "8XY6"),
+            (subn(1, 2), "8XY7"),
+            (shl(1, 2), "8XYE"),
+            (sne_vx_vy(1, 2), "9XY0"),
+            (ld_i(0x208), "ANNN"),
+            (jp_v0(0x208), "BNNN"),
+            (rnd(1, 2), "CXNN"),
+            (drw(1, 2, 3), "DXYN"),
+            (skp(1), "EX9E"),
+            (sknp(1), "EXA1"),
+            (ld_vx_dt(1), "FX07"),
+            (ld_vx_k(1), "FX0A"),
+            (ld_dt(1), "FX15"),
+            (ld_st(1), "FX18"),
+            (add_i(1), "FX1E"),
+            (ld_f(1), "FX29"),
+            (ld_b(1), "FX33"),
+            (store(1), "FX55"),
+            (load(1), "FX65"),
+            (scd(1), "00CN"),
+            (scr(), "00FB"),
+            (scl(), "00FC"),
+            (exit(), "00FD"),
+            (low(), "00FE"),
+            (high(), "00FF"),
+            (ld_hf(1), "FX30"),
+            (ld_r(1), "FX75"),
+            (ld_vx_r(1), "FX85"),
+        ];
+
+        for (bytes, pattern) in instructions {
+            let instruction = disassembler::decode(u16::from_be_bytes(bytes)).unwrap();
+            assert_eq!(pattern, instruction.pattern);
+        }
+
+        assert_eq!([0xD1, 0x24], drw(1, 2, 4));
+        assert_eq!([0x1A, 0xBC], jp(0xFABC));
+        assert_eq!([0xF0, 0x00, 0x12, 0x34], ld_i_long(0x1234));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::chip8::Chip8;
+use crate::error::HaltReason;
+use crate::timing::MAX_CATCH_UP_FRAMES;
+
+use std::future::Future;
+use std::time::Duration;
+
+/// What `Chip8Runtime` needs from an async host, e.g. a browser page through
+/// `wasm-bindgen-futures`, or a native async runtime
+pub trait Host {
+    type Sleep: Future<Output = ()>;
+
+    /// Time elapsed since any fixed start, such as `performance.now()` in a browser where
+    /// `std::time::Instant` is not available
+    fn now(&self) -> Duration;
+
+    /// Future completing after `duration`, such as a `setTimeout` promise
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+
+    /// Called before the frames due run, to push the key events received meanwhile with
+    /// `Chip8::push_key_event`
+    fn poll_input(&mut self, chip8: &mut Chip8);
+
+    /// Called after the frames due ran, to draw the display and play the sound
+    fn present(&mut self, chip8: &Chip8);
+}
+
+/// Runs a machine in real time from an async event loop: the frames due run, then the
+/// runtime yields to the host until the next one instead of blocking the thread
+pub struct Chip8Runtime {
+    chip8: Chip8,
+    cycles_per_frame: u32,
+    frame_duration: Duration,
+}
+
+impl Chip8Runtime {
+    /// `hz` frames per second of `cycles_per_frame` instructions
+    pub fn new(chip8: Chip8, cycles_per_frame: u32, hz: u32) -> Self {
+        Chip8Runtime {
+            chip8,
+            cycles_per_frame,
+            frame_duration: Duration::from_secs(1) / hz.max(1),
+        }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// For the host to load a program or resume between runs
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    /// Run the frames as they fall due until the program halts, sleeping through `host`
+    /// in between. Frames missed during a stall (e.g. a background browser tab) are dropped
+    /// past a few. Returns why the program halted, it can be resumed and run again.
+    pub async fn run<H: Host>(&mut self, host: &mut H) -> HaltReason {
+        let mut next_frame = host.now() + self.frame_duration;
+
+        loop {
+            if let Some(reason) = self.chip8.interpreter.halt_reason() {
+                return reason.clone();
+            }
+
+            host.poll_input(&mut self.chip8);
+            let now = host.now();
+            let mut frames = 0;
+            while now >= next_frame && !self.chip8.interpreter.stop() {
+                self.chip8.run_frame(self.cycles_per_frame);
+                self.chip8.frame();
+                frames += 1;
+                next_frame += self.frame_duration;
+
+                if frames == MAX_CATCH_UP_FRAMES {
+                    next_frame = now + self.frame_duration;
+                }
+            }
+            if frames > 0 {
+                host.present(&self.chip8);
+            }
+
+            if !self.chip8.interpreter.stop() {
+                host.sleep(next_frame.saturating_sub(now)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::KeyEvent;
+    use crate::rom_builder::*;
+
+    use std::cell::Cell;
+    use std::future::{self, Ready};
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Poll a future to completion, without a waker since the fake host never waits
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future
+                .as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+            {
+                return output;
+            }
+        }
+    }
+
+    /// Host whose sleeps complete at once, advancing its clock
+    struct FakeHost {
+        time: Cell<Duration>,
+        presents: u32,
+        press: Option<(Duration, u8)>, // key pressed once the clock reaches the time
+    }
+
+    impl Host for FakeHost {
+        type Sleep = Ready<()>;
+
+        fn now(&self) -> Duration {
+            self.time.get()
+        }
+
+        fn sleep(&self, duration: Duration) -> Ready<()> {
+            self.time.set(self.time.get() + duration);
+            future::ready(())
+        }
+
+        fn poll_input(&mut self, chip8: &mut Chip8) {
+            if let Some((time, key)) = self.press {
+                if self.time.get() >= time {
+                    chip8.push_key_event(KeyEvent { key, down: true });
+                    self.press = None;
+                }
+            }
+        }
+
+        fn present(&mut self, _chip8: &Chip8) {
+            self.presents += 1;
+        }
+    }
+
+    #[test]
+    fn test_runtime() {
+        let mut chip8 = Chip8::new();
+        chip8.load(
+            &[
+                add_vx(0, 1),  // 200: V0 += 1
+                se_vx(0, 100), // 202
+                jp(0x200),     // 204
+                halt(),        // 206: the 300th instruction, in frame 30
+            ]
+            .concat(),
+        );
+        let mut runtime = Chip8Runtime::new(chip8, 10, 60);
+        let mut host = FakeHost {
+            time: Cell::new(Duration::from_secs(5)),
+            presents: 0,
+            press: None,
+        };
+
+        let reason = block_on(runtime.run(&mut host));
+
+        assert_eq!(HaltReason::NullOpcode { pc: 0x206 }, reason);
+        assert_eq!(30, runtime.chip8().metrics().frames);
+        assert_eq!(30, host.presents);
+        assert_eq!(
+            Duration::from_secs(5) + Duration::from_secs(1) / 60 * 30,
+            host.time.get()
+        );
+    }
+
+    #[test]
+    fn test_runtime_input() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx_k(1), halt()].concat());
+        let mut runtime = Chip8Runtime::new(chip8, 10, 60);
+        let mut host = FakeHost {
+            time: Cell::new(Duration::ZERO),
+            presents: 0,
+            press: Some((Duration::from_secs(1) / 60 * 30, 0xA)),
+        };
+
+        let reason = block_on(runtime.run(&mut host));
+
+        // pressed when the 30th frame falls due, which sees it
+        assert_eq!(HaltReason::NullOpcode { pc: 0x202 }, reason);
+        assert_eq!(0xA, runtime.chip8().interpreter.vx[1]);
+        assert_eq!(30, runtime.chip8().metrics().frames);
+    }
+}
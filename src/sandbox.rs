@@ -0,0 +1,151 @@
+use crate::chip8::Chip8;
+use crate::debugger;
+
+use std::str::FromStr;
+
+pub const HELP: &str = "\
+6A02, 6A 02       execute an instruction (hexadecimal), written to memory at PC
+regs              print the registers
+reset             clear the display, the registers and the memory
+help              print this help
+quit              exit the sandbox";
+
+/// Sandbox inputs, as typed on the command line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    Instruction(u16),
+    Registers,
+    Reset,
+    Help,
+    Quit,
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "regs" | "r" => return Ok(Input::Registers),
+            "reset" => return Ok(Input::Reset),
+            "help" | "h" => return Ok(Input::Help),
+            "quit" | "q" => return Ok(Input::Quit),
+            _ => (),
+        }
+
+        // the two bytes of the instruction may be apart, as in listings
+        let digits: String = s.split_whitespace().collect();
+        let digits = digits.trim_start_matches("0x");
+        match digits.len() {
+            4 => u16::from_str_radix(digits, 16)
+                .map(Input::Instruction)
+                .map_err(|_| format!("not an instruction nor a command: {} (try help)", s)),
+            _ => Err(format!(
+                "instructions have 4 hexadecimal digits, e.g. 6A02: {} (try help)",
+                s.trim()
+            )),
+        }
+    }
+}
+
+/// Executes instructions typed one at a time against a machine with no program, for people
+/// learning CHIP-8 to see what each one does. Each instruction is written at PC and
+/// executed, so jumps and skips move where the next one goes.
+pub struct Sandbox {
+    chip8: Chip8,
+}
+
+impl Sandbox {
+    pub fn new(mut chip8: Chip8) -> Self {
+        chip8.load(&[]);
+        Sandbox { chip8 }
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    /// Run an input, returns what to print
+    pub fn execute(&mut self, input: Input) -> String {
+        match input {
+            Input::Instruction(opcode) => self.instruction(opcode),
+            Input::Registers => debugger::register_values(&self.chip8),
+            Input::Reset => {
+                self.chip8.reset_hard();
+                debugger::register_values(&self.chip8)
+            }
+            Input::Help => HELP.to_string(),
+            Input::Quit => String::new(),
+        }
+    }
+
+    fn instruction(&mut self, opcode: u16) -> String {
+        let pc = self.chip8.interpreter.pc();
+        if pc as usize + 1 >= self.chip8.memory.size() {
+            return format!("PC {:03X} is at the end of memory, jump back or reset", pc);
+        }
+
+        let [high, low] = opcode.to_be_bytes();
+        self.chip8.memory.write(pc, high);
+        self.chip8.memory.write(pc + 1, low);
+        // a draw waiting for the next frame (display_wait quirk) is done waiting
+        if self.chip8.interpreter.waiting_vblank() {
+            self.chip8.begin_frame();
+        }
+
+        let mut out = format!(
+            "{:03X}: {:04X} {}\n",
+            pc,
+            opcode,
+            debugger::mnemonic(opcode)
+        );
+        if let Some(reason) = self.chip8.step() {
+            out.push_str(&format!("stopped: {}\n", reason));
+            self.chip8.interpreter.resume();
+        } else if opcode & 0xF0FF == 0xF00A && self.chip8.interpreter.pc() == pc {
+            out.push_str("no key held: hold one in the window and type it again\n");
+        }
+        out.push_str(&debugger::register_values(&self.chip8));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+
+    #[test]
+    fn test_sandbox() {
+        assert_eq!(Ok(Input::Instruction(0x6A02)), "6A 02".parse());
+        assert_eq!(Ok(Input::Instruction(0xD005)), "0xd005".parse());
+        assert_eq!(Ok(Input::Registers), " REGS".parse::<Input>());
+        assert!("6A0".parse::<Input>().is_err());
+        assert!("GG00".parse::<Input>().is_err());
+
+        let mut sandbox = Sandbox::new(Chip8::new());
+        let out = sandbox.execute(Input::Instruction(0x6A02));
+        assert!(out.starts_with("200: 6A02 LD VA, 02\nPC 202  "));
+        assert_eq!(0x02, sandbox.chip8().interpreter.registers()[0xA]);
+
+        // the glyph of 0 drawn at (VA, V0)
+        sandbox.execute(Input::Instruction(0xF029));
+        sandbox.execute(Input::Instruction(0xDA05));
+        assert_eq!(1, Display::read_pixel(&sandbox.chip8().memory, 2, 0));
+
+        let out = sandbox.execute(Input::Instruction(0xF00A));
+        assert!(out.contains("\nno key held"));
+        assert_eq!(0x206, sandbox.chip8().interpreter.pc());
+
+        let out = sandbox.execute(Input::Instruction(0x0000));
+        assert!(out.contains("\nstopped: "));
+        assert!(!sandbox.chip8().interpreter.stop());
+
+        sandbox.execute(Input::Reset);
+        assert_eq!(0x200, sandbox.chip8().interpreter.pc());
+        assert_eq!(0, Display::read_pixel(&sandbox.chip8().memory, 2, 0));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::chip8::Chip8;
+use crate::framebuffer::{FrameBuffer, HEIGHT, WIDTH};
+use crate::memory::{AddressSpace, DISPLAY_SIZE};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Version of the save state format, states of another version are refused
+pub const VERSION: u32 = 1;
+
+/// Differing bytes at most this far apart are shown as one memory range
+const RANGE_GAP: usize = 4;
+
+/// Memory ranges listed at most, the others are counted
+const MAX_RANGES: usize = 16;
+
+/// Bytes of a range shown at most, before and after
+const MAX_RANGE_BYTES: usize = 8;
+
+/// The registers, timers, stack, memory and display of a machine at a frame, saved with F6 to
+/// compare with `statediff`. Settings, keys and peripherals are not part of it.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub rom_name: String,
+    pub frame: u64, // frames run when saved
+    pub pc: u16,
+    pub i: u16,
+    pub v: Vec<u8>,
+    pub dt: u8,
+    pub st: u8,
+    pub stack: Vec<u16>, // return addresses of the levels in use
+    #[serde(with = "crate::movie::hex")]
+    pub ram: Vec<u8>,
+    #[serde(with = "crate::movie::hex")]
+    pub display: Vec<u8>,
+}
+
+impl SaveState {
+    pub fn new(chip8: &Chip8, rom_name: &str, frame: u64) -> Self {
+        let interpreter = &chip8.interpreter;
+        SaveState {
+            version: VERSION,
+            rom_name: rom_name.to_string(),
+            frame,
+            pc: interpreter.pc(),
+            i: interpreter.index(),
+            v: interpreter.registers().to_vec(),
+            dt: interpreter.delay_timer(),
+            st: interpreter.sound_timer(),
+            stack: interpreter.stack().to_vec(),
+            ram: chip8.memory.ram().to_vec(),
+            display: chip8.memory.display().to_vec(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let text = std::fs::read_to_string(path)?;
+        let state: SaveState = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if state.version != VERSION {
+            return Err(invalid(format!(
+                "save state format version {}, expected {}",
+                state.version, VERSION
+            )));
+        }
+        if state.v.len() != 16 {
+            return Err(invalid(format!("{} registers", state.v.len())));
+        }
+        if AddressSpace::from_size(state.ram.len()).is_none() {
+            return Err(invalid(format!("{} bytes of memory", state.ram.len())));
+        }
+        if state.display.len() != DISPLAY_SIZE as usize {
+            return Err(invalid(format!("{} bytes of display", state.display.len())));
+        }
+
+        Ok(state)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    pub fn frame_buffer(&self) -> FrameBuffer {
+        let mut data = [0; DISPLAY_SIZE as usize];
+        data.copy_from_slice(&self.display);
+        FrameBuffer::from_bytes(data)
+    }
+}
+
+/// How two save states differ: registers, ranges of memory and display pixels
+pub struct StateDiff<'a> {
+    a: &'a SaveState,
+    b: &'a SaveState,
+    pub registers: Vec<(String, String, String)>, // name, value in a, value in b
+    pub ranges: Vec<Range<usize>>, // of memory, each starting and ending with a differing byte
+    pub bytes: usize,              // of memory differing
+    pub pixels: Vec<(u8, u8)>,
+}
+
+impl<'a> StateDiff<'a> {
+    pub fn new(a: &'a SaveState, b: &'a SaveState) -> Self {
+        let registers = registers(a)
+            .into_iter()
+            .zip(registers(b))
+            .filter(|((_, value_a), (_, value_b))| value_a != value_b)
+            .map(|((name, value_a), (_, value_b))| (name, value_a, value_b))
+            .collect();
+
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut bytes = 0;
+        let differing =
+            (0..a.ram.len().min(b.ram.len())).filter(|&addr| a.ram[addr] != b.ram[addr]);
+        for addr in differing {
+            bytes += 1;
+            match ranges.last_mut() {
+                Some(range) if addr - range.end < RANGE_GAP => range.end = addr + 1,
+                _ => ranges.push(addr..addr + 1),
+            }
+        }
+
+        StateDiff {
+            a,
+            b,
+            registers,
+            ranges,
+            bytes,
+            pixels: a.frame_buffer().diff(&b.frame_buffer()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+            && self.ranges.is_empty()
+            && self.pixels.is_empty()
+            && self.a.ram.len() == self.b.ram.len()
+    }
+}
+
+impl fmt::Display for StateDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (a, b) = (self.a, self.b);
+        if a.rom_name != b.rom_name {
+            writeln!(f, "programs differ: {} -> {}", a.rom_name, b.rom_name)?;
+        }
+        writeln!(f, "frames {} -> {}", a.frame, b.frame)?;
+        if self.is_empty() {
+            return writeln!(f, "the states are identical");
+        }
+
+        if !self.registers.is_empty() {
+            writeln!(f, "\nregisters:")?;
+            for (name, value_a, value_b) in &self.registers {
+                writeln!(f, "  {:<5} {} -> {}", name, value_a, value_b)?;
+            }
+        }
+
+        if a.ram.len() != b.ram.len() {
+            writeln!(
+                f,
+                "\nmemory sizes differ: {} -> {} bytes, the first {} compared",
+                a.ram.len(),
+                b.ram.len(),
+                a.ram.len().min(b.ram.len())
+            )?;
+        }
+        if !self.ranges.is_empty() {
+            writeln!(
+                f,
+                "\nmemory: {} bytes differ in {} ranges",
+                self.bytes,
+                self.ranges.len()
+            )?;
+            for range in self.ranges.iter().take(MAX_RANGES) {
+                let addresses = match range.len() {
+                    1 => format!("{:03X}", range.start),
+                    _ => format!("{:03X}-{:03X}", range.start, range.end - 1),
+                };
+                writeln!(
+                    f,
+                    "  {:<9} {} -> {}",
+                    addresses,
+                    hex_bytes(&a.ram[range.clone()]),
+                    hex_bytes(&b.ram[range.clone()])
+                )?;
+            }
+            if self.ranges.len() > MAX_RANGES {
+                writeln!(f, "  and {} more ranges", self.ranges.len() - MAX_RANGES)?;
+            }
+        }
+
+        if !self.pixels.is_empty() {
+            writeln!(
+                f,
+                "\ndisplay: {} pixels differ (+ lit in b only, - lit in a only)",
+                self.pixels.len()
+            )?;
+            let (frame_a, frame_b) = (a.frame_buffer(), b.frame_buffer());
+            for y in 0..HEIGHT as u8 {
+                let row: String = (0..WIDTH as u8)
+                    .map(|x| match (frame_a.pixel(x, y), frame_b.pixel(x, y)) {
+                        (true, true) => '#',
+                        (false, true) => '+',
+                        (true, false) => '-',
+                        (false, false) => '.',
+                    })
+                    .collect();
+                writeln!(f, "  {}", row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Names and values of the registers, timers and stack of a state
+fn registers(state: &SaveState) -> Vec<(String, String)> {
+    let mut registers = vec![
+        ("PC".to_string(), format!("{:03X}", state.pc)),
+        ("I".to_string(), format!("{:03X}", state.i)),
+    ];
+    for (x, value) in state.v.iter().enumerate() {
+        registers.push((format!("V{:X}", x), format!("{:02X}", value)));
+    }
+    let stack: Vec<String> = state
+        .stack
+        .iter()
+        .map(|addr| format!("{:03X}", addr))
+        .collect();
+    registers.extend([
+        ("DT".to_string(), format!("{:02X}", state.dt)),
+        ("ST".to_string(), format!("{:02X}", state.st)),
+        ("stack".to_string(), format!("[{}]", stack.join(" "))),
+    ]);
+    registers
+}
+
+/// Bytes of a memory range, the middle of long ones elided
+fn hex_bytes(bytes: &[u8]) -> String {
+    let hex = |bytes: &[u8]| -> Vec<String> {
+        bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+    };
+    match bytes.len() {
+        len if len > MAX_RANGE_BYTES => format!(
+            "{} .. {}",
+            hex(&bytes[..MAX_RANGE_BYTES / 2]).join(" "),
+            hex(&bytes[len - MAX_RANGE_BYTES / 2..]).join(" ")
+        ),
+        _ => hex(bytes).join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_save_load() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx(3, 5), ld_i(0x300), call(0x208)].concat());
+        for _ in 0..3 {
+            chip8.step();
+        }
+        Display::write_pixel(&mut chip8.memory, 1, 0);
+        let state = SaveState::new(&chip8, "test.ch8", 7);
+
+        let path = std::env::temp_dir().join(format!("chip8-test-{}.state", std::process::id()));
+        state.save(&path).unwrap();
+        let loaded = SaveState::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(state, loaded.unwrap());
+        assert_eq!(vec![0x206], state.stack);
+        assert!(state.frame_buffer().pixel(1, 0));
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&ld_vx(3, 5));
+        let a = SaveState::new(&chip8, "test.ch8", 0);
+        assert!(StateDiff::new(&a, &a).is_empty());
+        assert!(StateDiff::new(&a, &a)
+            .to_string()
+            .ends_with("the states are identical\n"));
+
+        chip8.step();
+        for (addr, value) in [(0x300, 1), (0x303, 2), (0x310, 3)] {
+            chip8.memory.write(addr, value);
+        }
+        Display::write_pixel(&mut chip8.memory, 2, 1);
+        let b = SaveState::new(&chip8, "test.ch8", 1);
+
+        let diff = StateDiff::new(&a, &b);
+        assert_eq!(
+            vec![
+                ("PC".to_string(), "200".to_string(), "202".to_string()),
+                ("V3".to_string(), "00".to_string(), "05".to_string()),
+            ],
+            diff.registers
+        );
+        // the display is in memory too
+        assert_eq!(vec![0x300..0x304, 0x310..0x311, 0xF08..0xF09], diff.ranges);
+        assert_eq!(vec![(2, 1)], diff.pixels);
+
+        let text = diff.to_string();
+        assert!(text.contains("\n  300-303   00 00 00 00 -> 01 00 00 02\n  310       00 -> 03\n"));
+        assert!(text.contains("\n  ..+....."));
+        assert_eq!(
+            "00 01 02 03 .. 06 07 08 09",
+            hex_bytes(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9])
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use clap::ValueEnum;
+
+/// Edge smoothing of the monochrome display before the palette colors it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum Filter {
+    /// Square pixels
+    #[default]
+    None,
+    /// Each pixel split in 2x2, rounding the corners of diagonals (EPX/AdvMAME2x)
+    Scale2x,
+    /// Scale2x applied twice, each pixel split in 4x4, smoother on large windows
+    Scale4x,
+}
+
+impl Filter {
+    /// Sub-pixels per side of each display pixel
+    pub fn factor(self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x => 2,
+            Filter::Scale4x => 4,
+        }
+    }
+
+    /// `pixels` of a `width` by `height` image, upscaled by `factor()` on each side
+    pub fn apply(self, pixels: &[bool], width: usize, height: usize) -> Vec<bool> {
+        match self {
+            Filter::None => pixels.to_vec(),
+            Filter::Scale2x => scale2x(pixels, width, height),
+            Filter::Scale4x => scale2x(&scale2x(pixels, width, height), width * 2, height * 2),
+        }
+    }
+}
+
+/// `pixels` of a `width` by `height` image, twice as large on each side. Each pixel E
+/// takes the color of its neighbors B (above), D (left), F (right) and H (below) in the
+/// corner between two of them of the same color, unless its edges are all the same.
+pub fn scale2x(pixels: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let mut scaled = vec![false; width * height * 4];
+    // neighbors beyond the edges repeat the pixel
+    let at = |x: usize, y: usize| pixels[y * width + x];
+
+    for y in 0..height {
+        for x in 0..width {
+            let e = at(x, y);
+            let b = if y > 0 { at(x, y - 1) } else { e };
+            let d = if x > 0 { at(x - 1, y) } else { e };
+            let f = if x + 1 < width { at(x + 1, y) } else { e };
+            let h = if y + 1 < height { at(x, y + 1) } else { e };
+
+            let corners = match b != h && d != f {
+                true => [
+                    if d == b { d } else { e },
+                    if b == f { f } else { e },
+                    if d == h { d } else { e },
+                    if h == f { f } else { e },
+                ],
+                false => [e; 4],
+            };
+            let top = (y * 2) * width * 2 + x * 2;
+            let bottom = top + width * 2;
+            scaled[top..top + 2].copy_from_slice(&corners[..2]);
+            scaled[bottom..bottom + 2].copy_from_slice(&corners[2..]);
+        }
+    }
+    scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(rows: &[&str]) -> Vec<bool> {
+        rows.iter()
+            .flat_map(|row| row.chars().map(|c| c == '#'))
+            .collect()
+    }
+
+    #[test]
+    fn test_scale2x() {
+        // the steps of a diagonal are filled in
+        let diagonal = image(&["....", ".#..", "..#.", "...."]);
+        assert_eq!(
+            image(&[
+                "........", "........", "..##....", "..###...", "...###..", "....##..", "........",
+                "........",
+            ]),
+            scale2x(&diagonal, 4, 4)
+        );
+
+        // lone pixels and straight edges stay square
+        let square = image(&["...", ".#.", "..."]);
+        assert_eq!(
+            image(&["......", "......", "..##..", "..##..", "......", "......"]),
+            Filter::Scale2x.apply(&square, 3, 3)
+        );
+        assert_eq!(16 * 9, Filter::Scale4x.apply(&square, 3, 3).len());
+        assert_eq!(square, Filter::None.apply(&square, 3, 3));
+    }
+}
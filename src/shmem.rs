@@ -0,0 +1,131 @@
+use crate::framebuffer::{FrameBuffer, HEIGHT, WIDTH};
+use crate::palette::Palette;
+use crate::rawvideo;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// First bytes of the region, for readers to check what they mapped
+pub const MAGIC: &[u8; 4] = b"C8FB";
+
+/// Version of the layout of the region
+pub const VERSION: u32 = 1;
+
+/// Offset of the sequence number, a little endian u64 after the magic, version, width and
+/// height (little endian u32s)
+pub const SEQUENCE_OFFSET: u64 = 16;
+
+/// Offset of the RGBA pixels, row after row
+pub const PIXELS_OFFSET: u64 = 24;
+
+/// File backing the shared-memory region `name`: in /dev/shm on Linux, where
+/// `shm_open("/<name>")` opens it, in the temporary directory elsewhere. A name with a
+/// directory is a path to a file to map.
+pub fn region_path(name: &str) -> PathBuf {
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return path.to_path_buf();
+    }
+
+    let shm = Path::new("/dev/shm");
+    match cfg!(target_os = "linux") && shm.is_dir() {
+        true => shm.join(name),
+        false => std::env::temp_dir().join(name),
+    }
+}
+
+/// Publishes the latest frame in a file other processes map, e.g. capture tools.
+/// The sequence number is odd while a frame is written and twice the frame count after, so
+/// readers copy the pixels when it is even and keep them if it did not change meanwhile.
+/// The file is removed when dropped.
+pub struct FrameExport {
+    file: File,
+    path: PathBuf,
+    frames: u64,
+}
+
+impl FrameExport {
+    /// Create the region at `path`, holding a header and no frame yet
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(PIXELS_OFFSET + (WIDTH * HEIGHT * 4) as u64)?;
+
+        let mut header = Vec::with_capacity(PIXELS_OFFSET as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(WIDTH as u32).to_le_bytes());
+        header.extend_from_slice(&(HEIGHT as u32).to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes());
+        file.write_all(&header)?;
+
+        Ok(FrameExport {
+            file,
+            path: path.to_path_buf(),
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the frame of the region
+    pub fn publish(&mut self, frame: &FrameBuffer, palette: &Palette) -> io::Result<()> {
+        self.write_sequence(self.frames * 2 + 1)?;
+        self.file.seek(SeekFrom::Start(PIXELS_OFFSET))?;
+        self.file.write_all(&rawvideo::rgba(frame, palette))?;
+        self.frames += 1;
+        self.write_sequence(self.frames * 2)
+    }
+
+    fn write_sequence(&mut self, sequence: u64) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(SEQUENCE_OFFSET))?;
+        self.file.write_all(&sequence.to_le_bytes())
+    }
+}
+
+impl Drop for FrameExport {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    #[test]
+    fn test_publish() {
+        let path = std::env::temp_dir().join(format!("chip8-test-frame-{}", std::process::id()));
+        let mut export = FrameExport::create(&path).unwrap();
+        let palette = Palette::new();
+
+        let mut memory = Memory::new();
+        Display::write_pixel(&mut memory, 1, 0);
+        export
+            .publish(&FrameBuffer::from_memory(&memory), &palette)
+            .unwrap();
+        export
+            .publish(&FrameBuffer::from_memory(&memory), &palette)
+            .unwrap();
+
+        let region = std::fs::read(&path).unwrap();
+        assert_eq!(PIXELS_OFFSET as usize + 64 * 32 * 4, region.len());
+        assert_eq!(MAGIC, &region[0..4]);
+        assert_eq!([64, 0, 0, 0, 32, 0, 0, 0], region[8..16]);
+        assert_eq!(4u64.to_le_bytes(), region[16..24]);
+        assert_eq!(palette.foreground(), region[28..32]);
+
+        drop(export);
+        assert!(!path.exists());
+        assert_eq!(PathBuf::from("frames/chip8"), region_path("frames/chip8"));
+    }
+}
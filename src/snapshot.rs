@@ -0,0 +1,113 @@
+use crate::chip8::Chip8;
+use crate::interpreter::Interpreter;
+use crate::memory::{Memory, DISPLAY_SIZE};
+
+/// Copy of the interpreter, RAM and display of a machine, to go back in time.
+/// The state of peripherals mapped in memory is not part of it.
+#[derive(Clone)]
+pub struct Snapshot {
+    interpreter: Interpreter,
+    ram: Box<[u8]>,                       // with the size of the address space
+    display: [u8; DISPLAY_SIZE as usize], // also in the RAM unless the display buffer is external
+}
+
+impl Snapshot {
+    pub fn take(chip8: &Chip8) -> Self {
+        Snapshot::from_parts(&chip8.interpreter, &chip8.memory)
+    }
+
+    pub fn from_parts(interpreter: &Interpreter, memory: &Memory) -> Self {
+        Snapshot {
+            interpreter: interpreter.clone(),
+            ram: memory.ram().into(),
+            display: memory.display(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn restore(&self, chip8: &mut Chip8) {
+        // the counters keep the work done since, rewinding is work too
+        let metrics = chip8.interpreter.metrics;
+        chip8.interpreter = self.interpreter.clone();
+        chip8.interpreter.metrics = metrics;
+        chip8.memory.restore_ram(&self.ram);
+        for (i, &byte) in self.display.iter().enumerate() {
+            chip8.memory.set_display_byte(i as u16, byte);
+        }
+    }
+
+    pub fn state_hash(&self) -> u64 {
+        hash_parts(&self.interpreter, &self.ram[..], &self.display)
+    }
+}
+
+/// 64-bit FNV-1a hash of the registers, stack, timers, RAM and display of a machine.
+/// Integers are hashed big endian, so the hash is the same on every platform and can be
+/// stored in tests or sent over the network.
+pub fn state_hash(interpreter: &Interpreter, memory: &Memory) -> u64 {
+    hash_parts(interpreter, memory.ram(), &memory.display())
+}
+
+fn hash_parts(interpreter: &Interpreter, ram: &[u8], display: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    let registers = [interpreter.pc, interpreter.vi]
+        .into_iter()
+        .chain(interpreter.stack().iter().copied())
+        .flat_map(u16::to_be_bytes)
+        .chain([interpreter.sc, interpreter.dt, interpreter.st])
+        .chain(interpreter.vx);
+
+    registers
+        .chain(ram.iter().copied())
+        .chain(display.iter().copied())
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::*;
+
+    #[test]
+    fn test_restore() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx(0, 5), ld_i(0x300), store(0)].concat());
+        chip8.step();
+        let snapshot = Snapshot::take(&chip8);
+        chip8.step();
+        chip8.step();
+        assert_eq!(5, chip8.memory.read(0x300));
+
+        snapshot.restore(&mut chip8);
+
+        assert_eq!(0x202, chip8.interpreter.pc);
+        assert_eq!(0, chip8.memory.read(0x300));
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let mut chip8 = Chip8::new();
+        chip8.load(&[ld_vx(0, 5), ld_i(0x300), store(0)].concat());
+        let before = chip8.state_hash();
+        assert_eq!(before, Snapshot::take(&chip8).state_hash());
+
+        chip8.step();
+        assert_ne!(before, chip8.state_hash());
+
+        // a constant, not only equal within a run: fails if the hashed bytes change
+        let mut chip8 = Chip8::new();
+        chip8.load(&ld_vx(0, 5));
+        assert_eq!(0x69E9_2AEF_B7D5_6CCB, chip8.state_hash());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::quirks::Profile;
+
+use std::time::{Duration, Instant};
+
+/// How long the splash stays over the program when it starts
+pub const SPLASH_DURATION: Duration = Duration::from_secs(2);
+
+/// Longest title shown, in characters, to fit in the window
+const MAX_TITLE: usize = 56;
+
+/// Title of the program and the settings it runs with, shown over the display when it
+/// starts to check the right ones were applied
+pub struct Splash {
+    lines: Vec<String>,
+    shown: Instant,
+}
+
+impl Splash {
+    pub fn new(
+        title: &str,
+        author: Option<&str>,
+        profile: Profile,
+        ips: u32,
+        now: Instant,
+    ) -> Self {
+        let mut lines = vec![title.chars().take(MAX_TITLE).collect()];
+        if let Some(author) = author {
+            lines.push(format!(
+                "BY {}",
+                author.chars().take(MAX_TITLE - 3).collect::<String>()
+            ));
+        }
+        lines.push(String::new());
+        lines.push(format!("PROFILE: {}", profile.name()));
+        lines.push(format!("{} IPS", ips));
+
+        Splash { lines, shown: now }
+    }
+
+    /// Lines to draw at `now`, if the splash is still showing
+    pub fn lines(&self, now: Instant) -> Option<&[String]> {
+        (now.duration_since(self.shown) < SPLASH_DURATION).then_some(self.lines.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splash() {
+        let start = Instant::now();
+        let splash = Splash::new("Pong", Some("Paul Vervalin"), Profile::Vip, 540, start);
+
+        assert_eq!(
+            Some(
+                &[
+                    "Pong".to_string(),
+                    "BY Paul Vervalin".to_string(),
+                    String::new(),
+                    "PROFILE: vip".to_string(),
+                    "540 IPS".to_string(),
+                ][..]
+            ),
+            splash.lines(start + Duration::from_millis(1999))
+        );
+        assert_eq!(None, splash.lines(start + SPLASH_DURATION));
+    }
+}
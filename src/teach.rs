@@ -0,0 +1,261 @@
+use crate::chip8::Chip8;
+use crate::debugger;
+use crate::error::HaltReason;
+use crate::opcodes;
+use crate::timing::FRAME_RATE;
+
+use std::fmt;
+
+/// Instructions per second of the teach mode by default
+pub const DEFAULT_RATE: u32 = 2;
+
+/// Instructions of the memory strip before the current one, and after it
+const STRIP_BEFORE: u16 = 2;
+const STRIP_AFTER: u16 = 3;
+
+/// What each instruction does in plain English, with the operands as in the mnemonics
+fn template(pattern: &str) -> Option<&'static str> {
+    let template = match pattern {
+        "0000" => "stop: no instruction here, the program ended",
+        "00E0" => "clear the screen",
+        "00EE" => "return to the address saved by the last call",
+        "00CN" => "scroll the screen down {n} rows",
+        "00DN" => "scroll the screen up {n} rows",
+        "00FB" => "scroll the screen right 4 pixels",
+        "00FC" => "scroll the screen left 4 pixels",
+        "00FD" => "exit the interpreter",
+        "00FE" => "switch to the low resolution, 64x32",
+        "00FF" => "switch to the high resolution, 128x64",
+        "1NNN" => "jump to {nnn}",
+        "2NNN" => "call the subroutine at {nnn}, saving where to return",
+        "3XNN" => "skip the next instruction if {vx} is {nn}",
+        "4XNN" => "skip the next instruction if {vx} is not {nn}",
+        "5XY0" => "skip the next instruction if {vx} equals {vy}",
+        "5XY2" => "store {vx} to {vy} in memory from I",
+        "5XY3" => "load {vx} to {vy} from memory at I",
+        "6XNN" => "set {vx} to {nn}",
+        "7XNN" => "add {nn} to {vx}, VF untouched",
+        "8XY0" => "copy {vy} into {vx}",
+        "8XY1" => "set {vx} to {vx} OR {vy}",
+        "8XY2" => "set {vx} to {vx} AND {vy}",
+        "8XY3" => "set {vx} to {vx} XOR {vy}",
+        "8XY4" => "add {vy} to {vx}, VF = 1 if it carries past FF",
+        "8XY5" => "subtract {vy} from {vx}, VF = 0 if it borrows",
+        "8XY6" => "shift right by one bit, VF = the bit shifted out",
+        "8XY7" => "set {vx} to {vy} minus {vx}, VF = 0 if it borrows",
+        "8XYE" => "shift left by one bit, VF = the bit shifted out",
+        "9XY0" => "skip the next instruction if {vx} is not {vy}",
+        "ANNN" => "point I at {nnn}",
+        "BNNN" => "jump to {nnn} plus V0",
+        "CXNN" => "set {vx} to a random number AND {nn}",
+        "DXY0" => "draw the 16x16 sprite at I at ({vx}, {vy}), VF = 1 on collision",
+        "DXYN" => "draw {n} sprite rows from I at ({vx}, {vy}), VF = 1 on collision",
+        "EX9E" => "skip the next instruction if the key in {vx} is held",
+        "EXA1" => "skip the next instruction if the key in {vx} is not held",
+        "F000" => "point I at the 16 bit address that follows",
+        "FN01" => "draw on the planes {x} from now on",
+        "F002" => "load 16 bytes of audio pattern from I",
+        "FX07" => "copy the delay timer into {vx}",
+        "FX0A" => "wait for a key press, its number going into {vx}",
+        "FX15" => "set the delay timer to {vx}, counting down at 60 Hz",
+        "FX18" => "set the sound timer to {vx}, beeping until it is 0",
+        "FX1E" => "add {vx} to I",
+        "FX29" => "point I at the font glyph of the digit in {vx}",
+        "FX30" => "point I at the big font glyph of the digit in {vx}",
+        "FX33" => "store the 3 decimal digits of {vx} at I, I+1, I+2",
+        "FX3A" => "set the audio pitch to {vx}",
+        "FX55" => "store V0 to {vx} in memory from I",
+        "FX65" => "load V0 to {vx} from memory at I",
+        "FX75" => "save V0 to {vx} in the flag registers",
+        "FX85" => "load V0 to {vx} from the flag registers",
+        _ => return None,
+    };
+    Some(template)
+}
+
+/// What an instruction does in plain English, e.g. "set VA to 02"
+pub fn describe(opcode: u16) -> String {
+    match opcodes::lookup(opcode) {
+        Some(row) => match template(row.pattern) {
+            Some(template) => opcodes::format_operands(template, opcode),
+            None => row.format(opcode),
+        },
+        None => format!("{:04X} is not an instruction", opcode),
+    }
+}
+
+/// An instruction executed by the teach mode
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Step {
+    pub pc: u16,
+    pub opcode: u16,
+    pub next_pc: u16,
+    pub halt: Option<HaltReason>, // why the interpreter halted on it, if it did
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:03X}: {:04X} {:<16} {}",
+            self.pc,
+            self.opcode,
+            debugger::mnemonic(self.opcode),
+            describe(self.opcode)
+        )
+    }
+}
+
+/// Runs a program a few instructions per second to teach how it works, describing each
+/// instruction executed. The timers tick once every `cycles_per_frame` instructions, so the
+/// program sees them as at full speed, only slowed down with everything else.
+pub struct Teacher {
+    rate: u32, // instructions per second
+    cycles_per_frame: u32,
+    cycle: u32,  // instructions executed in the current emulated frame
+    credit: u32, // instructions due, in 1/FRAME_RATE
+    last: Option<Step>,
+}
+
+impl Teacher {
+    /// `rate` instructions per second, up to one per frame
+    pub fn new(rate: u32, cycles_per_frame: u32) -> Self {
+        Teacher {
+            rate: rate.clamp(1, FRAME_RATE),
+            cycles_per_frame: cycles_per_frame.max(1),
+            cycle: 0,
+            credit: 0,
+            last: None,
+        }
+    }
+
+    /// Called at each 60 Hz frame in place of `Chip8::run_frame`, returns the instruction
+    /// executed if one was due
+    pub fn frame(&mut self, chip8: &mut Chip8) -> Option<&Step> {
+        if chip8.interpreter.stop() {
+            return None;
+        }
+        self.credit += self.rate;
+        if self.credit < FRAME_RATE {
+            return None;
+        }
+        self.credit -= FRAME_RATE;
+
+        // a draw waiting for the next frame (display_wait quirk) ends the frame early
+        if self.cycle == 0 || chip8.interpreter.waiting_vblank() {
+            chip8.begin_frame();
+            self.cycle = 0;
+        }
+        chip8
+            .interpreter
+            .set_frame_phase(self.cycle as f32 / self.cycles_per_frame as f32);
+
+        let pc = chip8.interpreter.pc();
+        let opcode = chip8.interpreter.next(&chip8.memory);
+        let halt = chip8.step();
+        self.cycle = (self.cycle + 1) % self.cycles_per_frame;
+
+        self.last = Some(Step {
+            pc,
+            opcode,
+            next_pc: chip8.interpreter.pc(),
+            halt,
+        });
+        self.last.as_ref()
+    }
+
+    pub fn last(&self) -> Option<&Step> {
+        self.last.as_ref()
+    }
+
+    /// Lines shown over the display: how PC moved, the memory around the last instruction
+    /// with its bytes between brackets, and what it did
+    pub fn lines(&self, chip8: &Chip8) -> Vec<String> {
+        let Some(step) = &self.last else {
+            return vec![format!("PC {:03X}", chip8.interpreter.pc())];
+        };
+
+        let start = step.pc.wrapping_sub(2 * STRIP_BEFORE);
+        let mut strip = format!("{:03X}:", start);
+        for i in 0..STRIP_BEFORE + 1 + STRIP_AFTER {
+            let addr = start.wrapping_add(2 * i);
+            let bytes = format!(
+                "{:02X} {:02X}",
+                chip8.memory.read_wrapped(addr),
+                chip8.memory.read_wrapped(addr.wrapping_add(1))
+            );
+            match addr == step.pc {
+                true => strip.push_str(&format!(" [{}]", bytes)),
+                false => strip.push_str(&format!(" {}", bytes)),
+            }
+        }
+
+        vec![
+            format!(
+                "PC {:03X} -> {:03X}  {:04X} {}",
+                step.pc,
+                step.next_pc,
+                step.opcode,
+                debugger::mnemonic(step.opcode)
+            ),
+            strip,
+            describe(step.opcode),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::SCALE;
+    use crate::opcodes::OPCODES;
+    use crate::rom_builder::*;
+    use crate::text;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!("set VA to 02", describe(0x6A02));
+        assert_eq!(
+            "draw 15 sprite rows from I at (VA, VB), VF = 1 on collision",
+            describe(0xDABF)
+        );
+        assert_eq!("5001 is not an instruction", describe(0x5001));
+
+        // every instruction is described, on one line of the screen
+        for row in &OPCODES {
+            assert!(template(row.pattern).is_some(), "{}", row.pattern);
+            let widest = describe(row.value | !row.mask & 0xEEEE);
+            assert!(text::width(&widest) < 64 * SCALE - 4, "{}", widest);
+        }
+    }
+
+    #[test]
+    fn test_teacher() {
+        let program = [
+            &ld_vx(0xA, 0x02)[..], // 200
+            &jp(0x200),            // 202
+        ]
+        .concat();
+        let mut chip8 = Chip8::new();
+        chip8.load(&program);
+
+        // 2 instructions per second, one every 30 frames
+        let mut teacher = Teacher::new(2, 11);
+        assert!((0..29).all(|_| teacher.frame(&mut chip8).is_none()));
+        let step = teacher.frame(&mut chip8).cloned().unwrap();
+        assert_eq!("200: 6A02 LD VA, 02        set VA to 02", step.to_string());
+        assert_eq!(0x202, step.next_pc);
+
+        for _ in 0..30 {
+            teacher.frame(&mut chip8);
+        }
+        assert_eq!(
+            vec![
+                "PC 202 -> 200  1200 JP 200",
+                "1FE: 00 00 6A 02 [12 00] 00 00 00 00 00 00",
+                "jump to 200",
+            ],
+            teacher.lines(&chip8)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+/// 3x5 pixels font used to draw menus and messages over the display.
+/// Each row is 3 bits, the most significant bit being the left pixel.
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+
+/// Horizontal distance between two characters
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+
+/// Vertical distance between two lines
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 60] = [
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('*', [0b101, 0b010, 0b101, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('"', [0b101, 0b101, 0b000, 0b000, 0b000]),
+    ('|', [0b010, 0b010, 0b010, 0b010, 0b010]),
+    ('$', [0b011, 0b110, 0b010, 0b011, 0b110]),
+];
+
+/// Rows of a character, lowercase letters are drawn as uppercase and
+/// unknown characters as '?'
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+
+    GLYPHS
+        .iter()
+        .find(|(glyph_char, _)| *glyph_char == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph_char, _)| *glyph_char == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap()
+}
+
+/// Width in pixels of a single line of text
+pub fn width(text: &str) -> usize {
+    (text.chars().count() * ADVANCE).saturating_sub(1)
+}
+
+/// Call `plot(x, y)` for every lit pixel of a single line of text, relative to its top left corner
+pub fn render<F: FnMut(usize, usize)>(text: &str, mut plot: F) {
+    for (i, c) in text.chars().enumerate() {
+        for (y, row) in glyph(c).iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if (row >> (GLYPH_WIDTH - 1 - x)) & 1 == 1 {
+                    plot(i * ADVANCE + x, y);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut pixels = Vec::new();
+        render("-I", |x, y| pixels.push((x, y)));
+
+        assert_eq!(
+            vec![
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (4, 0),
+                (5, 0),
+                (6, 0),
+                (5, 1),
+                (5, 2),
+                (5, 3),
+                (4, 4),
+                (5, 4),
+                (6, 4)
+            ],
+            pixels
+        );
+        assert_eq!(7, width("-I"));
+        assert_eq!(glyph('?'), glyph('~'));
+        assert_eq!(glyph('A'), glyph('a'));
+    }
+}
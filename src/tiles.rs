@@ -0,0 +1,109 @@
+use crate::quirks::Profile;
+
+use clap::ValueEnum;
+
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Grid of 64x32 screens drawn in one window
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Layout {
+    pub fn single() -> Self {
+        Layout {
+            columns: 1,
+            rows: 1,
+        }
+    }
+
+    /// Smallest grid holding `count` screens, with at least as many columns as rows
+    pub fn for_count(count: usize) -> Self {
+        let count = count.max(1);
+        let mut columns = 1;
+        while columns * columns < count {
+            columns += 1;
+        }
+
+        Layout {
+            columns,
+            rows: count.div_ceil(columns),
+        }
+    }
+
+    /// Column and row of a screen, filled row by row
+    pub fn position(&self, screen: usize) -> (usize, usize) {
+        (screen % self.columns, screen / self.columns)
+    }
+}
+
+/// Program run in an additional tile, PATH or PATH@PROFILE on the command line
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TileSpec {
+    pub rom: PathBuf,
+    pub profile: Option<Profile>,
+}
+
+impl FromStr for TileSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once('@') {
+            Some((rom, profile)) if !rom.is_empty() => Ok(TileSpec {
+                rom: PathBuf::from(rom),
+                profile: Some(Profile::from_str(profile, true)?),
+            }),
+            _ => Ok(TileSpec {
+                rom: PathBuf::from(s),
+                profile: None,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(Layout::single(), Layout::for_count(1));
+        assert_eq!(
+            Layout {
+                columns: 2,
+                rows: 1
+            },
+            Layout::for_count(2)
+        );
+        assert_eq!(
+            Layout {
+                columns: 3,
+                rows: 2
+            },
+            Layout::for_count(5)
+        );
+        assert_eq!((1, 1), Layout::for_count(5).position(4));
+    }
+
+    #[test]
+    fn test_parse_tile() {
+        assert_eq!(
+            Ok(TileSpec {
+                rom: PathBuf::from("roms/pong.ch8"),
+                profile: Some(Profile::Vip)
+            }),
+            "roms/pong.ch8@vip".parse()
+        );
+        assert_eq!(
+            Ok(TileSpec {
+                rom: PathBuf::from("pong.ch8"),
+                profile: None
+            }),
+            "pong.ch8".parse()
+        );
+        assert!("pong.ch8@c64".parse::<TileSpec>().is_err());
+    }
+}
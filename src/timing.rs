@@ -0,0 +1,529 @@
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+/// Rate of the delay and sound timers, and of the display refresh, unless PAL timing is chosen
+pub const FRAME_RATE: u32 = 60;
+
+/// Instructions executed per frame by default (about 660 per second)
+pub const DEFAULT_CYCLES_PER_FRAME: u32 = 11;
+
+/// Frames run at once at most after a stall (e.g. window dragged), the rest are dropped
+pub(crate) const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// What FX07 reads from the delay timer
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimerMode {
+    /// The value as updated at 60 Hz frame boundaries, what loops calibrated on real hardware expect
+    Frame,
+    /// A timer running for exactly N/60 s from the moment it was set, smoother for homebrew
+    Interpolated,
+}
+
+/// Rate of the frames, ticking the timers and ending the display waits
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum TimerRate {
+    /// PAL machines, some European VIP programs were tuned for it
+    #[value(name = "50")]
+    Pal,
+    /// NTSC machines such as the original COSMAC VIP
+    #[value(name = "60")]
+    Ntsc,
+}
+
+impl TimerRate {
+    pub fn hz(self) -> u32 {
+        match self {
+            TimerRate::Pal => 50,
+            TimerRate::Ntsc => FRAME_RATE,
+        }
+    }
+}
+
+/// Converts elapsed real time into frames, 60 Hz by default
+pub struct FrameClock {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FrameClock {
+    pub fn new(start: Instant) -> Self {
+        FrameClock::with_rate(start, FRAME_RATE)
+    }
+
+    pub fn with_rate(start: Instant, hz: u32) -> Self {
+        let frame_duration = Duration::from_secs(1) / hz.max(1);
+
+        FrameClock {
+            frame_duration,
+            next_frame: start + frame_duration,
+        }
+    }
+
+    /// Number of whole frames elapsed since the last call
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        let mut frames = 0;
+
+        while now >= self.next_frame {
+            frames += 1;
+            self.next_frame += self.frame_duration;
+
+            if frames == MAX_CATCH_UP_FRAMES {
+                self.next_frame = now + self.frame_duration;
+                break;
+            }
+        }
+
+        frames
+    }
+
+    /// When the next frame is due
+    pub fn next_frame(&self) -> Instant {
+        self.next_frame
+    }
+}
+
+/// How often the window is presented, independently of the rate of the frames run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameLimit {
+    /// Present at the refresh rate of the monitor, without tearing
+    Vsync,
+    /// Present as often as possible
+    Off,
+    /// Present at most N times per second, without vsync
+    Cap(u32),
+}
+
+impl FrameLimit {
+    /// Whether presenting waits for the monitor refresh
+    pub fn vsync(self) -> bool {
+        self == FrameLimit::Vsync
+    }
+}
+
+/// Parse a frame limit: vsync, off, or a number of renders per second
+pub fn parse_frame_limit(s: &str) -> Result<FrameLimit, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "vsync" => Ok(FrameLimit::Vsync),
+        "off" => Ok(FrameLimit::Off),
+        fps => match fps.parse::<u32>() {
+            Ok(fps) if fps > 0 => Ok(FrameLimit::Cap(fps)),
+            _ => Err(format!("expected vsync, off or renders per second: {}", s)),
+        },
+    }
+}
+
+/// Spaces the renders out under `FrameLimit::Cap`, renders are not limited otherwise
+pub struct RenderPacer {
+    interval: Option<Duration>,
+    next_render: Instant,
+}
+
+impl RenderPacer {
+    pub fn new(limit: FrameLimit, start: Instant) -> Self {
+        RenderPacer {
+            interval: match limit {
+                FrameLimit::Cap(fps) => Some(Duration::from_secs(1) / fps.max(1)),
+                FrameLimit::Vsync | FrameLimit::Off => None,
+            },
+            next_render: start,
+        }
+    }
+
+    /// Whether to render at `now`, scheduling the next render if so
+    pub fn render(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.interval else {
+            return true;
+        };
+        if now < self.next_render {
+            return false;
+        }
+
+        // renders missed during a stall are not caught up
+        self.next_render += interval;
+        if self.next_render <= now {
+            self.next_render = now + interval;
+        }
+        true
+    }
+
+    /// When the next render is due, None when renders are not limited
+    pub fn next_render(&self) -> Option<Instant> {
+        self.interval.map(|_| self.next_render)
+    }
+}
+
+/// Renders skipped in a row at most by default when running the frames overruns real time
+pub const DEFAULT_MAX_FRAME_SKIP: u32 = 3;
+
+/// Skips rendering while running the frames and rendering them takes longer than the real time
+/// they stand for, e.g. on a slow machine or with a large turbo multiplier, so emulation keeps
+/// up with real time. At most `max_skips` renders are skipped in a row so the display still
+/// updates.
+pub struct FrameSkipper {
+    frame_duration: Duration,
+    max_skips: u32,
+    skipped: u32,          // renders skipped in a row
+    render_time: Duration, // of the last render, expected again
+}
+
+impl FrameSkipper {
+    pub fn new(hz: u32, max_skips: u32) -> Self {
+        FrameSkipper {
+            frame_duration: Duration::from_secs(1) / hz.max(1),
+            max_skips,
+            skipped: 0,
+            render_time: Duration::ZERO,
+        }
+    }
+
+    /// Whether to render after running `due` frames of real time in `elapsed`, the render
+    /// taking as long as the last one
+    pub fn render(&mut self, due: u32, elapsed: Duration) -> bool {
+        let overrun = due > 0 && elapsed + self.render_time > self.frame_duration * due;
+        if overrun && self.skipped < self.max_skips {
+            self.skipped += 1;
+            false
+        } else {
+            self.skipped = 0;
+            true
+        }
+    }
+
+    /// Record how long a render took
+    pub fn rendered(&mut self, took: Duration) {
+        self.render_time = took;
+    }
+}
+
+/// Default speed multiplier while turbo is held
+pub const DEFAULT_TURBO: u32 = 4;
+
+/// Highest speed multiplier accepted for turbo
+pub const MAX_TURBO: u32 = 16;
+
+/// Runs several frames per real frame while engaged, timers included, so programs keep their
+/// relative timing and only the audio needs care (see `audio::Buzzer`)
+pub struct Turbo {
+    multiplier: u32,
+    engaged: bool,
+}
+
+impl Turbo {
+    /// `multiplier` is capped to `MAX_TURBO`
+    pub fn new(multiplier: u32) -> Self {
+        Turbo {
+            multiplier: multiplier.clamp(1, MAX_TURBO),
+            engaged: false,
+        }
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        self.multiplier
+    }
+
+    pub fn engaged(&self) -> bool {
+        self.engaged
+    }
+
+    pub fn set_engaged(&mut self, engaged: bool) {
+        self.engaged = engaged;
+    }
+
+    /// Frames to run for `due` frames of real time
+    pub fn frames(&self, due: u32) -> u32 {
+        match self.engaged {
+            true => due * self.multiplier,
+            false => due,
+        }
+    }
+}
+
+impl Default for Turbo {
+    fn default() -> Self {
+        Turbo::new(DEFAULT_TURBO)
+    }
+}
+
+/// Counts the renders to report how many were presented each second
+pub struct RenderRate {
+    since: Instant,
+    renders: u32,
+}
+
+impl RenderRate {
+    pub fn new(start: Instant) -> Self {
+        RenderRate {
+            since: start,
+            renders: 0,
+        }
+    }
+
+    /// Count a render at `now`, returns the renders of the last second once it is over
+    pub fn render(&mut self, now: Instant) -> Option<u32> {
+        self.renders += 1;
+        if now.duration_since(self.since) < Duration::from_secs(1) {
+            return None;
+        }
+
+        self.since = now;
+        Some(std::mem::take(&mut self.renders))
+    }
+}
+
+/// Measures how long key events wait between reaching the machine and the start of the frame
+/// whose instructions see them, the input lag of running the frames in batches
+#[derive(Default)]
+pub struct InputLatency {
+    pending: Vec<Instant>, // key events not seen by a frame yet
+    events: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl InputLatency {
+    pub fn new() -> Self {
+        InputLatency::default()
+    }
+
+    /// A key event pushed to the machine at `at`
+    pub fn received(&mut self, at: Instant) {
+        self.pending.push(at);
+    }
+
+    /// A frame starting at `at`, sampling the keys before its instructions
+    pub fn sampled(&mut self, at: Instant) {
+        for received in self.pending.drain(..) {
+            let latency = at.saturating_duration_since(received);
+            self.events += 1;
+            self.total += latency;
+            self.max = self.max.max(latency);
+        }
+    }
+
+    pub fn report(&self) -> String {
+        if self.events == 0 {
+            return "input latency: no key events".to_string();
+        }
+        format!(
+            "input latency over {} key events: mean {:.1} ms, max {:.1} ms",
+            self.events,
+            self.total.as_secs_f64() * 1000.0 / self.events as f64,
+            self.max.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Variation of the instructions run each frame, as timings differ between machines and
+/// emulators, reproducible from its seed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Jitter {
+    pub percent: u8, // of the cycles per frame, up or down
+    pub seed: u64,
+}
+
+/// Instructions of successive frames with a jitter
+pub struct CycleBudget {
+    jitter: Jitter,
+    rng: StdRng,
+}
+
+impl CycleBudget {
+    pub fn new(jitter: Jitter) -> Self {
+        CycleBudget {
+            jitter,
+            rng: StdRng::seed_from_u64(jitter.seed),
+        }
+    }
+
+    pub fn jitter(&self) -> Jitter {
+        self.jitter
+    }
+
+    /// Instructions of the next frame, `cycles` give or take the jitter, at least one unless
+    /// `cycles` is 0
+    pub fn next(&mut self, cycles: u32) -> u32 {
+        if cycles == 0 {
+            return 0;
+        }
+        let spread = cycles as u64 * self.jitter.percent as u64 / 100;
+        let low = (cycles as u64).saturating_sub(spread).max(1);
+        let high = cycles as u64 + spread;
+        self.rng.gen_range(low..=high) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    /// Times the timers tick during one second of real time, polled every millisecond
+    fn ticks_per_second(rate: TimerRate) -> u8 {
+        let start = Instant::now();
+        let mut clock = FrameClock::with_rate(start, rate.hz());
+        let mut interpreter = Interpreter::new();
+        interpreter.dt = 255;
+        interpreter.st = 255;
+
+        for ms in 1..=1000 {
+            for _ in 0..clock.frames_due(start + Duration::from_millis(ms)) {
+                interpreter.decrement_timers();
+            }
+        }
+
+        assert_eq!(interpreter.dt, interpreter.st);
+        255 - interpreter.dt
+    }
+
+    #[test]
+    fn test_timer_rate() {
+        assert_eq!(60, ticks_per_second(TimerRate::Ntsc));
+        assert_eq!(50, ticks_per_second(TimerRate::Pal));
+    }
+
+    #[test]
+    fn test_frames_due() {
+        let start = Instant::now();
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut clock = FrameClock::new(start);
+
+        assert_eq!(0, clock.frames_due(start + frame / 2));
+        assert_eq!(1, clock.frames_due(start + frame));
+        assert_eq!(2, clock.frames_due(start + frame * 3 + frame / 2));
+        assert_eq!(0, clock.frames_due(start + frame * 3 + frame / 2));
+
+        // a stall only runs a few frames
+        assert_eq!(MAX_CATCH_UP_FRAMES, clock.frames_due(start + frame * 100));
+        assert_eq!(0, clock.frames_due(start + frame * 100));
+        assert_eq!(1, clock.frames_due(start + frame * 101));
+    }
+
+    #[test]
+    fn test_frame_skipper() {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut skipper = FrameSkipper::new(FRAME_RATE, 2);
+
+        assert!(skipper.render(1, frame / 2));
+        assert!(skipper.render(0, frame * 2));
+        assert!(skipper.render(4, frame * 3));
+
+        // overruns skip two renders in a row at most
+        assert!(!skipper.render(1, frame * 2));
+        assert!(!skipper.render(2, frame * 3));
+        assert!(skipper.render(1, frame * 2));
+        assert!(!skipper.render(1, frame * 2));
+
+        let mut never = FrameSkipper::new(FRAME_RATE, 0);
+        assert!(never.render(1, frame * 2));
+
+        // rendering counts along with emulation
+        let mut slow_render = FrameSkipper::new(FRAME_RATE, 1);
+        assert!(slow_render.render(1, frame / 2));
+        slow_render.rendered(frame * 3 / 4);
+        assert!(!slow_render.render(1, frame / 2));
+        assert!(slow_render.render(1, frame / 2));
+        slow_render.rendered(frame / 4);
+        assert!(slow_render.render(1, frame / 2));
+    }
+
+    #[test]
+    fn test_frame_limit() {
+        assert_eq!(Ok(FrameLimit::Vsync), parse_frame_limit("VSync"));
+        assert_eq!(Ok(FrameLimit::Off), parse_frame_limit("off"));
+        assert_eq!(Ok(FrameLimit::Cap(144)), parse_frame_limit("144"));
+        assert!(parse_frame_limit("0").is_err());
+        assert!(parse_frame_limit("fast").is_err());
+
+        let start = Instant::now();
+        let interval = Duration::from_secs(1) / 30;
+        let mut pacer = RenderPacer::new(FrameLimit::Cap(30), start);
+
+        assert!(pacer.render(start));
+        assert!(!pacer.render(start + interval / 2));
+        assert!(pacer.render(start + interval));
+        assert_eq!(Some(start + interval * 2), pacer.next_render());
+
+        // a stall renders once, then every interval again
+        assert!(pacer.render(start + interval * 10));
+        assert!(!pacer.render(start + interval * 10));
+        assert_eq!(Some(start + interval * 11), pacer.next_render());
+
+        let mut unlimited = RenderPacer::new(FrameLimit::Off, start);
+        assert!(unlimited.render(start));
+        assert!(unlimited.render(start));
+        assert_eq!(None, unlimited.next_render());
+    }
+
+    #[test]
+    fn test_turbo() {
+        let mut turbo = Turbo::new(100);
+        assert_eq!(MAX_TURBO, turbo.multiplier());
+        assert_eq!(2, turbo.frames(2));
+
+        turbo.set_engaged(true);
+        assert_eq!(2 * MAX_TURBO, turbo.frames(2));
+        assert_eq!(0, turbo.frames(0));
+    }
+
+    #[test]
+    fn test_render_rate() {
+        let start = Instant::now();
+        let mut rate = RenderRate::new(start);
+
+        for ms in (20..1000).step_by(20) {
+            assert_eq!(None, rate.render(start + Duration::from_millis(ms)));
+        }
+        assert_eq!(Some(50), rate.render(start + Duration::from_secs(1)));
+        assert_eq!(None, rate.render(start + Duration::from_millis(1020)));
+    }
+
+    #[test]
+    fn test_input_latency() {
+        let start = Instant::now();
+        let mut latency = InputLatency::new();
+        assert_eq!("input latency: no key events", latency.report());
+
+        latency.received(start);
+        latency.received(start + Duration::from_millis(10));
+        latency.sampled(start + Duration::from_millis(16));
+        latency.sampled(start + Duration::from_millis(33));
+        latency.received(start + Duration::from_millis(40));
+        latency.sampled(start + Duration::from_millis(50));
+
+        assert_eq!(
+            "input latency over 3 key events: mean 10.7 ms, max 16.0 ms",
+            latency.report()
+        );
+    }
+
+    #[test]
+    fn test_cycle_budget() {
+        let jitter = Jitter {
+            percent: 20,
+            seed: 7,
+        };
+        let mut budget = CycleBudget::new(jitter);
+        let budgets: Vec<u32> = (0..100).map(|_| budget.next(100)).collect();
+        assert!(budgets.iter().all(|cycles| (80..=120).contains(cycles)));
+        assert!(budgets.iter().any(|&cycles| cycles != budgets[0]));
+
+        // the same seed gives the same budgets
+        let mut again = CycleBudget::new(jitter);
+        assert_eq!(
+            budgets,
+            (0..100).map(|_| again.next(100)).collect::<Vec<_>>()
+        );
+
+        let mut budget = CycleBudget::new(Jitter {
+            percent: 100,
+            seed: 7,
+        });
+        assert!((0..100).all(|_| budget.next(1) >= 1));
+        assert_eq!(0, budget.next(0));
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+/// How long a toast stays over the game
+pub const TOAST_DURATION: Duration = Duration::from_secs(2);
+
+/// Short message shown over the game for a while as feedback of an action, e.g.
+/// "Palette: amber". A new toast replaces the one showing.
+pub struct Toasts {
+    current: Option<(String, Instant)>, // message and when it was shown
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Toasts { current: None }
+    }
+
+    pub fn show(&mut self, message: &str, now: Instant) {
+        self.current = Some((message.to_string(), now));
+    }
+
+    /// Message to draw at `now`, if one is still showing
+    pub fn current(&self, now: Instant) -> Option<&str> {
+        self.current
+            .as_ref()
+            .filter(|(_, shown)| now.duration_since(*shown) < TOAST_DURATION)
+            .map(|(message, _)| message.as_str())
+    }
+}
+
+impl Default for Toasts {
+    fn default() -> Self {
+        Toasts::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toasts() {
+        let start = Instant::now();
+        let mut toasts = Toasts::new();
+        assert_eq!(None, toasts.current(start));
+
+        toasts.show("Turbo 4x", start);
+        assert_eq!(Some("Turbo 4x"), toasts.current(start + TOAST_DURATION / 2));
+
+        // replaced, showing for the whole duration again
+        toasts.show("Palette: amber", start + TOAST_DURATION / 2);
+        assert_eq!(
+            Some("Palette: amber"),
+            toasts.current(start + TOAST_DURATION)
+        );
+        assert_eq!(None, toasts.current(start + TOAST_DURATION * 2));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::chip8::Chip8;
+use crate::quirks::{Profile, Quirks};
+
+const CYCLES_PER_FRAME: u32 = 50;
+const MAX_FRAMES: usize = 60;
+
+/// Each test writes 1 at RESULTS_LOC + test index if the quirk behavior was observed
+const RESULTS_LOC: u16 = 0x0EA0;
+
+/// Quirk detection program, results are written in `Quirks::NAMES` order
+#[rustfmt::skip]
+const QUIRKS_ROM: [u8; 192] = [
+    // vf_reset: VF is 0 after 8XY1
+    0x6F, 0x05, // 200: VF = 5
+    0x60, 0x01, // 202: V0 = 1
+    0x61, 0x02, // 204: V1 = 2
+    0x80, 0x11, // 206: V0 = V0 | V1
+    0x80, 0xF0, // 208: V0 = VF
+    0x63, 0x00, // 20A: V3 = 0
+    0x40, 0x00, // 20C: skip next if V0 != 0
+    0x63, 0x01, // 20E: V3 = 1
+    0xAE, 0xA0, // 210: I = RESULTS_LOC
+    0x80, 0x30, // 212: V0 = V3
+    0xF0, 0x55, // 214: mem write V0 at addr I
+
+    // memory_increment: FX65 reads the byte after the one written by FX55
+    0xAE, 0xB1, // 216: I = 0xEB1
+    0x60, 0xAB, // 218: V0 = 0xAB
+    0xF0, 0x55, // 21A: mem write V0 at addr I
+    0xAE, 0xB0, // 21C: I = 0xEB0
+    0x60, 0x00, // 21E: V0 = 0
+    0xF0, 0x55, // 220: mem write V0 at addr I
+    0xF0, 0x65, // 222: mem read V0 at addr I
+    0x63, 0x00, // 224: V3 = 0
+    0x30, 0x00, // 226: skip next if V0 == 0
+    0x63, 0x01, // 228: V3 = 1
+    0xAE, 0xA1, // 22A: I = RESULTS_LOC + 1
+    0x80, 0x30, // 22C: V0 = V3
+    0xF0, 0x55, // 22E: mem write V0 at addr I
+
+    // shifting: 8XY6 shifts VX instead of VY
+    0x60, 0x10, // 230: V0 = 0x10
+    0x61, 0x04, // 232: V1 = 0x04
+    0x80, 0x16, // 234: V0 = V1 >> 1 (or V0 >> 1)
+    0x63, 0x00, // 236: V3 = 0
+    0x40, 0x08, // 238: skip next if V0 != 0x08
+    0x63, 0x01, // 23A: V3 = 1
+    0xAE, 0xA4, // 23C: I = RESULTS_LOC + 4
+    0x80, 0x30, // 23E: V0 = V3
+    0xF0, 0x55, // 240: mem write V0 at addr I
+
+    // jumping: B29C jumps to 0x29C + V2 instead of 0x29C + V0
+    0x60, 0x00, // 242: V0 = 0
+    0x62, 0x04, // 244: V2 = 4
+    0xB2, 0x9C, // 246: jump to 0x29C + V0 (or 0x29C + V2), sets V4
+    0xAE, 0xA5, // 248: I = RESULTS_LOC + 5
+    0x80, 0x40, // 24A: V0 = V4
+    0xF0, 0x55, // 24C: mem write V0 at addr I
+
+    // clipping: a sprite drawn at the right edge does not wrap to the left
+    0x00, 0xE0, // 24E: clear screen
+    0xA2, 0xA4, // 250: I = sprite
+    0x60, 0x3E, // 252: V0 = 62
+    0x61, 0x00, // 254: V1 = 0
+    0xD0, 0x11, // 256: draw sprite at (62, 0)
+    0x60, 0x00, // 258: V0 = 0
+    0xD0, 0x11, // 25A: draw sprite at (0, 0), collides if the first one wrapped
+    0x80, 0xF0, // 25C: V0 = VF
+    0x63, 0x00, // 25E: V3 = 0
+    0x40, 0x00, // 260: skip next if V0 != 0
+    0x63, 0x01, // 262: V3 = 1
+    0xAE, 0xA3, // 264: I = RESULTS_LOC + 3
+    0x80, 0x30, // 266: V0 = V3
+    0xF0, 0x55, // 268: mem write V0 at addr I
+    0x00, 0xE0, // 26A: clear screen
+
+    // display_wait: at most 2 sprites are drawn during one frame
+    0x65, 0x01, // 26C: V5 = 1
+    0xF5, 0x15, // 26E: delay timer = V5
+    0xF5, 0x07, // 270: V5 = delay timer
+    0x35, 0x00, // 272: skip next if V5 == 0
+    0x12, 0x70, // 274: jump to 0x270 (wait for the next frame)
+    0x66, 0x00, // 276: V6 = 0 (draw counter)
+    0x65, 0x01, // 278: V5 = 1
+    0xF5, 0x15, // 27A: delay timer = V5
+    0x60, 0x00, // 27C: V0 = 0
+    0x61, 0x00, // 27E: V1 = 0
+    0xA2, 0xA4, // 280: I = sprite
+    0xD0, 0x11, // 282: draw sprite at (0, 0)
+    0x76, 0x01, // 284: V6 = V6 + 1
+    0xF5, 0x07, // 286: V5 = delay timer
+    0x35, 0x00, // 288: skip next if V5 == 0
+    0x12, 0x82, // 28A: jump to 0x282 (draw until the end of the frame)
+    0x67, 0x02, // 28C: V7 = 2
+    0x87, 0x65, // 28E: V7 = V7 - V6, VF = 1 if V6 <= 2
+    0x83, 0xF0, // 290: V3 = VF
+    0xAE, 0xA2, // 292: I = RESULTS_LOC + 2
+    0x80, 0x30, // 294: V0 = V3
+    0xF0, 0x55, // 296: mem write V0 at addr I
+    0x00, 0xE0, // 298: clear screen
+    0x12, 0xA6, // 29A: jump to 0x2A6
+
+    // jumping targets
+    0x64, 0x00, // 29C: V4 = 0
+    0x12, 0x48, // 29E: jump back to 0x248
+    0x64, 0x01, // 2A0: V4 = 1
+    0x12, 0x48, // 2A2: jump back to 0x248
+
+    0xF0, 0xF0, // 2A4: sprite, two rows drawn by the collision_rows test
+
+    // collision_rows: VF is 2 after drawing a sprite of two rows twice
+    0xA2, 0xA4, // 2A6: I = sprite
+    0x60, 0x00, // 2A8: V0 = 0
+    0xD0, 0x02, // 2AA: draw 2 rows at (0, 0)
+    0xD0, 0x02, // 2AC: draw them again, both rows collide
+    0x80, 0xF0, // 2AE: V0 = VF
+    0x63, 0x00, // 2B0: V3 = 0
+    0x40, 0x02, // 2B2: skip next if V0 != 2
+    0x63, 0x01, // 2B4: V3 = 1
+    0xAE, 0xA6, // 2B6: I = RESULTS_LOC + 6
+    0x80, 0x30, // 2B8: V0 = V3
+    0xF0, 0x55, // 2BA: mem write V0 at addr I
+    0x00, 0xE0, // 2BC: clear screen
+    0x00, 0x00, // 2BE: stop
+];
+
+/// Run the detection program headlessly and return the quirks it observed
+pub fn detect(quirks: Quirks) -> Quirks {
+    let mut chip8 = Chip8::new();
+    chip8.interpreter.set_quirks(quirks);
+    chip8.load(&QUIRKS_ROM);
+
+    for _ in 0..MAX_FRAMES {
+        chip8.run_frame(CYCLES_PER_FRAME);
+    }
+
+    let detected = |test: u16| chip8.memory.read(RESULTS_LOC + test) == 1;
+
+    Quirks {
+        vf_reset: detected(0),
+        memory_increment: detected(1),
+        display_wait: detected(2),
+        clipping: detected(3),
+        shifting: detected(4),
+        jumping: detected(5),
+        collision_rows: detected(6),
+    }
+}
+
+/// Print a table of the detected quirks for each built-in profile
+pub fn run() {
+    print!("{:<18}", "quirk");
+    for profile in Profile::ALL {
+        print!("{:<12}", profile.name());
+    }
+    println!();
+
+    let results: Vec<(Quirks, Quirks)> = Profile::ALL
+        .iter()
+        .map(|profile| (profile.quirks(), detect(profile.quirks())))
+        .collect();
+
+    for (i, name) in Quirks::NAMES.iter().enumerate() {
+        print!("{:<18}", name);
+        for (expected, detected) in &results {
+            let state = if detected.values()[i] { "on" } else { "off" };
+            let status = if expected.values()[i] == detected.values()[i] {
+                "ok"
+            } else {
+                "FAIL"
+            };
+            print!("{:<12}", format!("{} {}", state, status));
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_profile_quirks() {
+        for profile in Profile::ALL {
+            assert_eq!(profile.quirks(), detect(profile.quirks()), "{:?}", profile);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between two checks of the watched file
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Notices changes of a file by polling its modification time, to reload settings edited
+/// while running
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>, // None while the file is missing
+    next_check: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path, now: Instant) -> Self {
+        FileWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            next_check: now + POLL_INTERVAL,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file was created or modified since the last change seen, checked at most
+    /// every `POLL_INTERVAL`. A deleted file is not a change, the settings stay as they are.
+    pub fn changed(&mut self, now: Instant) -> bool {
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + POLL_INTERVAL;
+
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+
+    #[test]
+    fn test_changed() {
+        let dir = std::env::temp_dir().join("chip8-interpreter-test-watch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("display-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let start = Instant::now();
+        let mut watcher = FileWatcher::new(&path, start);
+
+        // created
+        std::fs::write(&path, "fade = 1").unwrap();
+        assert!(!watcher.changed(start));
+        assert!(watcher.changed(start + POLL_INTERVAL));
+        assert!(!watcher.changed(start + POLL_INTERVAL * 2));
+
+        // modified, with a time set explicitly as file systems may store seconds only
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+        assert!(watcher.changed(start + POLL_INTERVAL * 3));
+
+        // deleted
+        std::fs::remove_file(&path).unwrap();
+        assert!(!watcher.changed(start + POLL_INTERVAL * 4));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::filenames;
+use crate::quirks::Profile;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Values of the placeholders of the window title format
+pub struct TitleVars<'a> {
+    pub rom: &'a str,           // file name of the program
+    pub title: Option<&'a str>, // from the .c8c container
+    pub profile: Profile,
+    pub ips: u32,
+    pub fps: u32, // renders presented during the last second
+}
+
+/// Format of the window title, with overrides keyed by program file name
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowTitle {
+    pub format: String,
+    pub roms: BTreeMap<String, String>,
+}
+
+impl Default for WindowTitle {
+    fn default() -> Self {
+        WindowTitle {
+            format: "CHIP-8".to_string(),
+            roms: BTreeMap::new(),
+        }
+    }
+}
+
+impl WindowTitle {
+    /// The defaults if the file is missing, an error if it is invalid or has a bad format
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(WindowTitle::default()),
+            Err(e) => return Err(e.to_string()),
+        };
+        let title: WindowTitle = toml::from_str(&text).map_err(|e| e.to_string())?;
+
+        let vars = TitleVars {
+            rom: "",
+            title: None,
+            profile: Profile::Modern,
+            ips: 0,
+            fps: 0,
+        };
+        for format in std::iter::once(&title.format).chain(title.roms.values()) {
+            expand(format, &vars)?;
+        }
+
+        Ok(title)
+    }
+
+    /// Format for the program `rom`, its override if it has one
+    pub fn format_for(&self, rom: &str) -> &str {
+        self.roms.get(rom).unwrap_or(&self.format)
+    }
+
+    /// Title of the window for `vars.rom`
+    pub fn title(&self, vars: &TitleVars) -> String {
+        // checked by `load`
+        expand(self.format_for(vars.rom), vars).unwrap_or_default()
+    }
+}
+
+/// Expand `{rom}` (file name without extension), `{title}` (of the container, `{rom}`
+/// otherwise), `{profile}`, `{ips}` and `{fps}` in `format`. `{{` and `}}` are literal braces.
+pub fn expand(format: &str, vars: &TitleVars) -> Result<String, String> {
+    filenames::expand_with(format, |placeholder| match placeholder {
+        "rom" => Some(filenames::rom_stem(vars.rom)),
+        "title" => Some(
+            vars.title
+                .map_or_else(|| filenames::rom_stem(vars.rom), str::to_string),
+        ),
+        "profile" => Some(vars.profile.name().to_string()),
+        "ips" => Some(vars.ips.to_string()),
+        "fps" => Some(vars.fps.to_string()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_title() {
+        let title: WindowTitle = toml::from_str(
+            r#"
+            format = "CHIP-8 — {rom} [{profile}] {fps}fps"
+
+            [roms]
+            "INVADERS.ch8" = "{title} at {ips} IPS"
+            "#,
+        )
+        .unwrap();
+        let mut vars = TitleVars {
+            rom: "pong.ch8",
+            title: None,
+            profile: Profile::Vip,
+            ips: 600,
+            fps: 59,
+        };
+
+        assert_eq!("CHIP-8 — pong [vip] 59fps", title.title(&vars));
+        vars.rom = "INVADERS.ch8";
+        vars.title = Some("Space Invaders");
+        assert_eq!("Space Invaders at 600 IPS", title.title(&vars));
+
+        assert_eq!("CHIP-8", WindowTitle::default().title(&vars));
+        assert!(expand("{rom} {speed}", &vars).is_err());
+    }
+}
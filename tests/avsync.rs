@@ -0,0 +1,23 @@
+use chip8_interpreter::avsync;
+use chip8_interpreter::quirks::Profile;
+
+use clap::ValueEnum;
+
+#[test]
+fn test_sync_rom() {
+    for profile in Profile::value_variants() {
+        for cycles_per_frame in [7, 11, 30, 1000] {
+            let monitor = avsync::run(&avsync::SYNC_ROM, *profile, cycles_per_frame, 300);
+            let offsets = monitor.offsets();
+
+            assert!(offsets.len() >= 9, "{:?} {}", profile, cycles_per_frame);
+            assert!(
+                monitor.in_sync(),
+                "{:?} at {} cycles per frame: {:?}",
+                profile,
+                cycles_per_frame,
+                offsets
+            );
+        }
+    }
+}